@@ -1,10 +1,6 @@
 /// Define the maximum amount of entries in the lua output vector
 const LUA_OUTPUT_BUFFER_SIZE: usize = 100;
 
-/// Define custom url of this application
-/// The registry keys for opening this url should already be added by the installer
-const CUSTOM_URL: &str = "matthias://";
-
 /// Define the url to the deocumentation
 const DOCUMENTATION_URL: &str = "https://matthias.gitbook.io/matthiasdocu";
 
@@ -17,7 +13,10 @@ use egui::{
 };
 use egui_extras::{Column, TableBuilder};
 use egui_notify::Toast;
-use std::fs::{self};
+use std::{
+    collections::BTreeMap,
+    fs::{self},
+};
 use tap::TapFallible;
 use tokio_util::sync::CancellationToken;
 
@@ -28,9 +27,23 @@ mod lua;
 mod server;
 mod ui;
 
-use self::backend::{display_error_message, ClientMessage, UserInformation};
+use self::backend::{
+    display_error_message, display_info_message, get_output_device_names, AudioPlayback, Bookmark,
+    ClientMessage, UserInformation, VoiceActivationMode,
+};
+
+use self::backend::{
+    ClientConnection, ConnectionRefusedError, ConnectionState, MessagingMode, Reconnection,
+    ServerMaster, ServerSession,
+};
 
-use self::backend::{ClientConnection, ConnectionState, ServerMaster};
+use self::ui::client_ui::client_actions::{
+    audio_recording::{get_input_device_info, get_input_devices, InputDeviceSettings},
+    connection_link::ConnectionLink,
+    lan_discovery::{discover_servers, DiscoveredServer},
+    message_cache::MessageCache,
+    video_source::{get_monitor_names, ScreenRegion, VideoSourceSettings},
+};
 
 impl eframe::App for backend::Application
 {
@@ -41,26 +54,33 @@ impl eframe::App for backend::Application
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>)
     {
-        //try to close connection if there is one
-        let username = self.login_username.clone();
-        let mut connection = self.client_connection.clone();
-        let password = self.client_connection.password.clone();
-        let uuid = self.opened_user_information.uuid.clone();
-        let toasts = self.toasts.clone();
-
-        //Disconnect from server
-        if let ConnectionState::Connected(_) = self.client_connection.state {
-            tokio::task::spawn(async move {
-                match ClientConnection::disconnect(&mut connection, username, password, uuid).await
-                {
-                    Ok(_) => {},
-                    Err(err) => {
+        //Disconnect from the opened server and the servers running in the background
+        for session in std::iter::once(&self.server_session).chain(&self.server_sessions) {
+            //Save the messages of the servers, so they can be displayed instantly the next time
+            self.save_message_cache(session);
+
+            if let ConnectionState::Connected(_) = session.client_connection.state {
+                let username = self.login_username.clone();
+                let mut connection = session.client_connection.clone();
+                let password = session.client_connection.password.clone();
+                let uuid = self.opened_user_information.uuid.clone();
+                let toasts = self.toasts.clone();
+
+                tokio::task::spawn(async move {
+                    if let Err(err) =
+                        ClientConnection::disconnect(&mut connection, username, password, uuid)
+                            .await
+                    {
                         //Avoid panicking when trying to display a Notification
                         //This is very rare but can still happen
                         display_error_message(err, toasts);
-                    },
-                };
-            });
+                    };
+                });
+            }
+
+            session.autosync_shutdown_token.cancel();
+            session.voip_shutdown_token.cancel();
+            session.voip_video_shutdown_token.cancel();
         }
 
         //clean up after server and client
@@ -76,11 +96,10 @@ impl eframe::App for backend::Application
             Err(err) => println!("{err}"),
         }
 
+        self.lan_discovery_shutdown_token.cancel();
+
         //Shut down the server
         self.server_shutdown_token.cancel();
-        self.autosync_shutdown_token.cancel();
-        self.voip_shutdown_token.cancel();
-        self.voip_video_shutdown_token.cancel();
 
         //Signal the voice recorder function to stop
         let _ = self.record_audio_interrupter.send(());
@@ -104,30 +123,25 @@ impl eframe::App for backend::Application
             let startup_link = startup_args[1].clone();
 
             //Check for URL, and also only run this code if we have signed in
-            if startup_link.contains(CUSTOM_URL) && self.main.client_mode {
-                //Get the address the link connects to
-                let link = &startup_link[CUSTOM_URL.len()..];
-
-                match link.find("&") {
-                    Some(sep_idx) => {
-                        let (address, password) = link.split_at(sep_idx);
+            if ConnectionLink::is_connection_link(&startup_link) && self.main.client_mode {
+                match ConnectionLink::parse(&startup_link) {
+                    Ok(connection_link) => {
+                        //Set address so itll be displayed in the ui too
+                        self.server_session
+                            .send_on_ip
+                            .clone_from(&connection_link.address);
 
-                        //Leave out parts of the password which are artificially added to the argmuent
-                        let password = &password[1..password.len() - 1];
+                        //The invite is sent instead of the password, if the link doesnt contain an invite the password has to be entered
+                        self.server_session.invite_token =
+                            connection_link.invite_token.unwrap_or_default();
 
                         //Connect to server
                         self.connect_to_server(
                             ctx,
-                            address.to_string(),
-                            Some(password.to_string()),
+                            connection_link.address,
+                            Some(self.server_session.client_password.clone()),
                         );
 
-                        //Set address so itll be displayed in the ui too
-                        self.client_ui.send_on_ip = address.to_string();
-
-                        //Set password so itll be displayed in the ui too
-                        self.client_ui.client_password = password.to_string();
-
                         //Show settings window to alert user
                         self.settings_window = true;
 
@@ -146,8 +160,8 @@ impl eframe::App for backend::Application
                             },
                         }
                     },
-                    None => {
-                        display_error_message("Invalid link.", self.toasts.clone());
+                    Err(err) => {
+                        display_error_message(format!("Invalid link: {err}"), self.toasts.clone());
                     },
                 }
 
@@ -159,6 +173,9 @@ impl eframe::App for backend::Application
         //Display notifications
         self.toasts.lock().unwrap().show(ctx);
 
+        //The events are coming from the opened server, until the background servers are updated
+        self.set_lua_server_address(&self.server_session.send_on_ip);
+
         self.client_ui
             .extension
             .event_call_extensions(lua::EventCall::OnDraw, &self.lua, None);
@@ -191,6 +208,21 @@ impl eframe::App for backend::Application
             self.state_client(_frame, ctx);
         }
 
+        //Listen for the servers announcing themselves on the local network
+        if self.main.client_mode && self.lan_discovery_thread.is_none() {
+            let discovered_servers = self.discovered_servers.clone();
+            let shutdown_token = self.lan_discovery_shutdown_token.child_token();
+            let ctx = ctx.clone();
+
+            tokio::spawn(async move {
+                if let Err(err) = discover_servers(discovered_servers, shutdown_token, ctx).await {
+                    tracing::error!("Failed to discover the servers on the local network: {err}");
+                }
+            });
+
+            self.lan_discovery_thread = Some(());
+        }
+
         //Create value
         let mut settings_window = self.settings_window && self.main.client_mode;
 
@@ -203,6 +235,8 @@ impl eframe::App for backend::Application
                     self.client_settings_ui(ui, ctx);
 
                     self.server_setup_ui(ui, ctx);
+
+                    self.account_settings_ui(ui);
                 }
             });
 
@@ -210,106 +244,197 @@ impl eframe::App for backend::Application
         self.settings_window = settings_window;
 
         //Bookmarks windows
-        egui::Window::new("Bookmarks")
-            .open(&mut self.main.bookmark_mode)
-            .show(ctx, |ui| {
-                ui.label(RichText::from("Saved ip addresses"));
-                match UserInformation::deserialize(
-                    &fs::read_to_string(self.opened_user_information.path.clone()).unwrap(),
-                    self.opened_user_information.password.clone(),
-                ) {
-                    Ok(mut user_info) => {
-                        if ui.button("Save ip address").clicked() {
-                            user_info.add_bookmark_entry(self.client_ui.send_on_ip.clone());
-
-                            let _ = user_info
-                                .write_file(self.opened_user_information.path.clone())
-                                .tap_err_dbg(|err| tracing::error!("{err}"));
-                        };
+        self.bookmarks_window(ctx);
 
-                        ui.separator();
+        self.with_opened_session(|app, session| {
+            //Try to reconnect if the connection has dropped
+            app.reconnect_to_server(session, ctx);
 
-                        let bookmark_entries = user_info.bookmarked_ips.clone();
+            //Connection receiver
+            app.receive_connection(session);
+        });
 
-                        ui.group(|ui| {
-                            if !bookmark_entries.is_empty() {
-                                egui::ScrollArea::vertical().show(ui, |ui| {
-                                    for (index, item) in bookmark_entries.iter().enumerate() {
-                                        ui.horizontal(|ui| {
-                                            if ui.button(RichText::from(item.clone())).clicked() {
-                                                self.client_ui.send_on_ip.clone_from(item);
-                                            }
-                                            ui.with_layout(
-                                                Layout::right_to_left(Align::Min),
-                                                |ui| {
-                                                    if ui
-                                                        .button(RichText::from("-").strong())
-                                                        .clicked()
-                                                    {
-                                                        user_info.delete_bookmark_entry(index);
-
-                                                        //Dont check if user already exists because we overwrite the file which was already there
-                                                        let _ = user_info
-                                                            .write_file(
-                                                                self.opened_user_information
-                                                                    .path
-                                                                    .clone(),
-                                                            )
-                                                            .tap_err_dbg(|err| {
-                                                                tracing::error!("{err}")
-                                                            });
-                                                    }
-                                                },
-                                            );
-                                        });
-                                    }
-                                });
-                            }
-                            else {
-                                ui.label(RichText::from("Add your favorite servers!").strong());
-                            }
-                        });
-                    },
-                    Err(err) => eprintln!("{err}"),
-                };
-            });
+        //Receive the messages of the servers which arent opened
+        self.update_background_servers(ctx);
+
+        //Voip instance listener
+        match self.voip_connection_receiver.try_recv() {
+            Ok(voip) => {
+                self.server_session.voip = Some(voip.clone());
+
+                self.send_msg(ClientMessage::construct_voip_connect(
+                    &self.opened_user_information.uuid,
+                    voip.socket.local_addr().unwrap().port(),
+                    self.client_ui.voip_mixing_mode,
+                    self.server_session.voip_room.clone(),
+                ));
+
+                //Send the volumes set for the participants, so that the server can apply them if it mixes the call
+                for participant_audio in self.client_ui.participant_audio.iter() {
+                    self.send_msg(ClientMessage::construct_voip_event(
+                        self.opened_user_information.uuid.clone(),
+                        backend::ClientVoipRequest::ParticipantVolume(
+                            participant_audio.key().clone(),
+                            participant_audio.effective_volume(),
+                        ),
+                    ));
+                }
+            },
+            Err(_err) => {},
+        }
+    }
+}
+
+impl backend::Application
+{
+    /// This function spawn an async tokio thread, to send the message passed in as the argument to the opened server, this function does not await a response from the server
+    /// If the message has an id, it is kept in the outbox until the server acknowledges it
+    pub fn send_msg(&self, message: ClientMessage)
+    {
+        if message.message_id.is_some() {
+            self.outbox
+                .lock()
+                .unwrap()
+                .push(message.clone(), self.server_session.send_on_ip.clone());
+        }
+
+        self.deliver_msg(&self.server_session, message);
+    }
+
+    /// Calls the function with the opened server's session
+    /// The session is taken out of the application while the function runs, so the function can modify both of them
+    pub fn with_opened_session(&mut self, function: impl FnOnce(&mut Self, &mut ServerSession))
+    {
+        let mut session = std::mem::take(&mut self.server_session);
+
+        function(self, &mut session);
+
+        self.server_session = session;
+    }
 
-        //Connection receiver
-        match self.connection_receiver.try_recv() {
+    /// Reads the cached messages of the server, if there arent any an empty message list is returned
+    fn load_message_cache(&self, address: &str) -> ServerMaster
+    {
+        MessageCache::new(&self.opened_user_information, address)
+            .and_then(|message_cache| message_cache.load_messages())
+            .unwrap_or_default()
+    }
+
+    /// Saves the messages of the server session to its cache
+    pub fn save_message_cache(&self, session: &ServerSession)
+    {
+        save_message_cache(
+            &self.opened_user_information,
+            &session.send_on_ip,
+            &session.incoming_messages,
+        );
+    }
+
+    /// Receives the result of the connection attempts made to the server of the session
+    fn receive_connection(&mut self, session: &mut ServerSession)
+    {
+        match session.connection_receiver.try_recv() {
             Ok(connection) => {
                 if let Some(connection) = connection {
+                    let is_reconnection = session.client_connection.reconnection.is_some();
+
                     //Modify client_connection
-                    self.client_connection = connection.0;
+                    session.client_connection = connection.0;
 
                     //Modify local message list
                     let incoming_sync_message: Result<ServerMaster, serde_json::Error> =
                         serde_json::from_str(&connection.1);
 
                     //Modify the base64 encoded string of send on ip, so it can be used in different places without having to re-encode every frame
-                    self.client_ui.send_on_ip_base64_encoded =
-                        general_purpose::URL_SAFE_NO_PAD.encode(self.client_ui.send_on_ip.clone());
+                    session.send_on_ip_base64_encoded =
+                        general_purpose::URL_SAFE_NO_PAD.encode(session.send_on_ip.clone());
 
                     if let Ok(incoming_message) = incoming_sync_message {
-                        self.client_ui.incoming_messages = incoming_message;
+                        self.update_connected_bookmark(
+                            &session.send_on_ip,
+                            &incoming_message.server_fingerprint,
+                        );
+
+                        //If we have reconnected, the server has only sent the messages we have missed
+                        //The cached messages are dropped if they have been synced from another instance of the server
+                        if incoming_message.first_message_index != 0
+                            && incoming_message.server_instance_id
+                                == session.incoming_messages.server_instance_id
+                        {
+                            let incoming_messages = &mut session.incoming_messages;
+
+                            incoming_messages
+                                .message_list
+                                .truncate(incoming_message.first_message_index);
+                            incoming_messages
+                                .message_list
+                                .extend(incoming_message.message_list);
+
+                            incoming_messages.reaction_list = incoming_message.reaction_list;
+                            incoming_messages.user_seen_list = incoming_message.user_seen_list;
+                            incoming_messages.connected_clients_profile =
+                                incoming_message.connected_clients_profile;
+                            incoming_messages.ongoing_voip_call =
+                                incoming_message.ongoing_voip_call;
+                        }
+                        else {
+                            session.incoming_messages = incoming_message;
+                        }
+
+                        if is_reconnection {
+                            match self.toasts.lock() {
+                                Ok(mut toasts) => {
+                                    let mut toast = Toast::success("Reconnected to the server!");
+                                    toast.set_duration(Some(std::time::Duration::from_secs(4)));
+
+                                    toasts.add(toast);
+                                },
+                                Err(_err) => {
+                                    tracing::error!("{}", _err.to_string());
+                                },
+                            }
+                        }
+
+                        //Save the synced messages, so they can be displayed the next time instantly
+                        self.save_message_cache(session);
+
+                        //Send the messages which havent been acknowledged before the connection was made
+                        self.resend_outbox(session);
 
                         //Callback
                         self.client_ui.extension.event_call_extensions(
                             crate::app::lua::EventCall::OnConnect,
                             &self.lua,
-                            Some(self.client_ui.send_on_ip.clone()),
+                            Some(session.send_on_ip.clone()),
                         );
                     }
                     else {
                         eprintln!("Failed to convert {} to ServerMaster", connection.1)
                     }
                 }
+                else if let Some(reconnection) = &mut session.client_connection.reconnection {
+                    if reconnection.is_refused() {
+                        session.client_connection.reconnection = None;
+
+                        self.reset_client_connection(session);
+
+                        session.client_connection.state = ConnectionState::Error;
+                    }
+                    else {
+                        //The attempt has failed, we try again later
+                        reconnection.schedule_next_attempt();
+                    }
+                }
                 else {
                     // A race condition will occur if we connected successfully after getting a connection error (request timed out)
                     // So we check if we have already made the connection before actually modifying the value based on the timed out request
-                    if !matches!(self.client_connection.state, ConnectionState::Connected(_)) {
+                    if !matches!(
+                        session.client_connection.state,
+                        ConnectionState::Connected(_)
+                    ) {
                         //If we received a None it means we have an error
-                        self.client_connection.state = ConnectionState::Error;
-                        self.disconnect_from_server();
+                        session.client_connection.state = ConnectionState::Error;
+                        self.disconnect_from_server(session);
                     }
                 }
             },
@@ -317,49 +442,179 @@ impl eframe::App for backend::Application
                 // tracing::error!("{}", _err.to_string());
             },
         }
+    }
 
-        //Voip instance listener
-        match self.voip_connection_receiver.try_recv() {
-            Ok(voip) => {
-                self.client_ui.voip = Some(voip.clone());
+    /// Keeps the servers which arent opened up to date
+    /// Their connections are received, reconnected and synced just like the opened server's, and the messages received count as unread
+    fn update_background_servers(&mut self, ctx: &egui::Context)
+    {
+        let mut server_sessions = std::mem::take(&mut self.server_sessions);
 
-                self.send_msg(ClientMessage::construct_voip_connect(
-                    &self.opened_user_information.uuid,
-                    voip.socket.local_addr().unwrap().port(),
-                ))
-            },
-            Err(_err) => {},
+        for session in &mut server_sessions {
+            let message_count = session.incoming_messages.message_list.len();
+
+            self.update_server_session(session, ctx);
+
+            session.unread_messages += session
+                .incoming_messages
+                .message_list
+                .len()
+                .saturating_sub(message_count);
         }
+
+        self.server_sessions = server_sessions;
+
+        //The events are coming from the opened server again
+        self.set_lua_server_address(&self.server_session.send_on_ip);
     }
-}
 
-impl backend::Application
-{
-    /// This function spawn an async tokio thread, to send the message passed in as the argument, this function does not await a response from the server
-    pub fn send_msg(&self, message: ClientMessage)
+    /// Reconnects, receives the connection and the messages of the server session
+    /// The lua extensions can read the address of the server the events are coming from through the ```ServerAddress``` global
+    fn update_server_session(&mut self, session: &mut ServerSession, ctx: &egui::Context)
     {
-        let connection = self.client_connection.clone();
+        self.set_lua_server_address(&session.send_on_ip);
+
+        self.reconnect_to_server(session, ctx);
+
+        self.receive_connection(session);
+
+        self.client_recv(session, ctx);
+    }
+
+    /// Sets the ```ServerAddress``` lua global to the address of the server the events are coming from
+    pub fn set_lua_server_address(&self, address: &str)
+    {
+        if let Err(err) = self
+            .lua
+            .globals()
+            .set(lua::SERVER_ADDRESS_GLOBAL, address.to_string())
+        {
+            tracing::error!("{}", err);
+        }
+    }
+
+    /// Opens the server session, the previously opened server will keep running in the background
+    pub fn switch_server(&mut self, index: usize)
+    {
+        std::mem::swap(&mut self.server_session, &mut self.server_sessions[index]);
+
+        //The session now contains the previously opened server, which has been read until now
+        self.server_sessions[index].unread_messages = 0;
+
+        //Reset the ui state which belongs to the previous server
+        self.client_ui.messaging_mode = MessagingMode::Normal;
+        self.client_ui.scroll_to_message = None;
+        self.client_ui.scroll_to_message_index = None;
+        self.client_ui.outgoing_call_invitation = None;
+    }
+
+    /// Opens a new empty server, the previously opened server will keep running in the background
+    pub fn add_server(&mut self)
+    {
+        self.server_sessions.push(ServerSession::default());
+
+        self.switch_server(self.server_sessions.len() - 1);
+
+        //The address of the new server can be entered in the settings
+        self.settings_window = true;
+    }
+
+    /// Disconnects from the opened server, and opens the last server session
+    pub fn close_server(&mut self)
+    {
+        let Some(last_index) = self.server_sessions.len().checked_sub(1)
+        else {
+            return;
+        };
+
+        self.with_opened_session(|app, session| {
+            match session.client_connection.state {
+                ConnectionState::Connected(_) => app.disconnect_from_server(session),
+                _ => {
+                    app.reset_client_connection(session);
+                    session.client_connection.reset_state();
+                },
+            }
+        });
+
+        self.switch_server(last_index);
+
+        //The closed server's state is in the last session now
+        self.server_sessions.pop();
+    }
+
+    /// Sends the message to the server of the session without adding it to the outbox, if sending an outbox message fails the outbox is notified
+    fn deliver_msg(&self, session: &ServerSession, message: ClientMessage)
+    {
+        let connection = session.client_connection.clone();
+        let outbox = self.outbox.clone();
 
         tokio::spawn(async move {
+            let message_id = message.message_id.clone();
+
             match connection.send_message(message).await {
                 //We dont need the server's reply since we dont handle it here
                 Ok(_server_reply) => {},
                 Err(err) => {
                     tracing::error!("{:?}", err.source());
                     tracing::error!("{}", err);
+
+                    //The message stays in the outbox, it will be sent again after reconnecting
+                    if let Some(message_id) = message_id {
+                        outbox.lock().unwrap().on_send_failed(&message_id);
+                    }
                 },
             };
         });
     }
 
+    /// Sends the messages of the outbox again, which havent been acknowledged by the server we have connected to
+    /// The server ignores the messages it has already stored, so sending them twice is not a problem
+    fn resend_outbox(&self, session: &ServerSession)
+    {
+        let messages = self
+            .outbox
+            .lock()
+            .unwrap()
+            .messages_to_resend(&session.send_on_ip);
+
+        for message in messages {
+            self.deliver_msg(session, message);
+        }
+    }
+
+    /// Sends a failed message of the outbox again, to the server it was originally sent to
+    /// If we arent connected to that server, the message is sent after (re)connecting to it
+    pub fn retry_outbox_message(&self, message_id: &str)
+    {
+        let entry = self.outbox.lock().unwrap().retry(message_id);
+
+        let Some(entry) = entry
+        else {
+            return;
+        };
+
+        let session = std::iter::once(&self.server_session)
+            .chain(self.server_sessions.iter())
+            .find(|session| session.send_on_ip == entry.server_address);
+
+        if let Some(session) = session {
+            self.deliver_msg(session, entry.message);
+        }
+    }
+
     /// This function resets clientconnection and all of its other attributes (self.client_ui.incoming_msg, self.autosync_should_run)
-    fn reset_client_connection(&mut self)
+    /// The messages are kept and saved to the cache, so they can still be read while offline
+    fn reset_client_connection(&mut self, session: &mut ServerSession)
     {
-        self.client_ui.incoming_messages = ServerMaster::default();
+        self.save_message_cache(session);
+
+        //The calls cant be followed without a connection
+        session.incoming_messages.ongoing_voip_call = Default::default();
 
-        self.autosync_shutdown_token.cancel();
+        session.autosync_shutdown_token.cancel();
 
-        self.client_connection.state = ConnectionState::Disconnected;
+        session.client_connection.state = ConnectionState::Disconnected;
     }
 
     fn client_settings_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context)
@@ -367,16 +622,21 @@ impl backend::Application
         ui.collapsing("Client", |ui| {
             ui.label("Connect to an ip address");
 
-            let compare_ip = self.client_ui.send_on_ip.clone();
+            let compare_ip = self.server_session.send_on_ip.clone();
 
             ui.allocate_ui(vec2(ui.available_width(), 25.), |ui| {
                 ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
                     ui.add_enabled_ui(
-                        matches!(self.client_connection.state, ConnectionState::Disconnected)
-                            || matches!(self.client_connection.state, ConnectionState::Error),
+                        matches!(
+                            self.server_session.client_connection.state,
+                            ConnectionState::Disconnected
+                        ) || matches!(
+                            self.server_session.client_connection.state,
+                            ConnectionState::Error
+                        ),
                         |ui| {
                             ui.add(
-                                TextEdit::singleline(&mut self.client_ui.send_on_ip)
+                                TextEdit::singleline(&mut self.server_session.send_on_ip)
                                     .hint_text("Address"),
                             )
                             .on_hover_text(
@@ -385,13 +645,15 @@ impl backend::Application
                         },
                     );
 
-                    match &self.client_connection.state {
+                    match &self.server_session.client_connection.state {
                         ConnectionState::Connected(_) => {
                             if ui
                                 .button(RichText::from("Disconnect").color(Color32::RED))
                                 .clicked()
                             {
-                                self.disconnect_from_server();
+                                self.with_opened_session(|app, session| {
+                                    app.disconnect_from_server(session)
+                                });
 
                                 //Callback
                                 self.client_ui.extension.event_call_extensions(
@@ -409,21 +671,37 @@ impl backend::Application
                                 .clicked()
                             {
                                 //Reset client
-                                self.reset_client_connection();
+                                self.with_opened_session(|app, session| {
+                                    app.reset_client_connection(session)
+                                });
+                            }
+                        },
+                        ConnectionState::Reconnecting => {
+                            if ui
+                                .button(
+                                    RichText::from("Stop reconnecting").color(Color32::LIGHT_GRAY),
+                                )
+                                .clicked()
+                            {
+                                //Reset client
+                                self.with_opened_session(|app, session| {
+                                    app.reset_client_connection(session)
+                                });
+                                self.server_session.client_connection.reset_state();
                             }
                         },
                         _ => {
                             if ui.button("Connect").clicked() {
                                 self.connect_to_server(
                                     ctx,
-                                    self.client_ui.send_on_ip.clone(),
-                                    Some(self.client_ui.client_password.clone()),
+                                    self.server_session.send_on_ip.clone(),
+                                    Some(self.server_session.client_password.clone()),
                                 );
                             }
                         },
                     }
 
-                    ui.label(match self.client_connection.state {
+                    ui.label(match self.server_session.client_connection.state {
                         ConnectionState::Connected(_) => {
                             RichText::from("Connected").color(Color32::GREEN)
                         },
@@ -436,6 +714,12 @@ impl backend::Application
                         ConnectionState::Error => {
                             RichText::from("Error when trying to connect").color(Color32::RED)
                         },
+                        ConnectionState::Reconnecting => {
+                            RichText::from(reconnection_status(
+                                &self.server_session.client_connection,
+                            ))
+                            .color(Color32::YELLOW)
+                        },
                     });
 
                     ui.allocate_ui(vec2(25., 25.), |ui| {
@@ -451,26 +735,63 @@ impl backend::Application
                 });
             });
 
-            let compare_passwords = self.client_ui.client_password.clone();
+            let compare_passwords = self.server_session.client_password.clone();
 
             ui.add_enabled(
-                matches!(self.client_connection.state, ConnectionState::Disconnected)
-                    || matches!(self.client_connection.state, ConnectionState::Error),
+                matches!(
+                    self.server_session.client_connection.state,
+                    ConnectionState::Disconnected
+                ) || matches!(
+                    self.server_session.client_connection.state,
+                    ConnectionState::Error
+                ),
                 |ui: &mut egui::Ui| {
                     ui.add(
-                        TextEdit::singleline(&mut self.client_ui.client_password)
+                        TextEdit::singleline(&mut self.server_session.client_password)
                             .hint_text("Password (Optional)"),
                     )
                 },
             );
 
-            if compare_passwords != self.client_ui.client_password
-                || self.client_ui.send_on_ip != compare_ip
+            //The invite of the opened link is used instead of the password
+            if !self.server_session.invite_token.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::from("Joining with an invite").weak());
+
+                    if ui
+                        .add_enabled(
+                            matches!(
+                                self.server_session.client_connection.state,
+                                ConnectionState::Disconnected
+                            ) || matches!(
+                                self.server_session.client_connection.state,
+                                ConnectionState::Error
+                            ),
+                            egui::Button::new("Use password instead"),
+                        )
+                        .clicked()
+                    {
+                        self.server_session.invite_token.clear();
+                    }
+                });
+            }
+
+            if compare_passwords != self.server_session.client_password
+                || self.server_session.send_on_ip != compare_ip
             {
-                self.server_sender_thread = None;
-                self.client_ui.incoming_messages = ServerMaster::default();
+                self.server_session.server_sender_thread = None;
+                self.server_session.incoming_messages = ServerMaster::default();
             }
 
+            //The invite belongs to the server of the opened link
+            if self.server_session.send_on_ip != compare_ip {
+                self.server_session.invite_token.clear();
+            }
+
+            ui.collapsing("Servers on the local network", |ui| {
+                self.discovered_servers_ui(ui, ctx);
+            });
+
             //Draw the extensions part of the ui
             ui.collapsing("Extensions", |ui| {
                 self.client_extension(ui, ctx);
@@ -491,6 +812,344 @@ impl backend::Application
                     )
                     .unwrap_or_default();
             });
+
+            ui.collapsing("Voice activation", |ui| {
+                let mut voice_activation = self.client_ui.voice_activation.lock().unwrap();
+
+                egui::ComboBox::from_label("Transmission mode")
+                    .selected_text(voice_activation.mode.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            VoiceActivationMode::Continuous,
+                            VoiceActivationMode::VoiceActivity,
+                            VoiceActivationMode::PushToTalk,
+                        ] {
+                            ui.selectable_value(&mut voice_activation.mode, mode, mode.to_string());
+                        }
+                    });
+
+                match voice_activation.mode {
+                    VoiceActivationMode::Continuous => {},
+                    VoiceActivationMode::VoiceActivity => {
+                        ui.horizontal(|ui| {
+                            ui.label("Threshold");
+                            ui.add(
+                                Slider::new(&mut voice_activation.threshold_db, -80.0..=0.0)
+                                    .suffix(" dB"),
+                            );
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Hangover time");
+                            ui.add(
+                                Slider::new(&mut voice_activation.hangover_ms, 0..=2000)
+                                    .suffix(" ms"),
+                            );
+                        });
+                    },
+                    VoiceActivationMode::PushToTalk => {
+                        egui::ComboBox::from_label("Push-to-talk key")
+                            .selected_text(voice_activation.push_to_talk_key.name())
+                            .show_ui(ui, |ui| {
+                                for key in egui::Key::ALL {
+                                    ui.selectable_value(
+                                        &mut voice_activation.push_to_talk_key,
+                                        *key,
+                                        key.name(),
+                                    );
+                                }
+                            });
+
+                        ui.label(
+                            RichText::from(
+                                "The key is registered while Matthias is not focused too.",
+                            )
+                            .weak(),
+                        );
+                    },
+                }
+            });
+
+            ui.collapsing("Video source", |ui| {
+                //The source is opened when the video is turned on, so the ongoing video isnt affected
+                ui.horizontal(|ui| {
+                    if ui
+                        .radio(
+                            self.client_ui.video_source == VideoSourceSettings::Camera,
+                            VideoSourceSettings::Camera.to_string(),
+                        )
+                        .clicked()
+                    {
+                        self.client_ui.video_source = VideoSourceSettings::Camera;
+                    }
+
+                    if ui
+                        .radio(
+                            matches!(
+                                self.client_ui.video_source,
+                                VideoSourceSettings::Screen { .. }
+                            ),
+                            "Screen",
+                        )
+                        .clicked()
+                        && !matches!(
+                            self.client_ui.video_source,
+                            VideoSourceSettings::Screen { .. }
+                        )
+                    {
+                        self.client_ui.video_source = VideoSourceSettings::Screen {
+                            monitor: None,
+                            region: None,
+                        };
+                    }
+
+                    if ui
+                        .radio(
+                            matches!(self.client_ui.video_source, VideoSourceSettings::File(_)),
+                            "Video file",
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Video", &["mp4", "mkv", "avi", "mov", "webm"])
+                            .pick_file()
+                        {
+                            self.client_ui.video_source = VideoSourceSettings::File(path);
+                        }
+                    }
+
+                    if ui
+                        .radio(
+                            self.client_ui.video_source == VideoSourceSettings::TestPattern,
+                            VideoSourceSettings::TestPattern.to_string(),
+                        )
+                        .clicked()
+                    {
+                        self.client_ui.video_source = VideoSourceSettings::TestPattern;
+                    }
+                });
+
+                match &mut self.client_ui.video_source {
+                    VideoSourceSettings::Screen { monitor, region } => {
+                        egui::ComboBox::from_label("Monitor")
+                            .selected_text(monitor.clone().unwrap_or_else(|| "Primary".to_string()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(monitor, None, "Primary");
+
+                                match get_monitor_names() {
+                                    Ok(monitor_names) => {
+                                        for monitor_name in monitor_names {
+                                            ui.selectable_value(
+                                                monitor,
+                                                Some(monitor_name.clone()),
+                                                monitor_name,
+                                            );
+                                        }
+                                    },
+                                    Err(err) => {
+                                        ui.label(
+                                            RichText::from(err.to_string()).color(Color32::RED),
+                                        );
+                                    },
+                                }
+                            });
+
+                        let mut capture_region = region.is_some();
+
+                        ui.checkbox(&mut capture_region, "Capture region")
+                            .on_hover_text("Only capture a part of the monitor");
+
+                        if capture_region != region.is_some() {
+                            *region = capture_region.then(ScreenRegion::default);
+                        }
+
+                        if let Some(region) = region {
+                            ui.horizontal(|ui| {
+                                ui.label("Position");
+                                ui.add(egui::DragValue::new(&mut region.x).prefix("x: "));
+                                ui.add(egui::DragValue::new(&mut region.y).prefix("y: "));
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Size");
+                                ui.add(
+                                    egui::DragValue::new(&mut region.width)
+                                        .prefix("width: ")
+                                        .range(2..=u32::MAX),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut region.height)
+                                        .prefix("height: ")
+                                        .range(2..=u32::MAX),
+                                );
+                            });
+                        }
+                    },
+                    VideoSourceSettings::File(path) => {
+                        ui.label(RichText::from(path.display().to_string()).weak());
+                    },
+                    VideoSourceSettings::Camera | VideoSourceSettings::TestPattern => (),
+                }
+            });
+
+            ui.collapsing("Audio input", |ui| {
+                //The ongoing recordings reopen the device when these are modified
+                let mut input_device = self.client_ui.input_device.lock().unwrap();
+
+                egui::ComboBox::from_label("Input device")
+                    .selected_text(
+                        input_device
+                            .device
+                            .clone()
+                            .unwrap_or_else(|| "Default".to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        let mut selected_input_device = input_device.device.clone();
+
+                        ui.selectable_value(&mut selected_input_device, None, "Default");
+
+                        match get_input_devices() {
+                            Ok(input_devices) => {
+                                for device in input_devices {
+                                    ui.selectable_value(
+                                        &mut selected_input_device,
+                                        Some(device.name.clone()),
+                                        device.name,
+                                    );
+                                }
+                            },
+                            Err(err) => {
+                                ui.label(RichText::from(err.to_string()).color(Color32::RED));
+                            },
+                        }
+
+                        //Reset the config if the device has been changed, as the new device might not support it
+                        if selected_input_device != input_device.device {
+                            *input_device = InputDeviceSettings {
+                                device: selected_input_device,
+                                ..Default::default()
+                            };
+                        }
+                    });
+
+                egui::ComboBox::from_label("Sample rate")
+                    .selected_text(
+                        input_device
+                            .sample_rate
+                            .map(|sample_rate| format!("{sample_rate} Hz"))
+                            .unwrap_or_else(|| "Default".to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut input_device.sample_rate, None, "Default");
+
+                        match get_input_device_info(&input_device) {
+                            Ok(device_info) => {
+                                for sample_rate in device_info.sample_rates {
+                                    ui.selectable_value(
+                                        &mut input_device.sample_rate,
+                                        Some(sample_rate),
+                                        format!("{sample_rate} Hz"),
+                                    );
+                                }
+                            },
+                            Err(err) => {
+                                ui.label(RichText::from(err.to_string()).color(Color32::RED));
+                            },
+                        }
+                    });
+
+                egui::ComboBox::from_label("Channels")
+                    .selected_text(
+                        input_device
+                            .channels
+                            .map(|channels| channels.to_string())
+                            .unwrap_or_else(|| "Default".to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut input_device.channels, None, "Default");
+
+                        match get_input_device_info(&input_device) {
+                            Ok(device_info) => {
+                                for channels in device_info.channels {
+                                    ui.selectable_value(
+                                        &mut input_device.channels,
+                                        Some(channels),
+                                        channels.to_string(),
+                                    );
+                                }
+                            },
+                            Err(err) => {
+                                ui.label(RichText::from(err.to_string()).color(Color32::RED));
+                            },
+                        }
+                    });
+            });
+
+            ui.collapsing("Audio output", |ui| {
+                //Changing the output device would stop the audio of the ongoing call
+                ui.add_enabled_ui(self.server_session.voip.is_none(), |ui| {
+                    let mut selected_output_device = self.client_ui.output_device.clone();
+
+                    egui::ComboBox::from_label("Output device")
+                        .selected_text(
+                            selected_output_device
+                                .clone()
+                                .unwrap_or_else(|| "Default".to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut selected_output_device, None, "Default");
+
+                            match get_output_device_names() {
+                                Ok(device_names) => {
+                                    for device_name in device_names {
+                                        ui.selectable_value(
+                                            &mut selected_output_device,
+                                            Some(device_name.clone()),
+                                            device_name,
+                                        );
+                                    }
+                                },
+                                Err(err) => {
+                                    ui.label(RichText::from(err.to_string()).color(Color32::RED));
+                                },
+                            }
+                        });
+
+                    if selected_output_device != self.client_ui.output_device {
+                        match AudioPlayback::new(selected_output_device.as_deref()) {
+                            Ok(audio_playback) => {
+                                self.client_ui.audio_playback = audio_playback;
+                                self.client_ui.output_device = selected_output_device;
+                            },
+                            Err(err) => {
+                                display_error_message(err, self.toasts.clone());
+                            },
+                        }
+                    }
+                })
+                .response
+                .on_disabled_hover_text("Leave the call to change the output device");
+            });
+
+            ui.collapsing("Audio processing", |ui| {
+                let mut audio_processing = self.client_ui.audio_processing.lock().unwrap();
+
+                ui.checkbox(&mut audio_processing.high_pass_filter, "High-pass filter")
+                    .on_hover_text("Removes low frequency rumble from the microphone's audio");
+
+                ui.checkbox(&mut audio_processing.noise_suppression, "Noise suppression")
+                    .on_hover_text("Quiets the background noise between words");
+
+                ui.checkbox(
+                    &mut audio_processing.automatic_gain_control,
+                    "Automatic gain control",
+                )
+                .on_hover_text("Keeps your voice at a steady volume");
+
+                ui.label(
+                    RichText::from("These are applied to both calls and voice messages.").weak(),
+                );
+            });
         });
     }
 
@@ -503,18 +1162,32 @@ impl backend::Application
     {
         let username = self.login_username.clone();
 
-        let sender = self.connection_sender.clone();
+        let sender = self.server_session.connection_sender.clone();
 
         //Reset shutdown token
-        self.autosync_shutdown_token = CancellationToken::new();
+        self.server_session.autosync_shutdown_token = CancellationToken::new();
 
         //Clone ctx so we can call request repaint from another thread
         let ctx = ctx.clone();
 
         let user_information = self.opened_user_information.clone();
 
-        //Reset all messages and everything else
-        self.client_ui.incoming_messages = ServerMaster::default();
+        //Display the cached messages instantly, the server will only send the ones which arent cached
+        self.server_session.incoming_messages = self.load_message_cache(&address);
+
+        let client_message_counter = Some(self.server_session.incoming_messages.message_list.len())
+            .filter(|message_count| *message_count != 0);
+
+        let server_instance_id = Some(
+            self.server_session
+                .incoming_messages
+                .server_instance_id
+                .clone(),
+        )
+        .filter(|server_instance_id| !server_instance_id.is_empty());
+
+        let invite_token =
+            Some(self.server_session.invite_token.clone()).filter(|token| !token.is_empty());
 
         //Forget all imaes so the cached imges will be deleted
         ctx.forget_all_images();
@@ -528,6 +1201,10 @@ impl backend::Application
                 password,
                 &user_information.uuid,
                 user_information.profile,
+                user_information.profile_pictures,
+                client_message_counter,
+                server_instance_id,
+                invite_token,
             )
             .await
             {
@@ -550,28 +1227,28 @@ impl backend::Application
         });
 
         //reset autosync
-        self.server_sender_thread = None;
+        self.server_session.server_sender_thread = None;
 
-        self.client_connection.state = ConnectionState::Connecting;
+        self.server_session.client_connection.state = ConnectionState::Connecting;
     }
 
-    fn disconnect_from_server(&mut self)
+    fn disconnect_from_server(&mut self, session: &mut ServerSession)
     {
         let username = self.login_username.clone();
 
-        let mut connection = self.client_connection.clone();
+        let mut connection = session.client_connection.clone();
 
-        let password = self.client_connection.password.clone();
+        let password = session.client_connection.password.clone();
 
         let uuid = self.opened_user_information.uuid.clone();
 
         let toasts = self.toasts.clone();
 
         //Shut down threadsa nad reset state
-        self.reset_client_connection();
+        self.reset_client_connection(session);
 
-        self.voip_shutdown_token.cancel();
-        self.voip_video_shutdown_token.cancel();
+        session.voip_shutdown_token.cancel();
+        session.voip_video_shutdown_token.cancel();
 
         //Disconnect from server
         tokio::task::spawn(async move {
@@ -586,7 +1263,505 @@ impl backend::Application
         });
 
         //Reset client, as we are already disconnecting above
-        self.client_connection.reset_state();
+        session.client_connection.reset_state();
+    }
+
+    /// Switches to the offline state after the connection has dropped, the messages are kept so that the ui stays usable while we try to reconnect
+    pub fn start_reconnecting(&mut self, session: &mut ServerSession)
+    {
+        //Save what we have received so far, the rest will be synced after reconnecting
+        self.save_message_cache(session);
+
+        //Signal the remaining threads to be shut down
+        session.autosync_shutdown_token.cancel();
+        session.server_sender_thread = None;
+
+        //The voip call cant continue without the server
+        session.voip_shutdown_token.cancel();
+        session.voip_video_shutdown_token.cancel();
+
+        //Signal the voice recorder function to stop, the recorder is only running for the session which has started the call
+        if session.voip_thread.is_some() {
+            let _ = self.record_audio_interrupter.send(());
+        }
+
+        session.voip = None;
+        session.voip_thread = None;
+
+        //The ongoing calls will be synced again after reconnecting
+        session.incoming_messages.ongoing_voip_call = Default::default();
+
+        session.client_connection.state = ConnectionState::Reconnecting;
+        session.client_connection.reconnection = Some(Reconnection::default());
+    }
+
+    /// Makes a reconnection attempt if the connection has dropped and the next attempt is due
+    /// We authenticate again, and only ask the server for the messages we have missed
+    fn reconnect_to_server(&mut self, session: &mut ServerSession, ctx: &egui::Context)
+    {
+        let Some(reconnection) = &mut session.client_connection.reconnection
+        else {
+            return;
+        };
+
+        if !reconnection.is_due() {
+            //Make sure the ui is updated when the attempt is due
+            if !reconnection.in_progress {
+                ctx.request_repaint_after(
+                    reconnection
+                        .next_attempt_at
+                        .saturating_duration_since(std::time::Instant::now()),
+                );
+            }
+
+            return;
+        }
+
+        reconnection.in_progress = true;
+
+        //Reset shutdown token, so that the threads of the new connection can be spawned
+        session.autosync_shutdown_token = CancellationToken::new();
+        session.server_sender_thread = None;
+
+        let address = session.send_on_ip.clone();
+        let password = session.client_password.clone();
+        let username = self.login_username.clone();
+        let user_information = self.opened_user_information.clone();
+        let client_message_counter = session.incoming_messages.message_list.len();
+        let server_instance_id = session.incoming_messages.server_instance_id.clone();
+        let invite_token = Some(session.invite_token.clone()).filter(|token| !token.is_empty());
+        let sender = session.connection_sender.clone();
+        let refused = reconnection.refused.clone();
+        let toasts = self.toasts.clone();
+        let ctx = ctx.clone();
+
+        tokio::task::spawn(async move {
+            match ClientConnection::connect_to_server(
+                address,
+                username,
+                Some(password),
+                &user_information.uuid,
+                user_information.profile,
+                user_information.profile_pictures,
+                Some(client_message_counter),
+                Some(server_instance_id),
+                invite_token,
+            )
+            .await
+            {
+                Ok(ok) => {
+                    if let Err(err) = sender.send(Some(ok)) {
+                        tracing::error!("{}", err);
+                    };
+                },
+                Err(err) => {
+                    //The server would refuse the next attempts too, so the user is notified instead
+                    if err.downcast_ref::<ConnectionRefusedError>().is_some() {
+                        refused.store(true, std::sync::atomic::Ordering::Relaxed);
+
+                        display_error_message(format!("Stopped reconnecting: {err}"), toasts);
+                    }
+                    else {
+                        tracing::error!("Reconnection attempt failed: {err}");
+                    }
+
+                    if let Err(err) = sender.send(None) {
+                        tracing::error!("{}", err);
+                    };
+                },
+            };
+
+            ctx.request_repaint();
+        });
+    }
+
+    /// Lists the saved servers grouped by their folders, the bookmarks can be edited in a separate window
+    fn bookmarks_window(&mut self, ctx: &egui::Context)
+    {
+        let mut bookmark_mode = self.main.bookmark_mode;
+
+        egui::Window::new("Bookmarks")
+            .open(&mut bookmark_mode)
+            .show(ctx, |ui| {
+                ui.label(RichText::from("Saved servers"));
+
+                if ui.button("Save ip address").clicked() {
+                    let address = self.server_session.send_on_ip.trim().to_string();
+
+                    if address.is_empty() {
+                        display_info_message(
+                            "Enter the address of the server, then save it.",
+                            self.toasts.clone(),
+                        );
+                    }
+                    else if self
+                        .opened_user_information
+                        .bookmark_mut(&address)
+                        .is_none()
+                    {
+                        self.opened_user_information.add_bookmark_entry(address);
+
+                        self.save_user_information();
+                    }
+                };
+
+                ui.separator();
+
+                //The bookmarks without a folder are displayed first
+                let mut folders: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+                for (index, bookmark) in self.opened_user_information.bookmarks.iter().enumerate() {
+                    folders
+                        .entry(bookmark.folder.trim().to_string())
+                        .or_default()
+                        .push(index);
+                }
+
+                ui.group(|ui| {
+                    if folders.is_empty() {
+                        ui.label(RichText::from("Add your favorite servers!").strong());
+
+                        return;
+                    }
+
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        for (folder, indexes) in folders {
+                            if folder.is_empty() {
+                                for index in indexes {
+                                    self.bookmark_entry_ui(ui, index);
+                                }
+                            }
+                            else {
+                                ui.collapsing(folder, |ui| {
+                                    for index in indexes {
+                                        self.bookmark_entry_ui(ui, index);
+                                    }
+                                });
+                            }
+                        }
+                    });
+                });
+            });
+
+        self.main.bookmark_mode = bookmark_mode;
+
+        self.bookmark_editor_window(ctx);
+    }
+
+    fn bookmark_entry_ui(&mut self, ui: &mut egui::Ui, index: usize)
+    {
+        //The bookmark might have been deleted this frame
+        let Some(bookmark) = self.opened_user_information.bookmarks.get(index).cloned()
+        else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            let last_connected = bookmark.last_connected.map_or_else(
+                || "Never connected".to_string(),
+                |last_connected| {
+                    format!(
+                        "Last connected: {}",
+                        last_connected
+                            .with_timezone(&chrono::Local)
+                            .format("%Y.%m.%d. %H:%M")
+                    )
+                },
+            );
+
+            if ui
+                .button(RichText::from(bookmark.display_name()))
+                .on_hover_text(format!("{}\n{last_connected}", bookmark.address))
+                .clicked()
+            {
+                self.server_session.send_on_ip.clone_from(&bookmark.address);
+                self.server_session.invite_token.clear();
+
+                if let Some(password) = &bookmark.password {
+                    self.server_session.client_password.clone_from(password);
+                }
+            }
+
+            ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                if ui.button(RichText::from("-").strong()).clicked() {
+                    self.opened_user_information.delete_bookmark_entry(index);
+
+                    //The indexes of the bookmarks after the deleted one have changed
+                    self.main.edited_bookmark = None;
+
+                    self.save_user_information();
+                }
+
+                if ui.button("Edit").clicked() {
+                    self.main.edited_bookmark = Some((index, bookmark.clone()));
+                }
+
+                if bookmark.auto_connect {
+                    ui.label(RichText::from("Auto-connect").weak())
+                        .on_hover_text("Connects to the server after logging in");
+                }
+            });
+        });
+    }
+
+    /// The changes are only saved to the user's file after clicking save
+    fn bookmark_editor_window(&mut self, ctx: &egui::Context)
+    {
+        let Some((index, mut bookmark)) = self.main.edited_bookmark.take()
+        else {
+            return;
+        };
+
+        let mut is_open = true;
+        let mut is_finished = false;
+
+        egui::Window::new("Edit bookmark")
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                egui::Grid::new("bookmark_editor").num_columns(2).show(ui, |ui| {
+                    ui.label("Name");
+                    ui.add(TextEdit::singleline(&mut bookmark.name).hint_text(bookmark.address.as_str()));
+                    ui.end_row();
+
+                    ui.label("Address");
+                    ui.text_edit_singleline(&mut bookmark.address);
+                    ui.end_row();
+
+                    ui.label("Folder");
+                    ui.add(TextEdit::singleline(&mut bookmark.folder).hint_text("None"));
+                    ui.end_row();
+
+                    ui.label("Password");
+                    ui.horizontal(|ui| {
+                        let mut save_password = bookmark.password.is_some();
+
+                        ui.checkbox(&mut save_password, "Save");
+
+                        if save_password {
+                            let password = bookmark.password.get_or_insert_with(String::new);
+
+                            ui.add(TextEdit::singleline(password).password(true));
+                        }
+                        else {
+                            bookmark.password = None;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Server identifier");
+                    ui.horizontal(|ui| {
+                        match &bookmark.fingerprint {
+                            Some(fingerprint) => {
+                                ui.label(RichText::from(&fingerprint[..16.min(fingerprint.len())]).monospace())
+                                    .on_hover_text(fingerprint.as_str());
+
+                                if ui
+                                    .button("Forget")
+                                    .on_hover_text("The identifier is saved again the next time you connect")
+                                    .clicked()
+                                {
+                                    bookmark.fingerprint = None;
+                                }
+                            },
+                            None => {
+                                ui.label(RichText::from("Saved when connecting").weak());
+                            },
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                ui.checkbox(&mut bookmark.auto_connect, "Connect after logging in");
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        bookmark.address = bookmark.address.trim().to_string();
+
+                        if let Some(saved_bookmark) =
+                            self.opened_user_information.bookmarks.get_mut(index)
+                        {
+                            *saved_bookmark = bookmark.clone();
+
+                            self.save_user_information();
+                        }
+
+                        is_finished = true;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        is_finished = true;
+                    }
+                });
+            });
+
+        if is_open && !is_finished {
+            self.main.edited_bookmark = Some((index, bookmark));
+        }
+    }
+
+    /// Connects to the bookmarks which are set to auto-connect, every server is opened in its own session
+    pub fn auto_connect_bookmarks(&mut self, ctx: &egui::Context)
+    {
+        let bookmarks: Vec<Bookmark> = self
+            .opened_user_information
+            .bookmarks
+            .iter()
+            .filter(|bookmark| bookmark.auto_connect)
+            .cloned()
+            .collect();
+
+        for (index, bookmark) in bookmarks.into_iter().enumerate() {
+            //The first server is opened in the current session
+            if index != 0 {
+                self.server_sessions.push(ServerSession::default());
+
+                self.switch_server(self.server_sessions.len() - 1);
+            }
+
+            self.server_session.send_on_ip.clone_from(&bookmark.address);
+            self.server_session.invite_token.clear();
+            self.server_session.client_password = bookmark.password.unwrap_or_default();
+
+            self.connect_to_server(
+                ctx,
+                bookmark.address,
+                Some(self.server_session.client_password.clone()),
+            );
+        }
+    }
+
+    /// Updates the bookmark of the server after connecting to it
+    /// The identifier of the server is saved when first connecting, after that the user is warned if it changes
+    /// The identifier is only a hint that a different server answers on the address (ie. it has been reinstalled), it doesnt prove the identity of the server
+    /// It is received after the credentials have been sent, and any server which has seen it can send the same identifier
+    fn update_connected_bookmark(&mut self, address: &str, server_fingerprint: &str)
+    {
+        let Some(bookmark) = self.opened_user_information.bookmark_mut(address)
+        else {
+            return;
+        };
+
+        bookmark.last_connected = Some(chrono::Utc::now());
+
+        //The older servers dont send an identifier
+        if !server_fingerprint.is_empty() {
+            match &bookmark.fingerprint {
+                Some(fingerprint) if fingerprint != server_fingerprint => {
+                    display_error_message(
+                        format!(
+                            "The identifier of {} has changed, a different server might be answering on its address! If the server has been reinstalled, forget its identifier in the bookmark's settings.",
+                            bookmark.display_name()
+                        ),
+                        self.toasts.clone(),
+                    );
+                },
+                Some(_) => {},
+                None => bookmark.fingerprint = Some(server_fingerprint.to_string()),
+            }
+        }
+
+        self.save_user_information();
+    }
+
+    /// Stops the threads of every server, and returns to the login page
+    pub fn log_out(&mut self)
+    {
+        self.server_session.autosync_shutdown_token.cancel();
+        self.server_session.server_sender_thread = None;
+
+        self.save_message_cache(&self.server_session);
+
+        //Stop the threads of the servers which arent opened
+        for session in std::mem::take(&mut self.server_sessions) {
+            self.save_message_cache(&session);
+
+            session.autosync_shutdown_token.cancel();
+        }
+
+        //Dont keep the entered passwords after logging out
+        self.account_management = Default::default();
+
+        self.main.client_mode = false;
+    }
+
+    /// Overwrites the user's file with the opened user's information
+    pub fn save_user_information(&self)
+    {
+        let _ = self
+            .opened_user_information
+            .write_file(self.opened_user_information.path.clone())
+            .tap_err_dbg(|err| tracing::error!("{err}"));
+    }
+
+    /// Lists the servers which have announced themselves on the local network, they can be joined with one click
+    fn discovered_servers_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context)
+    {
+        let mut discovered_servers: Vec<(String, DiscoveredServer)> = self
+            .discovered_servers
+            .iter()
+            .filter(|discovered_server| !discovered_server.is_timed_out())
+            .map(|discovered_server| {
+                (
+                    discovered_server.key().clone(),
+                    discovered_server.value().clone(),
+                )
+            })
+            .collect();
+
+        if discovered_servers.is_empty() {
+            ui.label(RichText::from("No servers have been found on the local network.").weak());
+
+            return;
+        }
+
+        discovered_servers.sort_by(|(_, a), (_, b)| a.announcement.name.cmp(&b.announcement.name));
+
+        let can_join = matches!(
+            self.server_session.client_connection.state,
+            ConnectionState::Disconnected | ConnectionState::Error
+        );
+
+        for (address, discovered_server) in discovered_servers {
+            let announcement = discovered_server.announcement;
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::from(&announcement.name).strong());
+                ui.label(RichText::from(&address).weak());
+
+                if announcement.requires_password {
+                    ui.label("🔒").on_hover_text("A password is needed to join");
+                }
+
+                ui.label(format!("👥 {}", announcement.connected_clients));
+
+                if ui
+                    .add_enabled(can_join, egui::Button::new("Join"))
+                    .clicked()
+                {
+                    self.server_session.send_on_ip.clone_from(&address);
+                    self.server_session.invite_token.clear();
+
+                    //The password has to be entered before connecting
+                    if announcement.requires_password
+                        && self.server_session.client_password.is_empty()
+                    {
+                        display_info_message(
+                            "Enter the password of the server, then connect.",
+                            self.toasts.clone(),
+                        );
+                    }
+                    else {
+                        self.connect_to_server(
+                            ctx,
+                            address.clone(),
+                            Some(self.server_session.client_password.clone()),
+                        );
+                    }
+                }
+            });
+        }
     }
 
     /// Draw the extension part of the ui in the settings
@@ -863,3 +2038,42 @@ pub fn read_extensions_dir() -> anyhow::Result<Vec<ExtensionProperties>>
 
     Ok(extensions)
 }
+
+/// Returns the text displaying the state of the reconnection
+pub fn reconnection_status(client_connection: &ClientConnection) -> String
+{
+    match &client_connection.reconnection {
+        Some(reconnection) if reconnection.in_progress => {
+            format!("Reconnecting (attempt {})...", reconnection.attempt + 1)
+        },
+        Some(reconnection) => {
+            format!(
+                "Connection lost, reconnecting in {}s",
+                reconnection
+                    .next_attempt_at
+                    .saturating_duration_since(std::time::Instant::now())
+                    .as_secs()
+                    + 1
+            )
+        },
+        None => String::from("Reconnecting"),
+    }
+}
+
+/// Saves the messages of the server to its cache, nothing is saved if there arent any messages
+pub fn save_message_cache(
+    user_information: &UserInformation,
+    address: &str,
+    messages: &ServerMaster,
+)
+{
+    if messages.message_list.is_empty() {
+        return;
+    }
+
+    if let Err(err) = MessageCache::new(user_information, address)
+        .and_then(|message_cache| message_cache.save_messages(messages))
+    {
+        tracing::error!("Failed to save the message cache: {err}");
+    }
+}
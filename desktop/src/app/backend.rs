@@ -3,7 +3,20 @@ use super::{
     lua::{Extension, LuaOutput},
     read_extensions_dir,
     server::SharedFields,
-    ui::register::create_dynamic_image_from_bytes,
+    ui::{
+        client_ui::client_actions::{
+            audio_processing::AudioProcessingSettings,
+            audio_recording::InputDeviceSettings,
+            call_recording::CallRecorder,
+            connection_quality::ConnectionQuality,
+            jitter_buffer::{AudioFrame, RemoteSpeakers},
+            lan_discovery::DiscoveredServer,
+            outbox::Outbox,
+            video_quality::{ReceiverReport, ReceiverStatistics, VideoQualityController},
+            video_source::{VideoSource, VideoSourceSettings},
+        },
+        register::create_dynamic_image_from_bytes,
+    },
 };
 use aes_gcm::{
     aead::{generic_array::GenericArray, Aead, KeyInit},
@@ -13,10 +26,12 @@ use anyhow::{bail, ensure, Error, Result};
 use argon2::Config;
 use base64::{engine::general_purpose, Engine};
 use chrono::{DateTime, NaiveDate, Utc};
-use dashmap::DashMap;
+use cpal::traits::{DeviceTrait, HostTrait};
+use dashmap::{DashMap, DashSet};
 use egui::{
     load::{BytesPoll, LoadError},
-    vec2, Align2, Color32, FontId, Image, Pos2, Rect, Response, RichText, Stroke, Ui, Vec2,
+    vec2, Align2, Color32, FontId, Image, Pos2, Rect, Response, RichText, Stroke, TextureHandle,
+    Ui, Vec2,
 };
 use egui_notify::{Toast, Toasts};
 use image::{DynamicImage, ImageOutputFormat};
@@ -24,23 +39,24 @@ use indexmap::IndexMap;
 use mlua::Lua;
 use mlua_proc_macro::ToTable;
 use rand::{rngs::ThreadRng, Rng};
+use reed_solomon_erasure::galois_8::ReedSolomon;
 use regex::Regex;
 use rfd::FileDialog;
 use rodio::{OutputStream, OutputStreamHandle, Sink};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     env,
     fmt::{Debug, Display},
     fs,
     io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
-    net::SocketAddr,
-    path::PathBuf,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, AtomicI64},
         mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use strum::{EnumDiscriminants, EnumMessage};
 use strum_macros::EnumString;
@@ -53,7 +69,6 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
-use wincam::Webcam;
 
 #[derive(serde::Deserialize, serde::Serialize, ToTable, Clone)]
 #[serde(default)]
@@ -104,6 +119,11 @@ pub struct Application
     /// This field gets directly modified by the server thread
     pub server_connected_clients_profile: Arc<DashMap<String, ClientProfile>>,
 
+    #[serde(skip)]
+    /// The profile pictures of the clients who have connected to the server, paired with their sha256 hash
+    /// This field gets directly modified by the server thread
+    pub server_profile_pictures: Arc<DashMap<String, Vec<u8>>>,
+
     ///Public ip address, checked by pinging external website
     #[serde(skip)]
     pub public_ip: String,
@@ -111,17 +131,24 @@ pub struct Application
     ///server settings
     pub server_req_password: bool,
 
-    ///Server shutdown handler channel
-    #[serde(skip)]
-    pub server_shutdown_token: CancellationToken,
+    ///The name of the server, which is displayed to the clients finding it on the local network
+    pub server_name: String,
 
-    ///Voip thread shutdown token
-    #[serde(skip)]
-    pub voip_shutdown_token: CancellationToken,
+    ///Whether the server announces itself on the local network
+    pub server_lan_discovery: bool,
 
-    ///Webcam recorder shutdown token
+    ///How many hours the minted invites are valid for, the invites dont expire if this is 0
+    pub invite_expiry_hours: u32,
+
+    ///How many clients can join with a minted invite, the invites can be used any amount of times if this is 0
+    pub invite_max_uses: u32,
+
+    ///The role of the clients joining with the minted invites
+    pub invite_role: ClientRole,
+
+    ///Server shutdown handler channel
     #[serde(skip)]
-    pub voip_video_shutdown_token: CancellationToken,
+    pub server_shutdown_token: CancellationToken,
 
     ///What is the server's password set to
     pub server_password: String,
@@ -159,6 +186,9 @@ pub struct Application
     #[serde(skip)]
     pub register: Register,
 
+    #[serde(skip)]
+    pub account_management: AccountManagement,
+
     /*
         Main
     */
@@ -171,9 +201,10 @@ pub struct Application
     #[table(skip)]
     pub client_ui: Client,
 
-    #[table(save)]
-    #[serde(skip)]
-    pub client_connection: ClientConnection,
+    /// The state of the opened server, the servers which arent opened are stored in ```server_sessions```
+    //This is exported as its own table in ```set_global_lua_table```
+    #[table(skip)]
+    pub server_session: ServerSession,
 
     ///data sync
     #[serde(skip)]
@@ -181,13 +212,6 @@ pub struct Application
     #[serde(skip)]
     pub dtx: Arc<mpsc::Sender<String>>,
 
-    /// Server connection
-    /// This channel hosts a Client connection and the sync message sent by the server in a String format
-    #[serde(skip)]
-    pub connection_receiver: Arc<mpsc::Receiver<Option<(ClientConnection, String)>>>,
-    #[serde(skip)]
-    pub connection_sender: mpsc::Sender<Option<(ClientConnection, String)>>,
-
     /// Voip (UdpSocket) maker
     /// When a successful ```Voip``` instance is created it is sent over from the async thread
     #[serde(skip)]
@@ -195,29 +219,29 @@ pub struct Application
     #[serde(skip)]
     pub voip_connection_sender: mpsc::Sender<Voip>,
 
-    ///Server - client syncing thread
+    ///Voip image sender thread
     #[serde(skip)]
-    pub server_sender_thread: Option<()>,
+    pub voip_video_thread: Option<()>,
 
-    /// Voip audio sender thread
+    /// The messages which havent been acknowledged by the server yet, this is loaded when logging in
     #[serde(skip)]
-    pub voip_thread: Option<()>,
+    pub outbox: Arc<Mutex<Outbox>>,
 
-    ///Voip image sender thread
+    /// The servers we are connected to besides the opened one
     #[serde(skip)]
-    pub voip_video_thread: Option<()>,
+    pub server_sessions: Vec<ServerSession>,
 
+    /// The servers which have announced themselves on the local network, the key is their address
     #[serde(skip)]
-    /// This is what the main thread uses to receive messages from the sync thread
-    pub server_output_receiver: Arc<Receiver<Option<String>>>,
+    pub discovered_servers: Arc<DashMap<String, DiscoveredServer>>,
 
+    /// This is ```Some``` while we are listening for the servers' announcements
     #[serde(skip)]
-    /// This is what the sync thread uses to send messages to the main thread
-    pub server_output_sender: Sender<Option<String>>,
+    pub lan_discovery_thread: Option<()>,
 
+    /// The shutdown token of the thread listening for the servers' announcements
     #[serde(skip)]
-    /// This is what the main thread uses to send the shutdown message to the sync thread
-    pub autosync_shutdown_token: CancellationToken,
+    pub lan_discovery_shutdown_token: CancellationToken,
 
     #[serde(skip)]
     pub audio_file: Arc<Mutex<PathBuf>>,
@@ -236,27 +260,19 @@ impl Default for Application
 
         let (audio_bytes_tx, audio_bytes_rx) = mpsc::channel::<Vec<u8>>();
 
-        let (connection_sender, connection_receiver) =
-            mpsc::channel::<Option<(ClientConnection, String)>>();
-
-        let (server_output_sender, server_output_receiver) = mpsc::channel::<Option<String>>();
-
         let (voip_connection_sender, voip_connection_receiver) = mpsc::channel::<Voip>();
 
         Self {
             voip_video_thread: None,
-            voip_video_shutdown_token: CancellationToken::new(),
             startup_args: None,
             record_audio_interrupter: mpsc::channel::<()>().0,
             toasts: Arc::new(Mutex::new(Toasts::new())),
 
-            voip_shutdown_token: CancellationToken::new(),
-            voip_thread: None,
-
             //Make it so we can import any kind of library
             lua: unsafe { Arc::new(Lua::unsafe_new()) },
 
             register: Register::default(),
+            account_management: AccountManagement::default(),
 
             audio_file: Arc::new(Mutex::new(PathBuf::from(format!(
                 "{}\\Matthias\\Client\\voice_recording.wav",
@@ -276,6 +292,11 @@ impl Default for Application
 
             //server settings
             server_req_password: false,
+            server_name: String::new(),
+            server_lan_discovery: true,
+            invite_expiry_hours: 24,
+            invite_max_uses: 0,
+            invite_role: ClientRole::default(),
             server_password: String::default(),
             open_on_port: String::default(),
 
@@ -302,31 +323,27 @@ impl Default for Application
 
             //client main
             client_ui: Client::default(),
-
-            client_connection: ClientConnection::default(),
+            server_session: ServerSession::default(),
 
             //font
             font_size: 20.,
 
             //emoji button
 
-            //Server connection
-            connection_sender,
-            connection_receiver: Arc::new(connection_receiver),
-
             //data sync
             drx: Arc::new(drx),
             dtx: Arc::new(dtx),
-            server_sender_thread: None,
-
-            server_output_receiver: Arc::new(server_output_receiver),
-            server_output_sender,
 
             voip_connection_receiver: Arc::new(voip_connection_receiver),
             voip_connection_sender,
 
-            autosync_shutdown_token: CancellationToken::new(),
+            outbox: Arc::new(Mutex::new(Outbox::default())),
+            server_sessions: Vec::new(),
+            discovered_servers: Arc::new(DashMap::new()),
+            lan_discovery_thread: None,
+            lan_discovery_shutdown_token: CancellationToken::new(),
             server_connected_clients_profile: Arc::new(DashMap::new()),
+            server_profile_pictures: Arc::new(DashMap::new()),
             opened_user_information: UserInformation::default(),
         }
     }
@@ -340,7 +357,11 @@ impl Application
     {
         self.client_ui.clone().set_lua_table_function(&self.lua);
         self.clone().set_lua_table_function(&self.lua);
-        self.client_connection
+        self.server_session
+            .clone()
+            .set_lua_table_function(&self.lua);
+        self.server_session
+            .client_connection
             .clone()
             .set_lua_table_function(&self.lua);
         self.opened_user_information
@@ -367,6 +388,18 @@ impl Application
                 },
             }
 
+            //Open the output device selected in the settings
+            if let Some(output_device) = data.client_ui.output_device.clone() {
+                match AudioPlayback::new(Some(&output_device)) {
+                    Ok(audio_playback) => {
+                        data.client_ui.audio_playback = audio_playback;
+                    },
+                    Err(err) => {
+                        tracing::error!("{}", err);
+                    },
+                }
+            }
+
             let output_list = data.client_ui.extension.output.clone();
 
             return set_lua_functions(data, output_list, cc);
@@ -770,15 +803,6 @@ pub struct Client
     ///This checks if the text editor is open or not
     pub usr_msg_expanded: bool,
 
-    ///This is the full address of the destination a message is supposed to be sent to
-    pub send_on_ip: String,
-
-    ///self.send_on_ip encoded into base64, this is supposedly for ease of use, I dont know why its even here
-    pub send_on_ip_base64_encoded: String,
-
-    ///The password the user has entered for server auth
-    pub client_password: String,
-
     ///This gem of a variable is used to contain animation's state
     pub animation_state: f32,
 
@@ -801,15 +825,6 @@ pub struct Client
     #[table(save)]
     pub message_buffer: String,
 
-    ///Incoming messages, this is the whole packet which get sent to all the clients, this contains all the messages, and the info about them
-    #[serde(skip)]
-    #[table(save)]
-    pub incoming_messages: ServerMaster,
-
-    /// Last seen message's index, this will get sent
-    #[serde(skip)]
-    pub last_seen_msg_index: Arc<Mutex<usize>>,
-
     ///emoji fasz
     pub random_emoji: String,
     pub emoji: Vec<String>,
@@ -826,11 +841,62 @@ pub struct Client
     #[table(save)]
     pub voice_recording_start: Option<DateTime<Utc>>,
 
-    #[serde(skip)]
-    pub voip: Option<Voip>,
-
     /// This entry contains the volume percentage of the microphone, this is modified in the settings
     pub microphone_volume: Arc<AtomicI64>,
+
+    /// The ```VoipMixingMode``` of the calls we create, this is ignored when joining an ongoing call
+    pub voip_mixing_mode: VoipMixingMode,
+
+    /// This entry contains the voice activation settings, this is modified in the settings
+    pub voice_activation: Arc<Mutex<VoiceActivationSettings>>,
+
+    /// This entry contains which stages of the microphone's audio processing are enabled, this is modified in the settings
+    /// These are applied to both voip calls and voice messages
+    pub audio_processing: Arc<Mutex<AudioProcessingSettings>>,
+
+    /// The name of the output device selected in the settings, if this is ```None``` the default output device is used
+    pub output_device: Option<String>,
+
+    /// The input device and its config selected in the settings, the ongoing recordings reopen the device if this is modified
+    pub input_device: Arc<Mutex<InputDeviceSettings>>,
+
+    /// This entry contains the local volume and mute of the participants of voip calls, paired with their uuid
+    pub participant_audio: Arc<DashMap<String, ParticipantAudioSettings>>,
+
+    /// The video source opened when the video is turned on in a call, this is modified in the settings
+    pub video_source: VideoSourceSettings,
+
+    /// Whether the received video streams are recorded alongside the audio when recording a call
+    pub record_call_video: bool,
+
+    /// The invitation we have sent, this is ```Some``` until the invited client answers it
+    #[serde(skip)]
+    pub outgoing_call_invitation: Option<OutgoingCallInvitation>,
+}
+
+/// An invitation to a private call we have received
+#[derive(Debug, Clone)]
+pub struct IncomingCallInvitation
+{
+    /// The uuid of the client who has invited us
+    pub caller: String,
+
+    /// The name of the private voice room we were invited to
+    pub room: String,
+
+    /// When the invitation has arrived, the server cancels the invitation after ```INVITATION_TIMEOUT```
+    pub received_at: Instant,
+}
+
+/// An invitation to a private call we have sent
+#[derive(Debug, Clone)]
+pub struct OutgoingCallInvitation
+{
+    /// The uuid of the client we have invited
+    pub invitee: String,
+
+    /// The name of the private voice room we have created for the call
+    pub room: String,
 }
 
 impl Default for Client
@@ -865,9 +931,6 @@ impl Default for Client
             animation_state: 0.0,
             drop_file_animation: false,
             usr_msg_expanded: false,
-            send_on_ip: String::new(),
-            send_on_ip_base64_encoded: String::new(),
-            client_password: String::new(),
             emoji: vec![
                 "😐", "😍", "😉", "😈", "😇", "😆", "😅", "😄", "😃", "😂", "😁", "😀",
             ]
@@ -880,13 +943,19 @@ impl Default for Client
 
             //msg
             message_buffer: String::new(),
-            incoming_messages: ServerMaster::default(),
 
             voice_recording_start: None,
-            last_seen_msg_index: Arc::new(Mutex::new(0)),
             emoji_selector_index: 0,
-            voip: None,
             microphone_volume: Arc::new(AtomicI64::new(100)),
+            voip_mixing_mode: VoipMixingMode::default(),
+            voice_activation: Arc::new(Mutex::new(VoiceActivationSettings::default())),
+            audio_processing: Arc::new(Mutex::new(AudioProcessingSettings::default())),
+            output_device: None,
+            input_device: Arc::new(Mutex::new(InputDeviceSettings::default())),
+            participant_audio: Arc::new(DashMap::new()),
+            video_source: VideoSourceSettings::default(),
+            record_call_video: false,
+            outgoing_call_invitation: None,
         }
     }
 }
@@ -903,6 +972,10 @@ pub struct Main
     #[serde(skip)]
     pub bookmark_mode: bool,
 
+    ///The index and the unsaved copy of the bookmark opened in the editor
+    #[serde(skip)]
+    pub edited_bookmark: Option<(usize, Bookmark)>,
+
     ///Client mode main switch
     #[serde(skip)]
     pub client_mode: bool,
@@ -911,6 +984,27 @@ pub struct Main
     pub register_mode: bool,
 }
 
+///The state of the account management part of the settings, and the account import of the login page
+#[derive(Clone, Debug, Default)]
+pub struct AccountManagement
+{
+    /// The edited copy of the profile and its pictures, this is ```None``` until the user starts editing it
+    pub edited_profile: Option<(ClientProfile, ProfilePictures)>,
+
+    pub current_password: String,
+    pub new_password: String,
+    pub new_password_again: String,
+
+    /// The password the exported bundle is encrypted with
+    pub bundle_password: String,
+
+    /// The bundle selected for importing
+    pub imported_bundle_path: Option<PathBuf>,
+
+    /// The password entered to confirm the deletion of the account
+    pub delete_password: String,
+}
+
 ///All the stuff important to the registration process
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct Register
@@ -1036,11 +1130,36 @@ pub struct ClientProfile
     /// The client's birthdate
     pub birth_date: NaiveDate,
 
-    /// This entry hold the profile's 64x64 profile picture
-    pub small_profile_picture: Vec<u8>,
+    /// The sha256 hash of the profile's 64x64 profile picture
+    /// The pictures arent sent with the profile, the clients request them from the server by their hash and cache them
+    /// The message caches saved by older versions contain the pictures instead of the hashes, so these are empty when reading those
+    #[serde(default)]
+    pub small_profile_picture_hash: String,
 
-    /// This entry hold the profile's 256x256 profile picture
-    pub normal_profile_picture: Vec<u8>,
+    /// The sha256 hash of the profile's 256x256 profile picture
+    #[serde(default)]
+    pub normal_profile_picture_hash: String,
+}
+
+impl ClientProfile
+{
+    /// Sets the hashes of the profile to the hashes of the pictures
+    pub fn set_profile_pictures(&mut self, profile_pictures: &ProfilePictures)
+    {
+        self.small_profile_picture_hash = sha256::digest(&profile_pictures.small);
+        self.normal_profile_picture_hash = sha256::digest(&profile_pictures.normal);
+    }
+}
+
+/// The profile pictures of a ```ClientProfile```, these are only sent to the server when connecting or updating the profile
+#[derive(serde::Deserialize, serde::Serialize, Default, Clone, Debug, PartialEq)]
+pub struct ProfilePictures
+{
+    /// The 64x64 profile picture
+    pub small: Vec<u8>,
+
+    /// The 256x256 profile picture
+    pub normal: Vec<u8>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Default, Clone, Debug)]
@@ -1090,7 +1209,7 @@ pub struct ClientSyncMessage
     pub sync_attribute: Option<ConnectionType>,
 
     /// This is used to tell the server how many messages it has to send, if its a None it will automatically sync all messages
-    /// This value is ignored if the `sync_attribute` field is Some(_)
+    /// When connecting this is only Some(_) if the client is reconnecting, and already has the messages before this index
     pub client_message_counter: Option<usize>,
 
     /// The index of the last seen message by the user, this is sent so we can display which was the last message the user has seen, if its None we ignore the value
@@ -1102,6 +1221,40 @@ pub struct ClientSyncMessage
     /// This field is used when connecting, the server will save the uuid and the username pair
     /// The client will not send their username except here, and the server is expected to pair the name to the message
     pub username: String,
+
+    /// The invite token the client has received in a connection link, this is only sent when connecting
+    /// If the token is valid the server doesnt check the password
+    #[serde(default)]
+    pub invite_token: Option<String>,
+
+    /// The instance id of the server the client's messages have been synced from, this is only sent when reconnecting
+    /// The server only sends the missed messages if it has not been restarted since, because the message list is not the same after a restart
+    #[serde(default)]
+    pub server_instance_id: Option<String>,
+}
+
+/// The role of a client, the clients connecting with the server's password are always members
+/// The clients joining with an invite get the role of the invite
+#[derive(Default, serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientRole
+{
+    /// Can send messages, files and edit their messages
+    #[default]
+    Member,
+
+    /// Can only read the messages, react to them and join calls
+    ReadOnly,
+}
+
+impl Display for ClientRole
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str(match self {
+            ClientRole::Member => "Member",
+            ClientRole::ReadOnly => "Read only",
+        })
+    }
 }
 
 #[derive(Default, serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -1109,7 +1262,8 @@ pub enum ConnectionType
 {
     #[default]
     Disconnect,
-    Connect(ClientProfile),
+    /// The pictures of the profile are only sent when connecting, the other clients request them from the server by their hash
+    Connect(ClientProfile, ProfilePictures),
 }
 
 ///This is used by the client for requesting file
@@ -1169,6 +1323,10 @@ pub enum ClientFileRequestType
     /// This enum is used when the client is requesting another client's information (```ClientProfile``` struct)
     /// The wrapped value in this enum is an encrypted (aes256: ```fn encrypt_aes256()```) uuid (In string)
     ClientRequest(String),
+
+    /// This enum is used when the client is requesting a profile picture, which it hasnt cached yet
+    /// The wrapped value in this enum is the sha256 hash of the picture, the server replies with a ```ServerReplyType::Image```
+    ProfilePictureRequest(String),
 }
 
 ///Client outgoing message types
@@ -1190,6 +1348,15 @@ pub enum ClientMessageType
     MessageEdit(ClientMessageEdit),
 
     VoipConnection(ClientVoipRequest),
+
+    /// The client sends this periodically, the server answers it with the same sequence number
+    /// This is used to measure the round trip time, and to detect if the connection has died
+    Heartbeat(u64),
+
+    /// The client has changed its profile, the server validates and stores it, then broadcasts it to every connected client
+    /// The username can not be changed, a profile with a different username is ignored
+    /// Only the profile is broadcasted, the pictures are stored by the server until the clients request them
+    ProfileUpdate(ClientProfile, ProfilePictures),
 }
 
 /// The variant of the reaction message
@@ -1215,6 +1382,11 @@ pub struct ClientMessage
 
     /// When was this message sent
     pub message_date: String,
+
+    /// The id generated by the client for the messages which are sent through the outbox
+    /// The server acknowledges these messages with their id, and ignores the ones it has already stored
+    #[serde(default)]
+    pub message_id: Option<String>,
 }
 
 impl ClientMessage
@@ -1241,6 +1413,7 @@ impl ClientMessage
             }),
             uuid,
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: Some(Uuid::new_v4().to_string()),
         }
     }
 
@@ -1256,6 +1429,7 @@ impl ClientMessage
             //If the password is set as None (Meaning the user didnt enter any password) just send the message with an empty string
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: Some(Uuid::new_v4().to_string()),
         }
     }
 
@@ -1290,6 +1464,7 @@ impl ClientMessage
 
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: Some(Uuid::new_v4().to_string()),
         }
     }
 
@@ -1304,6 +1479,7 @@ impl ClientMessage
             })),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1322,6 +1498,7 @@ impl ClientMessage
             })),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1344,9 +1521,12 @@ impl ClientMessage
                 client_message_counter: Some(client_message_counter),
                 last_seen_message_index,
                 username: author.to_string(),
+                invite_token: None,
+                server_instance_id: None,
             }),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1357,20 +1537,27 @@ impl ClientMessage
         uuid: &str,
         last_seen_message_index: Option<usize>,
         profile: ClientProfile,
+        profile_pictures: ProfilePictures,
+        client_message_counter: Option<usize>,
+        server_instance_id: Option<String>,
+        invite_token: Option<String>,
     ) -> ClientMessage
     {
         ClientMessage {
             replying_to: None,
             message_type: ClientMessageType::SyncMessage(ClientSyncMessage {
-                sync_attribute: Some(ConnectionType::Connect(profile)),
+                sync_attribute: Some(ConnectionType::Connect(profile, profile_pictures)),
                 password,
-                //If its used for connecting / disconnecting this value is ignored
-                client_message_counter: None,
+                //When connecting this is only Some if we are reconnecting, the server only sends the messages we have missed
+                client_message_counter,
                 last_seen_message_index,
                 username: author,
+                invite_token,
+                server_instance_id,
             }),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1391,9 +1578,12 @@ impl ClientMessage
                 client_message_counter: None,
                 last_seen_message_index: None,
                 username: author,
+                invite_token: None,
+                server_instance_id: None,
             }),
             uuid,
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1407,6 +1597,7 @@ impl ClientMessage
             )),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1420,6 +1611,7 @@ impl ClientMessage
             )),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1433,6 +1625,7 @@ impl ClientMessage
             )),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1448,6 +1641,36 @@ impl ClientMessage
             )),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
+        }
+    }
+
+    pub fn construct_profile_picture_request_msg(hash: String, uuid: &str) -> ClientMessage
+    {
+        ClientMessage {
+            replying_to: None,
+            message_type: ClientMessageType::FileRequestType(
+                ClientFileRequestType::ProfilePictureRequest(hash),
+            ),
+            uuid: uuid.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
+        }
+    }
+
+    /// The profile update is not stored in the outbox, the profile is sent again when connecting anyway
+    pub fn construct_profile_update_msg(
+        profile: ClientProfile,
+        profile_pictures: ProfilePictures,
+        uuid: &str,
+    ) -> ClientMessage
+    {
+        ClientMessage {
+            replying_to: None,
+            message_type: ClientMessageType::ProfileUpdate(profile, profile_pictures),
+            uuid: uuid.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1462,16 +1685,27 @@ impl ClientMessage
             message_type: ClientMessageType::MessageEdit(ClientMessageEdit { index, new_message }),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
-    pub fn construct_voip_connect(uuid: &str, port: u16) -> ClientMessage
+    pub fn construct_voip_connect(
+        uuid: &str,
+        port: u16,
+        mixing_mode: VoipMixingMode,
+        room: String,
+    ) -> ClientMessage
     {
         ClientMessage {
             replying_to: None,
-            message_type: ClientMessageType::VoipConnection(ClientVoipRequest::Connect(port)),
+            message_type: ClientMessageType::VoipConnection(ClientVoipRequest::Connect(
+                port,
+                mixing_mode,
+                room,
+            )),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1482,6 +1716,48 @@ impl ClientMessage
             message_type: ClientMessageType::VoipConnection(ClientVoipRequest::Disconnect),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
+        }
+    }
+
+    pub fn construct_voip_invite(uuid: &str, invitee: String, room: String) -> ClientMessage
+    {
+        ClientMessage {
+            replying_to: None,
+            message_type: ClientMessageType::VoipConnection(ClientVoipRequest::Invite(
+                invitee, room,
+            )),
+            uuid: uuid.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
+        }
+    }
+
+    pub fn construct_voip_invitation_answer(
+        uuid: &str,
+        room: String,
+        answer: InvitationAnswer,
+    ) -> ClientMessage
+    {
+        ClientMessage {
+            replying_to: None,
+            message_type: ClientMessageType::VoipConnection(ClientVoipRequest::AnswerInvitation(
+                room, answer,
+            )),
+            uuid: uuid.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
+        }
+    }
+
+    pub fn construct_heartbeat_msg(uuid: &str, sequence_number: u64) -> ClientMessage
+    {
+        ClientMessage {
+            replying_to: None,
+            message_type: ClientMessageType::Heartbeat(sequence_number),
+            uuid: uuid.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 
@@ -1492,42 +1768,248 @@ impl ClientMessage
             message_type: ClientMessageType::VoipConnection(event),
             uuid: uuid.to_string(),
             message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            message_id: None,
         }
     }
 }
 
-///This manages all the settings and variables for maintaining a connection with the server (from client)
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, ToTable)]
-pub struct ClientConnection
+/// The state of a server the client is connected to
+/// The opened server's session is stored in ```Application::server_session```, the others in ```Application::server_sessions```
+#[derive(serde::Deserialize, serde::Serialize, Clone, ToTable)]
+#[serde(default)]
+pub struct ServerSession
 {
     #[table(save)]
     #[serde(skip)]
-    pub client_secret: Vec<u8>,
+    pub client_connection: ClientConnection,
 
-    #[table(save)]
+    ///This is the full address of the destination a message is supposed to be sent to
+    pub send_on_ip: String,
+
+    ///self.send_on_ip encoded into base64, this is supposedly for ease of use, I dont know why its even here
+    pub send_on_ip_base64_encoded: String,
+
+    ///The password the user has entered for server auth
+    pub client_password: String,
+
+    ///The token of the invite link the user has opened, this is used instead of the password when connecting
     #[serde(skip)]
-    ///This enum wraps the server handle ```Connected(_)```, it also functions as a Sort of Option wrapper
-    pub state: ConnectionState,
+    pub invite_token: String,
 
+    ///Incoming messages, this is the whole packet which get sent to all the clients, this contains all the messages, and the info about them
+    #[serde(skip)]
     #[table(save)]
+    pub incoming_messages: ServerMaster,
+
+    /// Last seen message's index, this will get sent
     #[serde(skip)]
-    //Password which was used to connect (and could connect with, it has been password matched with the server)
-    pub password: String,
-}
+    pub last_seen_msg_index: Arc<Mutex<usize>>,
 
-impl ClientConnection
-{
-    /// This is a wrapper function for ```client::send_message```
-    pub async fn send_message(self, message: ClientMessage) -> anyhow::Result<ServerReply>
-    {
-        if let ConnectionState::Connected(connection) = &self.state {
-            #[allow(unused_must_use)]
-            {
-                Ok(connection.send_message(message).await?)
-            }
-        }
-        else {
-            bail!("There is no active connection to send the message on.")
+    ///Server - client syncing thread
+    #[serde(skip)]
+    pub server_sender_thread: Option<()>,
+
+    #[serde(skip)]
+    /// This is what the main thread uses to receive messages from the sync thread
+    pub server_output_receiver: Arc<Receiver<Option<String>>>,
+
+    #[serde(skip)]
+    /// This is what the sync thread uses to send messages to the main thread
+    pub server_output_sender: Sender<Option<String>>,
+
+    /// Server connection
+    /// This channel hosts a Client connection and the sync message sent by the server in a String format
+    #[serde(skip)]
+    pub connection_receiver: Arc<mpsc::Receiver<Option<(ClientConnection, String)>>>,
+    #[serde(skip)]
+    pub connection_sender: mpsc::Sender<Option<(ClientConnection, String)>>,
+
+    #[serde(skip)]
+    /// This is what the main thread uses to send the shutdown message to the sync thread
+    pub autosync_shutdown_token: CancellationToken,
+
+    /// The voip call is always ```None``` while the server isnt opened, as the servers cant be switched during a call
+    #[serde(skip)]
+    pub voip: Option<Voip>,
+
+    /// Voip audio sender thread
+    #[serde(skip)]
+    pub voip_thread: Option<()>,
+
+    ///Voip thread shutdown token
+    #[serde(skip)]
+    pub voip_shutdown_token: CancellationToken,
+
+    ///Webcam recorder shutdown token
+    #[serde(skip)]
+    pub voip_video_shutdown_token: CancellationToken,
+
+    #[serde(skip)]
+    /// The round trip time and the packet loss of our connection to the server, this is measured with heartbeats
+    pub connection_quality: Arc<Mutex<ConnectionQuality>>,
+
+    /// The name of the voice room we join when starting a call
+    pub voip_room: String,
+
+    /// The invitations to private calls we havent answered yet, these ring until they time out
+    #[serde(skip)]
+    pub incoming_call_invitations: Vec<IncomingCallInvitation>,
+
+    /// The amount of messages received since the server has been opened the last time
+    #[serde(skip)]
+    pub unread_messages: usize,
+
+    /// The hashes of the requested profile pictures, paired with the time they were requested
+    /// The pictures which arent received in ```PROFILE_PICTURE_REQUEST_TIMEOUT``` are requested again
+    #[serde(skip)]
+    pub profile_picture_requests: HashMap<String, Instant>,
+}
+
+impl Default for ServerSession
+{
+    fn default() -> Self
+    {
+        let (server_output_sender, server_output_receiver) = mpsc::channel::<Option<String>>();
+
+        let (connection_sender, connection_receiver) =
+            mpsc::channel::<Option<(ClientConnection, String)>>();
+
+        Self {
+            client_connection: ClientConnection::default(),
+            send_on_ip: String::new(),
+            send_on_ip_base64_encoded: String::new(),
+            client_password: String::new(),
+            invite_token: String::new(),
+            incoming_messages: ServerMaster::default(),
+            last_seen_msg_index: Arc::new(Mutex::new(0)),
+            server_sender_thread: None,
+            server_output_receiver: Arc::new(server_output_receiver),
+            server_output_sender,
+            connection_receiver: Arc::new(connection_receiver),
+            connection_sender,
+            autosync_shutdown_token: CancellationToken::new(),
+            voip: None,
+            voip_thread: None,
+            voip_shutdown_token: CancellationToken::new(),
+            voip_video_shutdown_token: CancellationToken::new(),
+            connection_quality: Arc::new(Mutex::new(ConnectionQuality::default())),
+            voip_room: DEFAULT_VOICE_ROOM.to_string(),
+            incoming_call_invitations: Vec::new(),
+            unread_messages: 0,
+            profile_picture_requests: HashMap::new(),
+        }
+    }
+}
+
+/// The amount of time we wait for the server to send a requested profile picture, before requesting it again
+pub const PROFILE_PICTURE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+///This manages all the settings and variables for maintaining a connection with the server (from client)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, ToTable)]
+pub struct ClientConnection
+{
+    #[table(save)]
+    #[serde(skip)]
+    pub client_secret: Vec<u8>,
+
+    #[table(save)]
+    #[serde(skip)]
+    ///This enum wraps the server handle ```Connected(_)```, it also functions as a Sort of Option wrapper
+    pub state: ConnectionState,
+
+    #[table(save)]
+    #[serde(skip)]
+    //Password which was used to connect (and could connect with, it has been password matched with the server)
+    pub password: String,
+
+    #[serde(skip)]
+    /// This is ```Some``` while the connection has dropped, and we are trying to reconnect to the server
+    pub reconnection: Option<Reconnection>,
+}
+
+/// The delay before the first reconnection attempt, this is doubled after every failed attempt
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest delay between two reconnection attempts
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// The state of the automatic reconnection to the server
+/// The delays between the attempts grow exponentially, with a random jitter so that the clients of a restarted server dont reconnect all at once
+#[derive(Debug, Clone)]
+pub struct Reconnection
+{
+    /// The amount of failed attempts
+    pub attempt: u32,
+
+    /// The time the next attempt is made at
+    pub next_attempt_at: Instant,
+
+    /// Whether there is an attempt in progress
+    pub in_progress: bool,
+
+    /// This is set by the attempt if the server has refused the connection, in this case the reconnection is stopped
+    pub refused: Arc<AtomicBool>,
+}
+
+impl Default for Reconnection
+{
+    fn default() -> Self
+    {
+        Self {
+            attempt: 0,
+            next_attempt_at: Instant::now() + Self::backoff_delay(0),
+            in_progress: false,
+            refused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Reconnection
+{
+    /// Schedules the next attempt after a failed one
+    pub fn schedule_next_attempt(&mut self)
+    {
+        self.attempt += 1;
+        self.in_progress = false;
+        self.next_attempt_at = Instant::now() + Self::backoff_delay(self.attempt);
+    }
+
+    /// Returns whether the next attempt should be made
+    pub fn is_due(&self) -> bool
+    {
+        !self.in_progress && Instant::now() >= self.next_attempt_at
+    }
+
+    /// Returns whether the server has refused the last attempt, the next attempts would be refused too
+    pub fn is_refused(&self) -> bool
+    {
+        self.refused.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the delay before the attempt, the delay is picked randomly from the upper half of the exponential backoff
+    fn backoff_delay(attempt: u32) -> Duration
+    {
+        let backoff = RECONNECT_BASE_DELAY
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(RECONNECT_MAX_DELAY);
+
+        backoff / 2 + backoff.mul_f32(rand::thread_rng().gen_range(0.0..0.5))
+    }
+}
+
+impl ClientConnection
+{
+    /// This is a wrapper function for ```client::send_message```
+    pub async fn send_message(self, message: ClientMessage) -> anyhow::Result<ServerReply>
+    {
+        if let ConnectionState::Connected(connection) = &self.state {
+            #[allow(unused_must_use)]
+            {
+                Ok(connection.send_message(message).await?)
+            }
+        }
+        else {
+            bail!("There is no active connection to send the message on.")
         }
     }
 
@@ -1544,6 +2026,14 @@ impl ClientConnection
         uuid: &str,
         //Profile
         profile: ClientProfile,
+        //The pictures of the profile, these are stored by the server
+        profile_pictures: ProfilePictures,
+        //The amount of messages we already have, if this is Some the server only sends the messages we have missed
+        client_message_counter: Option<usize>,
+        //The instance id of the server the messages we already have have been synced from
+        server_instance_id: Option<String>,
+        //The token of the invite link the client is joining with
+        invite_token: Option<String>,
     ) -> anyhow::Result<(Self, String)>
     {
         let hashed_password = encrypt(password.clone().unwrap_or(String::from("")));
@@ -1553,6 +2043,10 @@ impl ClientConnection
             uuid,
             None,
             profile,
+            profile_pictures,
+            client_message_counter,
+            server_instance_id,
+            invite_token,
         );
 
         //Ping server to receive custom uuid, and to also get if server ip is valid
@@ -1562,15 +2056,18 @@ impl ClientConnection
         let (server_reply, server_handle) =
             connect_to_server(client_handle, connection_msg).await?;
 
-        ensure!(server_reply != "Invalid Password!", "Invalid password!");
-        ensure!(
-            server_reply != "Invalid Client!",
-            "Outdated client or connection!"
-        );
-        ensure!(
-            server_reply != "You have been banned!",
-            "You have been banned from this server!"
-        );
+        let refusal_reason = match server_reply.as_str() {
+            "Invalid Password!" => Some("Invalid password!"),
+            "Invalid invite!" => Some("The invite is invalid, it has expired or has been revoked!"),
+            "Invalid Client!" => Some("Outdated client or connection!"),
+            "You have been banned!" => Some("You have been banned from this server!"),
+            "Invalid profile!" => Some("The server has refused your profile!"),
+            _ => None,
+        };
+
+        if let Some(refusal_reason) = refusal_reason {
+            return Err(ConnectionRefusedError(refusal_reason.to_string()).into());
+        }
 
         //This the key the server replied, and this is what well need to decrypt the messages, overwrite the client_secret variable
         let client_secret = hex::decode(server_reply)?;
@@ -1599,6 +2096,7 @@ impl ClientConnection
                 client_secret,
                 state: ConnectionState::Connected(connection_pair),
                 password: hashed_password,
+                reconnection: None,
             },
             server_reply,
         ))
@@ -1608,6 +2106,7 @@ impl ClientConnection
     {
         self.client_secret = Vec::new();
         self.state = ConnectionState::default();
+        self.reconnection = None;
     }
 
     /// This function is used to __DISCONNECT__ from a server, with this the ```ClientConnection``` instance is destroyed (reset to its default values)
@@ -1690,6 +2189,9 @@ pub enum ConnectionState
     Disconnected,
     Connecting,
     Error,
+
+    /// The connection has dropped, the details of the reconnection are stored in ```ClientConnection::reconnection```
+    Reconnecting,
 }
 
 impl Debug for ConnectionState
@@ -1701,6 +2203,7 @@ impl Debug for ConnectionState
             ConnectionState::Disconnected => "Disconnected",
             ConnectionState::Connecting => "Connecting",
             ConnectionState::Error => "Error",
+            ConnectionState::Reconnecting => "Reconnecting",
         })
     }
 }
@@ -1732,6 +2235,33 @@ pub struct ServerFileUpload
     pub signature: String,
 }
 
+/// The multicast group the servers announce themselves on, so the clients in the local network can find them
+pub const LAN_DISCOVERY_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 255, 77, 77);
+
+/// The port the announcements are sent to
+pub const LAN_DISCOVERY_PORT: u16 = 50077;
+
+/// How often the servers announce themselves
+pub const LAN_ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// This is what the servers send to the multicast group periodically
+/// The address of the server isnt included, as the clients can read it from the packet
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerAnnouncement
+{
+    /// The name set by the host of the server
+    pub name: String,
+
+    /// The port the server is listening on
+    pub port: u16,
+
+    /// Whether a password is needed to connect to the server
+    pub requires_password: bool,
+
+    /// The amount of clients connected to the server
+    pub connected_clients: usize,
+}
+
 /// This enum holds all the Server reply types so it can be decoded more easily on the client side
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum ServerReplyType
@@ -1748,6 +2278,15 @@ pub enum ServerReplyType
     /// The requested client's profile
     /// The first value is the encrypted uuid
     Client(ServerClientReply),
+
+    /// The answer to a heartbeat of the client, this contains the sequence number of the heartbeat
+    Heartbeat(u64),
+
+    /// The server has stored the message with this id, so it can be removed from the client's outbox
+    Acknowledgement(String),
+
+    /// The server has refused to store the message with this id, the second value contains the reason
+    Rejected(String, String),
 }
 
 /// This struct holds everything important so the client can save and handle client profiles
@@ -1891,6 +2430,11 @@ pub enum ServerMessageType
     /// This message holds the State of the Voip service
     #[strum_discriminants(strum(message = "Voip state"))]
     VoipState(ServerVoipState),
+
+    /// This message contains the updated profile of the client who has sent it, the clients replace their stored profile with it
+    /// This message is not stored in the message list
+    #[strum_discriminants(strum(message = "Profile update"))]
+    ProfileUpdate(ClientProfile),
 }
 
 /// The types of message the server can "send"
@@ -1946,6 +2490,7 @@ impl ServerOutput
             message_type:
                 match normal_msg.message_type {
                     ClientMessageType::FileRequestType(_) => unimplemented!("Converting request packets isnt implemented, because they shouldnt be displayed by the client"),
+                    ClientMessageType::Heartbeat(_) => unimplemented!("Converting heartbeats isnt implemented, because they are answered directly to the client"),
                     ClientMessageType::FileUpload(upload) => {
                         match upload_type {
                             ServerMessageTypeDiscriminants::Upload => {
@@ -1987,6 +2532,7 @@ impl ServerOutput
                             ServerMessageTypeDiscriminants::Edit => unreachable!(),
                             ServerMessageTypeDiscriminants::Reaction => unreachable!(),
                             ServerMessageTypeDiscriminants::Server => unreachable!(),
+                            ServerMessageTypeDiscriminants::ProfileUpdate => unreachable!(),
                         }
                     },
                     ClientMessageType::NormalMessage(message) => {
@@ -2000,30 +2546,64 @@ impl ServerOutput
                     },
                     ClientMessageType::VoipConnection(voip_message_type) => {
                         let server_message = match voip_message_type {
-                            ClientVoipRequest::Connect(_) => {
+                            ClientVoipRequest::Connect(_, _, room) => {
                                 ServerVoipEvent {
                                     event: VoipEvent::Connected,
                                     uuid: uuid.clone(),
+                                    room: room.clone(),
                                 }
                             },
                             ClientVoipRequest::Disconnect => {
                                 ServerVoipEvent {
                                     event: VoipEvent::Disconnected,
                                     uuid: uuid.clone(),
+                                    //The room is set by the server, as the client has only sent it when connecting
+                                    room: String::new(),
                                 }
                             },
                             ClientVoipRequest::ImageConnected => {
                                 ServerVoipEvent {
                                     event: VoipEvent::ImageConnected,
                                     uuid: uuid.clone(),
+                                    //The room is set by the server, as the client has only sent it when connecting
+                                    room: String::new(),
                                 }
                             },
                             ClientVoipRequest::ImageDisconnected => {
                                 ServerVoipEvent {
                                     event: VoipEvent::ImageDisconnected,
                                     uuid: uuid.clone(),
+                                    //The room is set by the server, as the client has only sent it when connecting
+                                    room: String::new(),
+                                }
+                            },
+                            ClientVoipRequest::RecordingStarted => {
+                                ServerVoipEvent {
+                                    event: VoipEvent::RecordingStarted,
+                                    uuid: uuid.clone(),
+                                    //The room is set by the server, as the client has only sent it when connecting
+                                    room: String::new(),
+                                }
+                            },
+                            ClientVoipRequest::RecordingStopped => {
+                                ServerVoipEvent {
+                                    event: VoipEvent::RecordingStopped,
+                                    uuid: uuid.clone(),
+                                    //The room is set by the server, as the client has only sent it when connecting
+                                    room: String::new(),
+                                }
+                            },
+                            ClientVoipRequest::AnswerInvitation(room, _) => {
+                                //The server only broadcasts the missed calls, the caller's uuid is set by the server
+                                ServerVoipEvent {
+                                    event: VoipEvent::MissedCall(String::new()),
+                                    uuid: uuid.clone(),
+                                    room: room.clone(),
                                 }
                             },
+                            //Invitations are only sent to the invited client, and the volumes are only used by the mixer, these are never broadcasted
+                            ClientVoipRequest::Invite(_, _)
+                            | ClientVoipRequest::ParticipantVolume(_, _) => unreachable!(),
                         };
 
                         ServerMessageType::VoipEvent(server_message)
@@ -2052,6 +2632,10 @@ impl ServerOutput
                     ClientMessageType::MessageEdit(message) => {
                         ServerMessageType::Edit(ServerMessageEdit { index: message.index as i32, new_message: message.new_message })
                     },
+                    //The profile has already been validated by the server, the pictures are requested by the clients which havent cached them yet
+                    ClientMessageType::ProfileUpdate(profile, _) => {
+                        ServerMessageType::ProfileUpdate(profile)
+                    },
                 },
             author: username,
             message_date: normal_msg.message_date,
@@ -2077,8 +2661,23 @@ pub struct ServerMaster
     ///This entry holds all the connected user's profile
     pub connected_clients_profile: HashMap<String, ClientProfile>,
 
-    ///This entry shows all the client connected to the Voip call, if there is a a call
+    ///This entry shows the voice rooms with an ongoing call, and the clients connected to them
     pub ongoing_voip_call: ServerVoipState,
+
+    /// The index of the first message in ```message_list```, this is only non-zero if the client has reconnected
+    /// In that case the client already has the messages before this index, and only the missed ones are sent
+    #[serde(default)]
+    pub first_message_index: usize,
+
+    /// The identifier of the server, this is only sent when connecting
+    /// This is not a proof of the server's identity, it only shows whether a different server answers on the address
+    #[serde(default)]
+    pub server_fingerprint: String,
+
+    /// The id of the server's instance, this changes every time the server is started
+    /// The cached messages are only continued if the id hasnt changed, because the indexes of the messages are not the same after a restart
+    #[serde(default)]
+    pub server_instance_id: String,
 }
 
 impl ServerMaster
@@ -2158,11 +2757,13 @@ impl ClientLastSeenMessage
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum ClientVoipRequest
 {
-    /// A voip a call will be automatically issued if there is no ongoing call
-    /// The inner value of conncect is the port the Client ```UdpSocket``` is opened on
-    Connect(u16),
+    /// A voip a call will be automatically issued if there is no ongoing call in the room
+    /// The first inner value of conncect is the port the Client ```UdpSocket``` is opened on
+    /// The second inner value is the ```VoipMixingMode``` of the call, this is only taken into account if the client is the one creating the call
+    /// The third inner value is the name of the voice room the client is joining, if the client is in another room they are disconnected from it
+    Connect(u16, VoipMixingMode, String),
 
-    /// The voip call will automatically stop once there are no connected clients
+    /// The client leaves the voice room they are in, the voip call of the room will automatically stop once there are no connected clients
     Disconnect,
 
     /// The client has enabled video
@@ -2170,6 +2771,65 @@ pub enum ClientVoipRequest
 
     /// The client has disabled video
     ImageDisconnected,
+
+    /// The client has started recording the call
+    RecordingStarted,
+
+    /// The client has stopped recording the call
+    RecordingStopped,
+
+    /// The client invites another client to a private call
+    /// The first inner value is the uuid of the invited client
+    /// The second inner value is the name of the private voice room the client has created for the call
+    Invite(String, String),
+
+    /// The client answers an invitation to a private call
+    /// The first inner value is the name of the private voice room the client was invited to
+    /// The second inner value is the answer of the client, only a missed call is broadcasted to the other clients
+    AnswerInvitation(String, InvitationAnswer),
+
+    /// The client has changed the volume of another participant, this is only used by the server's mixer as the mixed audio cant be split up by the client
+    /// The first inner value is the uuid of the participant
+    /// The second inner value is the volume of the participant, this is 0 if the participant has been muted
+    ParticipantVolume(String, f32),
+}
+
+/// This enum contains the possible answers to a call invitation
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvitationAnswer
+{
+    /// The invited client has joined the private call
+    Accepted,
+
+    /// The invited client has declined the invitation
+    Declined,
+
+    /// The invitation has timed out, or the caller has left the call before it was answered
+    Missed,
+}
+
+/// This enum decides how the server distributes the audio of a voip call, this is picked by the creator of the call
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoipMixingMode
+{
+    /// Every participant's audio is relayed to every other participant, the clients play the streams themselves
+    #[default]
+    Relay,
+
+    /// The server mixes the participants' audio, and sends one stream to every client (Excluding the client's own voice)
+    /// This reduces the download bandwidth of the clients in bigger calls
+    Mixed,
+}
+
+impl Display for VoipMixingMode
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str(match self {
+            VoipMixingMode::Relay => "Relay",
+            VoipMixingMode::Mixed => "Server mixing",
+        })
+    }
 }
 
 /// This enum is used to display if a client has joined or left the Voip call, this is a ```ServerMessageType```
@@ -2186,6 +2846,15 @@ pub enum VoipEvent
 
     /// The client has disabled video
     ImageDisconnected,
+
+    /// The client has started recording the call
+    RecordingStarted,
+
+    /// The client has stopped recording the call
+    RecordingStopped,
+
+    /// The client has missed a private call, the inner value is the uuid of the caller
+    MissedCall(String),
 }
 
 /// This struct holds all important information, when informing clients / servers about a ```VoipEvent```
@@ -2196,13 +2865,58 @@ pub struct ServerVoipEvent
     pub uuid: String,
     /// The event the user has initiated
     pub event: VoipEvent,
+    /// The name of the voice room the event has happened in
+    #[serde(default)]
+    pub room: String,
 }
 
-///The struct contains all the useful information for displaying an ongoing voip connection.
+/// The name of the voice room the clients join by default
+pub const DEFAULT_VOICE_ROOM: &str = "General";
+
+/// The prefix of the private voice rooms' names, these rooms can only be joined by the clients invited to them
+pub const PRIVATE_VOICE_ROOM_PREFIX: &str = "private:";
+
+/// The amount of time an incoming call invitation rings for, before the server reports it as a missed call
+pub const INVITATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns whether the voice room is a private call
+pub fn is_private_voice_room(room: &str) -> bool
+{
+    room.starts_with(PRIVATE_VOICE_ROOM_PREFIX)
+}
+
+///The struct contains all the useful information for displaying the ongoing voip connections.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ServerVoipState
 {
-    pub connected_clients: Option<Vec<String>>,
+    /// The voice rooms with an ongoing call, paired with their name
+    /// A room only exists while there are clients connected to it
+    pub rooms: BTreeMap<String, ServerVoipRoomState>,
+}
+
+impl ServerVoipState
+{
+    /// Returns the name and the state of the voice room the client is connected to
+    pub fn room_of(&self, uuid: &str) -> Option<(&String, &ServerVoipRoomState)>
+    {
+        self.rooms.iter().find(|(_, room)| {
+            room.connected_clients
+                .iter()
+                .any(|connected_client| connected_client == uuid)
+        })
+    }
+}
+
+///The struct contains all the useful information for displaying the ongoing voip connection of a voice room.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ServerVoipRoomState
+{
+    /// The uuids of the clients connected to the room
+    pub connected_clients: Vec<String>,
+
+    /// The uuids of the clients recording the call, every participant displays an indicator while this isnt empty
+    #[serde(default)]
+    pub recording_clients: Vec<String>,
 }
 
 /// This num contains the actions the server can take, these are sent to the client
@@ -2213,6 +2927,28 @@ pub enum ServerVoipRequest
     ConnectionStart(ServerVoipStart),
     /// This enum acts as a ```packet``` and i handed out to all the clients connected to the call
     ConnectionClosed(ServerVoipClose),
+
+    /// This is only sent to the invited client, when they are invited to a private call
+    Invitation(ServerCallInvitation),
+
+    /// This is only sent to the caller, when the invited client has answered their invitation
+    /// The inner value contains the name of the private voice room, the uuid of the invited client and their answer
+    InvitationAnswered(String, String, InvitationAnswer),
+
+    /// This is only sent to the invited client, when the caller has left the private call before the invitation was answered
+    /// The inner value is the name of the private voice room
+    InvitationCancelled(String),
+}
+
+/// This struct contains all the information the invited client needs to join a private call
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerCallInvitation
+{
+    /// The uuid of the client who has sent the invitation
+    pub caller: String,
+
+    /// The name of the private voice room the client was invited to
+    pub room: String,
 }
 
 /// This struct contains all the information important for the non connected clients
@@ -2242,8 +2978,36 @@ pub struct ServerVoipClose
     pub reason: String,
 }
 
-pub type ImageBuffer = Arc<DashMap<String, IndexMap<String, HashMap<String, Option<Vec<u8>>>>>>;
+/// The image buffer of a call.
+/// The ```DashMap``` contains the ```ImageFrame```s (value) paired with the uuid of their author (key)
+/// The ```IndexMap``` contains the ```ImageFrame```s (value) paired with their identificator (key), in the order of their arrival
+pub type ImageBuffer = Arc<DashMap<String, IndexMap<String, ImageFrame>>>;
+
+/// The volumes the clients have set for the other participants of the calls, paired with the uuid of the client
+/// The inner ```HashMap``` pairs the uuid of the participant with the volume, this is used by the mixer of the rooms
+pub type ParticipantVolumes = Arc<DashMap<String, HashMap<String, f32>>>;
+
+/// The UDP sockets of the server shared by the voice rooms, and the listener thread relaying the incoming packets to the client manager threads
+/// This is created when the first voice room is created, and is shut down once there are no voice rooms left
+#[derive(Debug, Clone)]
+pub struct ServerVoipRelay
+{
+    /// The socket the server is listening on for incoming messages from ipv6 addresses
+    pub socket_v6: Option<Arc<UdpSocket>>,
+
+    /// The socket the server is listening on for incoming messages from ipv4 addresses
+    pub socket_v4: Arc<UdpSocket>,
+
+    /// This field pairs the SocketAddr of every client connected to any of the rooms to their client manager thread's sender
+    /// The second part of the tuple is for shutting down the client manager thread, if they disconnect
+    pub connected_client_thread_channels:
+        Arc<DashMap<SocketAddr, (Arc<tokio::sync::mpsc::Sender<Vec<u8>>>, CancellationToken)>>,
+
+    /// The cancellation token cancels the listener thread
+    pub thread_cancellation_token: CancellationToken,
+}
 
+/// A voice room with an ongoing call, every room has its own participants, relay (client manager threads) and mixer
 #[derive(Debug, Clone)]
 pub struct ServerVoip
 {
@@ -2252,6 +3016,7 @@ pub struct ServerVoip
 
     /// This field contains a ```HashMap``` which pairs the SocketAddr to the client's listening thread's sender (So that the receiver thread can receive the ```Vec<u8>``` sent by the sender)
     /// The second part of the tuple is for shutting down the client manager thread, if they disconnect
+    /// This is shared by all of the rooms, see ```ServerVoipRelay```
     pub connected_client_thread_channels:
         Arc<DashMap<SocketAddr, (Arc<tokio::sync::mpsc::Sender<Vec<u8>>>, CancellationToken)>>,
 
@@ -2264,18 +3029,29 @@ pub struct ServerVoip
     /// The socket the server is listening on for incoming messages from ipv4 addresses
     pub socket_v4: Arc<UdpSocket>,
 
-    /// The cancellation token cancels threads, which are for listening and relaying (Distributing info)
+    /// The cancellation token cancels the threads of the room (ie. the mixer)
     pub thread_cancellation_token: CancellationToken,
 
-    /// This entry makes sure the 2 threads are only spawned once
+    /// This entry makes sure the threads of the room are only spawned once
     pub threads: Option<()>,
 
     /// This field contains the Video(Image) buffer of the clients.
-    /// If header file's hashes are paired we send / relay the image to all the other clients.
-    /// The ```DashMap``` contains the HeaderMessages (value) paired with the uuid's of the clients (key)
-    /// The ```IndexMap``` contains the MessageParts (value)  paired with the HeaderMessage's uuid (key)
-    /// The ```HashMap``` contains the image bytes (value) paired with the byte hash (key)
+    /// If enough parts of a frame have arrived to rebuild it, we relay the image to all the other clients.
     pub image_buffer: ImageBuffer,
+
+    /// This field decides whether the audio is relayed or mixed, this is set by the creator of the call
+    pub mixing_mode: VoipMixingMode,
+
+    /// This field contains the decoded audio frames of the clients waiting to be mixed, paired with the uuid of the client
+    /// This is only used if the ```mixing_mode``` is ```VoipMixingMode::Mixed```
+    pub mixer_buffer: Arc<DashMap<String, VecDeque<AudioFrame>>>,
+
+    /// This field contains the uuids of the clients recording the call
+    pub recording_clients: Arc<DashSet<String>>,
+
+    /// This field contains the volumes the clients have set for the other participants, this is shared by all of the rooms
+    /// This is only used if the ```mixing_mode``` is ```VoipMixingMode::Mixed```
+    pub participant_volumes: ParticipantVolumes,
 }
 
 impl ServerVoip
@@ -2300,8 +3076,30 @@ impl ServerVoip
         self.connected_client_thread_channels
             .remove(&removed_address);
 
+        self.mixer_buffer.remove(&uuid);
+
+        //A client cant record a call they have left
+        self.recording_clients.remove(&uuid);
+
         Ok(())
     }
+
+    /// Returns the state of the room, which is displayed by the clients
+    pub fn state(&self) -> ServerVoipRoomState
+    {
+        ServerVoipRoomState {
+            connected_clients: self
+                .connected_clients
+                .iter()
+                .map(|entry| entry.key().clone())
+                .collect(),
+            recording_clients: self
+                .recording_clients
+                .iter()
+                .map(|entry| entry.key().clone())
+                .collect(),
+        }
+    }
 }
 
 /// This enum holds the variants of a UdpMessage
@@ -2314,6 +3112,14 @@ pub enum UdpMessageType
     ImageHeader = 3,
     /// Image message
     Image = 2,
+    /// Keyframe request message, this is relayed to the client whose video stream is requested
+    KeyframeRequest = 4,
+    /// Receiver report message, this is relayed to the client whose video stream is reported
+    ReceiverReport = 5,
+    /// Heartbeat message, the server sends it back to the client it was received from
+    Heartbeat = 6,
+    /// Mixed voice message, this is only sent by the server's mixer, it contains the uuids of the clients speaking in the mix
+    MixedVoice = 7,
 }
 
 impl UdpMessageType
@@ -2324,6 +3130,10 @@ impl UdpMessageType
             1 => Self::Voice,
             2 => Self::Image,
             3 => Self::ImageHeader,
+            4 => Self::KeyframeRequest,
+            5 => Self::ReceiverReport,
+            6 => Self::Heartbeat,
+            7 => Self::MixedVoice,
 
             _ => unimplemented!("Branch not covered"),
         }
@@ -2337,9 +3147,9 @@ pub struct Voip
     /// The clients socket, which they're listening on for packets (audio, image)
     pub socket: Arc<UdpSocket>,
 
-    /// This handle is used to take pictures with the host's camera
+    /// This handle is used to take the frames of the video we are sending (ie. the host's camera)
     /// If we are in a voice call this is ```None``` by default
-    pub camera_handle: Arc<tokio::sync::Mutex<Option<Webcam>>>,
+    pub camera_handle: Arc<tokio::sync::Mutex<Option<Box<dyn VideoSource>>>>,
 
     /// Signals whether there is a camera handle open
     pub camera_handle_is_open: Arc<AtomicBool>,
@@ -2347,8 +3157,32 @@ pub struct Voip
     /// Whether the microphone should record audio
     pub enable_microphone: Arc<AtomicBool>,
 
+    /// Whether we are currently sending speech, this is used to display the speaking indicator
+    pub is_speaking: Arc<AtomicBool>,
+
+    /// The remote speakers of the call, with their own ```Sink``` and ```JitterBuffer```
+    pub remote_speakers: RemoteSpeakers,
+
     /// This field serves as a UDP protocol of some sorts, it is used as an ```ImageBuffer```
     pub image_buffer: ImageBuffer,
+
+    /// The latest decoded video frame of the participants, paired with their uuid
+    pub video_frames: Arc<DashMap<String, TextureHandle>>,
+
+    /// This is set when a participant has requested a keyframe of our video stream
+    pub keyframe_requested: Arc<AtomicBool>,
+
+    /// This decides the quality of our video stream based on the participants' reports
+    pub video_quality_controller: Arc<Mutex<VideoQualityController>>,
+
+    /// The reception statistics of the participants' video streams, paired with their uuid
+    pub video_receiver_statistics: Arc<DashMap<String, ReceiverStatistics>>,
+
+    /// The round trip time and the packet loss of our connection to the server's voip service
+    pub connection_quality: Arc<Mutex<ConnectionQuality>>,
+
+    /// The recorder of the call, this is ```Some``` while we are recording the call
+    pub call_recorder: Arc<Mutex<Option<CallRecorder>>>,
 }
 
 impl Voip
@@ -2366,23 +3200,33 @@ impl Voip
             camera_handle: Arc::new(tokio::sync::Mutex::new(None)),
             camera_handle_is_open: Arc::new(AtomicBool::new(false)),
             enable_microphone: Arc::new(AtomicBool::new(true)),
+            is_speaking: Arc::new(AtomicBool::new(false)),
+            remote_speakers: Arc::new(DashMap::new()),
             image_buffer: Arc::new(DashMap::new()),
+            video_frames: Arc::new(DashMap::new()),
+            keyframe_requested: Arc::new(AtomicBool::new(false)),
+            video_quality_controller: Arc::new(Mutex::new(VideoQualityController::default())),
+            video_receiver_statistics: Arc::new(DashMap::new()),
+            call_recorder: Arc::new(Mutex::new(None)),
+            connection_quality: Arc::new(Mutex::new(ConnectionQuality::default())),
         })
     }
 
-    /// This function sets the ```camera_handle``` in this ```Voip``` instance.
+    /// This function opens the video source and sets it as the ```camera_handle``` in this ```Voip``` instance.
     /// __NOTE: This doesnt inherently mean that a video call will start, it will just set the ```Voip``` instance.__
     /// This function uses an async thread to set the value.
-    pub fn add_camera_handle(&self) -> anyhow::Result<()>
+    pub fn add_camera_handle(&self, video_source: &VideoSourceSettings) -> anyhow::Result<()>
     {
         let camera_handle = self.camera_handle.clone();
 
+        let video_source = video_source.open()?;
+
         //Create camera handle thread
         //This thread modifies the camera_handle directly
         tokio::spawn(async move {
             let mut camera_handle = camera_handle.lock().await;
 
-            *camera_handle = Some(Webcam::new_def_auto_detect().unwrap());
+            *camera_handle = Some(video_source);
         });
 
         Ok(())
@@ -2429,24 +3273,37 @@ impl Voip
 
         Ok(Self {
             socket: Arc::new(socket_handle),
-            camera_handle: Arc::new(tokio::sync::Mutex::new(
-                Some(Webcam::new_def_auto_detect()?),
-            )),
+            camera_handle: Arc::new(tokio::sync::Mutex::new(Some(
+                VideoSourceSettings::Camera.open()?,
+            ))),
             camera_handle_is_open: Arc::new(AtomicBool::new(false)),
             enable_microphone: Arc::new(AtomicBool::new(true)),
+            is_speaking: Arc::new(AtomicBool::new(false)),
+            remote_speakers: Arc::new(DashMap::new()),
             image_buffer: Arc::new(DashMap::new()),
+            video_frames: Arc::new(DashMap::new()),
+            keyframe_requested: Arc::new(AtomicBool::new(false)),
+            video_quality_controller: Arc::new(Mutex::new(VideoQualityController::default())),
+            video_receiver_statistics: Arc::new(DashMap::new()),
+            call_recorder: Arc::new(Mutex::new(None)),
+            connection_quality: Arc::new(Mutex::new(ConnectionQuality::default())),
         })
     }
 
-    /// This function sends the audio and the uuid in one message, this packet is encrypted (The audio's bytes is appended to the uuid's)
+    /// This function sends the audio, the sequence number and the uuid in one message, this packet is encrypted (The audio's bytes is appended to the uuid's)
     /// Any length of audio can be sent because the header is included with the packet
+    /// The sequence number is used by the receiver's jitter buffer to reorder the packets, and to detect lost ones
     pub async fn send_audio(
         &self,
         uuid: String,
         mut bytes: Vec<u8>,
+        sequence_number: u64,
         encryption_key: &[u8],
     ) -> anyhow::Result<()>
     {
+        //Append the sequence number to the audio bytes
+        bytes.append(&mut sequence_number.to_be_bytes().to_vec());
+
         //Append the uuid to the audio bytes
         bytes.append(uuid.as_bytes().to_vec().as_mut());
 
@@ -2456,6 +3313,58 @@ impl Voip
         Ok(())
     }
 
+    /// This function requests a keyframe of the participant's video stream, this is sent when we can not decode their stream due to a lost frame
+    /// The server relays the request to the participant specified by the uuid
+    pub async fn request_keyframe(&self, uuid: String, encryption_key: &[u8])
+        -> anyhow::Result<()>
+    {
+        self.send_bytes(
+            uuid.as_bytes().to_vec(),
+            encryption_key,
+            UdpMessageType::KeyframeRequest,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// This function sends a report about the reception of the participant's video stream
+    /// The server relays the report to the participant specified by the uuid
+    /// __Receiver report message contents:__
+    /// - ```[..len - 36]``` = Contains the serialized ```ReceiverReport```
+    /// - ```[len - 36..]``` = Contains the uuid of the participant whose stream is reported
+    pub async fn send_receiver_report(
+        &self,
+        uuid: String,
+        report: &ReceiverReport,
+        encryption_key: &[u8],
+    ) -> anyhow::Result<()>
+    {
+        let mut bytes = serde_json::to_vec(report)?;
+
+        bytes.append(&mut uuid.as_bytes().to_vec());
+
+        self.send_bytes(bytes, encryption_key, UdpMessageType::ReceiverReport)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sends a heartbeat to the server, the server sends it back so that the round trip time and the packet loss can be measured
+    pub async fn send_heartbeat(&self, encryption_key: &[u8]) -> anyhow::Result<()>
+    {
+        let sequence_number = self.connection_quality.lock().unwrap().on_heartbeat_sent();
+
+        self.send_bytes(
+            sequence_number.to_be_bytes().to_vec(),
+            encryption_key,
+            UdpMessageType::Heartbeat,
+        )
+        .await?;
+
+        Ok(())
+    }
+
     /// This function sends bytes on the UdpSocket the instance contains
     /// The bytes passed to this function are automatically encrypted by the provided encryption key
     /// Message type appends a set isize to the message so that the server can identify each message
@@ -2493,6 +3402,7 @@ impl Voip
         Ok(())
     }
 
+    /// This function sends the image in parts, with parity parts so that the receivers can rebuild it even if some of the parts are lost
     pub async fn send_image(
         &self,
         uuid: String,
@@ -2500,24 +3410,17 @@ impl Voip
         encryption_key: &[u8],
     ) -> anyhow::Result<()>
     {
-        //Create image parts by splitting it every 60000 bytes
-        let image_parts_tuple: Vec<(String, &[u8])> = bytes
-            .chunks(60000)
-            .map(|image_part| (sha256::digest(image_part), image_part))
+        //Create image parts and parity parts
+        let (header_message, parts) = fragment_image(uuid.clone(), bytes)?;
+
+        let image_parts_tuple: Vec<(String, &[u8])> = header_message
+            .image_parts_hash
+            .iter()
+            .cloned()
+            .zip(parts.iter().map(|part| part.as_slice()))
             .collect();
 
-        let image_parts = Vec::from_iter(image_parts_tuple.iter().map(|part| part.0.clone()));
-
-        let identificator = sha256::digest(
-            image_parts
-                .iter()
-                .flat_map(|hash| hash.as_bytes().to_vec())
-                .collect::<Vec<u8>>(),
-        );
-
-        //Create header message
-        let header_message =
-            ImageHeader::new(uuid.clone(), image_parts.clone(), identificator.clone());
+        let identificator = header_message.identificator.clone();
 
         //Send image header
         self.send_bytes(
@@ -2532,13 +3435,21 @@ impl Voip
         self.send_image_parts(image_parts_tuple, uuid, encryption_key, identificator)
             .await?;
 
+        //Send the image header again, so that the image can be rebuilt even if the first copy is lost
+        self.send_bytes(
+            serde_json::to_string(&header_message)?.as_bytes().to_vec(),
+            encryption_key,
+            UdpMessageType::ImageHeader,
+        )
+        .await?;
+
         Ok(())
     }
 
     /// Send the images specified in the ```image_parts_tuple``` argument
     /// __Image message contents:__
     /// - ```[len - 64..]``` = Contains the hash (sha256 hash) of the image part we are sending
-    /// - ```[..len - 64]``` = Contains the image part we are sending (JPEG image)
+    /// - ```[..len - 64]``` = Contains the image part we are sending (Encoded video frame)
     /// - **The hash length is 64 bytes.**
     /// - **The identificator is 64 bytes.**
     /// - **The uuid is 36 bytes.**
@@ -2607,6 +3518,78 @@ impl Default for AudioPlayback
     }
 }
 
+impl AudioPlayback
+{
+    /// Opens the output device with the specified name, if the name is ```None``` the default output device is opened
+    pub fn new(device_name: Option<&str>) -> anyhow::Result<Self>
+    {
+        let (stream, stream_handle) = match device_name {
+            Some(device_name) => {
+                let device = cpal::default_host()
+                    .output_devices()?
+                    .find(|device| device.name().is_ok_and(|name| name == device_name))
+                    .ok_or_else(|| Error::msg(format!("Output device not found: {device_name}")))?;
+
+                OutputStream::try_from_device(&device)?
+            },
+            None => OutputStream::try_default()?,
+        };
+
+        Ok(Self {
+            _stream: Arc::new(stream),
+            stream_handle,
+            sink_list: Vec::new(),
+            settings_list: Vec::new(),
+        })
+    }
+}
+
+/// Returns the name of all the output devices of the default host
+pub fn get_output_device_names() -> anyhow::Result<Vec<String>>
+{
+    Ok(cpal::default_host()
+        .output_devices()?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// The local audio settings of a participant in a voip call, these are only applied on our side
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ParticipantAudioSettings
+{
+    /// The volume of the participant's audio
+    pub volume: f32,
+
+    /// Whether the participant has been muted by us
+    pub muted: bool,
+}
+
+impl Default for ParticipantAudioSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            volume: 1.,
+            muted: false,
+        }
+    }
+}
+
+impl ParticipantAudioSettings
+{
+    /// Returns the volume the participant's ```Sink``` should be set to
+    pub fn effective_volume(&self) -> f32
+    {
+        if self.muted {
+            0.
+        }
+        else {
+            self.volume
+        }
+    }
+}
+
 #[derive(Clone)]
 ///This is used by the audio player, this is where you can set the speed and volume etc
 pub struct AudioSettings
@@ -2640,6 +3623,65 @@ impl Default for AudioSettings
     }
 }
 
+/// This enum decides when the microphone's audio is sent in a voip call
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoiceActivationMode
+{
+    /// The audio is always sent while the microphone is enabled
+    #[default]
+    Continuous,
+
+    /// The audio is only sent if the ```VoiceActivityDetector``` has detected speech
+    VoiceActivity,
+
+    /// The audio is only sent while the push-to-talk key is held down
+    PushToTalk,
+}
+
+impl Display for VoiceActivationMode
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str(match self {
+            VoiceActivationMode::Continuous => "Continuous",
+            VoiceActivationMode::VoiceActivity => "Voice activity",
+            VoiceActivationMode::PushToTalk => "Push-to-talk",
+        })
+    }
+}
+
+/// The settings of the voice activation, these are modified in the settings and read by the voip sender thread
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct VoiceActivationSettings
+{
+    /// Decides when the microphone's audio is sent
+    pub mode: VoiceActivationMode,
+
+    /// The loudness (in dBFS) the microphone's audio has to reach to be considered speech
+    pub threshold_db: f32,
+
+    /// The amount of time (in milliseconds) the audio is still sent after the speech has ended
+    pub hangover_ms: u64,
+
+    /// The key which has to be held down to talk in push-to-talk mode
+    /// The key is read from the operating system, so it is registered while the application isnt focused too
+    pub push_to_talk_key: egui::Key,
+}
+
+impl Default for VoiceActivationSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            mode: VoiceActivationMode::default(),
+            threshold_db: -45.,
+            hangover_ms: 300,
+            push_to_talk_key: egui::Key::F8,
+        }
+    }
+}
+
 /*
 Maunally create a struct which implements the following traits:
                                                             Read
@@ -2782,14 +3824,17 @@ pub fn ipv6_get() -> Result<String, std::io::Error>
 pub struct UserInformation
 {
     pub profile: ClientProfile,
+    /// The pictures of the profile, the profile only contains their hashes
+    pub profile_pictures: ProfilePictures,
     /// the client's username
     pub username: String,
     /// IMPORTANT: PASSWORD *IS* ENCRYPTED BY FUNCTIONS IMPLEMENTED BY THIS TYPE
     pub password: String,
     /// The uuid isnt encrypted
     pub uuid: String,
-    /// bookmarked ips are empty by default, IMPORTANT: THESE ARE *NOT* ENCRYPTED BY DEFAULT
-    pub bookmarked_ips: Vec<String>,
+    /// The bookmarks are empty by default, IMPORTANT: THESE ARE *NOT* ENCRYPTED BY DEFAULT, only with the whole file
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
     /// The path to the logged in user's file
     pub path: PathBuf,
 }
@@ -2809,19 +3854,28 @@ impl UserInformation
         path: PathBuf,
     ) -> Self
     {
-        Self {
+        let profile_pictures = ProfilePictures {
+            small: small_profile_picture,
+            normal: normal_profile_picture,
+        };
+
+        let mut profile = ClientProfile {
             username: username.clone(),
+            full_name,
+            gender,
+            birth_date,
+            ..Default::default()
+        };
+
+        profile.set_profile_pictures(&profile_pictures);
+
+        Self {
+            username,
             password: encrypt(password),
             uuid,
-            bookmarked_ips: Vec::new(),
-            profile: ClientProfile {
-                username,
-                full_name,
-                gender,
-                birth_date,
-                normal_profile_picture,
-                small_profile_picture,
-            },
+            bookmarks: Vec::new(),
+            profile,
+            profile_pictures,
             path,
         }
     }
@@ -2839,19 +3893,53 @@ impl UserInformation
         let hashed_password = sha256::digest(self.password.clone());
         let encryption_key = hex::decode(hashed_password)?;
 
-        encrypt_aes256(serde_json::to_string(&self)?, &encryption_key)
+        Ok(serde_json::to_string(&AccountFileEnvelope {
+            version: ACCOUNT_FILE_VERSION,
+            data: encrypt_aes256(serde_json::to_string(&self)?, &encryption_key)?,
+        })?)
     }
 
     /// This deserializer function automatically decrypts the string the *encrypt_aes256* fn to Self
-    pub fn deserialize(serialized_struct: &str, password: String) -> anyhow::Result<Self>
+    /// The file is migrated to the current version, the returned version is the one the file was saved with
+    /// If the file has been decrypted but couldnt be read, an ```AccountFileError``` is returned
+    pub fn deserialize(serialized_struct: &str, password: String) -> anyhow::Result<(Self, u32)>
     {
         let hashed_password = sha256::digest(password);
         let encryption_key = hex::decode(hashed_password)?;
 
-        Ok(serde_json::from_str::<Self>(&decrypt_aes256(
-            serialized_struct,
-            &encryption_key,
-        )?)?)
+        let (version, encrypted_data) =
+            match serde_json::from_str::<AccountFileEnvelope>(serialized_struct) {
+                Ok(envelope) => (envelope.version, envelope.data),
+                //The files saved before the envelope was introduced only contain the encrypted data
+                Err(_) => (1, serialized_struct.trim().to_string()),
+            };
+
+        let account_file_error = |reason: String| AccountFileError { version, reason };
+
+        if version > ACCOUNT_FILE_VERSION {
+            return Err(account_file_error(format!(
+                "it has been saved by a newer version of Matthias (This version can read up to version {ACCOUNT_FILE_VERSION}), please update the application."
+            ))
+            .into());
+        }
+
+        //If the data isnt even encrypted, the file has been damaged
+        if hex::decode(&encrypted_data).map_or(true, |bytes| bytes.len() < 12) {
+            return Err(account_file_error("the file is damaged".to_string()).into());
+        }
+
+        let decrypted_data = decrypt_aes256(&encrypted_data, &encryption_key)?;
+
+        let mut user_information = serde_json::from_str::<serde_json::Value>(&decrypted_data)
+            .map_err(|err| account_file_error(err.to_string()))?;
+
+        migrate_account_file(&mut user_information, version)
+            .map_err(|err| account_file_error(format!("failed to upgrade it: {err}")))?;
+
+        let user_information = serde_json::from_value::<Self>(user_information)
+            .map_err(|err| account_file_error(err.to_string()))?;
+
+        Ok((user_information, version))
     }
 
     /// Write file to the specified path
@@ -2868,21 +3956,217 @@ impl UserInformation
         Ok(())
     }
 
-    /// Add a bookmark entry which can be converted to a string
-    pub fn add_bookmark_entry<T>(&mut self, item: T)
+    /// Add a bookmark entry, the address is displayed as its name until it is renamed
+    pub fn add_bookmark_entry<T>(&mut self, address: T)
     where
         T: ToString,
     {
-        self.bookmarked_ips.push(item.to_string());
+        self.bookmarks.push(Bookmark::new(address.to_string()));
     }
 
     /// Remove bookmark at index from the list, this can panic if the wrong index is passed in
     pub fn delete_bookmark_entry(&mut self, index: usize)
     {
-        self.bookmarked_ips.remove(index);
+        self.bookmarks.remove(index);
+    }
+
+    /// Returns the bookmark of the address, if it has been saved
+    pub fn bookmark_mut(&mut self, address: &str) -> Option<&mut Bookmark>
+    {
+        self.bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.address == address)
+    }
+}
+
+/// A saved server, the bookmarks are stored in the user's file
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Bookmark
+{
+    /// The name displayed in the bookmarks, if this is empty the address is displayed
+    pub name: String,
+
+    /// The address of the server
+    pub address: String,
+
+    /// The password of the server, this is stored inside the user's file so it is encrypted with it
+    pub password: Option<String>,
+
+    /// The identifier the server has sent the first time the client has connected to it
+    /// If a server with a different identifier answers on the address, the user is warned
+    /// This doesnt authenticate the server, a server impersonating it could send the same identifier
+    pub fingerprint: Option<String>,
+
+    /// When the client has last connected to the server
+    pub last_connected: Option<DateTime<Utc>>,
+
+    /// Whether the client connects to the server after logging in
+    pub auto_connect: bool,
+
+    /// The folder the bookmark is displayed in, the bookmarks without a folder are displayed on top
+    pub folder: String,
+}
+
+impl Bookmark
+{
+    pub fn new(address: String) -> Self
+    {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the name of the bookmark, or its address if it hasnt been named
+    pub fn display_name(&self) -> &str
+    {
+        if self.name.trim().is_empty() {
+            &self.address
+        }
+        else {
+            &self.name
+        }
+    }
+}
+
+/// The version of the account file's schema
+/// When a field of ```UserInformation``` is changed, this has to be increased and a migration has to be added to ```ACCOUNT_FILE_MIGRATIONS```
+pub const ACCOUNT_FILE_VERSION: u32 = 3;
+
+/// The migrations of the account file, the migration at index ```n``` upgrades the file from version ```n + 1``` to version ```n + 2```
+/// The migrations are run on the decrypted json, before it is deserialized into ```UserInformation```
+const ACCOUNT_FILE_MIGRATIONS: [fn(&mut serde_json::Value) -> Result<()>; 2] =
+    [migrate_bookmarked_ips, migrate_profile_pictures];
+
+/// The envelope around the encrypted account file
+/// The version is stored unencrypted, so the file can be migrated before it is deserialized
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AccountFileEnvelope
+{
+    version: u32,
+
+    /// The encrypted ```UserInformation```
+    data: String,
+}
+
+/// The server has refused the connection, because of the credentials or because the client has been banned
+/// Connecting again would be refused the same way, so these connections arent retried automatically
+#[derive(Debug)]
+pub struct ConnectionRefusedError(pub String);
+
+impl Display for ConnectionRefusedError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConnectionRefusedError {}
+
+/// The account file has been decrypted, but it couldnt be read or migrated
+/// This is separate from the decryption's error, so a damaged file isnt reported as an invalid password
+#[derive(Debug)]
+pub struct AccountFileError
+{
+    /// The version the file was saved with
+    pub version: u32,
+
+    pub reason: String,
+}
+
+impl Display for AccountFileError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(
+            f,
+            "The account file (version {}) couldnt be read: {}",
+            self.version, self.reason
+        )
     }
 }
 
+impl std::error::Error for AccountFileError {}
+
+/// Version 1 -> 2: The bookmarks were the bare addresses stored in ```bookmarked_ips```
+fn migrate_bookmarked_ips(user_information: &mut serde_json::Value) -> Result<()>
+{
+    let user_information = user_information
+        .as_object_mut()
+        .ok_or_else(|| Error::msg("The account isnt an object"))?;
+
+    let Some(bookmarked_ips) = user_information.remove("bookmarked_ips")
+    else {
+        return Ok(());
+    };
+
+    let bookmarks = serde_json::from_value::<Vec<String>>(bookmarked_ips)?
+        .into_iter()
+        .map(Bookmark::new)
+        .collect::<Vec<Bookmark>>();
+
+    user_information.insert("bookmarks".to_string(), serde_json::to_value(bookmarks)?);
+
+    Ok(())
+}
+
+/// Version 3 moved the profile pictures out of the profile, the profile only contains their hashes
+fn migrate_profile_pictures(user_information: &mut serde_json::Value) -> Result<()>
+{
+    let user_information = user_information
+        .as_object_mut()
+        .ok_or_else(|| Error::msg("The account isnt an object"))?;
+
+    let profile = user_information
+        .get_mut("profile")
+        .and_then(|profile| profile.as_object_mut())
+        .ok_or_else(|| Error::msg("The account doesnt contain a profile"))?;
+
+    let (Some(small_profile_picture), Some(normal_profile_picture)) = (
+        profile.remove("small_profile_picture"),
+        profile.remove("normal_profile_picture"),
+    )
+    else {
+        bail!("The profile doesnt contain its pictures");
+    };
+
+    let profile_pictures = ProfilePictures {
+        small: serde_json::from_value(small_profile_picture)?,
+        normal: serde_json::from_value(normal_profile_picture)?,
+    };
+
+    profile.insert(
+        "small_profile_picture_hash".to_string(),
+        sha256::digest(&profile_pictures.small).into(),
+    );
+    profile.insert(
+        "normal_profile_picture_hash".to_string(),
+        sha256::digest(&profile_pictures.normal).into(),
+    );
+
+    user_information.insert(
+        "profile_pictures".to_string(),
+        serde_json::to_value(profile_pictures)?,
+    );
+
+    Ok(())
+}
+
+/// Runs the migrations of the account file, from the version it was saved with
+fn migrate_account_file(user_information: &mut serde_json::Value, version: u32) -> Result<()>
+{
+    for migration in ACCOUNT_FILE_MIGRATIONS
+        .iter()
+        .skip(version.saturating_sub(1) as usize)
+    {
+        migration(user_information)?;
+    }
+
+    Ok(())
+}
+
 /// aes256 is decrypted by this function by a fixed key
 pub fn decrypt_aes256(string_to_be_decrypted: &str, key: &[u8]) -> anyhow::Result<String>
 {
@@ -2988,16 +4272,111 @@ pub fn login(username: String, password: String) -> Result<(UserInformation, Pat
 
     let path = PathBuf::from(format!("{app_data}\\Matthias\\{username}.szch"));
 
-    let file_contents: UserInformation =
-        UserInformation::deserialize(&fs::read_to_string(&path)?, encrypt(password.clone()))?;
+    let (mut file_contents, version) =
+        UserInformation::deserialize(&fs::read_to_string(&path)?, encrypt(password.clone()))
+            .map_err(|err| {
+                match err.downcast_ref::<AccountFileError>() {
+                    Some(account_file_error) => {
+                        Error::msg(account_file_recovery_message(account_file_error, &path))
+                    },
+                    None => err,
+                }
+            })?;
 
     let user_check = username == file_contents.username;
 
     ensure!(user_check, "File corrupted at the username entry");
 
+    //The stored path is outdated if the account has been imported from another computer, every file of the account is written next to the account file
+    if file_contents.path != path {
+        file_contents.path = path.clone();
+
+        //The outdated files are written after they have been backed up
+        if version == ACCOUNT_FILE_VERSION {
+            if let Err(err) = file_contents.write_file(path.clone()) {
+                tracing::error!("Failed to update the path of the account file: {err}");
+            }
+        }
+    }
+
+    //Upgrade the file, the original one is kept in case the upgraded file cant be read
+    if version < ACCOUNT_FILE_VERSION {
+        let backup_path = path.with_extension(format!("szch.v{version}.bak"));
+
+        match fs::copy(&path, &backup_path) {
+            Ok(_) => {
+                if let Err(err) = file_contents.write_file(path.clone()) {
+                    tracing::error!("Failed to upgrade the account file: {err}");
+                }
+            },
+            //The file is only upgraded if it has been backed up, it will be migrated again on the next login
+            Err(err) => {
+                tracing::error!("Failed to back up the account file before upgrading it: {err}");
+            },
+        }
+    }
+
     Ok((file_contents, path))
 }
 
+/// Returns the backups of the account file, these are created before upgrading the file
+pub fn account_file_backups(path: &Path) -> Vec<PathBuf>
+{
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    path.parent()
+        .and_then(|directory| fs::read_dir(directory).ok())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|entry_path| {
+                    entry_path
+                        .file_name()
+                        .map(|entry_name| entry_name.to_string_lossy())
+                        .is_some_and(|entry_name| {
+                            entry_name.starts_with(&format!("{file_name}.v"))
+                                && entry_name.ends_with(".bak")
+                        })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The message displayed when the account file couldnt be read, this lists the backups which can be restored
+fn account_file_recovery_message(account_file_error: &AccountFileError, path: &Path) -> String
+{
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let backups: Vec<String> = account_file_backups(path)
+        .iter()
+        .filter_map(|backup| backup.file_name())
+        .map(|backup| backup.to_string_lossy().to_string())
+        .collect();
+
+    let recovery = if backups.is_empty() {
+        "The file has not been modified, no backups have been found.".to_string()
+    }
+    else {
+        format!(
+            "The file has not been modified. To recover the account, replace {file_name} with one of its backups: {}",
+            backups.join(", ")
+        )
+    };
+
+    format!(
+        "{account_file_error}\n{recovery}\nThe account files are stored in: {}",
+        path.parent().unwrap_or(path).display()
+    )
+}
+
 ///Register a new profile
 pub fn register(register: Register) -> anyhow::Result<UserInformation>
 {
@@ -3113,7 +4492,6 @@ where
     }
 }
 
-
 /// This function fetches the incoming full message's length (it reads the 4 bytes and creates an u32 number from them, which it returns)
 ///Display Error message with a messagebox
 pub fn display_info_message<T>(display: T, toasts: Arc<Mutex<Toasts>>)
@@ -3546,6 +4924,18 @@ pub struct Reaction
     pub authors: Vec<String>,
 }
 
+/// The byte length of the parts an image is split into
+pub const IMAGE_PART_LENGTH: usize = 4096;
+
+/// The most amount of data parts an image is split into, if the image is larger the parts get longer
+const MAX_IMAGE_DATA_PARTS: usize = 200;
+
+/// The amount of data parts protected by one parity part
+const IMAGE_PARTS_PER_PARITY_PART: usize = 4;
+
+/// The amount of time an ```ImageFrame``` is kept in the ```ImageBuffer```, the frames which couldnt be rebuilt by then are lost
+pub const IMAGE_FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// This header struct contains the uuid of the author who has sent this header and the image parts the server would receive
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ImageHeader
@@ -3553,33 +4943,265 @@ pub struct ImageHeader
     /// The author of this header aka who constructed it
     pub uuid: String,
 
-    /// This entry contains the image parts in a list.
+    /// This entry contains the image parts in a list, the data parts are followed by the parity parts.
     /// The keys are the bytes hashsed with ```Sha256```.
     pub image_parts_hash: Vec<String>,
 
     /// Custom identifier of the ```ImageHeader``` the server uses this to know where the image part goes
     pub identificator: String,
+
+    /// The amount of data parts in ```image_parts_hash```, the rest are Reed-Solomon parity parts
+    pub data_parts: usize,
+
+    /// The length of the image, the last data part is padded to the length of the other parts
+    pub image_length: usize,
 }
 
 impl ImageHeader
 {
     /// Construct a new ```ImageHeader``` instance
-    pub fn new(uuid: String, image_parts_hash: Vec<String>, identificator: String) -> Self
+    pub fn new(
+        uuid: String,
+        image_parts_hash: Vec<String>,
+        identificator: String,
+        data_parts: usize,
+        image_length: usize,
+    ) -> Self
     {
         Self {
             uuid,
             image_parts_hash,
             identificator,
+            data_parts,
+            image_length,
         }
     }
 }
 
+/// Splits the image into equal length data parts, and creates Reed-Solomon parity parts for them.
+/// Any ```data_parts``` amount of the returned parts are enough to rebuild the image.
+/// The returned ```ImageHeader``` contains the hashes of the parts in the same order as the parts.
+pub fn fragment_image(uuid: String, bytes: &[u8]) -> anyhow::Result<(ImageHeader, Vec<Vec<u8>>)>
+{
+    let part_length = IMAGE_PART_LENGTH
+        .max((bytes.len() + MAX_IMAGE_DATA_PARTS - 1) / MAX_IMAGE_DATA_PARTS)
+        .min(bytes.len())
+        .max(1);
+
+    //Pad the last part, the parity can only be calculated for parts of the same length
+    let mut parts: Vec<Vec<u8>> = bytes
+        .chunks(part_length)
+        .map(|part| {
+            let mut part = part.to_vec();
+
+            part.resize(part_length, 0);
+
+            part
+        })
+        .collect();
+
+    if parts.is_empty() {
+        parts.push(vec![0; part_length]);
+    }
+
+    let data_parts = parts.len();
+    let parity_parts = (data_parts + IMAGE_PARTS_PER_PARITY_PART - 1) / IMAGE_PARTS_PER_PARITY_PART;
+
+    parts.extend(std::iter::repeat(vec![0; part_length]).take(parity_parts));
+
+    ReedSolomon::new(data_parts, parity_parts)?.encode(&mut parts)?;
+
+    let image_parts_hash: Vec<String> = parts
+        .iter()
+        .map(|part| sha256::digest(part.as_slice()))
+        .collect();
+
+    let identificator = sha256::digest(
+        image_parts_hash
+            .iter()
+            .flat_map(|hash| hash.as_bytes().to_vec())
+            .collect::<Vec<u8>>(),
+    );
+
+    Ok((
+        ImageHeader::new(
+            uuid,
+            image_parts_hash,
+            identificator,
+            data_parts,
+            bytes.len(),
+        ),
+        parts,
+    ))
+}
+
+/// The parts of an image received so far, the image can be rebuilt once ```data_parts``` amount of parts have arrived
+#[derive(Debug, Clone)]
+pub struct ImageFrame
+{
+    /// The header of the image, this is ```None``` if only parts of the image have arrived so far
+    pub header: Option<ImageHeader>,
+
+    /// The received parts, in the order of the hashes in the header
+    pub parts: Vec<Option<Vec<u8>>>,
+
+    /// The parts which have arrived before the header, they are put in their place once the header arrives
+    pub pending_parts: Vec<(String, Vec<u8>)>,
+
+    /// Whether the image has been rebuilt, the parts which werent needed to rebuild it are dropped
+    pub rebuilt: bool,
+
+    /// The time the first message of the frame has arrived, this is used to evict the frames which cant be rebuilt
+    pub received_at: Instant,
+}
+
+impl ImageFrame
+{
+    pub fn new(header: ImageHeader) -> Self
+    {
+        let mut image_frame = Self::without_header();
+
+        image_frame.set_header(header);
+
+        image_frame
+    }
+
+    /// Creates a frame for the parts which have arrived before their header
+    pub fn without_header() -> Self
+    {
+        Self {
+            header: None,
+            parts: Vec::new(),
+            pending_parts: Vec::new(),
+            rebuilt: false,
+            received_at: Instant::now(),
+        }
+    }
+
+    /// Sets the header of the frame, and puts the parts which have arrived before it in their place.
+    /// The header is sent more than once, so the copies arriving after the first one are ignored.
+    pub fn set_header(&mut self, header: ImageHeader)
+    {
+        if self.header.is_some() {
+            return;
+        }
+
+        self.parts = vec![None; header.image_parts_hash.len()];
+        self.header = Some(header);
+
+        for (hash, bytes) in std::mem::take(&mut self.pending_parts) {
+            if !self.insert_part(&hash, bytes) {
+                tracing::error!("Image part hash not found in the image header: {hash}");
+            }
+        }
+    }
+
+    /// Inserts the part into the place of its hash, returns false if the hash isnt in the header.
+    /// If the header hasnt arrived yet, the part is kept until it does.
+    pub fn insert_part(&mut self, hash: &str, bytes: Vec<u8>) -> bool
+    {
+        if self.rebuilt {
+            return true;
+        }
+
+        let Some(header) = &self.header
+        else {
+            self.pending_parts.push((hash.to_string(), bytes));
+
+            return true;
+        };
+
+        //Multiple parts can have the same hash if their contents are the same, so we fill the first empty place
+        let index = header
+            .image_parts_hash
+            .iter()
+            .zip(self.parts.iter())
+            .position(|(part_hash, part)| part_hash == hash && part.is_none());
+
+        match index {
+            Some(index) => {
+                self.parts[index] = Some(bytes);
+
+                true
+            },
+            None => {
+                header
+                    .image_parts_hash
+                    .iter()
+                    .any(|part_hash| part_hash == hash)
+            },
+        }
+    }
+
+    /// Rebuilds the image from the received parts, the lost data parts are reconstructed from the parity parts.
+    /// Returns ```None``` if not enough parts have arrived yet, or if the image has already been rebuilt.
+    pub fn try_rebuild(&self) -> anyhow::Result<Option<Vec<u8>>>
+    {
+        let Some(header) = &self.header
+        else {
+            return Ok(None);
+        };
+
+        let data_parts = header.data_parts;
+
+        if self.rebuilt || self.parts.iter().filter(|part| part.is_some()).count() < data_parts {
+            return Ok(None);
+        }
+
+        let mut parts = self.parts.clone();
+
+        //Reconstruct the lost data parts
+        if parts[..data_parts].iter().any(Option::is_none) {
+            ReedSolomon::new(data_parts, parts.len() - data_parts)?.reconstruct_data(&mut parts)?;
+        }
+
+        let mut image: Vec<u8> = parts
+            .into_iter()
+            .take(data_parts)
+            .flat_map(Option::unwrap_or_default)
+            .collect();
+
+        //Remove the padding
+        image.truncate(header.image_length);
+
+        Ok(Some(image))
+    }
+}
+
+/// Rebuilds the frame if enough of its parts have arrived, and drains the older frames because a newer image has been rebuilt.
+/// The rebuilt frame is kept until it gets evicted, so that its late parts and header copies dont create a new frame.
+fn rebuild_image_frame(
+    image_frames: &mut IndexMap<String, ImageFrame>,
+    identificator: &str,
+) -> anyhow::Result<Option<Vec<u8>>>
+{
+    let Some((index, _, image_frame)) = image_frames.get_full_mut(identificator)
+    else {
+        return Ok(None);
+    };
+
+    let Some(image_bytes) = image_frame.try_rebuild()?
+    else {
+        return Ok(None);
+    };
+
+    image_frame.rebuilt = true;
+
+    //The parts arent needed anymore
+    image_frame.parts.clear();
+
+    image_frames.drain(..index);
+
+    Ok(Some(image_bytes))
+}
+
 /// This function fetches the image header from the decrypted bytes.
 /// It inserts the image header into the ```ImageBuffer``` provided in the arguments.
+/// If all the parts needed have arrived before the header, the author's uuid and the rebuilt image are returned.
 pub fn get_image_header(
     decrypted_bytes: &Vec<u8>,
-    image_buffer: &Arc<DashMap<String, IndexMap<String, HashMap<String, Option<Vec<u8>>>>>>,
-) -> anyhow::Result<()>
+    image_buffer: &ImageBuffer,
+) -> anyhow::Result<Option<(String, Vec<u8>)>>
 {
     //Get actual message, we ignore the message type
     let message_bytes = decrypted_bytes.to_vec();
@@ -3590,19 +5212,232 @@ pub fn get_image_header(
     //```Deserialize``` string into ```ImageHeader``` struct
     let image_header = serde_json::from_str::<ImageHeader>(&message_as_string)?;
 
+    let uuid = image_header.uuid.clone();
+    let identificator = image_header.identificator.clone();
+
     //Try getting the uuid's ImageHeaders
-    //Insert IndexMap into the ```image_buffer```
-    if let Some(mut image_headers) = image_buffer.get_mut(&image_header.uuid) {
-        image_headers.value_mut().insert(
-            image_header.identificator.clone(),
-            HashMap::from_iter(
-                image_header
-                    .image_parts_hash
-                    .iter()
-                    .map(|hash| (hash.clone(), None)),
-            ),
+    //Insert the header into its frame, the parts may have arrived before it
+    let Some(mut image_frames) = image_buffer.get_mut(&uuid)
+    else {
+        return Ok(None);
+    };
+
+    image_frames
+        .entry(identificator.clone())
+        .or_insert_with(ImageFrame::without_header)
+        .set_header(image_header);
+
+    let rebuilt_image = rebuild_image_frame(&mut image_frames, &identificator)?;
+
+    Ok(rebuilt_image.map(|image_bytes| (uuid, image_bytes)))
+}
+
+/// Inserts the image part into its frame in the ```ImageBuffer```, the part is kept even if its header hasnt arrived yet.
+/// If enough parts of the image have arrived, the rebuilt image is returned.
+pub fn insert_image_part(
+    image_buffer: &ImageBuffer,
+    uuid: &str,
+    identificator: &str,
+    hash: &str,
+    bytes: Vec<u8>,
+) -> anyhow::Result<Option<Vec<u8>>>
+{
+    let Some(mut image_frames) = image_buffer.get_mut(uuid)
+    else {
+        bail!("User not found in the image header list: {uuid}");
+    };
+
+    let image_frame = image_frames
+        .entry(identificator.to_string())
+        .or_insert_with(ImageFrame::without_header);
+
+    if !image_frame.insert_part(hash, bytes) {
+        tracing::error!("Image part hash not found in the image header: {hash}");
+    }
+
+    rebuild_image_frame(&mut image_frames, identificator)
+}
+
+/// Evicts the frames which couldnt be rebuilt in ```IMAGE_FRAME_TIMEOUT```, their parts are lost.
+/// This is called periodically, so the frames are evicted even if the author has stopped sending images.
+pub fn evict_stale_image_frames(image_frames: &mut IndexMap<String, ImageFrame>)
+{
+    image_frames.retain(|_, image_frame| image_frame.received_at.elapsed() < IMAGE_FRAME_TIMEOUT);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const AUTHOR_UUID: &str = "b0e6a3a2-6b8f-4f0e-9a7c-2f5d8c1e4b3a";
+
+    /// Bytes which differ in every part, so the parts dont share their hashes
+    fn image_bytes(length: usize) -> Vec<u8>
+    {
+        (0..length).map(|index| (index * 31 % 251) as u8).collect()
+    }
+
+    fn image_buffer() -> ImageBuffer
+    {
+        let image_buffer: ImageBuffer = Arc::new(DashMap::new());
+
+        image_buffer.insert(AUTHOR_UUID.to_string(), IndexMap::new());
+
+        image_buffer
+    }
+
+    #[test]
+    fn rebuilds_image_with_lost_parts()
+    {
+        let bytes = image_bytes(IMAGE_PART_LENGTH * 10 + 123);
+
+        let (header, parts) = fragment_image(AUTHOR_UUID.to_string(), &bytes).unwrap();
+
+        let parity_parts = parts.len() - header.data_parts;
+
+        assert!(parity_parts > 0);
+
+        let mut image_frame = ImageFrame::new(header.clone());
+
+        //Lose as many data parts as there are parity parts
+        let lost_parts: Vec<usize> = (0..parity_parts).map(|index| index * 4).collect();
+
+        for (index, (hash, part)) in header.image_parts_hash.iter().zip(parts).enumerate() {
+            if lost_parts.contains(&index) {
+                continue;
+            }
+
+            assert!(image_frame.try_rebuild().unwrap().is_none());
+
+            assert!(image_frame.insert_part(hash, part));
+        }
+
+        assert_eq!(image_frame.try_rebuild().unwrap(), Some(bytes));
+    }
+
+    #[test]
+    fn rejects_unknown_parts()
+    {
+        let (header, _) = fragment_image(AUTHOR_UUID.to_string(), &image_bytes(100)).unwrap();
+
+        let mut image_frame = ImageFrame::new(header);
+
+        assert!(!image_frame.insert_part(&sha256::digest("unknown"), vec![0; 100]));
+    }
+
+    #[test]
+    fn rebuilds_image_when_header_arrives_last()
+    {
+        let image_buffer = image_buffer();
+
+        let bytes = image_bytes(IMAGE_PART_LENGTH * 3);
+
+        let (header, parts) = fragment_image(AUTHOR_UUID.to_string(), &bytes).unwrap();
+
+        //The first copy of the header has been lost
+        for (hash, part) in header.image_parts_hash.iter().zip(parts) {
+            let rebuilt_image = insert_image_part(
+                &image_buffer,
+                AUTHOR_UUID,
+                &header.identificator,
+                hash,
+                part,
+            )
+            .unwrap();
+
+            assert!(rebuilt_image.is_none());
+        }
+
+        let header_bytes = serde_json::to_string(&header).unwrap().into_bytes();
+
+        assert_eq!(
+            get_image_header(&header_bytes, &image_buffer).unwrap(),
+            Some((AUTHOR_UUID.to_string(), bytes))
         );
+
+        //The copies of the header arriving after the image has been rebuilt are ignored
+        assert!(get_image_header(&header_bytes, &image_buffer)
+            .unwrap()
+            .is_none());
     }
 
-    Ok(())
+    #[test]
+    fn drains_older_frames_after_rebuild()
+    {
+        let image_buffer = image_buffer();
+
+        let (old_header, _) = fragment_image(AUTHOR_UUID.to_string(), &image_bytes(10)).unwrap();
+
+        let bytes = image_bytes(20);
+
+        let (header, parts) = fragment_image(AUTHOR_UUID.to_string(), &bytes).unwrap();
+
+        for header in [&old_header, &header] {
+            get_image_header(
+                &serde_json::to_string(header).unwrap().into_bytes(),
+                &image_buffer,
+            )
+            .unwrap();
+        }
+
+        let mut parts = header.image_parts_hash.iter().zip(parts);
+
+        let (hash, part) = parts.next().unwrap();
+
+        assert_eq!(
+            insert_image_part(
+                &image_buffer,
+                AUTHOR_UUID,
+                &header.identificator,
+                hash,
+                part
+            )
+            .unwrap(),
+            Some(bytes)
+        );
+
+        //The parity parts arriving after the rebuild are dropped
+        for (hash, part) in parts {
+            assert!(insert_image_part(
+                &image_buffer,
+                AUTHOR_UUID,
+                &header.identificator,
+                hash,
+                part
+            )
+            .unwrap()
+            .is_none());
+        }
+
+        let image_frames = image_buffer.get(AUTHOR_UUID).unwrap();
+
+        assert_eq!(image_frames.len(), 1);
+        assert!(image_frames[&header.identificator].rebuilt);
+    }
+
+    #[test]
+    fn evicts_stale_frames()
+    {
+        let (header, _) = fragment_image(AUTHOR_UUID.to_string(), &image_bytes(10)).unwrap();
+
+        let mut stale_frame = ImageFrame::without_header();
+
+        stale_frame.received_at = Instant::now() - IMAGE_FRAME_TIMEOUT;
+
+        let mut image_frames = IndexMap::new();
+
+        image_frames.insert(String::from("stale"), stale_frame);
+        image_frames.insert(
+            header.identificator.clone(),
+            ImageFrame::new(header.clone()),
+        );
+
+        evict_stale_image_frames(&mut image_frames);
+
+        assert_eq!(
+            image_frames.keys().collect::<Vec<_>>(),
+            vec![&header.identificator]
+        );
+    }
 }
@@ -5,14 +5,21 @@ use tokio::{
     sync::Mutex,
 };
 
-use rodio::Sink;
-use std::{fs, path::PathBuf, sync::Arc, time::Duration};
+use rodio::{OutputStreamHandle, Sink};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::select;
 
 use crate::app::backend::{
-    decrypt_aes256, display_error_message, write_audio, write_file, ClientMessage,
-    ClientMessageType, ConnectionState, MessageReaction, PlaybackCursor, Reaction, ServerReplyType,
-    ServerSync, ServerVoipReply,
+    decrypt_aes256, display_error_message, is_private_voice_room, write_audio, write_file,
+    ClientMessage, ClientMessageType, ClientProfile, ConnectionState, IncomingCallInvitation,
+    InvitationAnswer, MessageReaction, PlaybackCursor, Reaction, ServerAudioReply, ServerReplyType,
+    ServerSession, ServerSync, ServerVoipReply, ServerVoipRequest, VoiceActivationMode,
+    DEFAULT_VOICE_ROOM,
 };
 
 use crate::app::backend::{Application, ServerMessageType};
@@ -32,20 +39,43 @@ pub const IDENTIFICATOR_BYTE_OFFSET: usize = 64;
 /// This is the byte length of the uuid's text representation (utf8)
 pub const UUID_STRING_BYTE_LENGTH: usize = 36;
 
-use super::backend::{fetch_incoming_message_length, get_image_header};
+/// This is the byte length of the sequence number of a voice packet (u64)
+pub const SEQUENCE_NUMBER_BYTE_LENGTH: usize = 8;
+
+use super::backend::{
+    evict_stale_image_frames, fetch_incoming_message_length, get_image_header, insert_image_part,
+    IMAGE_FRAME_TIMEOUT,
+};
 pub const VOIP_PACKET_BUFFER_LENGTH_MS: usize = 35;
 
-use image::ImageOutputFormat;
 use std::{
-    collections::VecDeque,
-    io::{BufReader, BufWriter, Cursor},
+    collections::{HashMap, VecDeque},
     sync::mpsc,
 };
 
-use crate::app::backend::{decrypt_aes256_bytes, ImageBuffer, UdpMessageType};
+use crate::app::{
+    backend::{decrypt_aes256_bytes, UdpMessageType, Voip},
+    server::SERVER_UUID,
+};
 
-use crate::app::ui::client_ui::client_actions::audio_recording::{
-    create_wav_file, record_audio_with_interrupt,
+use crate::app::ui::client_ui::client_actions::{
+    audio_processing::AudioProcessor,
+    audio_recording::{create_wav_file, record_audio_with_interrupt},
+    connection_quality::{ConnectionQuality, HEARTBEAT_INTERVAL},
+    jitter_buffer::{
+        create_mixed_audio_decoder, playout_remote_speakers, AudioFrame, RemoteSpeaker,
+        RemoteSpeakers,
+    },
+    message_cache::MessageCache,
+    outbox::SENT_MESSAGE_DISPLAY_DURATION,
+    video_codec::{
+        frame_number, DecodedVideoFrame, VideoDecoder, VideoEncoder, FRAME_NUMBER_BYTE_LENGTH,
+        FRAME_TYPE_BYTE_LENGTH,
+    },
+    video_quality::{ReceiverReport, RECEIVER_REPORT_INTERVAL},
+    voice_activity::{
+        calculate_loudness_db, is_key_held, VoiceActivityDetector, SPEAKING_THRESHOLD_DB,
+    },
 };
 
 /// Sends connection request to the specified server handle, returns the server's response, this function does not create a new thread, and may block
@@ -113,22 +143,23 @@ impl Application
     ///This function is used to send voice recording in a voip connection, this function spawns a thread which record 35ms of your voice then sends it to the linked voip destination
     pub fn client_voip_thread(&mut self, ctx: &egui::Context)
     {
-        if let Some(voip) = self.client_ui.voip.clone() {
+        if let Some(voip) = self.server_session.voip.clone() {
             let uuid = self.opened_user_information.uuid.clone();
-            let destination = self.client_ui.send_on_ip.clone();
-            let decryption_key = self.client_connection.client_secret.clone();
-            let cancel_token = self.voip_shutdown_token.clone();
+            let destination = self.server_session.send_on_ip.clone();
+            let decryption_key = self.server_session.client_connection.client_secret.clone();
+            let cancel_token = self.server_session.voip_shutdown_token.clone();
             let cancel_token_child = cancel_token.child_token();
             let uuid_clone = uuid.clone();
+            let reporter_uuid = uuid.clone();
             let decryption_key_clone = decryption_key.clone();
 
             //This instance of Voip is used when sending images
             let voip_image = voip.clone();
 
             let camera_handle = voip_image.camera_handle.clone();
-            let voice_recording_shutdown = self.voip_video_shutdown_token.clone();
+            let voice_recording_shutdown = self.server_session.voip_video_shutdown_token.clone();
 
-            self.voip_thread.get_or_insert_with(|| {
+            self.server_session.voip_thread.get_or_insert_with(|| {
                 let receiver_socket_part = voip.socket.clone();
                 let microphone_percentage = self.client_ui.microphone_volume.clone();
 
@@ -137,6 +168,10 @@ impl Application
                 self.record_audio_interrupter = tx;
 
                 let enable_microphone = voip.enable_microphone.clone();
+                let is_speaking = voip.is_speaking.clone();
+                let voice_activation = self.client_ui.voice_activation.clone();
+                let audio_processing = self.client_ui.audio_processing.clone();
+                let input_device = self.client_ui.input_device.clone();
 
                 //Sender thread
                 tokio::spawn(async move {
@@ -147,7 +182,19 @@ impl Application
                     voip.socket.connect(destination).await.unwrap();
 
                     //Start audio recorder
-                    let recording_handle = record_audio_with_interrupt(rx, microphone_percentage, voip_audio_buffer.clone(), enable_microphone.clone()).unwrap();
+                    let (recording_handle, recording_config) = record_audio_with_interrupt(rx, microphone_percentage, voip_audio_buffer.clone(), enable_microphone.clone(), input_device.clone()).unwrap();
+
+                    //The sequence number of the next voice packet, the receivers' jitter buffer reorders the packets based on this
+                    let mut sequence_number: u64 = 0;
+
+                    //The threshold and the hangover time are updated from the settings every time we send a packet
+                    let mut voice_activity_detector = VoiceActivityDetector::new(0., Duration::ZERO);
+
+                    //The config the recording thread has opened the input device with, the samples are recorded in this format
+                    let mut config = None;
+
+                    //The enabled stages are updated from the settings every time we send a packet
+                    let mut audio_processor = None;
 
                     //We can just send it because we have already set the default destination address
                     loop {
@@ -156,24 +203,80 @@ impl Application
                             //Record 35ms of audio, send it to the server
                             _ = tokio::time::sleep(Duration::from_millis(VOIP_PACKET_BUFFER_LENGTH_MS as u64)) => {
                                     //We create this scope to tell the compiler the recording handle wont be sent across any awaits
-                                    let playbackable_audio: Vec<u8> = {
+                                    let (mut recorded_samples, opened_config): (Vec<f32>, _) = {
                                         //Lock handle
                                         let mut recording_handle = recording_handle.lock().unwrap();
-                                        //Create wav bytes
-                                        let playbackable_audio: Vec<u8> = create_wav_file(
-                                            recording_handle.clone().into()
-                                        );
+                                        //Copy samples
+                                        let recorded_samples: Vec<f32> = recording_handle.clone().into();
                                         //Clear out buffer, make the capacity remain (We created this VecDeque with said default capacity)
                                         recording_handle.clear();
-                                        //Return wav bytes
-                                        playbackable_audio
+                                        //Return samples, with the config they have been recorded with
+                                        (recorded_samples, recording_config.lock().unwrap().clone())
                                     };
+
+                                    //Check if the input device has been reopened, the filters' state is only valid for the same format
+                                    if opened_config != config {
+                                        audio_processor = opened_config.as_ref().map(|opened_config| AudioProcessor::new(audio_processing.lock().unwrap().clone(), opened_config.channels(), opened_config.sample_rate().0));
+
+                                        config = opened_config;
+                                    }
+
+                                    //There is no input device to record from
+                                    let (Some(config), Some(audio_processor)) = (&config, &mut audio_processor) else {
+                                        is_speaking.store(false, std::sync::atomic::Ordering::Relaxed);
+
+                                        continue;
+                                    };
+
+                                    //Process the recorded audio before detecting voice activity, so that the noise doesnt trigger it
+                                    audio_processor.settings.clone_from(&audio_processing.lock().unwrap());
+                                    audio_processor.process(&mut recorded_samples);
+
+                                    //Decide whether we should send the recorded audio
+                                    let should_transmit = {
+                                        let voice_activation = voice_activation.lock().unwrap();
+
+                                        voice_activity_detector.threshold_db = voice_activation.threshold_db;
+                                        voice_activity_detector.hangover = Duration::from_millis(voice_activation.hangover_ms);
+
+                                        let voice_detected = voice_activity_detector.detect(&recorded_samples);
+
+                                        let should_transmit = enable_microphone.load(std::sync::atomic::Ordering::Relaxed) && match voice_activation.mode {
+                                            VoiceActivationMode::Continuous => true,
+                                            VoiceActivationMode::VoiceActivity => voice_detected,
+                                            VoiceActivationMode::PushToTalk => is_key_held(voice_activation.push_to_talk_key),
+                                        };
+
+                                        //Display the speaking indicator
+                                        is_speaking.store(should_transmit && voice_detected, std::sync::atomic::Ordering::Relaxed);
+
+                                        should_transmit
+                                    };
+
+                                    //Dont waste bandwidth on audio no one should hear
+                                    if !should_transmit {
+                                        continue;
+                                    }
+
+                                    //Add our own voice to the recording of the call
+                                    if let Some(call_recorder) = &*voip.call_recorder.lock().unwrap() {
+                                        call_recorder.push_audio(&uuid, &AudioFrame {
+                                            channels: config.channels(),
+                                            sample_rate: config.sample_rate().0,
+                                            samples: recorded_samples.clone(),
+                                        });
+                                    }
+
+                                    //Create wav bytes
+                                    let playbackable_audio: Vec<u8> = create_wav_file(recorded_samples, config);
                                     //Create audio chunks
                                     let audio_chunks = playbackable_audio.chunks(30000);
 
                                     //Avoid sending too much data (If there is more recorded we just iterate over the chunks and not send them at once)
                                     for chunk in audio_chunks {
-                                        voip.send_audio(uuid.clone(), chunk.to_vec(), &decryption_key).await.unwrap();
+                                        voip.send_audio(uuid.clone(), chunk.to_vec(), sequence_number, &decryption_key).await.unwrap();
+
+                                        sequence_number += 1;
                                     }
                             },
                             _ = cancel_token.cancelled() => {
@@ -187,15 +290,136 @@ impl Application
                 //Clone ctx
                 let ctx = ctx.clone();
 
-                //Create sink
-                let sink = Arc::new(rodio::Sink::try_new(&self.client_ui.audio_playback.stream_handle).unwrap());
-                let decryption_key = self.client_connection.client_secret.clone();
+                //Every remote speaker gets their own sink and jitter buffer
+                let remote_speakers = voip_image.remote_speakers.clone();
+                let stream_handle = self.client_ui.audio_playback.stream_handle.clone();
+                let decryption_key = self.server_session.client_connection.client_secret.clone();
+
+                let voip_receiver = voip_image.clone();
+
+                let playout_remote_speakers_handle = remote_speakers.clone();
+                let participant_audio = self.client_ui.participant_audio.clone();
+                let call_recorder = voip_image.call_recorder.clone();
+                let playout_cancel_token = cancel_token_child.clone();
+
+                //Playout thread
+                tokio::spawn(async move {
+                    let mut playout_interval = tokio::time::interval(Duration::from_millis(VOIP_PACKET_BUFFER_LENGTH_MS as u64));
+
+                    loop {
+                        select! {
+                            _ = playout_cancel_token.cancelled() => {
+                                //Exit thread
+                                break;
+                            },
+
+                            //Feed the sinks from the jitter buffers
+                            _ = playout_interval.tick() => {
+                                let call_recorder = call_recorder.lock().unwrap().clone();
+
+                                playout_remote_speakers(&playout_remote_speakers_handle, &participant_audio, call_recorder.as_ref());
+                            }
+                        }
+                    }
+                });
+
+                let voip_heartbeat = voip_image.clone();
+                let heartbeat_encryption_key = self.server_session.client_connection.client_secret.clone();
+                let heartbeat_cancel_token = cancel_token_child.clone();
+
+                //Heartbeat thread
+                tokio::spawn(async move {
+                    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+                    loop {
+                        select! {
+                            _ = heartbeat_cancel_token.cancelled() => {
+                                //Exit thread
+                                break;
+                            },
+
+                            //Measure the round trip time and the packet loss of the call
+                            _ = heartbeat_interval.tick() => {
+                                if let Err(err) = voip_heartbeat.send_heartbeat(&heartbeat_encryption_key).await {
+                                    tracing::error!("Failed to send voip heartbeat: {err}");
+                                }
+                            }
+                        }
+                    }
+                });
 
                 let image_buffer = voip_image.image_buffer.clone();
+                let eviction_cancel_token = cancel_token_child.clone();
+
+                //Image buffer eviction thread
+                tokio::spawn(async move {
+                    let mut eviction_interval = tokio::time::interval(IMAGE_FRAME_TIMEOUT);
+
+                    loop {
+                        select! {
+                            _ = eviction_cancel_token.cancelled() => {
+                                //Exit thread
+                                break;
+                            },
+
+                            //Evict the frames which couldnt be rebuilt, even if their author has stopped sending images
+                            _ = eviction_interval.tick() => {
+                                for mut image_frames in image_buffer.iter_mut() {
+                                    evict_stale_image_frames(&mut image_frames);
+                                }
+                            }
+                        }
+                    }
+                });
+
+                let voip_reporter = voip_image.clone();
+                let reporter_encryption_key = self.server_session.client_connection.client_secret.clone();
+                let reporter_cancel_token = cancel_token_child.clone();
+
+                //Receiver report thread
+                tokio::spawn(async move {
+                    let mut report_interval = tokio::time::interval(RECEIVER_REPORT_INTERVAL);
+
+                    loop {
+                        select! {
+                            _ = reporter_cancel_token.cancelled() => {
+                                //Exit thread
+                                break;
+                            },
+
+                            //Report the reception of every video stream to their sender
+                            _ = report_interval.tick() => {
+                                let reports: Vec<(String, ReceiverReport)> = voip_reporter.video_receiver_statistics.iter_mut().filter_map(|mut statistics| {
+                                    statistics.create_report(reporter_uuid.clone()).map(|report| (statistics.key().clone(), report))
+                                }).collect();
+
+                                for (uuid, report) in reports {
+                                    if let Err(err) = voip_reporter.send_receiver_report(uuid, &report, &reporter_encryption_key).await {
+                                        tracing::error!("Failed to send receiver report: {err}");
+                                    }
+                                }
+                            }
+                        }
+                    }
+                });
 
                 //Receiver thread
                 tokio::spawn(async move {
                     let ctx_clone = ctx.clone();
+
+                    //The decoders of the participants' video streams, paired with their uuid
+                    let mut video_decoders: HashMap<String, VideoDecoder> = HashMap::new();
+
+                    //The decoder of the audio mixed by the server, this is only used if the call is mixed
+                    let mut mixed_audio_decoder = match create_mixed_audio_decoder() {
+                        Ok(mixed_audio_decoder) => mixed_audio_decoder,
+                        Err(err) => {
+                            tracing::error!("Failed to create mixed audio decoder: {err}");
+
+                            return;
+                        },
+                    };
+
                     //Listen on socket, play audio
                     loop {
                         select! {
@@ -206,7 +430,7 @@ impl Application
 
                             //Receive bytes
                             _received_bytes_count = async {
-                                match receive_server_relay(receiver_socket_part.clone(), &decryption_key, sink.clone(), image_buffer.clone(), &ctx_clone).await {
+                                match receive_server_relay(receiver_socket_part.clone(), &decryption_key, remote_speakers.clone(), &stream_handle, voip_receiver.clone(), &mut video_decoders, &mut mixed_audio_decoder, &ctx_clone).await {
                                     Ok(_) => (),
                                     Err(err) => {
                                         tracing::error!("{}", err);
@@ -227,23 +451,62 @@ impl Application
             self.voip_video_thread.get_or_insert({
                 //Create image sender thread
                 tokio::spawn(async move {
+                    //The encoder keeps the previous frames, so that only the differences have to be sent
+                    let mut video_encoder = VideoEncoder::new(voip_image.keyframe_requested.clone());
+
+                    //The time the next frame should be sent at, the frame rate is decided by the quality controller
+                    let mut next_frame_at = tokio::time::Instant::now();
+
                     loop {
+                        select! {
+                            //Wait until the next frame is due
+                            _ = tokio::time::sleep_until(next_frame_at) => {},
+                            _ = voice_recording_shutdown.cancelled() => {
+                                //Exit thread
+                                break;
+                            },
+                        }
+
                         select! {
                             //Lock camera handle
                             mut camera_handle = camera_handle.lock() => {
                                 //Get image bytes from the cameras
                                 match camera_handle.as_mut() {
                                     Some(handle) => {
-                                        //Create buffer for image
-                                        let mut buffer = BufWriter::new(Cursor::new(Vec::new()));
-                                        //Get camera frame
-                                        let (camera_bytes, size) = handle.get_frame().unwrap_or_default();
+                                        let frame_started_at = tokio::time::Instant::now();
+
+                                        //The quality is adjusted based on the participants' reports
+                                        let quality = voip_image.video_quality_controller.lock().unwrap().current();
+
+                                        next_frame_at = frame_started_at + Duration::from_secs_f32(1. / quality.frame_rate as f32);
+
+                                        //Get the frame of the video source
+                                        let frame = match handle.get_frame() {
+                                            Ok(frame) => frame,
+                                            Err(err) => {
+                                                tracing::error!("{err}");
+
+                                                continue;
+                                            },
+                                        };
+
+                                        //Encode the raw image bytes into a keyframe or a delta frame
+                                        let encoded_frame = match video_encoder.encode(&frame.rgb, frame.width, frame.height, quality) {
+                                            Ok(encoded_frame) => encoded_frame,
+                                            Err(err) => {
+                                                tracing::error!("Failed to encode camera frame: {err}");
+
+                                                continue;
+                                            },
+                                        };
 
-                                        //Convert raw image bytes to jpeg
-                                        image::write_buffer_with_format(&mut buffer, &camera_bytes, size.width as u32, size.height as u32, image::ColorType::Rgb8, ImageOutputFormat::Jpeg(70)).unwrap();
+                                        //Save the time we have sent the frame at, so that the round trip time can be calculated from the reports
+                                        if let Some(frame_number) = frame_number(&encoded_frame) {
+                                            voip_image.video_quality_controller.lock().unwrap().on_frame_sent(frame_number);
+                                        }
 
                                         //Send image
-                                        voip_image.send_image(uuid_clone.clone(), &buffer.into_inner().unwrap().into_inner(), &decryption_key_clone).await.unwrap();
+                                        voip_image.send_image(uuid_clone.clone(), &encoded_frame, &decryption_key_clone).await.unwrap();
                                     },
                                     None => {
                                         //... camera handle has been removed
@@ -262,14 +525,77 @@ impl Application
         }
     }
 
-    ///This functions is used for clients to receive messages from the server (this doesnt not check validity of the order of the messages, although this may not be needed as tcp takes care of this)
-    pub fn client_recv(&mut self, ctx: &egui::Context)
+    /// Writes the audio file to the disk, and sends the sink created for it to the main thread
+    pub fn load_audio(&self, session: &ServerSession, audio: ServerAudioReply)
+    {
+        let stream_handle = self.client_ui.audio_playback.stream_handle.clone();
+
+        let sender = self.audio_save_tx.clone();
+
+        let path_to_audio = PathBuf::from(format!(
+            "{}\\Matthias\\Client\\{}\\Audios\\{}",
+            env!("APPDATA"),
+            session.send_on_ip_base64_encoded,
+            audio.signature
+        ));
+        let ip = session.send_on_ip.clone();
+
+        //Spawn writer thread
+        std::thread::spawn(move || {
+            let _ = write_audio(audio.clone(), ip);
+
+            while !path_to_audio.exists() {
+                //Block until it exists, we can do this because we are in a different thread then main
+            }
+
+            let file_stream_to_be_read = fs::read(&path_to_audio).unwrap_or_default();
+
+            let cursor = PlaybackCursor::new(file_stream_to_be_read);
+
+            let sink = Some(Arc::new(Sink::try_new(&stream_handle).unwrap()));
+
+            sender
+                .send((
+                    sink,
+                    cursor,
+                    //Is this needed
+                    audio.audio_idx,
+                    path_to_audio,
+                ))
+                .unwrap();
+        });
+    }
+
+    /// Stores the downloaded attachment in the server session's cache
+    fn cache_attachment(&self, session: &ServerSession, signature: &str, bytes: &[u8])
+    {
+        if let Err(err) = MessageCache::new(&self.opened_user_information, &session.send_on_ip)
+            .and_then(|message_cache| message_cache.save_attachment(signature, bytes))
+        {
+            tracing::error!("Failed to cache the attachment: {err}");
+        }
+    }
+
+    /// Reads a downloaded attachment from the opened server's cache
+    pub fn load_cached_attachment(&self, signature: &str) -> Option<Vec<u8>>
+    {
+        MessageCache::new(
+            &self.opened_user_information,
+            &self.server_session.send_on_ip,
+        )
+        .and_then(|message_cache| message_cache.load_attachment(signature))
+        .ok()
+    }
+
+    ///This functions is used for clients to receive messages from the server of the session (this doesnt not check validity of the order of the messages, although this may not be needed as tcp takes care of this)
+    pub fn client_recv(&mut self, session: &mut ServerSession, ctx: &egui::Context)
     {
         //This should only run when the connection is valid
-        if let ConnectionState::Connected(connection_pair) = self.client_connection.state.clone() {
-            self.server_sender_thread.get_or_insert({
+        if let ConnectionState::Connected(connection_pair) = session.client_connection.state.clone()
+        {
+            session.server_sender_thread.get_or_insert({
                 //Clone so we can move it into the closure
-                let sender = self.server_output_sender.clone();
+                let sender = session.server_output_sender.clone();
 
                 //Clone the reader so we can move it in the closure
                 let reader = connection_pair.reader.clone();
@@ -281,13 +607,17 @@ impl Application
                 let context_clone = ctx.clone();
 
                 //Thread cancellation token
-                let shutdown_token = self.autosync_shutdown_token.child_token();
+                let shutdown_token = session.autosync_shutdown_token.child_token();
 
                 //We have to clone for the 2nd thread
                 let shutdown_token_clone = shutdown_token.clone();
 
                 let toasts = self.toasts.clone();
 
+                //The reader thread decrypts the replies, so that it can answer the heartbeats without waiting for the ui
+                let encryption_key = session.client_connection.client_secret.clone();
+                let connection_quality = session.connection_quality.clone();
+
                 //Spawn server reader thread
                 tokio::spawn(async move {
                     loop {
@@ -310,10 +640,32 @@ impl Application
                                             break;
                                         }
 
+                                        //Decrypt the server's reply
+                                        let decrypted_message = match decrypt_aes256(&response, &encryption_key) {
+                                            Ok(decrypted_message) => decrypted_message,
+                                            Err(err) => {
+                                                tracing::error!("Failed to decrypt the server's reply: {err}");
+
+                                                display_error_message(err, toasts);
+
+                                                //The connection is faulty, the receiver will try to reconnect
+                                                let _ = sender_clone.send(None);
+
+                                                break;
+                                            },
+                                        };
+
+                                        //Answer the heartbeats here, so the measured round trip time doesnt depend on the frame rate of the ui
+                                        if let Ok(ServerReplyType::Heartbeat(sequence_number)) = serde_json::from_str::<ServerReplyType>(&decrypted_message) {
+                                            connection_quality.lock().unwrap().on_heartbeat_answered(sequence_number);
+
+                                            continue;
+                                        }
+
                                         //Request repaint
                                         context_clone.request_repaint();
                                         //Send to receiver
-                                        sender_clone.send(Some(response)).expect("Error occurred when trying to send message, after receiving message from client");
+                                        sender_clone.send(Some(decrypted_message)).expect("Error occurred when trying to send message, after receiving message from client");
                                     },
                                     Err(err) => {
                                         tracing::error!("{}", err);
@@ -337,15 +689,62 @@ impl Application
 
                 //Init sync message
                 let mut message = ClientMessage::construct_sync_msg(
-                    &self.client_connection.password,
+                    &session.client_connection.password,
                     &self.login_username,
                     &self.opened_user_information.uuid,
                     //Send how many messages we have, the server will compare it to its list, and then send the missing messages, reducing traffic
-                    self.client_ui.incoming_messages.message_list.len(),
-                    Some(*self.client_ui.last_seen_msg_index.lock().unwrap()),
+                    session.incoming_messages.message_list.len(),
+                    Some(*session.last_seen_msg_index.lock().unwrap()),
                 );
 
-                let last_seen_message_index = self.client_ui.last_seen_msg_index.clone();
+                let last_seen_message_index = session.last_seen_msg_index.clone();
+
+                //Clone so we can move it into the heartbeat thread
+                let heartbeat_connection_pair = connection_pair.clone();
+                let heartbeat_sender = sender.clone();
+                let heartbeat_shutdown_token = shutdown_token_clone.clone();
+                let heartbeat_uuid = self.opened_user_information.uuid.clone();
+                let heartbeat_toasts = self.toasts.clone();
+                let connection_quality = session.connection_quality.clone();
+
+                //The measurement starts over with every connection
+                *connection_quality.lock().unwrap() = ConnectionQuality::default();
+
+                //Spawn heartbeat thread
+                tokio::spawn(async move {
+                    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+                    loop {
+                        select! {
+                            _ = heartbeat_interval.tick() => {
+                                //If the server hasnt answered our heartbeats in time, the connection has died without the server disconnecting us
+                                if connection_quality.lock().unwrap().is_timed_out() {
+                                    display_error_message("The server has stopped responding.", heartbeat_toasts);
+
+                                    //Signal the main thread to reset the connection
+                                    let _ = heartbeat_sender.send(None);
+
+                                    break;
+                                }
+
+                                let sequence_number = connection_quality.lock().unwrap().on_heartbeat_sent();
+
+                                if let Err(err) = heartbeat_connection_pair.send_message(ClientMessage::construct_heartbeat_msg(&heartbeat_uuid, sequence_number)).await {
+                                    tracing::error!("{}", err);
+
+                                    //Error appeared, after this the tread quits, so there arent an inf amount of threads running
+                                    let _ = heartbeat_sender.send(None);
+
+                                    break;
+                                }
+                            }
+
+                            _ = heartbeat_shutdown_token.cancelled() => {
+                                break;
+                            }
+                        }
+                    }
+                });
 
                 //Spawn server syncer thread
                 tokio::spawn(async move {
@@ -390,337 +789,351 @@ impl Application
 
             //Try to receive the threads messages
             //Get sent to the channel to be displayed, if the connections errors out, do nothing lol cuz its prolly cuz the sender hadnt done anything
-            match self.server_output_receiver.try_recv() {
+            match session.server_output_receiver.try_recv() {
                 Ok(msg) => {
                     //show messages
-                    if let Some(message) = msg {
-                        //Decrypt the server's reply
-                        match decrypt_aes256(&message, &self.client_connection.client_secret) {
-                            Ok(decrypted_message) => {
-                                let incoming_struct: Result<ServerSync, serde_json::Error> =
-                                    serde_json::from_str(&decrypted_message);
-                                match incoming_struct {
-                                    Ok(msg) => {
-                                        //Always make sure to store the latest user_seen list
-                                        self.client_ui.incoming_messages.user_seen_list =
-                                            msg.user_seen_list;
-
-                                        //If its a sync message then we dont need to back it up
-                                        if matches!(
-                                            msg.message.message_type,
-                                            ServerMessageType::Sync(_)
-                                        ) {
-                                            return;
-                                        }
+                    if let Some(decrypted_message) = msg {
+                        let incoming_struct: Result<ServerSync, serde_json::Error> =
+                            serde_json::from_str(&decrypted_message);
+                        match incoming_struct {
+                            Ok(msg) => {
+                                //Always make sure to store the latest user_seen list
+                                session.incoming_messages.user_seen_list = msg.user_seen_list;
+
+                                //If its a sync message then we dont need to back it up
+                                if matches!(msg.message.message_type, ServerMessageType::Sync(_)) {
+                                    return;
+                                }
 
-                                        match &msg.message.message_type {
-                                            ServerMessageType::Edit(message) => {
-                                                if let Some(new_message) =
-                                                    message.new_message.clone()
+                                match &msg.message.message_type {
+                                    ServerMessageType::Edit(message) => {
+                                        if let Some(new_message) = message.new_message.clone() {
+                                            if let ServerMessageType::Normal(inner) =
+                                                &mut session.incoming_messages.message_list
+                                                    [message.index as usize]
+                                                    .message_type
+                                            {
+                                                inner.message = new_message;
+                                                inner.has_been_edited = true;
+                                            }
+                                        }
+                                        else {
+                                            session.incoming_messages.message_list
+                                                [message.index as usize]
+                                                .message_type = ServerMessageType::Deleted;
+                                        }
+                                    },
+                                    ServerMessageType::Reaction(message) => {
+                                        //Search if there has already been a reaction added
+                                        match &message.reaction_type {
+                                            crate::app::backend::ReactionType::Add(reaction) => {
+                                                if let Some(index) = session
+                                                    .incoming_messages
+                                                    .reaction_list[reaction.message_index]
+                                                    .message_reactions
+                                                    .iter()
+                                                    .position(|item| {
+                                                        item.emoji_name == reaction.emoji_name
+                                                    })
+                                                {
+                                                    //If yes, increment the reaction counter
+                                                    session.incoming_messages.reaction_list
+                                                        [reaction.message_index]
+                                                        .message_reactions[index]
+                                                        .authors
+                                                        .push(reaction.uuid.clone());
+                                                }
+                                                else {
+                                                    //If no, add a new reaction counter
+                                                    session.incoming_messages.reaction_list
+                                                        [reaction.message_index]
+                                                        .message_reactions
+                                                        .push(Reaction {
+                                                            emoji_name: reaction.emoji_name.clone(),
+                                                            authors: vec![reaction.uuid.clone()],
+                                                        })
+                                                }
+                                            },
+                                            crate::app::backend::ReactionType::Remove(reaction) => {
+                                                //Search for emoji in the emoji list
+                                                //If its not found, it a serious issue, or just internet inconsistency
+                                                if let Some(index) = session
+                                                    .incoming_messages
+                                                    .reaction_list[reaction.message_index]
+                                                    .message_reactions
+                                                    .iter()
+                                                    .position(|item| {
+                                                        item.emoji_name == reaction.emoji_name
+                                                    })
                                                 {
-                                                    if let ServerMessageType::Normal(inner) =
-                                                        &mut self
-                                                            .client_ui
-                                                            .incoming_messages
-                                                            .message_list
-                                                            [message.index as usize]
-                                                            .message_type
+                                                    //Borrow authors list as mutable
+                                                    let emoji_authors = &mut session
+                                                        .incoming_messages
+                                                        .reaction_list[reaction.message_index]
+                                                        .message_reactions[index]
+                                                        .authors;
+
+                                                    //Remove the user who has sent this message from the authors list
+                                                    match emoji_authors
+                                                        .iter()
+                                                        .position(|uuid| *uuid == reaction.uuid)
                                                     {
-                                                        inner.message = new_message;
-                                                        inner.has_been_edited = true;
+                                                        Some(idx) => {
+                                                            emoji_authors.remove(idx);
+                                                        },
+                                                        None => {
+                                                            tracing::error!("Tried to remove a non-author from the authors list.");
+                                                        },
+                                                    }
+                                                    //If the emoji is reacted with 0 times, it means it has been fully deleted from the list
+                                                    if emoji_authors.is_empty() {
+                                                        session.incoming_messages.reaction_list
+                                                            [reaction.message_index]
+                                                            .message_reactions
+                                                            .remove(index);
                                                     }
                                                 }
                                                 else {
-                                                    self.client_ui.incoming_messages.message_list
-                                                        [message.index as usize]
-                                                        .message_type = ServerMessageType::Deleted;
+                                                    tracing::error!("Emoji was already deleted before requesting removal");
                                                 }
                                             },
-                                            ServerMessageType::Reaction(message) => {
-                                                //Search if there has already been a reaction added
-                                                match &message.reaction_type {
-                                                    crate::app::backend::ReactionType::Add(
-                                                        reaction,
-                                                    ) => {
-                                                        if let Some(index) = self
-                                                            .client_ui
-                                                            .incoming_messages
-                                                            .reaction_list[reaction.message_index]
-                                                            .message_reactions
-                                                            .iter()
-                                                            .position(|item| {
-                                                                item.emoji_name
-                                                                    == reaction.emoji_name
-                                                            })
-                                                        {
-                                                            //If yes, increment the reaction counter
-                                                            self.client_ui
-                                                                .incoming_messages
-                                                                .reaction_list
-                                                                [reaction.message_index]
-                                                                .message_reactions[index]
-                                                                .authors
-                                                                .push(reaction.uuid.clone());
-                                                        }
-                                                        else {
-                                                            //If no, add a new reaction counter
-                                                            self.client_ui
-                                                                .incoming_messages
-                                                                .reaction_list
-                                                                [reaction.message_index]
-                                                                .message_reactions
-                                                                .push(Reaction {
-                                                                    emoji_name: reaction
-                                                                        .emoji_name
-                                                                        .clone(),
-                                                                    authors: vec![reaction
-                                                                        .uuid
-                                                                        .clone()],
-                                                                })
-                                                        }
-                                                    },
-                                                    crate::app::backend::ReactionType::Remove(
-                                                        reaction,
-                                                    ) => {
-                                                        //Search for emoji in the emoji list
-                                                        //If its not found, it a serious issue, or just internet inconsistency
-                                                        if let Some(index) = self
-                                                            .client_ui
-                                                            .incoming_messages
-                                                            .reaction_list[reaction.message_index]
-                                                            .message_reactions
-                                                            .iter()
-                                                            .position(|item| {
-                                                                item.emoji_name
-                                                                    == reaction.emoji_name
-                                                            })
-                                                        {
-                                                            //Borrow authors list as mutable
-                                                            let emoji_authors = &mut self
-                                                                .client_ui
-                                                                .incoming_messages
-                                                                .reaction_list
-                                                                [reaction.message_index]
-                                                                .message_reactions[index]
-                                                                .authors;
-
-                                                            //Remove the user who has sent this message from the authors list
-                                                            match emoji_authors.iter().position(
-                                                                |uuid| *uuid == reaction.uuid,
-                                                            ) {
-                                                                Some(idx) => {
-                                                                    emoji_authors.remove(idx);
-                                                                },
-                                                                None => {
-                                                                    tracing::error!("Tried to remove a non-author from the authors list.");
-                                                                },
-                                                            }
-                                                            //If the emoji is reacted with 0 times, it means it has been fully deleted from the list
-                                                            if emoji_authors.is_empty() {
-                                                                self.client_ui
-                                                                    .incoming_messages
-                                                                    .reaction_list
-                                                                    [reaction.message_index]
-                                                                    .message_reactions
-                                                                    .remove(index);
-                                                            }
-                                                        }
-                                                        else {
-                                                            tracing::error!("Emoji was already deleted before requesting removal");
-                                                        }
-                                                    },
+                                        }
+                                    },
+                                    ServerMessageType::VoipState(state) => {
+                                        //Check if the call was alive before the state update
+                                        let was_call_alive = session
+                                            .incoming_messages
+                                            .ongoing_voip_call
+                                            .rooms
+                                            .is_empty();
+
+                                        //Set state
+                                        session
+                                            .incoming_messages
+                                            .ongoing_voip_call
+                                            .clone_from(state);
+
+                                        //This is true only if the call was JUST started
+                                        if was_call_alive || !state.rooms.is_empty() {
+                                            //Callback
+                                            self.client_ui.extension.event_call_extensions(
+                                                crate::app::lua::EventCall::OnCallReceive,
+                                                &self.lua,
+                                                None,
+                                            );
+                                        }
+                                    },
+                                    //The profile is replaced in place, the update is not displayed as a message
+                                    ServerMessageType::ProfileUpdate(profile) => {
+                                        self.store_client_profile(
+                                            session,
+                                            ctx,
+                                            msg.message.uuid.clone(),
+                                            profile.clone(),
+                                        );
+                                    },
+                                    ServerMessageType::VoipEvent(voip_event) => {
+                                        match &voip_event.event {
+                                            //These messages can be added to the message stack
+                                            super::backend::VoipEvent::MissedCall(_) => {
+                                                self.add_message(session, msg.message.clone());
+                                            },
+                                            //The speaker's jitter buffer is removed when they join or leave, as their sequence numbers start over when they (re)join
+                                            //The packets of their previous stream may arrive after they have left, so the buffer is reset on both events
+                                            super::backend::VoipEvent::Connected
+                                            | super::backend::VoipEvent::Disconnected => {
+                                                if let Some(voip) = &session.voip {
+                                                    voip.remote_speakers.remove(&voip_event.uuid);
+                                                }
+
+                                                self.add_message(session, msg.message.clone());
+                                            },
+
+                                            //These message types have a side effect on the client's ```image_buffer```
+                                            //Add the uuid if connected
+                                            super::backend::VoipEvent::ImageConnected => {
+                                                if let Some(voip) = &session.voip {
+                                                    voip.image_buffer.insert(
+                                                        voip_event.uuid.clone(),
+                                                        IndexMap::new(),
+                                                    );
+                                                }
+                                                else {
+                                                    tracing::error!("Voip event called, but there is no voip instance");
+                                                }
+                                            },
+                                            //Remove the uuid if disconnected
+                                            super::backend::VoipEvent::ImageDisconnected => {
+                                                if let Some(voip) = &session.voip {
+                                                    voip.image_buffer.remove(&voip_event.uuid);
+
+                                                    //Remove the last frame and the statistics of their video stream
+                                                    voip.video_frames.remove(&voip_event.uuid);
+                                                    voip.video_receiver_statistics
+                                                        .remove(&voip_event.uuid);
+
+                                                    if let Some(call_recorder) =
+                                                        &*voip.call_recorder.lock().unwrap()
+                                                    {
+                                                        call_recorder
+                                                            .remove_video_stream(&voip_event.uuid);
+                                                    }
+                                                }
+                                                else {
+                                                    tracing::error!("Voip event called, but there is no voip instance");
+                                                    tracing::info!("The user disconnected from the call while having their webcam eanbled, this is defined behavior.");
                                                 }
                                             },
-                                            ServerMessageType::VoipState(state) => {
-                                                //Check if the call was alive before the state update
-                                                let was_call_alive = self
-                                                    .client_ui
+
+                                            //These messages have a side effect on the recording indicator, and are also added to the message stack
+                                            super::backend::VoipEvent::RecordingStarted => {
+                                                if let Some(room) = session
                                                     .incoming_messages
                                                     .ongoing_voip_call
-                                                    .connected_clients
-                                                    .is_none();
+                                                    .rooms
+                                                    .get_mut(&voip_event.room)
+                                                {
+                                                    if !room
+                                                        .recording_clients
+                                                        .contains(&voip_event.uuid)
+                                                    {
+                                                        room.recording_clients
+                                                            .push(voip_event.uuid.clone());
+                                                    }
+                                                }
 
-                                                //Set state
-                                                self.client_ui
+                                                self.add_message(session, msg.message.clone());
+                                            },
+                                            super::backend::VoipEvent::RecordingStopped => {
+                                                if let Some(room) = session
                                                     .incoming_messages
                                                     .ongoing_voip_call
-                                                    .connected_clients =
-                                                    state.connected_clients.clone();
-
-                                                //This is true only if the call was JUST started
-                                                if was_call_alive
-                                                    || state.connected_clients.is_some()
+                                                    .rooms
+                                                    .get_mut(&voip_event.room)
                                                 {
-                                                    //Callback
-                                                    self.client_ui.extension.event_call_extensions(
-                                                        crate::app::lua::EventCall::OnCallReceive,
-                                                        &self.lua,
-                                                        None,
-                                                    );
+                                                    room.recording_clients
+                                                        .retain(|uuid| *uuid != voip_event.uuid);
                                                 }
+
+                                                self.add_message(session, msg.message.clone());
                                             },
-                                            ServerMessageType::VoipEvent(voip_event) => {
-                                                match voip_event.event {
-                                                    //These messages can be added to the message stack
-                                                    super::backend::VoipEvent::Connected | super::backend::VoipEvent::Disconnected => {
-                                                        self.add_message(msg.message.clone());
-                                                    },
+                                        }
+                                    },
+                                    _ => {
+                                        let message = msg.message.clone();
 
-                                                    //These message types have a side effect on the client's ```image_buffer```
-                                                    //Add the uuid if connected
-                                                    super::backend::VoipEvent::ImageConnected => {
-                                                        if let Some(voip) = &self.client_ui.voip {
-                                                            voip.image_buffer.insert(voip_event.uuid.clone(), IndexMap::new());
-                                                        }
-                                                        else {
-                                                            tracing::error!("Voip event called, but there is no voip instance");
-                                                        }
-                                                    },
-                                                    //Remove the uuid if disconnected
-                                                    super::backend::VoipEvent::ImageDisconnected => {
-                                                        if let Some(voip) = &self.client_ui.voip {
-                                                            voip.image_buffer.remove(&voip_event.uuid);
-
-                                                            //Forget image
-                                                            ctx.forget_image(&format!("bytes://video_stream:{}", voip_event.uuid.clone()));
-                                                        }
-                                                        else {
-                                                            tracing::error!("Voip event called, but there is no voip instance");
-                                                            tracing::info!("The user disconnected from the call while having their webcam eanbled, this is defined behavior.");
-                                                        }
-                                                    },
+                                        self.add_message(session, message);
+                                    },
+                                }
+                            },
+                            //If converting the message to a ServerSync then it was probably a ServerReplyType enum
+                            Err(_err) => {
+                                let incoming_reply: Result<ServerReplyType, serde_json::Error> =
+                                    serde_json::from_str(&decrypted_message);
+
+                                match incoming_reply {
+                                    Ok(inner) => {
+                                        match inner {
+                                            ServerReplyType::File(file) => {
+                                                let _ = write_file(file);
+                                            },
+                                            ServerReplyType::Image(image) => {
+                                                //The requested profile picture has arrived
+                                                session
+                                                    .profile_picture_requests
+                                                    .remove(&image.signature);
+
+                                                //The server doesnt have the image, the empty reply isnt cached so it will be requested again next time
+                                                if !image.bytes.is_empty() {
+                                                    self.cache_attachment(
+                                                        session,
+                                                        &image.signature,
+                                                        &image.bytes,
+                                                    );
+
+                                                    //Forget image so itll be able to get displayed
+                                                    ctx.forget_image(&format!(
+                                                        "bytes://{}",
+                                                        image.signature
+                                                    ));
+
+                                                    //load image to the said URI
+                                                    ctx.include_bytes(
+                                                        format!("bytes://{}", image.signature),
+                                                        image.bytes,
+                                                    );
                                                 }
                                             },
-                                            _ => {
-                                                let message = msg.message.clone();
+                                            ServerReplyType::Audio(audio) => {
+                                                self.cache_attachment(
+                                                    session,
+                                                    &audio.signature,
+                                                    &audio.bytes,
+                                                );
+
+                                                self.load_audio(session, audio);
+                                            },
+                                            //Heartbeats are answered by the reader thread
+                                            ServerReplyType::Heartbeat(_) => {},
+                                            ServerReplyType::Acknowledgement(message_id) => {
+                                                self.outbox
+                                                    .lock()
+                                                    .unwrap()
+                                                    .acknowledge(&message_id);
+
+                                                //Remove the sent indicator once it has been displayed
+                                                ctx.request_repaint_after(
+                                                    SENT_MESSAGE_DISPLAY_DURATION,
+                                                );
+                                            },
+                                            ServerReplyType::Rejected(message_id, reason) => {
+                                                self.outbox.lock().unwrap().reject(&message_id);
 
-                                                self.add_message(message);
+                                                display_error_message(reason, self.toasts.clone());
+                                            },
+                                            ServerReplyType::Client(client_reply) => {
+                                                self.store_client_profile(
+                                                    session,
+                                                    ctx,
+                                                    client_reply.uuid,
+                                                    client_reply.profile,
+                                                );
                                             },
                                         }
                                     },
-                                    //If converting the message to a ServerSync then it was probably a ServerReplyType enum
                                     Err(_err) => {
                                         let incoming_reply: Result<
-                                            ServerReplyType,
+                                            ServerVoipReply,
                                             serde_json::Error,
                                         > = serde_json::from_str(&decrypted_message);
 
                                         match incoming_reply {
-                                            Ok(inner) => {
-                                                match inner {
-                                                    ServerReplyType::File(file) => {
-                                                        let _ = write_file(file);
-                                                    },
-                                                    ServerReplyType::Image(image) => {
-                                                        //Forget image so itll be able to get displayed
-                                                        ctx.forget_image(&format!(
-                                                            "bytes://{}",
-                                                            image.signature
-                                                        ));
-
-                                                        //load image to the said URI
-                                                        ctx.include_bytes(
-                                                            format!("bytes://{}", image.signature),
-                                                            image.bytes,
-                                                        );
-                                                    },
-                                                    ServerReplyType::Audio(audio) => {
-                                                        let stream_handle = self
-                                                            .client_ui
-                                                            .audio_playback
-                                                            .stream_handle
-                                                            .clone();
-
-                                                        let sender = self.audio_save_tx.clone();
-
-                                                        let path_to_audio = PathBuf::from(format!(
-                                                            "{}\\Matthias\\Client\\{}\\Audios\\{}",
-                                                            env!("APPDATA"),
-                                                            self.client_ui
-                                                                .send_on_ip_base64_encoded,
-                                                            audio.signature
-                                                        ));
-                                                        let ip = self.client_ui.send_on_ip.clone();
-
-                                                        //Spawn writer thread
-                                                        std::thread::spawn(move || {
-                                                            let _ = write_audio(audio.clone(), ip);
-
-                                                            while !path_to_audio.exists() {
-                                                                //Block until it exists, we can do this because we are in a different thread then main
-                                                            }
-
-                                                            let file_stream_to_be_read =
-                                                                fs::read(&path_to_audio)
-                                                                    .unwrap_or_default();
-
-                                                            let cursor = PlaybackCursor::new(
-                                                                file_stream_to_be_read,
-                                                            );
-
-                                                            let sink = Some(Arc::new(
-                                                                Sink::try_new(&stream_handle)
-                                                                    .unwrap(),
-                                                            ));
-
-                                                            sender
-                                                                .send((
-                                                                    sink,
-                                                                    cursor,
-                                                                    //Is this needed
-                                                                    audio.audio_idx,
-                                                                    path_to_audio,
-                                                                ))
-                                                                .unwrap();
-                                                        });
-                                                    },
-                                                    ServerReplyType::Client(client_reply) => {
-                                                        self.client_ui
-                                                            .incoming_messages
-                                                            .connected_clients_profile
-                                                            .insert(
-                                                                client_reply.uuid.clone(),
-                                                                client_reply.profile.clone(),
-                                                            );
-
-                                                        //Forget old placeholder bytes
-                                                        ctx.forget_image(&format!(
-                                                            "bytes://{}",
-                                                            client_reply.uuid
-                                                        ));
-
-                                                        //Pair URI with profile image
-                                                        ctx.include_bytes(
-                                                            format!(
-                                                                "bytes://{}",
-                                                                client_reply.uuid
-                                                            ),
-                                                            client_reply
-                                                                .profile
-                                                                .small_profile_picture,
+                                            Ok(voip_connection) => {
+                                                match voip_connection {
+                                                    ServerVoipReply::Success => {},
+                                                    ServerVoipReply::Fail(err) => {
+                                                        //Avoid panicking when trying to display a Notification
+                                                        //This is very rare but can still happen
+                                                        display_error_message(
+                                                            err.reason,
+                                                            self.toasts.clone(),
                                                         );
                                                     },
                                                 }
                                             },
                                             Err(_err) => {
-                                                let incoming_reply: Result<
-                                                    ServerVoipReply,
+                                                let incoming_request: Result<
+                                                    ServerVoipRequest,
                                                     serde_json::Error,
                                                 > = serde_json::from_str(&decrypted_message);
 
-                                                match incoming_reply {
-                                                    Ok(voip_connection) => {
-                                                        match voip_connection {
-                                                            ServerVoipReply::Success => {},
-                                                            ServerVoipReply::Fail(err) => {
-                                                                //Avoid panicking when trying to display a Notification
-                                                                //This is very rare but can still happen
-                                                                display_error_message(
-                                                                    err.reason,
-                                                                    self.toasts.clone(),
-                                                                );
-                                                            },
-                                                        }
+                                                match incoming_request {
+                                                    Ok(voip_request) => {
+                                                        self.handle_voip_request(
+                                                            session,
+                                                            voip_request,
+                                                            ctx,
+                                                        );
                                                     },
                                                     Err(_err) => {
                                                         tracing::error!("{_err}");
@@ -731,27 +1144,14 @@ impl Application
                                     },
                                 }
                             },
-                            Err(err) => {
-                                display_error_message(err, self.toasts.clone());
-
-                                //Assuming the connection is faulty we reset state
-                                self.reset_client_connection();
-
-                                //Try to dsiconnect from the server
-                                self.disconnect_from_server();
-
-                                self.client_connection.reset_state();
-                            },
                         }
                     }
                     else {
-                        //Signal the remaining thread to be shut down
-                        self.autosync_shutdown_token.cancel();
-
-                        self.server_sender_thread = None;
-
-                        //Then the thread got an error, we should reset the state
+                        //Then the thread got an error, the connection has probably dropped
                         tracing::error!("Client receiver or sync thread panicked");
+
+                        //Shut down the remaining threads and try to reconnect, the messages are kept while we are offline
+                        self.start_reconnecting(session);
                     }
                 },
                 Err(_err) => {
@@ -761,19 +1161,34 @@ impl Application
         }
     }
 
-    fn add_message(&mut self, message: super::backend::ServerOutput)
+    /// Stores the profile of the client, the profile pictures are displayed by their hash so the new pictures are requested automatically
+    fn store_client_profile(
+        &self,
+        session: &mut ServerSession,
+        ctx: &egui::Context,
+        uuid: String,
+        profile: ClientProfile,
+    )
+    {
+        //Forget old placeholder bytes
+        ctx.forget_image(&format!("bytes://{}", uuid));
+
+        session
+            .incoming_messages
+            .connected_clients_profile
+            .insert(uuid, profile);
+    }
+
+    fn add_message(&mut self, session: &mut ServerSession, message: super::backend::ServerOutput)
     {
         //Allocate Message vec for the new message
-        self.client_ui
+        session
             .incoming_messages
             .reaction_list
             .push(MessageReaction::default());
 
         //We can append the missing messages sent from the server, to the self.client_ui.incoming_msg.struct_list vector
-        self.client_ui
-            .incoming_messages
-            .message_list
-            .push(message.clone());
+        session.incoming_messages.message_list.push(message.clone());
 
         //Callback
         self.client_ui.extension.event_call_extensions(
@@ -782,24 +1197,125 @@ impl Application
             Some(message._struct_into_string()),
         );
     }
+
+    /// Handles the ```ServerVoipRequest```s, these are only sent to the clients they concern
+    fn handle_voip_request(
+        &mut self,
+        session: &mut ServerSession,
+        voip_request: ServerVoipRequest,
+        ctx: &egui::Context,
+    )
+    {
+        match voip_request {
+            ServerVoipRequest::ConnectionStart(_) => {
+                tracing::error!("Received a voip connection start, which is not used");
+            },
+            //The server has refused our connection, we should shut down our ```Voip``` instance without notifying the server
+            ServerVoipRequest::ConnectionClosed(close) => {
+                display_error_message(close.reason, self.toasts.clone());
+
+                //Shutdown listener and recorder thread
+                session.voip_shutdown_token.cancel();
+
+                //Signal the voice recorder function to stop, if it belongs to this session's call
+                if session.voip_thread.is_some() {
+                    let _ = self.record_audio_interrupter.send(());
+                }
+
+                //Reset state
+                session.voip = None;
+                session.voip_thread = None;
+
+                if is_private_voice_room(&session.voip_room) {
+                    session.voip_room = DEFAULT_VOICE_ROOM.to_string();
+                }
+            },
+            ServerVoipRequest::Invitation(invitation) => {
+                session
+                    .incoming_call_invitations
+                    .push(IncomingCallInvitation {
+                        caller: invitation.caller,
+                        room: invitation.room,
+                        received_at: Instant::now(),
+                    });
+
+                //Callback
+                self.client_ui.extension.event_call_extensions(
+                    crate::app::lua::EventCall::OnCallReceive,
+                    &self.lua,
+                    None,
+                );
+
+                //Display the ringing window
+                ctx.request_repaint();
+            },
+            ServerVoipRequest::InvitationAnswered(room, invitee, answer) => {
+                //Check if its the answer to our latest invitation
+                if self
+                    .client_ui
+                    .outgoing_call_invitation
+                    .as_ref()
+                    .is_some_and(|invitation| invitation.room == room)
+                {
+                    self.client_ui.outgoing_call_invitation = None;
+                }
+
+                let username = session
+                    .incoming_messages
+                    .connected_clients_profile
+                    .get(&invitee)
+                    .map(|profile| profile.username.clone())
+                    .unwrap_or(invitee);
+
+                match answer {
+                    InvitationAnswer::Accepted => {},
+                    InvitationAnswer::Declined => {
+                        display_error_message(
+                            format!("@{username} has declined your call."),
+                            self.toasts.clone(),
+                        );
+                    },
+                    InvitationAnswer::Missed => {
+                        display_error_message(
+                            format!("@{username} has missed your call."),
+                            self.toasts.clone(),
+                        );
+                    },
+                }
+            },
+            //The caller has hung up or the invitation has timed out, the server reports it as a missed call
+            ServerVoipRequest::InvitationCancelled(room) => {
+                session
+                    .incoming_call_invitations
+                    .retain(|invitation| invitation.room != room);
+            },
+        }
+    }
 }
 
 /// Receives packets on the given UdpSocket, messages are decrypted with the decrpytion key
-/// Automatically appends the decrypted audio bytes to the ```Sink``` if its an uadio packet
-/// I might rework this function so that we can see who is talking based on uuid
+/// Automatically inserts the decrypted audio into the speaker's ```JitterBuffer``` if its an audio packet
 async fn receive_server_relay(
     //Socket this function is Listening on
     receiver_socket_part: Arc<tokio::net::UdpSocket>,
     //Decryption key
     decryption_key: &[u8],
-    //The sink its appending the bytes to
-    sink: Arc<Sink>,
-    //This serves as the image buffer from the server
-    image_buffer: ImageBuffer,
+    //The remote speakers of the call, the audio is inserted into their jitter buffer
+    remote_speakers: RemoteSpeakers,
+    //The stream handle the new remote speakers' sinks are created on
+    stream_handle: &OutputStreamHandle,
+    //The voip instance, this contains the image buffer and the decoded video frames
+    voip: Voip,
+    //The decoders of the participants' video streams
+    video_decoders: &mut HashMap<String, VideoDecoder>,
+    //The decoder of the audio mixed by the server
+    mixed_audio_decoder: &mut opus::Decoder,
 
     ctx: &egui::Context,
 ) -> anyhow::Result<()>
 {
+    let image_buffer = voip.image_buffer.clone();
+
     //Create buffer for header, this is the size of the maximum udp packet so no error will appear
     let mut header_buf = vec![0; 65536];
 
@@ -825,10 +1341,23 @@ async fn receive_server_relay(
         decryption_key,
     )?;
 
+    //Drop the malformed packets, so that a peer cant crash the receiver thread
+    anyhow::ensure!(
+        decrypted_bytes.len() >= 4,
+        "Voip packet is too short, with length of {}",
+        decrypted_bytes.len()
+    );
+
     let message_flag_bytes: Vec<u8> = decrypted_bytes.drain(decrypted_bytes.len() - 4..).collect();
 
     match UdpMessageType::from_number(u32::from_be_bytes(message_flag_bytes.try_into().unwrap())) {
         UdpMessageType::Voice => {
+            anyhow::ensure!(
+                decrypted_bytes.len() >= UUID_STRING_BYTE_LENGTH + SEQUENCE_NUMBER_BYTE_LENGTH,
+                "Voice packet is too short, with length of {}",
+                decrypted_bytes.len()
+            );
+
             //The generated uuids are always a set amount of bytes, so we can safely extract them, and we know that the the left over bytes are audio
             let uuid = String::from_utf8(
                 decrypted_bytes
@@ -840,31 +1369,137 @@ async fn receive_server_relay(
             uuid::Uuid::parse_str(&uuid)
                 .map_err(|err| anyhow::Error::msg(format!("Error: {}, in uuid {}", err, uuid)))?;
 
-            //Play received bytes
-            sink.append(rodio::Decoder::new(BufReader::new(Cursor::new(
-                decrypted_bytes,
-            )))?);
+            //Get the sequence number of the packet, the left over bytes are the audio
+            let sequence_number = u64::from_be_bytes(
+                decrypted_bytes
+                    .drain(decrypted_bytes.len() - SEQUENCE_NUMBER_BYTE_LENGTH..)
+                    .collect::<Vec<u8>>()
+                    .try_into()
+                    .unwrap(),
+            );
+
+            let frame = AudioFrame::from_wav(&decrypted_bytes)?;
+
+            let mut remote_speaker = remote_speakers
+                .entry(uuid)
+                .or_try_insert_with(|| RemoteSpeaker::new(stream_handle))?;
+
+            //Update the speaking indicator of the speaker, request a repaint if they have just started speaking
+            if calculate_loudness_db(&frame.samples) >= SPEAKING_THRESHOLD_DB {
+                if !remote_speaker.is_speaking() {
+                    ctx.request_repaint();
+                }
+
+                remote_speaker.last_voice_activity = Some(Instant::now());
+            }
+
+            let jitter_buffer = remote_speaker.jitter_buffer.clone();
+
+            //Make sure to drop the reference so we dont hold the lock of the DashMap
+            drop(remote_speaker);
+
+            //Insert the received audio into the speaker's jitter buffer, it will be played by the playout thread
+            jitter_buffer.lock().unwrap().insert(sequence_number, frame);
+        },
+        UdpMessageType::MixedVoice => {
+            // [. . . . . . . . . len - 9 - 36 * n][len - 9 - 36 * n . . . len - 9][len - 9][len - 8 . . .]
+            //            OPUS FRAMES                       SPEAKER UUIDS             COUNT   SEQUENCE NUMBER
+            anyhow::ensure!(
+                decrypted_bytes.len() > SEQUENCE_NUMBER_BYTE_LENGTH,
+                "Mixed voice packet is too short, with length of {}",
+                decrypted_bytes.len()
+            );
+
+            let sequence_number = u64::from_be_bytes(
+                decrypted_bytes
+                    .drain(decrypted_bytes.len() - SEQUENCE_NUMBER_BYTE_LENGTH..)
+                    .collect::<Vec<u8>>()
+                    .try_into()
+                    .unwrap(),
+            );
+
+            let speakers_length =
+                decrypted_bytes.pop().unwrap_or_default() as usize * UUID_STRING_BYTE_LENGTH;
+
+            anyhow::ensure!(
+                decrypted_bytes.len() >= speakers_length,
+                "Mixed voice packet is too short for its speakers, with length of {}",
+                decrypted_bytes.len()
+            );
+
+            let speakers = decrypted_bytes
+                .split_off(decrypted_bytes.len() - speakers_length)
+                .chunks(UUID_STRING_BYTE_LENGTH)
+                .map(|uuid| {
+                    let uuid = String::from_utf8(uuid.to_vec())?;
+
+                    uuid::Uuid::parse_str(&uuid)?;
+
+                    Ok(uuid)
+                })
+                .collect::<anyhow::Result<Vec<String>>>()?;
+
+            let frame = AudioFrame::from_opus(&decrypted_bytes, mixed_audio_decoder)?;
+
+            //Update the speaking indicator of the clients speaking in the mix, request a repaint if they have just started speaking
+            for speaker in speakers {
+                let mut remote_speaker = remote_speakers
+                    .entry(speaker)
+                    .or_try_insert_with(|| RemoteSpeaker::new(stream_handle))?;
+
+                if !remote_speaker.is_speaking() {
+                    ctx.request_repaint();
+                }
+
+                remote_speaker.last_voice_activity = Some(Instant::now());
+            }
+
+            //The mix is played as the server's stream, the server has already applied the volumes we have set for the participants
+            let jitter_buffer = remote_speakers
+                .entry(SERVER_UUID.to_string())
+                .or_try_insert_with(|| RemoteSpeaker::new(stream_handle))?
+                .jitter_buffer
+                .clone();
+
+            jitter_buffer.lock().unwrap().insert(sequence_number, frame);
         },
         UdpMessageType::ImageHeader => {
-            get_image_header(&decrypted_bytes, &image_buffer).unwrap();
+            //The header can complete a frame whose parts have arrived before it
+            if let Some((uuid, image_bytes)) = get_image_header(&decrypted_bytes, &image_buffer)? {
+                decode_video_frame(
+                    &voip,
+                    video_decoders,
+                    uuid,
+                    &image_bytes,
+                    decryption_key,
+                    ctx,
+                )
+                .await?;
+            }
         },
         UdpMessageType::Image => {
             // [. . . . . . . . . . . len - 164][len - 164 . . . . . len - 100][len - 100. . . . . len - 64][len - 64 . . . .]
             //      IMAGE                           HASH                            UUID                      IDENTIFICATOR
             let message_bytes = decrypted_bytes.to_vec();
 
+            anyhow::ensure!(
+                message_bytes.len() >= HASH_BYTE_OFFSET,
+                "Image packet is too short, with length of {}",
+                message_bytes.len()
+            );
+
             //Get the identificator of the image part in bytes
             let indetificator_bytes =
                 message_bytes[message_bytes.len() - IDENTIFICATOR_BYTE_OFFSET..].to_vec();
 
-            let identificator = String::from_utf8(indetificator_bytes).unwrap();
+            let identificator = String::from_utf8(indetificator_bytes)?;
 
             //Get the hash of the image part in bytes
             let hash_bytes = message_bytes
                 [message_bytes.len() - HASH_BYTE_OFFSET..message_bytes.len() - UUID_BYTE_OFFSET]
                 .to_vec();
 
-            let hash = String::from_utf8(hash_bytes).unwrap();
+            let hash = String::from_utf8(hash_bytes)?;
 
             //Get the image part bytes
             let image = message_bytes[..message_bytes.len() - HASH_BYTE_OFFSET].to_vec();
@@ -873,62 +1508,135 @@ async fn receive_server_relay(
                 message_bytes[message_bytes.len() - UUID_BYTE_OFFSET
                     ..message_bytes.len() - IDENTIFICATOR_BYTE_OFFSET]
                     .to_vec(),
-            )
-            .unwrap();
+            )?;
 
             //Make sure to verify that the UUID we are parsing is really a uuid, because if its not we know we have parsed the bytes in an incorrect order
             uuid::Uuid::parse_str(&uuid)
                 .map_err(|err| anyhow::Error::msg(format!("Error: {}, in uuid {}", err, uuid)))?;
 
-            if let Some(mut image_header) = image_buffer.get_mut(&uuid) {
-                if let Some((index, _, contents)) = image_header.get_full_mut(&identificator) {
-                    if let Some(byte_pair) = contents.get_mut(&hash) {
-                        *byte_pair = Some(image);
-                    }
-                    else {
-                        tracing::error!("Image part hash not found in the image header: {hash}");
-                    }
+            //If enough parts of the image have arrived, rebuild it and display it
+            if let Some(image_bytes) =
+                insert_image_part(&image_buffer, &uuid, &identificator, &hash, image)?
+            {
+                decode_video_frame(
+                    &voip,
+                    video_decoders,
+                    uuid,
+                    &image_bytes,
+                    decryption_key,
+                    ctx,
+                )
+                .await?;
+            }
+        },
+        UdpMessageType::KeyframeRequest => {
+            //A participant couldnt decode our video stream, the next frame we send will be a keyframe
+            voip.keyframe_requested
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+        },
+        UdpMessageType::Heartbeat => {
+            //The server has sent back our heartbeat
+            let sequence_number = u64::from_be_bytes(decrypted_bytes[..8].try_into()?);
+
+            voip.connection_quality
+                .lock()
+                .unwrap()
+                .on_heartbeat_answered(sequence_number);
+        },
+        UdpMessageType::ReceiverReport => {
+            //Remove our uuid, the left over bytes are the report
+            decrypted_bytes.truncate(
+                decrypted_bytes
+                    .len()
+                    .saturating_sub(UUID_STRING_BYTE_LENGTH),
+            );
+
+            let report = serde_json::from_slice::<ReceiverReport>(&decrypted_bytes)?;
 
-                    //If all the parts of the image header had arrived send the image to all the clients
-                    if contents.iter().all(|(_, value)| value.is_some()) {
-                        let contents_clone = contents.clone();
+            //Adjust the quality of our video stream
+            voip.video_quality_controller
+                .lock()
+                .unwrap()
+                .on_report(&report);
+        },
+    }
+
+    Ok(())
+}
 
-                        //Combine the image part bytes
-                        let image_bytes: Vec<u8> = contents_clone
-                            .iter()
-                            .flat_map(|(_, value)| {
-                                <std::option::Option<std::vec::Vec<u8>> as Clone>::clone(value)
-                                    .unwrap()
-                            })
-                            .collect();
+/// Decodes a received video frame with the sender's decoder, and displays it
+/// If the frame cant be decoded due to a lost frame, a keyframe is requested from the sender
+/// A frame shorter than the frame trailer indicates that the sender has turned off their camera
+async fn decode_video_frame(
+    voip: &Voip,
+    video_decoders: &mut HashMap<String, VideoDecoder>,
+    uuid: String,
+    frame_bytes: &[u8],
+    encryption_key: &[u8],
+    ctx: &egui::Context,
+) -> anyhow::Result<()>
+{
+    //The sender has turned off their camera
+    if frame_bytes.len() < FRAME_TYPE_BYTE_LENGTH + FRAME_NUMBER_BYTE_LENGTH {
+        video_decoders.remove(&uuid);
+        voip.video_frames.remove(&uuid);
+        voip.video_receiver_statistics.remove(&uuid);
+
+        if let Some(call_recorder) = &*voip.call_recorder.lock().unwrap() {
+            call_recorder.remove_video_stream(&uuid);
+        }
 
-                        //Define uri
-                        let uri = format!("bytes://video_stream:{uuid}");
+        ctx.request_repaint();
 
-                        //Drain earlier ImageHeaders (and the current one), because a new one has arrived
-                        image_header.drain(index..=index);
+        return Ok(());
+    }
 
-                        //Its important to drop image header, so that we dont deadlock, due to me accessing the image_buffer later
-                        drop(image_header);
+    //Update the reception statistics of the stream, these are reported back to the sender
+    if let Some(frame_number) = frame_number(frame_bytes) {
+        voip.video_receiver_statistics
+            .entry(uuid.clone())
+            .or_default()
+            .on_frame(frame_number);
+    }
 
-                        //Forget image on that URI
-                        ctx.forget_image(&uri);
+    let video_decoder = match video_decoders.entry(uuid.clone()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => entry.insert(VideoDecoder::new()?),
+    };
 
-                        //Pair URI with bytes
-                        ctx.include_bytes(uri, image_bytes);
+    match video_decoder.decode(frame_bytes)? {
+        DecodedVideoFrame::Frame(image) => {
+            if let Some(mut statistics) = voip.video_receiver_statistics.get_mut(&uuid) {
+                statistics.resolution = Some(image.size);
+            }
 
-                        //Request repaint
-                        ctx.request_repaint();
-                    }
-                }
-                else {
-                    tracing::error!("Image header not found: {identificator}");
-                }
+            if let Some(call_recorder) = &*voip.call_recorder.lock().unwrap() {
+                call_recorder.push_video_frame(&uuid, &image);
             }
-            else {
-                tracing::error!("User not found in the image header list: {uuid}");
+
+            //Update the texture of the participant, or create one if this is their first frame
+            match voip.video_frames.get_mut(&uuid) {
+                Some(mut texture) => {
+                    texture.set(image, egui::TextureOptions::LINEAR);
+                },
+                None => {
+                    let texture = ctx.load_texture(
+                        format!("video_stream:{uuid}"),
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    );
+
+                    voip.video_frames.insert(uuid, texture);
+                },
             }
+
+            //Request repaint
+            ctx.request_repaint();
+        },
+        DecodedVideoFrame::KeyframeNeeded => {
+            voip.request_keyframe(uuid, encryption_key).await?;
         },
+        DecodedVideoFrame::Pending => (),
     }
 
     Ok(())
@@ -125,6 +125,15 @@ pub struct Extension
     pub extension_table_size: Vec2,
 }
 
+/// The name of the global containing the address of the server, which the events are coming from
+/// As the client can be connected to multiple servers, the extensions can use this to tell them apart
+/// ``` lua
+/// function OnChatReceive(message)
+///     print(ServerAddress)
+/// end
+/// ```
+pub const SERVER_ADDRESS_GLOBAL: &str = "ServerAddress";
+
 #[derive(Display)]
 /// These are the events which trigger a function call in the extensions.
 /// Please refer to the [Documentation](https://matthias.gitbook.io/matthias)
@@ -1,40 +1,74 @@
 pub const SERVER_UUID: &str = "00000000-0000-0000-0000-000000000000";
 pub const SERVER_AUTHOR: &str = "Server";
 
+/// The amount of audio frames the mixer keeps for every client, older frames get dropped
+const MIXER_BACKLOG_FRAMES: usize = 4;
+
+/// The largest profile picture accepted in a profile update, the pngs created by the client are much smaller than this
+const MAX_PROFILE_PICTURE_SIZE: usize = 1024 * 1024;
+
+/// The amount of the last delivered message ids remembered for every client.
+/// The messages sent again after a reconnection are among the last ones, unless the client has queued up more messages than this while offline
+const DELIVERED_MESSAGE_ID_WINDOW: usize = 256;
+
 use std::{
-    collections::HashMap, env, fs, io::Write, net::SocketAddr, path::PathBuf, sync::Arc,
-    time::Duration,
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet, VecDeque},
+    env, fs,
+    io::Write,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::app::client::{HASH_BYTE_OFFSET, IDENTIFICATOR_BYTE_OFFSET, UUID_BYTE_OFFSET};
+use crate::app::{
+    client::{
+        HASH_BYTE_OFFSET, IDENTIFICATOR_BYTE_OFFSET, SEQUENCE_NUMBER_BYTE_LENGTH, UUID_BYTE_OFFSET,
+        UUID_STRING_BYTE_LENGTH, VOIP_PACKET_BUFFER_LENGTH_MS,
+    },
+    ui::client_ui::client_actions::{
+        connection_quality::HEARTBEAT_TIMEOUT,
+        jitter_buffer::{create_mixed_audio_encoder, AudioFrame},
+        voice_activity::{calculate_loudness_db, SPEAKING_THRESHOLD_DB},
+    },
+};
 
-use anyhow::{bail, Error, Result};
-use chrono::Utc;
-use dashmap::DashMap;
+use anyhow::{bail, ensure, Error, Result};
+use base64::{engine::general_purpose, Engine};
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, DashSet};
 use egui::Context;
+use hmac::{Hmac, Mac};
 use indexmap::IndexMap;
+use sha2::Sha256;
 use tokio_util::sync::CancellationToken;
 
 use super::backend::{
-    encrypt, encrypt_aes256, fetch_incoming_message_length, ClientLastSeenMessage,
-    ClientMessageType, ClientProfile, ConnectedClient, ConnectionType, MessageReaction, Reaction,
-    ReactionType, ServerClientReply, ServerMessageType,
+    encrypt, encrypt_aes256, fetch_incoming_message_length, is_private_voice_room,
+    ClientLastSeenMessage, ClientMessageType, ClientProfile, ClientRole, ClientSyncMessage,
+    ConnectedClient, ConnectionType, InvitationAnswer, MessageReaction, ParticipantVolumes,
+    ProfilePictures, Reaction, ReactionType, ServerAnnouncement, ServerCallInvitation,
+    ServerClientReply, ServerMessageType,
     ServerMessageTypeDiscriminants::{
-        Audio, Edit, Image, Normal, Reaction as ServerMessageTypeDiscriminantReaction, Sync,
-        Upload, VoipEvent as Voip,
+        Audio, Edit, Image, Normal, ProfileUpdate as ServerMessageTypeDiscriminantProfileUpdate,
+        Reaction as ServerMessageTypeDiscriminantReaction, Sync, Upload, VoipEvent as Voip,
     },
-    ServerReplyType, ServerSync, ServerVoip, ServerVoipReply, ServerVoipState,
+    ServerReplyType, ServerSync, ServerVoip, ServerVoipClose, ServerVoipEvent, ServerVoipRelay,
+    ServerVoipReply, ServerVoipRequest, ServerVoipState, VoipEvent, VoipMixingMode,
+    INVITATION_TIMEOUT, LAN_ANNOUNCEMENT_INTERVAL, LAN_DISCOVERY_MULTICAST_ADDRESS,
+    LAN_DISCOVERY_PORT,
 };
 
 use super::backend::{
-    decrypt_aes256_bytes, encrypt_aes256_bytes, get_image_header,
-    ClientFileRequestType as ClientRequestTypeStruct, ClientFileUpload as ClientFileUploadStruct,
-    ClientMessage,
+    decrypt_aes256_bytes, encrypt_aes256_bytes, evict_stale_image_frames, fragment_image,
+    get_image_header, insert_image_part, ClientFileRequestType as ClientRequestTypeStruct,
+    ClientFileUpload as ClientFileUploadStruct, ClientMessage,
     ClientMessageType::{
-        FileRequestType, FileUpload, MessageEdit, NormalMessage, Reaction as ClientReaction,
-        SyncMessage, VoipConnection,
+        FileRequestType, FileUpload, Heartbeat, MessageEdit, NormalMessage, ProfileUpdate,
+        Reaction as ClientReaction, SyncMessage, VoipConnection,
     },
-    ImageHeader, ServerFileReply, ServerImageReply, ServerMaster, UdpMessageType,
+    ImageBuffer, ServerFileReply, ServerImageReply, ServerMaster, UdpMessageType,
+    IMAGE_FRAME_TIMEOUT,
 };
 
 use tokio::{
@@ -95,10 +129,52 @@ pub struct MessageService
     /// In this hashmap the key is the connecting client's uuid, and the value is the ClientProfile struct (which will later get converted to string with serde_json)
     pub connected_clients_profile: Arc<tokio::sync::Mutex<HashMap<String, ClientProfile>>>,
 
+    /// The profile pictures of the clients, paired with their sha256 hash
+    /// The profiles only contain the hashes, so the pictures arent sent in every sync, the clients request the ones they havent cached yet
+    /// This is shared with the ui, so the server's ui can display them
+    pub profile_pictures: Arc<DashMap<String, Vec<u8>>>,
+
     /// This field contains all the shared fields, these fields are shared with the frontend
     pub shared_fields: Arc<tokio::sync::Mutex<SharedFields>>,
 
-    pub voip: Option<ServerVoip>,
+    /// The voice rooms with an ongoing call, paired with their name
+    pub voip_rooms: BTreeMap<String, ServerVoip>,
+
+    /// The sockets and the listener thread shared by the voice rooms, this is ```Some``` while there are voice rooms
+    voip_relay: Option<ServerVoipRelay>,
+
+    /// The volumes the clients have set for the other participants, these are applied by the mixer of the rooms
+    /// These are kept while the server is running, so the volumes dont have to be sent again every time a client joins a call
+    participant_volumes: ParticipantVolumes,
+
+    /// The uuids of the clients allowed to join the private voice rooms, paired with the name of the room
+    /// The UDP packets of a private room are only relayed between its participants, as every room has its own client manager threads
+    private_voice_rooms: HashMap<String, HashSet<String>>,
+
+    /// The unanswered call invitations, paired with the name of the private voice room
+    /// The invitations which arent answered in ```INVITATION_TIMEOUT``` are reported as missed calls by the sync thread
+    pending_call_invitations: HashMap<String, PendingCallInvitation>,
+
+    /// The last time a message has been received from the clients, paired with their uuid
+    /// The clients send heartbeats periodically, so the ones which stay silent for ```HEARTBEAT_TIMEOUT``` are disconnected
+    client_last_activity: HashMap<String, Instant>,
+
+    /// The ids of the last ```DELIVERED_MESSAGE_ID_WINDOW``` messages stored from the clients, paired with their uuid
+    /// The clients send the messages of their outbox again after reconnecting, these messages are only acknowledged again, so they arent stored twice
+    delivered_message_ids: HashMap<String, VecDeque<String>>,
+
+    /// The clients which have joined with an invite, paired with their uuid
+    /// The value contains the id of the invite and the role the client has received
+    /// These clients can connect again without the invite's token, until the invite is revoked
+    invited_clients: HashMap<String, (String, ClientRole)>,
+
+    /// The identifier of the server, this is sent to the clients when connecting
+    /// This is not a proof of the server's identity, the clients only use it to notice if a different server answers on a saved address
+    server_fingerprint: String,
+
+    /// The id of this instance of the server, this is generated every time the server is started
+    /// The clients only resume their cached messages if this hasnt changed, because the message list is not kept after a restart
+    server_instance_id: String,
 
     opened_on_port: String,
 }
@@ -109,15 +185,206 @@ pub struct SharedFields
 {
     /// This list contains the banned uuids
     pub banned_uuids: Arc<tokio::sync::Mutex<Vec<String>>>,
+
+    /// The key the invite tokens are signed with, this is generated when the server is started
+    invite_signing_key: [u8; 32],
+
+    /// The invites minted by the host of the server
+    pub invites: Arc<tokio::sync::Mutex<Vec<Invite>>>,
+}
+
+/// A call invitation which hasnt been answered yet
+#[derive(Debug, Clone)]
+struct PendingCallInvitation
+{
+    /// The uuid of the client who has sent the invitation
+    caller: String,
+
+    /// The uuid of the invited client
+    invitee: String,
+
+    /// When the invitation has been sent, the invitation is missed after ```INVITATION_TIMEOUT```
+    sent_at: Instant,
+
+    /// The caller has left the private call before the invitation was answered
+    cancelled: bool,
+}
+
+/// An invite minted by the host, the clients joining with its token dont need the password of the server
+#[derive(Debug, Clone)]
+pub struct Invite
+{
+    pub id: String,
+
+    /// The signed token shared in the connection links
+    pub token: String,
+
+    /// The invite can not be used after this date, if this is ```None``` the invite doesnt expire
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// How many clients can join with the invite, if this is ```None``` it can be used any amount of times
+    pub max_uses: Option<u32>,
+
+    /// How many clients have joined with the invite
+    pub uses: u32,
+
+    /// The role of the clients joining with the invite
+    pub role: ClientRole,
+
+    /// The revoked invites cant be used, and the clients who have joined with them are disconnected
+    pub revoked: bool,
+}
+
+impl Invite
+{
+    pub fn is_expired(&self) -> bool
+    {
+        self.expires_at
+            .is_some_and(|expires_at| Utc::now() > expires_at)
+    }
+
+    /// Returns whether a new client can join with the invite
+    pub fn is_usable(&self) -> bool
+    {
+        !self.revoked
+            && !self.is_expired()
+            && self.max_uses.map_or(true, |max_uses| self.uses < max_uses)
+    }
+}
+
+/// The signed part of an invite token
+#[derive(serde::Serialize, serde::Deserialize)]
+struct InviteTokenPayload
+{
+    id: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl SharedFields
+{
+    /// Mints a new invite, and returns its token
+    /// The token consists of the base64 encoded payload and its signature, separated by a dot
+    pub fn create_invite(
+        &self,
+        valid_for: Option<chrono::Duration>,
+        max_uses: Option<u32>,
+        role: ClientRole,
+    ) -> Result<String>
+    {
+        let payload = InviteTokenPayload {
+            id: uuid::Uuid::new_v4().to_string(),
+            expires_at: valid_for.map(|valid_for| Utc::now() + valid_for),
+        };
+
+        let serialized_payload = serde_json::to_string(&payload)?;
+
+        let token = format!(
+            "{}.{}",
+            general_purpose::URL_SAFE_NO_PAD.encode(&serialized_payload),
+            general_purpose::URL_SAFE_NO_PAD.encode(
+                invite_mac(&self.invite_signing_key, serialized_payload.as_bytes())?
+                    .finalize()
+                    .into_bytes()
+            )
+        );
+
+        self.invites.try_lock()?.push(Invite {
+            id: payload.id,
+            token: token.clone(),
+            expires_at: payload.expires_at,
+            max_uses,
+            uses: 0,
+            role,
+            revoked: false,
+        });
+
+        Ok(token)
+    }
+
+    /// Verifies the signature of the token, and counts the use of its invite
+    /// Returns the id and the role of the invite, if a new client can join with it
+    async fn redeem_invite(&self, token: &str) -> Option<(String, ClientRole)>
+    {
+        let (payload, signature) = token.split_once('.')?;
+
+        let payload = general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        //The signature is compared in constant time, so the time it takes doesnt reveal how much of the signature is correct
+        invite_mac(&self.invite_signing_key, &payload)
+            .ok()?
+            .verify_slice(&signature)
+            .ok()?;
+
+        let payload = serde_json::from_slice::<InviteTokenPayload>(&payload).ok()?;
+
+        let mut invites = self.invites.lock().await;
+
+        let invite = invites
+            .iter_mut()
+            .find(|invite| invite.id == payload.id)
+            .filter(|invite| invite.is_usable())?;
+
+        invite.uses += 1;
+
+        Some((invite.id.clone(), invite.role))
+    }
+
+    async fn is_invite_revoked(&self, invite_id: &str) -> bool
+    {
+        self.invites
+            .lock()
+            .await
+            .iter()
+            .find(|invite| invite.id == invite_id)
+            .map_or(true, |invite| invite.revoked)
+    }
+}
+
+/// Returns the identifier of the server, the random bytes it is derived from are generated when the server is first started
+/// The clients store the identifier in their bookmarks, so they can notice if a different server answers on a saved address
+/// The identifier is sent in plain, so anyone who has received it can send it too, it doesnt authenticate the server
+/// The random bytes are stored in ```identity_directory```, which must not be one of the directories removed on exit
+fn load_server_fingerprint(identity_directory: &Path) -> Result<String>
+{
+    let identity_path = identity_directory.join("identity");
+
+    let identity = match fs::read(&identity_path) {
+        Ok(identity) => identity,
+        Err(_) => {
+            let identity = rand::random::<[u8; 32]>().to_vec();
+
+            fs::create_dir_all(identity_directory)?;
+            fs::write(&identity_path, &identity)?;
+
+            identity
+        },
+    };
+
+    Ok(sha256::digest(identity))
+}
+
+/// Returns the HMAC-SHA256 of the message with the key, this is used to sign the invite tokens
+fn invite_mac(key: &[u8; 32], message: &[u8]) -> Result<Hmac<Sha256>>
+{
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|err| Error::msg(err.to_string()))?;
+
+    mac.update(message);
+
+    Ok(mac)
 }
 
 /// Shutting down server also doesnt work we will have to figure a way out on how to stop client readers (probably a broadcast channel)
 pub async fn server_main(
     port: String,
     password: String,
+    //The server is only announced on the local network if this is ```Some```
+    lan_announcement_name: Option<String>,
     //This signals all the client receivers to be shut down
     cancellation_token: CancellationToken,
     connected_clients_profile_list: Arc<DashMap<String, ClientProfile>>,
+    //The server stores the profile pictures in this dashmap, so that the ui can display them
+    profile_pictures: Arc<DashMap<String, Vec<u8>>>,
     //We pass in ctx so we can request repaint when someone connects
     ctx: Context,
 ) -> anyhow::Result<Arc<tokio::sync::Mutex<SharedFields>>>
@@ -138,14 +405,47 @@ pub async fn server_main(
         },
     };
 
+    //This has to be created before the password and the port are moved
+    let lan_announcement = match lan_announcement_name {
+        Some(name) => {
+            Some(ServerAnnouncement {
+                name,
+                port: port.parse()?,
+                requires_password: !password.is_empty(),
+                connected_clients: 0,
+            })
+        },
+        None => None,
+    };
+
     //Server default information
     let msg_service = Arc::new(tokio::sync::Mutex::new(MessageService {
         passw: encrypt(password),
         decryption_key: rand::random::<[u8; 32]>(),
         opened_on_port: port,
+        //The server's directory is removed on exit, so the identity is stored next to it
+        server_fingerprint: load_server_fingerprint(&PathBuf::from(format!(
+            "{}\\Matthias\\ServerIdentity",
+            env!("APPDATA")
+        )))?,
+        server_instance_id: uuid::Uuid::new_v4().to_string(),
+        profile_pictures,
+        shared_fields: Arc::new(tokio::sync::Mutex::new(SharedFields {
+            invite_signing_key: rand::random::<[u8; 32]>(),
+            ..Default::default()
+        })),
         ..Default::default()
     }));
 
+    //Let the clients on the local network find the server
+    if let Some(lan_announcement) = lan_announcement {
+        spawn_lan_announcer(
+            msg_service.clone(),
+            lan_announcement,
+            cancellation_token.child_token(),
+        );
+    }
+
     //This is used to shutdown the main server thread
     let cancellation_child = cancellation_token.child_token();
 
@@ -205,7 +505,7 @@ pub async fn server_main(
                 _ = tokio::time::sleep(Duration::from_secs(3)) => {
                     ctx.request_repaint();
 
-                    let message_service_lock = message_service_clone.lock().await;
+                    let mut message_service_lock = message_service_clone.lock().await;
 
                     //The original client list contained by the server
                     let connected_clients_server = message_service_lock.connected_clients_profile.lock().await.clone();
@@ -230,8 +530,40 @@ pub async fn server_main(
                         }
                     }
 
-                    let voip = message_service_lock.voip.clone();
-                    if let Some(voip) = voip {
+                    //Find the clients which havent sent a heartbeat in time, their connection has probably died without them disconnecting
+                    let timed_out_clients: Vec<String> = clients
+                        .iter()
+                        .filter(|client| {
+                            message_service_lock
+                                .client_last_activity
+                                .get(&client.uuid)
+                                .is_some_and(|last_activity| last_activity.elapsed() > HEARTBEAT_TIMEOUT)
+                        })
+                        .map(|client| client.uuid.clone())
+                        .collect();
+
+                    drop(clients);
+
+                    for uuid in timed_out_clients {
+                        if let Err(err) = message_service_lock.handle_client_timeout(&uuid).await {
+                            tracing::error!("Failed to disconnect timed out client: {err}");
+                        }
+                    }
+
+                    //Report the unanswered call invitations as missed calls
+                    if let Err(err) = message_service_lock.expire_call_invitations().await {
+                        tracing::error!("Failed to expire call invitations: {err}");
+                    }
+
+                    //Disconnect the clients whose invite has been revoked
+                    for uuid in message_service_lock.connected_revoked_invitees().await {
+                        if let Err(err) = message_service_lock.handle_revoked_invitee(&uuid).await {
+                            tracing::error!("Failed to disconnect client with a revoked invite: {err}");
+                        }
+                    }
+
+                    let voip_rooms: Vec<ServerVoip> = message_service_lock.voip_rooms.values().cloned().collect();
+                    for voip in voip_rooms {
                         for banned_uuid in message_service_lock.shared_fields.lock().await.banned_uuids.lock().await.iter() {
                             voip.disconnect(banned_uuid.to_string()).unwrap_or_default();
                         }
@@ -254,6 +586,51 @@ pub async fn server_main(
     Ok(msg_svc.shared_fields.clone())
 }
 
+/// Announces the server on the local network periodically, until the token is cancelled
+/// The amount of connected clients is updated before every announcement
+fn spawn_lan_announcer(
+    msg_service: Arc<tokio::sync::Mutex<MessageService>>,
+    mut announcement: ServerAnnouncement,
+    cancellation_token: CancellationToken,
+)
+{
+    let _: JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+
+        //The announcements should stay in the local network
+        socket.set_multicast_ttl_v4(1)?;
+
+        let mut announcement_interval = tokio::time::interval(LAN_ANNOUNCEMENT_INTERVAL);
+
+        loop {
+            select! {
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+
+                _ = announcement_interval.tick() => {
+                    let connected_clients = msg_service.lock().await.connected_clients.clone();
+
+                    announcement.connected_clients = connected_clients.lock().await.len();
+
+                    if let Err(err) = socket
+                        .send_to(
+                            &serde_json::to_vec(&announcement)?,
+                            (LAN_DISCOVERY_MULTICAST_ADDRESS, LAN_DISCOVERY_PORT),
+                        )
+                        .await
+                    {
+                        //The network might not be available yet, so we keep trying
+                        tracing::error!("Failed to announce the server: {err}");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    });
+}
+
 /// This function does not need to be async since it spawn an async thread anyway
 /// Spawn reader thread, this will constantly listen to the client which was connected, this thread will only finish if the client disconnects
 #[inline]
@@ -384,13 +761,43 @@ where
     Ok(())
 }
 
+/// Sends a ```ServerVoipRequest``` only to the client with the specified uuid, this is used for the call invitations
+/// Returns whether the client was connected to the server
+async fn send_voip_request(
+    connected_clients: Arc<tokio::sync::Mutex<Vec<ConnectedClient>>>,
+    uuid: &str,
+    request: ServerVoipRequest,
+    key: [u8; 32],
+) -> anyhow::Result<bool>
+{
+    let client_handle = connected_clients
+        .lock()
+        .await
+        .iter()
+        .find(|client| client.uuid == uuid)
+        .and_then(|client| client.handle.clone());
+
+    let Some(client_handle) = client_handle
+    else {
+        return Ok(false);
+    };
+
+    send_message_to_client(
+        &mut *client_handle.lock().await,
+        encrypt_aes256(serde_json::to_string(&request)?, &key)?,
+    )
+    .await?;
+
+    Ok(true)
+}
+
 /// This function will create a management thread, but only if the ```voip.threads``` field is None (Preventing spawning multiple threads)
 pub fn create_client_voip_manager(
     voip: ServerVoip,
     shutdown_token: CancellationToken,
     key: [u8; 32],
     mut receiver: Receiver<Vec<u8>>,
-    #[allow(unused_variables)] listening_to: SocketAddr,
+    listening_to: SocketAddr,
     uuid: String,
 )
 {
@@ -398,6 +805,8 @@ pub fn create_client_voip_manager(
 
     //Spawn client management thread
     tokio::spawn(async move {
+        let mut eviction_interval = tokio::time::interval(IMAGE_FRAME_TIMEOUT);
+
         loop {
             let socket_v6 = voip.socket_v6.clone();
 
@@ -406,9 +815,7 @@ pub fn create_client_voip_manager(
             //Clone so we can move the value
             let voip_connected_clients = voip.connected_clients.clone();
 
-            let image_buffer: Arc<
-                DashMap<String, IndexMap<String, HashMap<String, Option<Vec<u8>>>>>,
-            > = message_buffer.clone();
+            let image_buffer: ImageBuffer = message_buffer.clone();
 
             select! {
                 _ = shutdown_token.cancelled() => {
@@ -416,13 +823,34 @@ pub fn create_client_voip_manager(
                     break;
                 },
 
+                //Evict the client's frames which couldnt be rebuilt, even if they have stopped sending images
+                _ = eviction_interval.tick() => {
+                    if let Some(mut image_frames) = image_buffer.get_mut(&uuid) {
+                        evict_stale_image_frames(&mut image_frames);
+                    }
+                },
+
                 //receive_message length by reading its first 4 bytes
                 received_bytes = receiver.recv() => {
                     if let Some(received_bytes) = received_bytes {
                         //Decrypt message
                     // [. . . . . .4][4 . . . . len - 4][len - 4..]
                     //  PACKET LENGTH       MESSAGE      MSG TYPE
-                    let mut decrypted_bytes = decrypt_aes256_bytes(&received_bytes, &key).unwrap();
+                    let mut decrypted_bytes = match decrypt_aes256_bytes(&received_bytes, &key) {
+                        Ok(decrypted_bytes) => decrypted_bytes,
+                        Err(err) => {
+                            tracing::error!("Failed to decrypt voip packet: {err}");
+
+                            continue;
+                        },
+                    };
+
+                    //Drop the malformed packets, so that a peer cant crash the thread
+                    if decrypted_bytes.len() < 4 {
+                        tracing::error!("Voip packet is too short, with length of {}", decrypted_bytes.len());
+
+                        continue;
+                    }
 
                     let message_type_bytes: Vec<u8> = decrypted_bytes.drain(decrypted_bytes.len() - 4..).collect();
 
@@ -430,6 +858,32 @@ pub fn create_client_voip_manager(
                     let message_type = UdpMessageType::from_number(u32::from_be_bytes(message_type_bytes.try_into().unwrap()));
 
                     match message_type {
+                        UdpMessageType::Voice if voip.mixing_mode == VoipMixingMode::Mixed => {
+                            if decrypted_bytes.len() < UUID_STRING_BYTE_LENGTH + SEQUENCE_NUMBER_BYTE_LENGTH {
+                                tracing::error!("Voice packet is too short, with length of {}", decrypted_bytes.len());
+
+                                continue;
+                            }
+
+                            //Remove the uuid and the sequence number, the left over bytes are the audio
+                            decrypted_bytes.truncate(decrypted_bytes.len() - UUID_STRING_BYTE_LENGTH - SEQUENCE_NUMBER_BYTE_LENGTH);
+
+                            match AudioFrame::from_wav(&decrypted_bytes) {
+                                Ok(frame) => {
+                                    let mut frames = voip.mixer_buffer.entry(uuid.clone()).or_default();
+
+                                    frames.push_back(frame);
+
+                                    //Drop the backlog, so the latency of the mixed stream doesnt keep growing
+                                    while frames.len() > MIXER_BACKLOG_FRAMES {
+                                        frames.pop_front();
+                                    }
+                                },
+                                Err(err) => {
+                                    tracing::error!("Failed to decode voice packet: {err}");
+                                },
+                            }
+                        }
                         UdpMessageType::Voice => {
                             //Spawn relay thread
                             tokio::spawn(async move {
@@ -482,23 +936,75 @@ pub fn create_client_voip_manager(
                                 }
                             });
                         }
+                        UdpMessageType::MixedVoice => {
+                            tracing::error!("Received mixed audio from a client, only the server mixes audio");
+                        }
+                        UdpMessageType::Heartbeat => {
+                            let socket = match listening_to.is_ipv6() {
+                                true => socket_v6.clone(),
+                                false => Some(socket_v4.clone()),
+                            };
+
+                            //Send the heartbeat back to the client unchanged, so that they can measure the round trip time
+                            if let Some(socket) = socket {
+                                if let Err(err) = send_bytes(decrypted_bytes, &key, message_type, socket, listening_to).await {
+                                    tracing::error!("Failed to answer voip heartbeat: {err}");
+                                }
+                            }
+                        }
+                        UdpMessageType::KeyframeRequest | UdpMessageType::ReceiverReport => {
+                            //The message ends with the uuid of the client whose video stream is requested or reported
+                            let requested_uuid = String::from_utf8_lossy(&decrypted_bytes[decrypted_bytes.len().saturating_sub(UUID_STRING_BYTE_LENGTH)..]).to_string();
+
+                            if let Some(requested_socket_addr) = voip_connected_clients.get(&requested_uuid).map(|entry| *entry.value()) {
+                                let socket = match requested_socket_addr.is_ipv6() {
+                                    true => socket_v6.clone(),
+                                    false => Some(socket_v4.clone()),
+                                };
+
+                                if let Some(socket) = socket {
+                                    //Relay the request to the client
+                                    if let Err(err) = send_bytes(decrypted_bytes, &key, message_type, socket, requested_socket_addr).await {
+                                        tracing::error!("Failed to relay video stream feedback: {err}");
+                                    }
+                                }
+                            }
+                            else {
+                                tracing::error!("Video stream feedback sent to a client not in the call: {requested_uuid}");
+                            }
+                        }
                         UdpMessageType::ImageHeader => {
-                            get_image_header(&decrypted_bytes, &image_buffer).unwrap();
+                            //The header can complete a frame whose parts have arrived before it
+                            match get_image_header(&decrypted_bytes, &image_buffer) {
+                                Ok(Some((author_uuid, image_bytes))) => {
+                                    forward_image(voip.clone(), key, author_uuid, image_bytes);
+                                },
+                                Ok(None) => (),
+                                Err(err) => {
+                                    tracing::error!("Failed to rebuild image: {err}");
+                                },
+                            }
                         }
                         UdpMessageType::Image => {
                             // [. . . . . . . . . . . len - 164][len - 164 . . . . . len - 100][len - 100. . . . . len - 64][len - 64 . . . .]
                             //      IMAGE                           HASH                            UUID                      IDENTIFICATOR
                             let message_bytes = decrypted_bytes.to_vec();
 
+                            if message_bytes.len() < HASH_BYTE_OFFSET {
+                                tracing::error!("Image packet is too short, with length of {}", message_bytes.len());
+
+                                continue;
+                            }
+
                             //Get the identificator of the image part in bytes
                             let indetificator_bytes = message_bytes[message_bytes.len() - IDENTIFICATOR_BYTE_OFFSET..].to_vec();
 
-                            let identificator = String::from_utf8(indetificator_bytes).unwrap();
+                            let identificator = String::from_utf8_lossy(&indetificator_bytes).to_string();
 
                             //Get the identificator of the image part in bytes
                             let hash_bytes = message_bytes[message_bytes.len() - HASH_BYTE_OFFSET..message_bytes.len() - UUID_BYTE_OFFSET].to_vec();
 
-                            let hash = String::from_utf8(hash_bytes).unwrap();
+                            let hash = String::from_utf8_lossy(&hash_bytes).to_string();
 
                             //Get the image part bytes
                             //We subtract 164 bytes to only get the image part
@@ -507,102 +1013,188 @@ pub fn create_client_voip_manager(
                             //THIS IS UNUSED AND SHOULD BE REMOVED
                             let _uuid_bytes = message_bytes[message_bytes.len() - UUID_BYTE_OFFSET..message_bytes.len() - IDENTIFICATOR_BYTE_OFFSET].to_vec();
 
-                            let author_uuid = String::from_utf8(_uuid_bytes).unwrap();
+                            let author_uuid = String::from_utf8_lossy(&_uuid_bytes).to_string();
+
+                            //If enough parts of the image have arrived, rebuild it and send it to all the clients
+                            match insert_image_part(&image_buffer, &uuid, &identificator, &hash, image) {
+                                Ok(Some(image_bytes)) => {
+                                    forward_image(voip.clone(), key, author_uuid, image_bytes);
+                                },
+                                Ok(None) => (),
+                                Err(err) => {
+                                    tracing::error!("Failed to rebuild image: {err}");
+                                },
+                            }
+                        }
+                    }
+                    }
+                },
+            }
+        }
+    });
+}
 
-                            if let Some(mut image_header) = image_buffer.get_mut(&uuid) {
-                                if let Some((index, _, contents)) = image_header.get_full_mut(&identificator) {
+/// Creates a voice room, the room uses the sockets of the ```ServerVoipRelay```
+fn create_voip_room(
+    voip_relay: &ServerVoipRelay,
+    mixing_mode: VoipMixingMode,
+    participant_volumes: &ParticipantVolumes,
+) -> ServerVoip
+{
+    ServerVoip {
+        connected_clients: Arc::new(DashMap::new()),
+        _established_since: Utc::now(),
+        socket_v6: voip_relay.socket_v6.clone(),
+        socket_v4: voip_relay.socket_v4.clone(),
+        thread_cancellation_token: CancellationToken::new(),
+        threads: None,
+        connected_client_thread_channels: voip_relay.connected_client_thread_channels.clone(),
+        image_buffer: Arc::new(DashMap::new()),
+        mixing_mode,
+        mixer_buffer: Arc::new(DashMap::new()),
+        recording_clients: Arc::new(DashSet::new()),
+        participant_volumes: participant_volumes.clone(),
+    }
+}
 
-                                    if let Some(byte_pair) = contents.get_mut(&hash) {
-                                        *byte_pair = Some(image);
-                                    }
-                                    else {
-                                        tracing::error!("Image part hash not found in the image header: {hash}");
-                                    }
+/// Spawns the mixer thread of a voip call, this is only used if the call's ```VoipMixingMode``` is ```Mixed```
+/// Every ```VOIP_PACKET_BUFFER_LENGTH_MS``` the thread takes one audio frame of every client, and sends each client the mix of the other clients' frames
+/// The frames are mixed with the volumes the client has set for the other participants, as the client cant split up the mix
+/// The mix is encoded with Opus, and is sent with the uuids of the clients speaking in it, so the clients can display who is speaking
+pub fn create_voip_mixer(voip: ServerVoip, key: [u8; 32])
+{
+    tokio::spawn(async move {
+        let mut mixer_interval =
+            tokio::time::interval(Duration::from_millis(VOIP_PACKET_BUFFER_LENGTH_MS as u64));
 
-                                    //If all the parts of the image header had arrived send the image to all the clients
-                                    if contents.iter().all(|(_, value)| value.is_some()) {
-                                        let contents_clone = contents.clone();
-                                        tokio::spawn(async move {
+        //The sequence number of the mixed stream, the clients' jitter buffer reorders the packets based on this
+        let mut sequence_number: u64 = 0;
 
+        //The encoders of the clients' mixed streams paired with their uuid, every stream has its own encoder as the encoder keeps state between the frames
+        let mut encoders: HashMap<String, opus::Encoder> = HashMap::new();
 
-                                        //Combine the image part bytes
-                                        let image_bytes: Vec<u8> = contents_clone.iter().flat_map(|(_, value)| {
-                                            <std::option::Option<std::vec::Vec<u8>> as Clone>::clone(value).unwrap()
-                                        }).collect();
+        loop {
+            select! {
+                _ = voip.thread_cancellation_token.cancelled() => {
+                    //Shutdown thread by exiting the loop
+                    break;
+                },
 
-                                        //Create image parts by splitting it every 60000 bytes
-                                        let image_parts_tuple: Vec<(String, &[u8])> = image_bytes
-                                            .chunks(60000)
-                                            .map(|image_part| (sha256::digest(image_part), image_part))
-                                            .collect();
+                _ = mixer_interval.tick() => {
+                    //Take one frame from every client
+                    let frames: Vec<(String, AudioFrame)> = voip
+                        .mixer_buffer
+                        .iter_mut()
+                        .filter_map(|mut entry| {
+                            let frame = entry.value_mut().pop_front()?;
 
-                                        let image_parts = Vec::from_iter(image_parts_tuple.iter().map(|part| part.0.clone()));
+                            Some((entry.key().clone(), frame))
+                        })
+                        .collect();
 
-                                        let identificator = sha256::digest(
-                                            image_parts
-                                                .iter()
-                                                .flat_map(|hash| hash.as_bytes().to_vec())
-                                                .collect::<Vec<u8>>(),
-                                        );
+                    sequence_number += 1;
 
-                                        for connected_client in voip_connected_clients.iter() {
-                                            let socket_addr = connected_client.value();
-
-                                            //Create header message
-                                            let header_message =
-                                                ImageHeader::new(author_uuid.clone(), image_parts.clone(), identificator.clone());
-
-                                            match socket_addr.is_ipv6() {
-                                                true => {
-                                                    // Send image header
-                                                    send_bytes(
-                                                        serde_json::to_string(&header_message).unwrap().as_bytes().to_vec(),
-                                                        &key,
-                                                        UdpMessageType::ImageHeader,
-                                                        socket_v6.clone().unwrap(),
-                                                        *socket_addr,
-                                                    )
-                                                    .await.unwrap();
-
-                                                    //Send image parts
-                                                    //We have already sent the image header
-                                                    send_image_parts(image_parts_tuple.clone(), author_uuid.clone(), &key, identificator.clone(), socket_v6.clone().unwrap(), *socket_addr)
-                                                        .await.unwrap();
-                                                },
-                                                false => {
-                                                    // Send image header
-                                                    send_bytes(
-                                                        serde_json::to_string(&header_message).unwrap().as_bytes().to_vec(),
-                                                        &key,
-                                                        UdpMessageType::ImageHeader,
-                                                        socket_v4.clone(),
-                                                        *socket_addr,
-                                                    )
-                                                    .await.unwrap();
-
-                                                    //Send image parts
-                                                    //We have already sent the image header
-                                                    send_image_parts(image_parts_tuple.clone(), author_uuid.clone(), &key, identificator.clone(), socket_v4.clone(), *socket_addr)
-                                                        .await.unwrap();
-                                                },
-                                            }
+                    //If no one is talking there is nothing to send
+                    if frames.is_empty() {
+                        continue;
+                    }
 
-                                        }
-                                        });
+                    let connected_clients: Vec<(String, SocketAddr)> = voip
+                        .connected_clients
+                        .iter()
+                        .map(|entry| (entry.key().clone(), *entry.value()))
+                        .collect();
+
+                    //Forget the encoders of the clients who have left the call
+                    encoders.retain(|uuid, _| connected_clients.iter().any(|(connected_uuid, _)| connected_uuid == uuid));
+
+                    for (listener_uuid, socket_addr) in connected_clients {
+                        //Every client's audio, except the listener's own
+                        let heard_frames: Vec<&(String, AudioFrame)> = frames
+                            .iter()
+                            .filter(|(uuid, _)| {
+                                //We dont send the user's voice to them in release builds
+                                #[cfg(not(debug_assertions))]
+                                {
+                                    *uuid != listener_uuid
+                                }
+
+                                //We allow voice loopback in debug builds
+                                #[cfg(debug_assertions)]
+                                {
+                                    let _ = (uuid, &listener_uuid);
 
-                                        //Drain earlier ImageHeaders (and the current one), because a new one has arrived
-                                        image_header.drain(index..=index);
-                                    };
+                                    true
                                 }
-                                else {
-                                    tracing::error!("Image header not found: {identificator}");
+                            })
+                            .collect();
+
+                        let mixed_frame = {
+                            let volumes = voip.participant_volumes.get(&listener_uuid);
+
+                            AudioFrame::mix(heard_frames.iter().map(|(uuid, frame)| {
+                                let volume = volumes
+                                    .as_ref()
+                                    .and_then(|volumes| volumes.get(uuid).copied())
+                                    .unwrap_or(1.);
+
+                                (frame, volume)
+                            }))
+                        };
+
+                        let Some(mixed_frame) = mixed_frame
+                        else {
+                            continue;
+                        };
+
+                        //Only the clients whose voice is loud enough are displayed as speaking, the amount of uuids has to fit in a byte
+                        let speakers: Vec<&String> = heard_frames
+                            .iter()
+                            .filter(|(_, frame)| calculate_loudness_db(&frame.samples) >= SPEAKING_THRESHOLD_DB)
+                            .map(|(uuid, _)| uuid)
+                            .take(u8::MAX as usize)
+                            .collect();
+
+                        let encoder = match encoders.entry(listener_uuid.clone()) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => {
+                                match create_mixed_audio_encoder() {
+                                    Ok(encoder) => entry.insert(encoder),
+                                    Err(err) => {
+                                        tracing::error!("Failed to create mixed audio encoder: {err}");
+
+                                        continue;
+                                    },
                                 }
-                            }
-                            else {
-                                tracing::error!("User not found in the image header list: {uuid}");
-                            };
+                            },
+                        };
+
+                        // [. . . . . . . . . len - 9 - 36 * n][len - 9 - 36 * n . . . len - 9][len - 9][len - 8 . . .]
+                        //            OPUS FRAMES                       SPEAKER UUIDS             COUNT   SEQUENCE NUMBER
+                        let mut bytes = match mixed_frame.to_opus(encoder) {
+                            Ok(bytes) => bytes,
+                            Err(err) => {
+                                tracing::error!("Failed to encode mixed audio: {err}");
+
+                                continue;
+                            },
+                        };
+
+                        for speaker in &speakers {
+                            bytes.extend_from_slice(speaker.as_bytes());
+                        }
+
+                        bytes.push(speakers.len() as u8);
+                        bytes.extend_from_slice(&sequence_number.to_be_bytes());
+
+                        let socket = match (socket_addr.is_ipv6(), voip.socket_v6.clone()) {
+                            (true, Some(socket_v6)) => socket_v6,
+                            _ => voip.socket_v4.clone(),
+                        };
+
+                        if let Err(err) = send_bytes(bytes, &key, UdpMessageType::MixedVoice, socket, socket_addr).await {
+                            tracing::error!("Failed to send mixed audio: {err}");
                         }
-                    }
                     }
                 },
             }
@@ -645,29 +1237,116 @@ async fn send_bytes(
     Ok(())
 }
 
-/// Send the images specified in the ```image_parts_tuple``` argument
-/// __Image message contents:__
-/// - ```[len - 64 - 64 - 36..len - 64 - 36]``` = Contains the hash (sha256 hash) of the image part we are sending
-/// - ```[len - 64 - 36.. len - 64]``` = Contains the UUID of the author who has sent the message
-/// - ```[..len - 64 - 64 - 36]``` = Contains the image part we are sending (JPEG image)
-/// - ```[len - 64..]``` = Contains the identificator of the part we are sending
-/// - **The hash length is 64 bytes.**
-/// - **The identificator is 64 bytes.**
-/// - **The uuid is 36 bytes.**
-async fn send_image_parts(
-    image_parts_tuple: Vec<(String, &[u8])>,
-    uuid: String,
-    encryption_key: &[u8],
-    identificator: String,
-    socket: Arc<UdpSocket>,
-    send_to: SocketAddr,
-) -> Result<(), Error>
+/// Relays the rebuilt image to all the clients in the call.
+/// The image is fragmented again, so that the parts lost on the way to the server are sent too.
+fn forward_image(voip: ServerVoip, key: [u8; 32], author_uuid: String, image_bytes: Vec<u8>)
 {
-    for (hash, bytes) in image_parts_tuple {
-        //Hash as bytes
-        let mut hash = hash.as_bytes().to_vec();
+    tokio::spawn(async move {
+        //Create new image parts and parity parts, the parts lost on the way to the server have been reconstructed
+        let (header_message, parts) = match fragment_image(author_uuid.clone(), &image_bytes) {
+            Ok(fragmented_image) => fragmented_image,
+            Err(err) => {
+                tracing::error!("Failed to fragment image: {err}");
 
-        //Append the hash to the bytes
+                return;
+            },
+        };
+
+        let header_bytes = match serde_json::to_string(&header_message) {
+            Ok(header) => header.into_bytes(),
+            Err(err) => {
+                tracing::error!("Failed to serialize image header: {err}");
+
+                return;
+            },
+        };
+
+        let image_parts_tuple: Vec<(String, &[u8])> = header_message
+            .image_parts_hash
+            .iter()
+            .cloned()
+            .zip(parts.iter().map(|part| part.as_slice()))
+            .collect();
+
+        let identificator = header_message.identificator.clone();
+
+        for connected_client in voip.connected_clients.iter() {
+            let socket_addr = *connected_client.value();
+
+            let socket = match socket_addr.is_ipv6() {
+                //The connected client's ip has ipv6 protocol
+                true => voip.socket_v6.clone(),
+                //The connected client's ip has ipv4 protocol
+                false => Some(voip.socket_v4.clone()),
+            };
+
+            let Some(socket) = socket
+            else {
+                continue;
+            };
+
+            //Send the image header, the image parts, then the image header again so that the image can be rebuilt even if the first copy is lost
+            let result = async {
+                send_bytes(
+                    header_bytes.clone(),
+                    &key,
+                    UdpMessageType::ImageHeader,
+                    socket.clone(),
+                    socket_addr,
+                )
+                .await?;
+
+                send_image_parts(
+                    image_parts_tuple.clone(),
+                    author_uuid.clone(),
+                    &key,
+                    identificator.clone(),
+                    socket.clone(),
+                    socket_addr,
+                )
+                .await?;
+
+                send_bytes(
+                    header_bytes.clone(),
+                    &key,
+                    UdpMessageType::ImageHeader,
+                    socket.clone(),
+                    socket_addr,
+                )
+                .await
+            }
+            .await;
+
+            if let Err(err) = result {
+                tracing::error!("Failed to relay image: {err}");
+            }
+        }
+    });
+}
+
+/// Send the images specified in the ```image_parts_tuple``` argument
+/// __Image message contents:__
+/// - ```[len - 64 - 64 - 36..len - 64 - 36]``` = Contains the hash (sha256 hash) of the image part we are sending
+/// - ```[len - 64 - 36.. len - 64]``` = Contains the UUID of the author who has sent the message
+/// - ```[..len - 64 - 64 - 36]``` = Contains the image part we are sending (Encoded video frame)
+/// - ```[len - 64..]``` = Contains the identificator of the part we are sending
+/// - **The hash length is 64 bytes.**
+/// - **The identificator is 64 bytes.**
+/// - **The uuid is 36 bytes.**
+async fn send_image_parts(
+    image_parts_tuple: Vec<(String, &[u8])>,
+    uuid: String,
+    encryption_key: &[u8],
+    identificator: String,
+    socket: Arc<UdpSocket>,
+    send_to: SocketAddr,
+) -> Result<(), Error>
+{
+    for (hash, bytes) in image_parts_tuple {
+        //Hash as bytes
+        let mut hash = hash.as_bytes().to_vec();
+
+        //Append the hash to the bytes
         let mut bytes = bytes.to_vec();
 
         //Append hash
@@ -709,10 +1388,74 @@ impl MessageService
 
         let req: ClientMessage = req_result.unwrap();
 
+        //Every message proves that the client is still alive, not only the heartbeats
+        self.client_last_activity
+            .insert(req.uuid.clone(), Instant::now());
+
+        //The message has already been stored, the client has sent it again because it hasnt received the acknowledgement
+        if let Some(message_id) = &req.message_id {
+            if self
+                .delivered_message_ids
+                .get(&req.uuid)
+                .is_some_and(|message_ids| message_ids.contains(message_id))
+            {
+                send_message_to_client(
+                    &mut *client_handle.lock().await,
+                    encrypt_aes256(
+                        serde_json::to_string(&ServerReplyType::Acknowledgement(
+                            message_id.clone(),
+                        ))?,
+                        &self.decryption_key,
+                    )?,
+                )
+                .await?;
+
+                return Ok(());
+            }
+        }
+
+        //The read only clients can only react to the messages
+        if matches!(
+            &req.message_type,
+            NormalMessage(_) | FileUpload(_) | MessageEdit(_)
+        ) && self
+            .invited_clients
+            .get(&req.uuid)
+            .is_some_and(|(_, role)| *role == ClientRole::ReadOnly)
+        {
+            //The message is removed from the client's outbox
+            if let Some(message_id) = &req.message_id {
+                send_message_to_client(
+                    &mut *client_handle.lock().await,
+                    encrypt_aes256(
+                        serde_json::to_string(&ServerReplyType::Rejected(
+                            message_id.clone(),
+                            "You can only read the messages on this server.".to_string(),
+                        ))?,
+                        &self.decryption_key,
+                    )?,
+                )
+                .await?;
+            }
+
+            return Ok(());
+        }
+
         //If its a Client reaction or a message edit we shouldnt allocate more MessageReactions, since those are not actual messages
         //HOWEVER, if their client connection or disconnection messages a reaction should be allocated because people can react to those
         if !(matches!(&req.message_type, ClientReaction(_))
             || matches!(&req.message_type, MessageEdit(_))
+            || matches!(&req.message_type, Heartbeat(_))
+            || matches!(&req.message_type, ProfileUpdate(_, _))
+            //Only the missed calls are displayed as messages, the rest of the invitation requests are only sent to the clients involved
+            || matches!(
+                &req.message_type,
+                VoipConnection(super::backend::ClientVoipRequest::Invite(_, _))
+                    | VoipConnection(super::backend::ClientVoipRequest::AnswerInvitation(
+                        _,
+                        InvitationAnswer::Accepted | InvitationAnswer::Declined
+                    ))
+            )
             || {
                 if let ClientMessageType::SyncMessage(sync_msg) = &req.message_type {
                     //If this is true (if sync_attribute is none) that means the client is syncing its last seen message index, thefor we shouldnt allocate a new reaction
@@ -737,11 +1480,13 @@ impl MessageService
         }
 
         if let ClientMessageType::SyncMessage(sync_msg) = &req.message_type {
-            if &sync_msg.password == self.passw.trim() {
+            if &sync_msg.password == self.passw.trim()
+                || self.authenticate_with_invite(&req.uuid, sync_msg).await
+            {
                 //Handle incoming connections and disconnections, if sync_attr is a None then its just a message for syncing
                 if let Some(sync_attr) = &sync_msg.sync_attribute {
                     match sync_attr {
-                        ConnectionType::Connect(profile) => {
+                        ConnectionType::Connect(profile, profile_pictures) => {
                             //Check if user has been banned
                             if self
                                 .shared_fields
@@ -762,22 +1507,58 @@ impl MessageService
                                 return Err(Error::msg("Client has been banned!"));
                             }
                             else {
+                                if let Err(err) =
+                                    self.store_profile_pictures(profile, profile_pictures)
+                                {
+                                    send_message_to_client(
+                                        &mut *client_handle.lock().await,
+                                        "Invalid profile!".to_string(),
+                                    )
+                                    .await?;
+
+                                    return Err(err.context("Client has sent an invalid profile!"));
+                                }
+
                                 let mut clients = self.connected_clients.lock().await;
 
                                 //Check if the client has already been connected once
-                                for client in clients.iter() {
+                                if let Some(client) =
+                                    clients.iter_mut().find(|client| client.uuid == req.uuid)
+                                {
                                     //If found, then the client is already connected
-                                    if client.uuid == req.uuid {
-                                        //This can only happen if the connection closed unexpectedly (If the client was stopped unexpectedly)
-                                        send_message_to_client(
-                                            &mut *client_handle.lock().await,
-                                            hex::encode(self.decryption_key),
+                                    //This can only happen if the connection closed unexpectedly (If the client was stopped unexpectedly)
+                                    //The client is reconnecting before the old connection has timed out, so we replace the old connection's handle
+                                    client.handle = Some(client_handle.clone());
+
+                                    drop(clients);
+
+                                    //The stored profile is kept, so the pictures sent with this connection might not be used
+                                    self.evict_unused_profile_pictures(
+                                        &*self.connected_clients_profile.lock().await,
+                                    );
+
+                                    //There is no connection message displayed, so the reaction allocated for it has to be removed
+                                    self.reactions.lock().await.pop();
+
+                                    send_message_to_client(
+                                        &mut *client_handle.lock().await,
+                                        hex::encode(self.decryption_key),
+                                    )
+                                    .await?;
+
+                                    //Send the messages the client has missed
+                                    send_message_to_client(
+                                        &mut *client_handle.lock().await,
+                                        self.full_sync_client(
+                                            sync_msg.client_message_counter,
+                                            sync_msg.server_instance_id.as_deref(),
                                         )
-                                        .await?;
+                                        .await?,
+                                    )
+                                    .await?;
 
-                                        //If found return, and end execution
-                                        return Ok(());
-                                    }
+                                    //If found return, and end execution
+                                    return Ok(());
                                 }
 
                                 //When spawning a client reader, we should announce it to the whole chat group (Adding a Server(UserConnect) enum to the messages list)
@@ -812,10 +1593,15 @@ impl MessageService
                                 ));
 
                                 //Store connected client's profile
-                                self.connected_clients_profile
-                                    .lock()
-                                    .await
-                                    .insert(req.uuid, profile.clone());
+                                let mut connected_clients_profile =
+                                    self.connected_clients_profile.lock().await;
+
+                                connected_clients_profile.insert(req.uuid, profile.clone());
+
+                                //The client might have connected before with different pictures
+                                self.evict_unused_profile_pictures(&connected_clients_profile);
+
+                                drop(connected_clients_profile);
 
                                 //Return custom key which the server's text will be encrypted with
                                 send_message_to_client(
@@ -827,7 +1613,11 @@ impl MessageService
                                 //Sync all messages, send all of the messages to the client, because we have already provided the decryption key
                                 send_message_to_client(
                                     &mut *client_handle.try_lock()?,
-                                    self.full_sync_client().await?,
+                                    self.full_sync_client(
+                                        sync_msg.client_message_counter,
+                                        sync_msg.server_instance_id.as_deref(),
+                                    )
+                                    .await?,
                                 )
                                 .await?;
                                 return Ok(());
@@ -844,6 +1634,8 @@ impl MessageService
                                         .handle_server_disconnect(client, &mut clients, index)
                                         .await?;
 
+                                    self.client_last_activity.remove(&req.uuid);
+
                                     sync_message_with_clients(
                                         Arc::new(tokio::sync::Mutex::new(clients.clone())),
                                         self.clients_last_seen_index.clone(),
@@ -860,8 +1652,17 @@ impl MessageService
                 }
             }
             else {
-                send_message_to_client(&mut *client_handle.try_lock()?, "Invalid Password!".into())
-                    .await?;
+                //The clients joining with an invite may not know the password at all, the clients whose invite has been revoked are told the same
+                let reply = if sync_msg.invite_token.is_some()
+                    || self.invited_clients.contains_key(&req.uuid)
+                {
+                    "Invalid invite!"
+                }
+                else {
+                    "Invalid Password!"
+                };
+
+                send_message_to_client(&mut *client_handle.try_lock()?, reply.into()).await?;
 
                 //return an error so the client listener thread stops
                 return Err(Error::msg("Invalid password entered by client!"));
@@ -896,12 +1697,45 @@ impl MessageService
             .any(|client| client.uuid == req.uuid)
         //Search through the list
         {
+            //The voice room the voip event has happened in, this is set while handling the request
+            let mut voip_event_room = String::new();
+
+            //The uuid of the caller, this is only set if the client has missed a call
+            let mut missed_call_caller = String::new();
+
             match &req.message_type {
                 VoipConnection(request) => {
                     match request {
-                        super::backend::ClientVoipRequest::Connect(port) => {
+                        super::backend::ClientVoipRequest::Connect(port, mixing_mode, room) => {
                             let socket_addr = SocketAddr::new(socket_addr.ip(), *port);
 
+                            //Only the invited clients can join a private room, the room is claimed by the first client connecting to it
+                            if is_private_voice_room(room) {
+                                let allowed_clients = self
+                                    .private_voice_rooms
+                                    .entry(room.clone())
+                                    .or_insert_with(|| HashSet::from([req.uuid.clone()]));
+
+                                if !allowed_clients.contains(&req.uuid) {
+                                    send_message_to_client(
+                                        &mut *client_handle.try_lock()?,
+                                        encrypt_aes256(
+                                            serde_json::to_string(
+                                                &ServerVoipRequest::ConnectionClosed(
+                                                    ServerVoipClose {
+                                                        reason: "You were not invited to this private call.".to_string(),
+                                                    },
+                                                ),
+                                            )?,
+                                            &self.decryption_key,
+                                        )?,
+                                    )
+                                    .await?;
+
+                                    return Ok(());
+                                }
+                            }
+
                             //Send important info to client (Session ID, etc)
                             send_message_to_client(
                                 &mut *client_handle.try_lock()?,
@@ -912,89 +1746,58 @@ impl MessageService
                             )
                             .await?;
 
-                            if let Some(ongoing_call) = &self.voip {
-                                ongoing_call.connect(req.uuid.clone(), socket_addr)?;
-                            }
-                            // If there is no ongoing call, we should create it
-                            else {
-                                let voip_server_instance =
-                                    self.create_voip_server(self.opened_on_port.clone()).await?;
+                            //A client can only be connected to one room at a time
+                            self.leave_voip_room(&req.uuid)?;
 
-                                //Immediately connect the user who has requested the voip call
-                                voip_server_instance.connect(req.uuid.clone(), socket_addr)?;
+                            //The rooms share the sockets of the server, these are created with the first room
+                            let voip_relay = self.get_or_create_voip_relay().await?;
 
-                                //Set voip server
-                                self.voip = Some(voip_server_instance);
+                            // If there is no ongoing call in the room, we should create it
+                            let voip = self.voip_rooms.entry(room.clone()).or_insert_with(|| {
+                                create_voip_room(
+                                    &voip_relay,
+                                    *mixing_mode,
+                                    &self.participant_volumes,
+                                )
+                            });
+
+                            voip.connect(req.uuid.clone(), socket_addr)?;
+
+                            //Create the threads of the room
+                            if voip.threads.is_none() {
+                                //Create mixer thread, if the creator of the call has asked for it
+                                if voip.mixing_mode == VoipMixingMode::Mixed {
+                                    create_voip_mixer(voip.clone(), self.decryption_key);
+                                }
+
+                                voip.threads = Some(());
                             }
 
-                            //We can safely assume its Some(_) here
-                            if let Some(voip) = self.voip.as_mut() {
-                                //Create handler thread
-                                voip.threads.get_or_insert_with(|| {
-                                    //Clone so we can move it into the thread
-                                    let socket_v6 = voip.socket_v6.clone();
-                                    let socket_v4 = voip.socket_v4.clone();
-                                    let connected_clients = voip.connected_client_thread_channels.clone();
-                                    let cancellation_token = voip.thread_cancellation_token.clone();
-
-                                    //Spawn manager thread
-                                    tokio::spawn(async move {
-                                        loop {
-                                            //Create buffer for header, this is the size of the maximum udp packet so no error will appear
-                                            let mut header_buf_v6 = vec![0; 65536];
-                                            let mut header_buf_v4 = vec![0; 65536];
-
-                                            let socket_v6 = socket_v6.clone().unwrap();
-
-                                            //Wait until we get a new message or until the thread token gets cancelled
-                                            select! {
-                                                //Wait until we recive a new message from the ipv6 binding
-                                                _ = socket_v6.peek_from(&mut header_buf_v6) => {
-                                                    send_message_to_connected_client(socket_v6.clone(), connected_clients.clone(), header_buf_v6).await;
-                                                }
-
-                                                //Wait until we recive a new message from the ipv6 binding
-                                                _ = socket_v4.peek_from(&mut header_buf_v4) => {
-                                                    send_message_to_connected_client(socket_v4.clone(), connected_clients.clone(), header_buf_v4).await;
-                                                }
-
-                                                //Wait until the token gets cancelled
-                                                _ = cancellation_token.cancelled() => {
-                                                    //End loop once the token gets cancelled
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    });
-                                });
-
-                                //Search if there is a channel for the handler thread of this connecting SocketAddr
-                                if voip
-                                    .connected_client_thread_channels
-                                    .get(&socket_addr)
-                                    .is_none()
-                                {
-                                    let (sender, receiver) = mpsc::channel::<Vec<u8>>(255);
+                            //Search if there is a channel for the handler thread of this connecting SocketAddr
+                            if voip
+                                .connected_client_thread_channels
+                                .get(&socket_addr)
+                                .is_none()
+                            {
+                                let (sender, receiver) = mpsc::channel::<Vec<u8>>(255);
 
-                                    //Create cancellation token for client
-                                    let client_manager_cancellation_token =
-                                        CancellationToken::new();
+                                //Create cancellation token for client
+                                let client_manager_cancellation_token = CancellationToken::new();
 
-                                    //Create voip manager for client
-                                    create_client_voip_manager(
-                                        voip.clone(),
-                                        client_manager_cancellation_token.clone(),
-                                        self.decryption_key,
-                                        receiver,
-                                        socket_addr,
-                                        req.uuid.clone(),
-                                    );
+                                //Create voip manager for client
+                                create_client_voip_manager(
+                                    voip.clone(),
+                                    client_manager_cancellation_token.clone(),
+                                    self.decryption_key,
+                                    receiver,
+                                    socket_addr,
+                                    req.uuid.clone(),
+                                );
 
-                                    voip.connected_client_thread_channels.insert(
-                                        socket_addr,
-                                        (Arc::new(sender), client_manager_cancellation_token),
-                                    );
-                                }
+                                voip.connected_client_thread_channels.insert(
+                                    socket_addr,
+                                    (Arc::new(sender), client_manager_cancellation_token),
+                                );
                             }
 
                             //Sync connected users with all users
@@ -1003,17 +1806,7 @@ impl MessageService
                                 self.clients_last_seen_index.clone(),
                                 ServerOutput {
                                     replying_to: None,
-                                    message_type: ServerMessageType::VoipState(ServerVoipState {
-                                        connected_clients: Some(
-                                            self.voip
-                                                .as_ref()
-                                                .unwrap()
-                                                .connected_clients
-                                                .iter()
-                                                .map(|f| f.key().clone())
-                                                .collect(),
-                                        ),
-                                    }),
+                                    message_type: ServerMessageType::VoipState(self.voip_state()),
                                     message_date: {
                                         Utc::now().format("%Y.%m.%d. %H:%M").to_string()
                                     },
@@ -1032,84 +1825,207 @@ impl MessageService
                             .await?;
                         },
                         super::backend::ClientVoipRequest::Disconnect => {
-                            if let Some(ongoing_voip) = self.voip.clone() {
-                                //Get who disconnected
-                                let connected_client =
-                                    ongoing_voip.connected_clients.get(&req.uuid).ok_or_else(
-                                        || Error::msg("Connected client not found based on UUID"),
-                                    )?;
-
-                                let socket_addr = connected_client.value();
-
-                                let client_manager_thread = ongoing_voip.connected_client_thread_channels.get(socket_addr).ok_or_else(|| Error::msg("Client not found in connected client list based on SocketAddr"))?;
+                            match self.leave_voip_room(&req.uuid)? {
+                                Some(room) => {
+                                    voip_event_room = room;
 
-                                //Cancel client manager thread
-                                client_manager_thread.1.cancel();
-
-                                //Make sure to drop the reference so we will not deadlock upon calling ```voip.disconnect```
-                                drop(connected_client);
-                                drop(client_manager_thread);
-
-                                //Blocks here
-                                ongoing_voip.disconnect(req.uuid.clone())?;
-
-                                if ongoing_voip.connected_clients.is_empty() {
-                                    //If the voip has no connected clients we can shut down the whole service
-                                    ongoing_voip.thread_cancellation_token.cancel();
-
-                                    //Reset voip's state
-                                    self.voip = None;
-                                }
-
-                                sync_message_with_clients(
-                                    self.connected_clients.clone(),
-                                    self.clients_last_seen_index.clone(),
-                                    ServerOutput {
-                                        replying_to: None,
-                                        message_type: ServerMessageType::VoipState(
-                                            ServerVoipState {
-                                                connected_clients: {
-                                                    //Match server Voip state
-                                                    self.voip.as_ref().map(|server_voip| {
-                                                        server_voip
-                                                            .connected_clients
-                                                            .iter()
-                                                            .map(|entry| entry.key().clone())
-                                                            .collect()
-                                                    })
-                                                },
+                                    sync_message_with_clients(
+                                        self.connected_clients.clone(),
+                                        self.clients_last_seen_index.clone(),
+                                        ServerOutput {
+                                            replying_to: None,
+                                            message_type: ServerMessageType::VoipState(
+                                                self.voip_state(),
+                                            ),
+                                            message_date: {
+                                                Utc::now().format("%Y.%m.%d. %H:%M").to_string()
                                             },
-                                        ),
-                                        message_date: {
-                                            Utc::now().format("%Y.%m.%d. %H:%M").to_string()
+                                            uuid: req.uuid.clone(),
+                                            author: String::new(),
                                         },
-                                        uuid: req.uuid.clone(),
-                                        author: String::new(),
-                                    },
-                                    self.decryption_key,
-                                )
-                                .await?;
-                            }
-                            else {
-                                tracing::error!("Voip disconnected from an offline server")
+                                        self.decryption_key,
+                                    )
+                                    .await?;
+                                },
+                                None => {
+                                    tracing::error!("Voip disconnected from an offline server")
+                                },
                             }
                         },
                         super::backend::ClientVoipRequest::ImageConnected => {
-                            if let Some(voip) = &mut self.voip {
+                            if let Some((room, voip)) = self.voip_room_of(&req.uuid) {
                                 voip.image_buffer.insert(req.uuid.clone(), IndexMap::new());
+
+                                voip_event_room = room.clone();
                             }
                             else {
                                 tracing::error!("Voip image connected to an offline server");
                             }
                         },
                         super::backend::ClientVoipRequest::ImageDisconnected => {
-                            if let Some(voip) = &mut self.voip {
+                            if let Some((room, voip)) = self.voip_room_of(&req.uuid) {
                                 voip.image_buffer.remove(&req.uuid);
+
+                                voip_event_room = room.clone();
                             }
                             else {
                                 tracing::error!("Voip image connected to an offline server");
                             }
                         },
+                        //The recording itself is done by the client, the server only keeps track of it so that the clients connecting later also display the indicator
+                        //The event is sent to every client after the request has been handled
+                        super::backend::ClientVoipRequest::RecordingStarted => {
+                            if let Some((room, voip)) = self.voip_room_of(&req.uuid) {
+                                voip.recording_clients.insert(req.uuid.clone());
+
+                                voip_event_room = room.clone();
+                            }
+                            else {
+                                tracing::error!("Voip recording started on an offline server");
+                            }
+                        },
+                        super::backend::ClientVoipRequest::RecordingStopped => {
+                            if let Some((room, voip)) = self.voip_room_of(&req.uuid) {
+                                voip.recording_clients.remove(&req.uuid);
+
+                                voip_event_room = room.clone();
+                            }
+                            else {
+                                tracing::error!("Voip recording stopped on an offline server");
+                            }
+                        },
+                        //The invitation is only sent to the invited client, it is not broadcasted
+                        super::backend::ClientVoipRequest::Invite(invitee, room) => {
+                            let fail_reason =
+                                if !is_private_voice_room(room) {
+                                    Some("Only private calls can be invited to.")
+                                }
+                                else if *invitee == req.uuid {
+                                    Some("You cannot invite yourself to a call.")
+                                }
+                                //The invitation may arrive before the caller has connected to the room, thus the room may not exist yet
+                                else if self.private_voice_rooms.get(room).is_some_and(
+                                    |allowed_clients| !allowed_clients.contains(&req.uuid),
+                                ) {
+                                    Some("You are not a participant of this private call.")
+                                }
+                                else if !self
+                                    .send_voip_request_to_client(
+                                        invitee,
+                                        ServerVoipRequest::Invitation(ServerCallInvitation {
+                                            caller: req.uuid.clone(),
+                                            room: room.clone(),
+                                        }),
+                                    )
+                                    .await?
+                                {
+                                    Some("The invited user is not connected to the server.")
+                                }
+                                else {
+                                    None
+                                };
+
+                            match fail_reason {
+                                Some(reason) => {
+                                    send_message_to_client(
+                                        &mut *client_handle.try_lock()?,
+                                        encrypt_aes256(
+                                            serde_json::to_string(&ServerVoipReply::Fail(
+                                                ServerVoipClose {
+                                                    reason: reason.to_string(),
+                                                },
+                                            ))?,
+                                            &self.decryption_key,
+                                        )?,
+                                    )
+                                    .await?;
+                                },
+                                None => {
+                                    let allowed_clients =
+                                        self.private_voice_rooms.entry(room.clone()).or_default();
+
+                                    allowed_clients.insert(req.uuid.clone());
+                                    allowed_clients.insert(invitee.clone());
+
+                                    self.pending_call_invitations.insert(
+                                        room.clone(),
+                                        PendingCallInvitation {
+                                            caller: req.uuid.clone(),
+                                            invitee: invitee.clone(),
+                                            sent_at: Instant::now(),
+                                            cancelled: false,
+                                        },
+                                    );
+                                },
+                            }
+
+                            return Ok(());
+                        },
+                        //The volumes are only used by the mixer, they are not broadcasted
+                        super::backend::ClientVoipRequest::ParticipantVolume(
+                            participant,
+                            volume,
+                        ) => {
+                            //Only the volumes of the existing clients are stored, so that a client cant fill up the memory of the server
+                            if volume.is_finite()
+                                && self
+                                    .connected_clients_profile
+                                    .lock()
+                                    .await
+                                    .contains_key(participant)
+                            {
+                                self.participant_volumes
+                                    .entry(req.uuid.clone())
+                                    .or_default()
+                                    .insert(participant.clone(), volume.clamp(0., 2.));
+                            }
+
+                            return Ok(());
+                        },
+                        super::backend::ClientVoipRequest::AnswerInvitation(room, answer) => {
+                            //Check if the client was really invited to the room
+                            if !self
+                                .pending_call_invitations
+                                .get(room)
+                                .is_some_and(|invitation| invitation.invitee == req.uuid)
+                            {
+                                tracing::error!("Answered a call invitation which doesnt exist");
+
+                                return Ok(());
+                            }
+
+                            let Some(PendingCallInvitation { caller, .. }) =
+                                self.pending_call_invitations.remove(room)
+                            else {
+                                return Ok(());
+                            };
+
+                            if *answer != InvitationAnswer::Accepted {
+                                if let Some(allowed_clients) =
+                                    self.private_voice_rooms.get_mut(room)
+                                {
+                                    allowed_clients.remove(&req.uuid);
+                                }
+                            }
+
+                            //Notify the caller about the answer
+                            self.send_voip_request_to_client(
+                                &caller,
+                                ServerVoipRequest::InvitationAnswered(
+                                    room.clone(),
+                                    req.uuid.clone(),
+                                    *answer,
+                                ),
+                            )
+                            .await?;
+
+                            //Only the missed calls are broadcasted
+                            if *answer != InvitationAnswer::Missed {
+                                return Ok(());
+                            }
+
+                            missed_call_caller = caller;
+                        },
                     }
                 },
 
@@ -1120,14 +2036,26 @@ impl MessageService
                 },
 
                 FileRequestType(request_type) => {
+                    if let Some(reply) = self.handle_request(request_type).await? {
+                        send_message_to_client(
+                            &mut *client_handle.try_lock()?,
+                            //Encrypt the request reply
+                            encrypt_aes256(reply, &self.decryption_key).unwrap(),
+                        )
+                        .await?;
+                    }
+
+                    return Ok(());
+                },
+
+                //Heartbeats are answered only to the client, with the same sequence number
+                Heartbeat(sequence_number) => {
                     send_message_to_client(
                         &mut *client_handle.try_lock()?,
-                        //Encrypt the request reply
                         encrypt_aes256(
-                            self.handle_request(request_type).await?,
+                            serde_json::to_string(&ServerReplyType::Heartbeat(*sequence_number))?,
                             &self.decryption_key,
-                        )
-                        .unwrap(),
+                        )?,
                     )
                     .await?;
 
@@ -1142,6 +2070,18 @@ impl MessageService
                     self.handle_reaction(reaction, &req).await;
                 },
 
+                ProfileUpdate(profile, profile_pictures) => {
+                    //Invalid profiles are not broadcasted, the client has already checked its own profile so this can only happen with a modified client
+                    if let Err(err) = self
+                        .update_client_profile(&req.uuid, profile, profile_pictures)
+                        .await
+                    {
+                        tracing::error!("Refused the profile update of {}: {err}", req.uuid);
+
+                        return Ok(());
+                    }
+                },
+
                 MessageEdit(edit) => {
                     match &mut self.messages.try_lock() {
                         Ok(messages_vec) => {
@@ -1175,90 +2115,405 @@ impl MessageService
             //We return the syncing function because after we have handled the request we return back the updated messages, which already contain the "side effects" of the client request
             //Please rework this, we should always be sending the latest message to all the clients so we are kept in sync, we only send all of them when we are connecting
             //We should send the incoming message to all of the clients, we are already storing the messages in self.messages
+            let mut server_output = ServerOutput::convert_clientmsg_to_servermsg(
+                req.clone(),
+                //Server file indexing, this is used as a handle for the client to ask files from the server
+                match &req.message_type {
+                    VoipConnection(_) => String::new(),
+
+                    //This is unreachable, as requests are handled elsewhere
+                    FileRequestType(_) => unreachable!(),
+
+                    //This is unreachable, as heartbeats are answered elsewhere
+                    Heartbeat(_) => unreachable!(),
+
+                    FileUpload(inner) => sha256::digest(&inner.bytes),
+
+                    //Some message types may not have a signature, they arent requested the same way as files
+                    NormalMessage(_) => String::new(),
+
+                    //Some message types may not have a signature, they arent requested the same way as files
+                    SyncMessage(_) => String::new(),
+
+                    //Some message types may not have a signature, they arent requested the same way as files
+                    ClientReaction(_) => String::new(),
+
+                    //Some message types may not have a signature, they arent requested the same way as files
+                    MessageEdit(_) => String::new(),
+
+                    //Some message types may not have a signature, they arent requested the same way as files
+                    ProfileUpdate(_, _) => String::new(),
+                },
+                //Get message type
+                match &req.message_type {
+                    FileRequestType(_) => unreachable!(),
+                    Heartbeat(_) => unreachable!(),
+                    FileUpload(inner) => {
+                        //We should match the upload type more specifically
+                        match inner.extension.clone().unwrap_or_default().as_str() {
+                            "png" | "jpeg" | "bmp" | "tiff" | "webp" | "gif" | "jpg" => Image,
+                            "wav" | "mp3" | "m4a" => Audio,
+                            _ => Upload,
+                        }
+                    },
+                    NormalMessage(_) => Normal,
+                    SyncMessage(_) => Sync,
+                    ClientReaction(_) => ServerMessageTypeDiscriminantReaction,
+                    MessageEdit(_) => Edit,
+                    ProfileUpdate(_, _) => ServerMessageTypeDiscriminantProfileUpdate,
+                    VoipConnection(_) => Voip,
+                },
+                req.uuid.clone(),
+                self.connected_clients_profile
+                    .lock()
+                    .await
+                    .get(&req.uuid)
+                    .unwrap()
+                    .clone()
+                    .username,
+            );
+
+            //The clients only send the room of the event when connecting, the rest of the events happen in the room they are connected to
+            if let ServerMessageType::VoipEvent(server_voip_event) = &mut server_output.message_type
+            {
+                if !voip_event_room.is_empty() {
+                    server_voip_event.room = voip_event_room;
+                }
+
+                if let VoipEvent::MissedCall(caller) = &mut server_voip_event.event {
+                    *caller = missed_call_caller;
+                }
+            }
+
             sync_message_with_clients(
                 self.connected_clients.clone(),
                 self.clients_last_seen_index.clone(),
-                ServerOutput::convert_clientmsg_to_servermsg(
-                    req.clone(),
-                    //Server file indexing, this is used as a handle for the client to ask files from the server
-                    match &req.message_type {
-                        VoipConnection(_) => String::new(),
+                server_output,
+                self.decryption_key,
+            )
+            .await
+            .expect("Syncing failed");
+
+            //Let the client know that the message has been stored, so it can be removed from its outbox
+            if let Some(message_id) = req.message_id {
+                let delivered_message_ids = self
+                    .delivered_message_ids
+                    .entry(req.uuid.clone())
+                    .or_default();
+
+                delivered_message_ids.push_back(message_id.clone());
+
+                //Forget the oldest ids, so the memory usage doesnt grow with every message
+                if delivered_message_ids.len() > DELIVERED_MESSAGE_ID_WINDOW {
+                    delivered_message_ids.pop_front();
+                }
+
+                send_message_to_client(
+                    &mut *client_handle.lock().await,
+                    encrypt_aes256(
+                        serde_json::to_string(&ServerReplyType::Acknowledgement(message_id))?,
+                        &self.decryption_key,
+                    )?,
+                )
+                .await?;
+            }
+
+            Ok(())
+        }
+        else {
+            send_message_to_client(&mut *client_handle.try_lock()?, "Invalid Password!".into())
+                .await?;
+
+            Err(Error::msg("Invalid password entered by client!"))
+        }
+    }
+
+    /// Returns the ```ServerVoipRelay``` shared by the voice rooms
+    /// If there are no voice rooms yet, the sockets are bound and the listener thread is spawned
+    async fn get_or_create_voip_relay(&mut self) -> anyhow::Result<ServerVoipRelay>
+    {
+        if let Some(voip_relay) = &self.voip_relay {
+            return Ok(voip_relay.clone());
+        }
+
+        // Create sockets
+        let socket_v4 = UdpSocket::bind(format!("0.0.0.0:{}", self.opened_on_port)).await?;
+        let socket_v6: Option<UdpSocket> = UdpSocket::bind(format!("[::]:{}", self.opened_on_port))
+            .await
+            .ok();
+
+        let voip_relay = ServerVoipRelay {
+            socket_v6: { socket_v6.map(Arc::new) },
+            socket_v4: Arc::new(socket_v4),
+            connected_client_thread_channels: Arc::new(DashMap::new()),
+            thread_cancellation_token: CancellationToken::new(),
+        };
+
+        //Clone so we can move it into the thread
+        let socket_v6 = voip_relay.socket_v6.clone();
+        let socket_v4 = voip_relay.socket_v4.clone();
+        let connected_clients = voip_relay.connected_client_thread_channels.clone();
+        let cancellation_token = voip_relay.thread_cancellation_token.clone();
+
+        //Spawn manager thread, this hands the packets to the client manager thread of their sender regardless of the room they are in
+        tokio::spawn(async move {
+            loop {
+                //Create buffer for header, this is the size of the maximum udp packet so no error will appear
+                let mut header_buf_v6 = vec![0; 65536];
+                let mut header_buf_v4 = vec![0; 65536];
+
+                let socket_v6 = socket_v6.clone().unwrap();
+
+                //Wait until we get a new message or until the thread token gets cancelled
+                select! {
+                    //Wait until we recive a new message from the ipv6 binding
+                    _ = socket_v6.peek_from(&mut header_buf_v6) => {
+                        send_message_to_connected_client(socket_v6.clone(), connected_clients.clone(), header_buf_v6).await;
+                    }
+
+                    //Wait until we recive a new message from the ipv6 binding
+                    _ = socket_v4.peek_from(&mut header_buf_v4) => {
+                        send_message_to_connected_client(socket_v4.clone(), connected_clients.clone(), header_buf_v4).await;
+                    }
+
+                    //Wait until the token gets cancelled
+                    _ = cancellation_token.cancelled() => {
+                        //End loop once the token gets cancelled
+                        break;
+                    }
+                }
+            }
+        });
+
+        self.voip_relay = Some(voip_relay.clone());
+
+        Ok(voip_relay)
+    }
+
+    /// Disconnects the client from the voice room they are connected to, the room is closed if there are no clients left in it
+    /// Returns the name of the room the client has left, or ```None``` if they werent connected to any of the rooms
+    fn leave_voip_room(&mut self, uuid: &str) -> anyhow::Result<Option<String>>
+    {
+        let Some((room, ongoing_voip)) = self
+            .voip_room_of(uuid)
+            .map(|(room, voip)| (room.clone(), voip.clone()))
+        else {
+            return Ok(None);
+        };
+
+        //Get who disconnected
+        let connected_client = ongoing_voip
+            .connected_clients
+            .get(uuid)
+            .ok_or_else(|| Error::msg("Connected client not found based on UUID"))?;
+
+        let socket_addr = connected_client.value();
+
+        let client_manager_thread = ongoing_voip
+            .connected_client_thread_channels
+            .get(socket_addr)
+            .ok_or_else(|| {
+                Error::msg("Client not found in connected client list based on SocketAddr")
+            })?;
+
+        //Cancel client manager thread
+        client_manager_thread.1.cancel();
+
+        //Make sure to drop the reference so we will not deadlock upon calling ```voip.disconnect```
+        drop(connected_client);
+        drop(client_manager_thread);
+
+        //Blocks here
+        ongoing_voip.disconnect(uuid.to_string())?;
+
+        if ongoing_voip.connected_clients.is_empty() {
+            //If the room has no connected clients we can shut down its threads
+            ongoing_voip.thread_cancellation_token.cancel();
+
+            self.voip_rooms.remove(&room);
+
+            //A private room cannot be joined once it has been closed
+            self.private_voice_rooms.remove(&room);
+        }
+
+        //If the caller has left before the invitation was answered, the invitation is cancelled
+        //The ringing window of the invited client is closed right away, the sync thread reports the invitation as a missed call
+        if let Some(invitation) = self
+            .pending_call_invitations
+            .get_mut(&room)
+            .filter(|invitation| invitation.caller == uuid)
+        {
+            invitation.cancelled = true;
+
+            //The cancellation is sent from a separate task, as this function isnt async
+            let connected_clients = self.connected_clients.clone();
+            let decryption_key = self.decryption_key;
+            let invitee = invitation.invitee.clone();
+            let cancelled_room = room.clone();
+
+            tokio::spawn(async move {
+                if let Err(err) = send_voip_request(
+                    connected_clients,
+                    &invitee,
+                    ServerVoipRequest::InvitationCancelled(cancelled_room),
+                    decryption_key,
+                )
+                .await
+                {
+                    tracing::error!("{err}");
+                }
+            });
+        }
+
+        //If there are no rooms left we can shut down the whole service
+        if self.voip_rooms.is_empty() {
+            if let Some(voip_relay) = self.voip_relay.take() {
+                voip_relay.thread_cancellation_token.cancel();
+            }
+        }
+
+        Ok(Some(room))
+    }
+
+    /// Reports the call invitations as missed calls, which havent been answered in ```INVITATION_TIMEOUT``` or have been cancelled by the caller
+    /// The invitations are missed too if the caller or the invited client has disconnected since
+    async fn expire_call_invitations(&mut self) -> anyhow::Result<()>
+    {
+        let connected_clients: HashSet<String> = self
+            .connected_clients
+            .lock()
+            .await
+            .iter()
+            .map(|client| client.uuid.clone())
+            .collect();
+
+        let expired_rooms: Vec<String> = self
+            .pending_call_invitations
+            .iter()
+            .filter(|(_, invitation)| {
+                invitation.cancelled
+                    || invitation.sent_at.elapsed() >= INVITATION_TIMEOUT
+                    || !connected_clients.contains(&invitation.caller)
+                    || !connected_clients.contains(&invitation.invitee)
+            })
+            .map(|(room, _)| room.clone())
+            .collect();
+
+        for room in expired_rooms {
+            self.report_missed_call(&room).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the pending invitation of the room, and broadcasts it as a missed call
+    /// The ringing window of the invited client is closed, and the caller is notified
+    async fn report_missed_call(&mut self, room: &str) -> anyhow::Result<()>
+    {
+        let Some(invitation) = self.pending_call_invitations.remove(room)
+        else {
+            return Ok(());
+        };
+
+        //The invited client cant join the call anymore, if the caller isnt in the private room it is closed
+        if self.voip_rooms.contains_key(room) {
+            if let Some(allowed_clients) = self.private_voice_rooms.get_mut(room) {
+                allowed_clients.remove(&invitation.invitee);
+            }
+        }
+        else {
+            self.private_voice_rooms.remove(room);
+        }
 
-                        //This is unreachable, as requests are handled elsewhere
-                        FileRequestType(_) => unreachable!(),
+        self.send_voip_request_to_client(
+            &invitation.invitee,
+            ServerVoipRequest::InvitationCancelled(room.to_string()),
+        )
+        .await?;
 
-                        FileUpload(inner) => sha256::digest(&inner.bytes),
+        self.send_voip_request_to_client(
+            &invitation.caller,
+            ServerVoipRequest::InvitationAnswered(
+                room.to_string(),
+                invitation.invitee.clone(),
+                InvitationAnswer::Missed,
+            ),
+        )
+        .await?;
 
-                        //Some message types may not have a signature, they arent requested the same way as files
-                        NormalMessage(_) => String::new(),
+        let username = self
+            .connected_clients_profile
+            .lock()
+            .await
+            .get(&invitation.invitee)
+            .map(|profile| profile.username.clone())
+            .unwrap_or_default();
 
-                        //Some message types may not have a signature, they arent requested the same way as files
-                        SyncMessage(_) => String::new(),
+        let server_msg = ServerOutput {
+            replying_to: None,
+            message_type: ServerMessageType::VoipEvent(ServerVoipEvent {
+                uuid: invitation.invitee.clone(),
+                event: VoipEvent::MissedCall(invitation.caller),
+                room: room.to_string(),
+            }),
+            author: username,
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            uuid: invitation.invitee,
+        };
 
-                        //Some message types may not have a signature, they arent requested the same way as files
-                        ClientReaction(_) => String::new(),
+        //The missed call can be reacted to, just like the other messages
+        self.reactions.lock().await.push(MessageReaction {
+            message_reactions: Vec::new(),
+        });
 
-                        //Some message types may not have a signature, they arent requested the same way as files
-                        MessageEdit(_) => String::new(),
-                    },
-                    //Get message type
-                    match &req.message_type {
-                        FileRequestType(_) => unreachable!(),
-                        FileUpload(inner) => {
-                            //We should match the upload type more specifically
-                            match inner.extension.clone().unwrap_or_default().as_str() {
-                                "png" | "jpeg" | "bmp" | "tiff" | "webp" | "gif" | "jpg" => Image,
-                                "wav" | "mp3" | "m4a" => Audio,
-                                _ => Upload,
-                            }
-                        },
-                        NormalMessage(_) => Normal,
-                        SyncMessage(_) => Sync,
-                        ClientReaction(_) => ServerMessageTypeDiscriminantReaction,
-                        MessageEdit(_) => Edit,
-                        VoipConnection(_) => Voip,
-                    },
-                    req.uuid.clone(),
-                    self.connected_clients_profile
-                        .lock()
-                        .await
-                        .get(&req.uuid)
-                        .unwrap()
-                        .clone()
-                        .username,
-                ),
-                self.decryption_key,
-            )
-            .await
-            .expect("Syncing failed");
+        self.messages.lock().await.push(server_msg.clone());
 
-            Ok(())
-        }
-        else {
-            send_message_to_client(&mut *client_handle.try_lock()?, "Invalid Password!".into())
-                .await?;
+        sync_message_with_clients(
+            self.connected_clients.clone(),
+            self.clients_last_seen_index.clone(),
+            server_msg,
+            self.decryption_key,
+        )
+        .await?;
 
-            Err(Error::msg("Invalid password entered by client!"))
-        }
+        Ok(())
+    }
+
+    /// Sends a ```ServerVoipRequest``` only to the specified client
+    /// Returns whether the client was connected to the server
+    async fn send_voip_request_to_client(
+        &self,
+        uuid: &str,
+        request: ServerVoipRequest,
+    ) -> anyhow::Result<bool>
+    {
+        send_voip_request(
+            self.connected_clients.clone(),
+            uuid,
+            request,
+            self.decryption_key,
+        )
+        .await
     }
 
-    async fn create_voip_server(&self, port: String) -> anyhow::Result<ServerVoip>
+    /// Returns the name and the ```ServerVoip``` of the voice room the client is connected to
+    fn voip_room_of(&self, uuid: &str) -> Option<(&String, &ServerVoip)>
     {
-        // Create sockets
-        let socket_v4 = UdpSocket::bind(format!("0.0.0.0:{port}")).await?;
-        let socket_v6: Option<UdpSocket> = UdpSocket::bind(format!("[::]:{port}")).await.ok();
+        self.voip_rooms
+            .iter()
+            .find(|(_, voip)| voip.connected_clients.contains_key(uuid))
+    }
 
-        //Return ServerVoip
-        Ok(ServerVoip {
-            connected_clients: Arc::new(DashMap::new()),
-            _established_since: Utc::now(),
-            socket_v6: { socket_v6.map(Arc::new) },
-            socket_v4: Arc::new(socket_v4),
-            thread_cancellation_token: CancellationToken::new(),
-            threads: None,
-            connected_client_thread_channels: Arc::new(DashMap::new()),
-            image_buffer: Arc::new(DashMap::new()),
-        })
+    /// Returns the state of the voice rooms, which is displayed by the clients
+    fn voip_state(&self) -> ServerVoipState
+    {
+        ServerVoipState {
+            rooms: self
+                .voip_rooms
+                .iter()
+                .map(|(room, voip)| (room.clone(), voip.state()))
+                .collect(),
+        }
     }
 
     async fn handle_server_disconnect(
@@ -1296,6 +2551,143 @@ impl MessageService
         Ok(server_msg)
     }
 
+    /// Disconnects a client which hasnt sent a heartbeat for ```HEARTBEAT_TIMEOUT```, and notifies the other clients
+    /// The client's connection has probably died, so we dont try to notify the client itself
+    async fn handle_client_timeout(&mut self, uuid: &str) -> anyhow::Result<()>
+    {
+        self.client_last_activity.remove(uuid);
+
+        let client = {
+            let mut clients = self.connected_clients.lock().await;
+
+            let Some(index) = clients.iter().position(|client| client.uuid == uuid)
+            else {
+                return Ok(());
+            };
+
+            clients.remove(index)
+        };
+
+        //Shutdown the connection, this may hang if the client's connection has died, so its done in a separate task
+        if let Some(client_handle) = client.handle {
+            tokio::spawn(async move {
+                if let Ok(mut client_handle) = client_handle.try_lock() {
+                    let _ = tokio::time::timeout(Duration::from_secs(1), client_handle.shutdown())
+                        .await;
+                }
+            });
+        }
+
+        //The client cant stay in the voice room either
+        if self.leave_voip_room(uuid)?.is_some() {
+            sync_message_with_clients(
+                self.connected_clients.clone(),
+                self.clients_last_seen_index.clone(),
+                ServerOutput {
+                    replying_to: None,
+                    message_type: ServerMessageType::VoipState(self.voip_state()),
+                    message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+                    uuid: uuid.to_string(),
+                    author: String::new(),
+                },
+                self.decryption_key,
+            )
+            .await?;
+        }
+
+        let Some(profile) = self
+            .connected_clients_profile
+            .lock()
+            .await
+            .get(uuid)
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        let server_msg = ServerOutput {
+            replying_to: None,
+            message_type: ServerMessageType::Server(super::backend::ServerMessage::Disconnect(
+                profile,
+            )),
+            author: SERVER_AUTHOR.to_string(),
+            message_date: { Utc::now().format("%Y.%m.%d. %H:%M").to_string() },
+            uuid: SERVER_UUID.to_string(),
+        };
+
+        //The disconnection message can be reacted to, just like the other ones
+        self.reactions.lock().await.push(MessageReaction {
+            message_reactions: Vec::new(),
+        });
+
+        self.messages.lock().await.push(server_msg.clone());
+
+        sync_message_with_clients(
+            self.connected_clients.clone(),
+            self.clients_last_seen_index.clone(),
+            server_msg,
+            self.decryption_key,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the connected clients who have joined with an invite, which has been revoked since
+    async fn connected_revoked_invitees(&self) -> Vec<String>
+    {
+        let connected_clients: Vec<String> = self
+            .connected_clients
+            .lock()
+            .await
+            .iter()
+            .map(|client| client.uuid.clone())
+            .collect();
+
+        let shared_fields = self.shared_fields.lock().await;
+
+        let mut revoked_invitees = Vec::new();
+
+        for uuid in connected_clients {
+            if let Some((invite_id, _)) = self.invited_clients.get(&uuid) {
+                if shared_fields.is_invite_revoked(invite_id).await {
+                    revoked_invitees.push(uuid);
+                }
+            }
+        }
+
+        revoked_invitees
+    }
+
+    /// Disconnects a client who has joined with an invite which has been revoked since
+    /// The client is notified like when connecting with an invalid invite, so it doesnt try to reconnect
+    async fn handle_revoked_invitee(&mut self, uuid: &str) -> anyhow::Result<()>
+    {
+        let client_handle = self
+            .connected_clients
+            .lock()
+            .await
+            .iter()
+            .find(|client| client.uuid == uuid)
+            .and_then(|client| client.handle.clone());
+
+        if let Some(client_handle) = client_handle {
+            let mut client_handle = client_handle.lock().await;
+
+            send_message_to_client(&mut *client_handle, "Invalid invite!".to_string()).await?;
+
+            //Signal disconnection
+            send_message_to_client(
+                &mut *client_handle,
+                "Server disconnecting from client.".to_owned(),
+            )
+            .await?;
+        }
+
+        //The client is removed the same way as the clients which have timed out
+        self.handle_client_timeout(uuid).await
+    }
+
     async fn handle_server_ban(
         &self,
         client: &ConnectedClient,
@@ -1342,6 +2734,134 @@ impl MessageService
         Ok(server_msg)
     }
 
+    /// Authenticates the clients which dont know the server's password with their invite
+    /// The clients which have already joined with an invite can connect again without its token, until the invite is revoked
+    async fn authenticate_with_invite(&mut self, uuid: &str, sync_msg: &ClientSyncMessage) -> bool
+    {
+        if let Some((invite_id, _)) = self.invited_clients.get(uuid) {
+            return !self
+                .shared_fields
+                .lock()
+                .await
+                .is_invite_revoked(invite_id)
+                .await;
+        }
+
+        //The token is only sent when connecting
+        let (Some(ConnectionType::Connect(_, _)), Some(invite_token)) =
+            (&sync_msg.sync_attribute, &sync_msg.invite_token)
+        else {
+            return false;
+        };
+
+        let Some(invited_client) = self
+            .shared_fields
+            .lock()
+            .await
+            .redeem_invite(invite_token)
+            .await
+        else {
+            return false;
+        };
+
+        self.invited_clients
+            .insert(uuid.to_string(), invited_client);
+
+        true
+    }
+
+    /// Replaces the stored profile of the connected client, if the updated profile is valid
+    /// The username can not be changed
+    async fn update_client_profile(
+        &self,
+        uuid: &str,
+        profile: &ClientProfile,
+        profile_pictures: &ProfilePictures,
+    ) -> Result<()>
+    {
+        let mut connected_clients_profile = self.connected_clients_profile.lock().await;
+
+        let Some(stored_profile) = connected_clients_profile.get_mut(uuid)
+        else {
+            bail!("The client is not connected");
+        };
+
+        ensure!(
+            stored_profile.username == profile.username,
+            "The username cant be changed"
+        );
+
+        self.store_profile_pictures(profile, profile_pictures)?;
+
+        *stored_profile = profile.clone();
+
+        self.evict_unused_profile_pictures(&connected_clients_profile);
+
+        Ok(())
+    }
+
+    /// Removes the profile pictures which none of the stored profiles use anymore
+    /// The pictures are stored every time a profile is sent, so the replaced pictures would fill up the memory of the server
+    fn evict_unused_profile_pictures(&self, profiles: &HashMap<String, ClientProfile>)
+    {
+        self.profile_pictures.retain(|hash, _| {
+            profiles.values().any(|profile| {
+                profile.small_profile_picture_hash == *hash
+                    || profile.normal_profile_picture_hash == *hash
+            })
+        });
+    }
+
+    /// Stores the pictures of the profile by their hash, so the clients can request them
+    /// The hashes of the profile have to match the pictures, and the pictures have to be the same size as the ones created when registering
+    fn store_profile_pictures(
+        &self,
+        profile: &ClientProfile,
+        profile_pictures: &ProfilePictures,
+    ) -> Result<()>
+    {
+        for (profile_picture, hash, size_of_side) in [
+            (
+                &profile_pictures.small,
+                &profile.small_profile_picture_hash,
+                64,
+            ),
+            (
+                &profile_pictures.normal,
+                &profile.normal_profile_picture_hash,
+                256,
+            ),
+        ] {
+            ensure!(
+                profile_picture.len() <= MAX_PROFILE_PICTURE_SIZE,
+                "The profile picture is too large"
+            );
+
+            ensure!(
+                sha256::digest(profile_picture) == *hash,
+                "The hash of the profile picture doesnt match"
+            );
+
+            let picture = image::load_from_memory(profile_picture)?;
+
+            ensure!(
+                picture.width() == size_of_side && picture.height() == size_of_side,
+                "The profile picture should be {size_of_side}x{size_of_side}"
+            );
+        }
+
+        self.profile_pictures.insert(
+            profile.small_profile_picture_hash.clone(),
+            profile_pictures.small.clone(),
+        );
+        self.profile_pictures.insert(
+            profile.normal_profile_picture_hash.clone(),
+            profile_pictures.normal.clone(),
+        );
+
+        Ok(())
+    }
+
     async fn handle_banned_uuid(
         &self,
         req: &ClientMessage,
@@ -1398,32 +2918,33 @@ impl MessageService
 
     /// This function returns a message containing a full sync (all the messages etc)
     /// It returns a ```ServerMaster``` converted to an encrypted string
-    async fn full_sync_client(&self) -> anyhow::Result<String>
+    /// If the client is reconnecting, ```client_message_counter``` is the amount of messages the client already has, and only the messages after those are sent
+    /// The missed messages are only sent if the client's messages have been synced from this instance of the server
+    async fn full_sync_client(
+        &self,
+        client_message_counter: Option<usize>,
+        server_instance_id: Option<&str>,
+    ) -> anyhow::Result<String>
     {
+        let messages = self.messages.try_lock().unwrap().clone();
+
+        //If the server has been restarted since, the client needs every message
+        let first_message_index = client_message_counter
+            .filter(|client_message_counter| *client_message_counter <= messages.len())
+            .filter(|_| server_instance_id == Some(self.server_instance_id.as_str()))
+            .unwrap_or_default();
+
         //Construct reply
         let server_master = ServerMaster {
-            //Return an empty message list
-            message_list: self.messages.try_lock().unwrap().clone(),
+            //Only return the messages the client doesnt have
+            message_list: messages[first_message_index..].to_vec(),
+            first_message_index,
             user_seen_list: self.clients_last_seen_index.try_lock().unwrap().clone(),
             reaction_list: (*self.reactions.try_lock().unwrap().clone()).to_vec(),
             connected_clients_profile: self.connected_clients_profile.try_lock().unwrap().clone(),
-            ongoing_voip_call: {
-                if let Some(voip) = &self.voip {
-                    ServerVoipState {
-                        connected_clients: Some(
-                            voip.connected_clients
-                                .iter()
-                                .map(|entry| entry.key().clone())
-                                .collect(),
-                        ),
-                    }
-                }
-                else {
-                    ServerVoipState {
-                        connected_clients: None,
-                    }
-                }
-            },
+            ongoing_voip_call: self.voip_state(),
+            server_fingerprint: self.server_fingerprint.clone(),
+            server_instance_id: self.server_instance_id.clone(),
         };
 
         //convert reply into string
@@ -1664,11 +3185,12 @@ impl MessageService
     }
 
     /// used to handle all the requests, route the user's request
+    /// Returns ```None``` if the request cant be answered, in this case the client doesnt get a reply
     #[inline]
     pub async fn handle_request(
         &self,
         request_type: &ClientRequestTypeStruct,
-    ) -> anyhow::Result<String>
+    ) -> anyhow::Result<Option<String>>
     {
         let reply = match request_type {
             ClientRequestTypeStruct::ImageRequest(img_request) => {
@@ -1713,9 +3235,26 @@ impl MessageService
                 }))
                 .unwrap_or_default()
             },
+            //The profile pictures are sent like the images, the client caches them by their hash
+            ClientRequestTypeStruct::ProfilePictureRequest(hash) => {
+                //The client will keep displaying the placeholder if we dont have the picture
+                let Some(profile_picture) = self
+                    .profile_pictures
+                    .get(hash)
+                    .map(|profile_picture| profile_picture.clone())
+                else {
+                    return Ok(None);
+                };
+
+                serde_json::to_string(&ServerReplyType::Image(ServerImageReply {
+                    bytes: profile_picture,
+                    signature: hash.clone(),
+                }))
+                .unwrap_or_default()
+            },
         };
 
-        Ok(reply)
+        Ok(Some(reply))
     }
 
     /// handle all the file uploads
@@ -1856,3 +3395,193 @@ async fn send_message_to_connected_client(
         },
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn server_fingerprint_survives_reload()
+    {
+        let identity_directory =
+            env::temp_dir().join(format!("matthias_identity_{}", uuid::Uuid::new_v4()));
+
+        let fingerprint = load_server_fingerprint(&identity_directory).unwrap();
+
+        assert_eq!(
+            load_server_fingerprint(&identity_directory).unwrap(),
+            fingerprint
+        );
+
+        fs::remove_dir_all(&identity_directory).unwrap();
+
+        //A new identity is generated if the stored one is lost
+        assert_ne!(
+            load_server_fingerprint(&identity_directory).unwrap(),
+            fingerprint
+        );
+
+        fs::remove_dir_all(&identity_directory).unwrap();
+    }
+
+    /// Creates the shared fields of a server with a random invite signing key
+    fn shared_fields() -> SharedFields
+    {
+        SharedFields {
+            invite_signing_key: rand::random::<[u8; 32]>(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn redeems_invite()
+    {
+        let shared_fields = shared_fields();
+
+        let token = shared_fields
+            .create_invite(None, None, ClientRole::ReadOnly)
+            .unwrap();
+
+        let (invite_id, role) = shared_fields.redeem_invite(&token).await.unwrap();
+
+        assert_eq!(role, ClientRole::ReadOnly);
+        assert_eq!(shared_fields.invites.lock().await[0].id, invite_id);
+        assert_eq!(shared_fields.invites.lock().await[0].uses, 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_signatures()
+    {
+        let shared_fields = shared_fields();
+
+        let token = shared_fields
+            .create_invite(None, None, ClientRole::Member)
+            .unwrap();
+
+        let (payload, signature) = token.split_once('.').unwrap();
+
+        //The token was signed by a different server
+        let other_token = shared_fields()
+            .create_invite(None, None, ClientRole::Member)
+            .unwrap();
+
+        let (_, other_signature) = other_token.split_once('.').unwrap();
+
+        assert!(shared_fields
+            .redeem_invite(&format!("{payload}.{other_signature}"))
+            .await
+            .is_none());
+
+        //The payload was changed after signing
+        let tampered_payload = general_purpose::URL_SAFE_NO_PAD.encode(
+            serde_json::to_string(&InviteTokenPayload {
+                id: uuid::Uuid::new_v4().to_string(),
+                expires_at: None,
+            })
+            .unwrap(),
+        );
+
+        assert!(shared_fields
+            .redeem_invite(&format!("{tampered_payload}.{signature}"))
+            .await
+            .is_none());
+
+        //The signature was truncated
+        assert!(shared_fields
+            .redeem_invite(&format!("{payload}.{}", &signature[..signature.len() / 2]))
+            .await
+            .is_none());
+
+        //Malformed tokens
+        for token in [
+            "",
+            ".",
+            payload,
+            "not base64.not base64",
+            format!("{payload}.").as_str(),
+        ] {
+            assert!(shared_fields.redeem_invite(token).await.is_none());
+        }
+
+        assert_eq!(shared_fields.invites.lock().await[0].uses, 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_invites()
+    {
+        let shared_fields = shared_fields();
+
+        let token = shared_fields
+            .create_invite(
+                Some(chrono::Duration::seconds(-1)),
+                None,
+                ClientRole::Member,
+            )
+            .unwrap();
+
+        assert!(shared_fields.redeem_invite(&token).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_exhausted_invites()
+    {
+        let shared_fields = shared_fields();
+
+        let token = shared_fields
+            .create_invite(None, Some(2), ClientRole::Member)
+            .unwrap();
+
+        assert!(shared_fields.redeem_invite(&token).await.is_some());
+        assert!(shared_fields.redeem_invite(&token).await.is_some());
+        assert!(shared_fields.redeem_invite(&token).await.is_none());
+
+        assert_eq!(shared_fields.invites.lock().await[0].uses, 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_revoked_invites()
+    {
+        let shared_fields = shared_fields();
+
+        let token = shared_fields
+            .create_invite(None, None, ClientRole::Member)
+            .unwrap();
+
+        let (invite_id, _) = shared_fields.redeem_invite(&token).await.unwrap();
+
+        assert!(!shared_fields.is_invite_revoked(&invite_id).await);
+
+        shared_fields.invites.lock().await[0].revoked = true;
+
+        assert!(shared_fields.is_invite_revoked(&invite_id).await);
+        assert!(shared_fields.redeem_invite(&token).await.is_none());
+    }
+
+    #[test]
+    fn evicts_unused_profile_pictures()
+    {
+        let message_service = MessageService::default();
+
+        for hash in ["small", "normal", "replaced"] {
+            message_service
+                .profile_pictures
+                .insert(hash.to_string(), vec![0]);
+        }
+
+        let profiles = HashMap::from([(
+            uuid::Uuid::new_v4().to_string(),
+            ClientProfile {
+                small_profile_picture_hash: "small".to_string(),
+                normal_profile_picture_hash: "normal".to_string(),
+                ..Default::default()
+            },
+        )]);
+
+        message_service.evict_unused_profile_pictures(&profiles);
+
+        assert!(message_service.profile_pictures.contains_key("small"));
+        assert!(message_service.profile_pictures.contains_key("normal"));
+        assert!(!message_service.profile_pictures.contains_key("replaced"));
+    }
+}
@@ -0,0 +1,391 @@
+use egui::{vec2, Button, Color32, Image, RichText, TextEdit};
+use egui_extras::DatePickerButton;
+
+use crate::app::{
+    backend::{
+        display_error_message, display_info_message, Application, ClientMessage, ConnectionState,
+        ProfilePictures, ServerMaster, UserInformation,
+    },
+    ui::client_ui::client_actions::{
+        account_management::{
+            change_password, create_profile_pictures, delete_account, export_account,
+            import_account, ACCOUNT_BUNDLE_EXTENSION,
+        },
+        outbox::Outbox,
+    },
+};
+
+impl Application
+{
+    /// The account part of the settings, every change is written to the user's file
+    pub fn account_settings_ui(&mut self, ui: &mut egui::Ui)
+    {
+        ui.collapsing("Account", |ui| {
+            ui.collapsing("Profile", |ui| {
+                self.profile_editor_ui(ui);
+            });
+
+            ui.collapsing("Change password", |ui| {
+                self.change_password_ui(ui);
+            });
+
+            ui.collapsing("Export account", |ui| {
+                self.export_account_ui(ui);
+            });
+
+            ui.collapsing("Delete account", |ui| {
+                self.delete_account_ui(ui);
+            });
+        });
+    }
+
+    fn profile_editor_ui(&mut self, ui: &mut egui::Ui)
+    {
+        let (mut profile, mut profile_pictures) = self
+            .account_management
+            .edited_profile
+            .take()
+            .unwrap_or_else(|| {
+                (
+                    self.opened_user_information.profile.clone(),
+                    self.opened_user_information.profile_pictures.clone(),
+                )
+            });
+
+        ui.horizontal(|ui| {
+            ui.label("Full name");
+            ui.text_edit_singleline(&mut profile.full_name);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Birthdate:");
+            ui.add(DatePickerButton::new(&mut profile.birth_date).id_source("account_birth_date"));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Gender");
+            egui::ComboBox::from_id_source("account_gender")
+                .selected_text(match profile.gender {
+                    Some(false) => "Male",
+                    Some(true) => "Female",
+                    None => "Rather not answer",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut profile.gender, Some(false), "Male");
+                    ui.selectable_value(&mut profile.gender, Some(true), "Female");
+                    ui.selectable_value(&mut profile.gender, None, "Rather not answer");
+                });
+        });
+
+        ui.horizontal(|ui| {
+            //The uri changes with the picture, so the new picture is displayed
+            ui.allocate_ui(vec2(64., 64.), |ui| {
+                ui.add(Image::from_bytes(
+                    format!(
+                        "bytes://account_profile_picture_{}",
+                        profile.small_profile_picture_hash
+                    ),
+                    profile_pictures.small.clone(),
+                ));
+            });
+
+            if ui.button("Change picture").clicked() {
+                if let Some(picture_path) = rfd::FileDialog::new()
+                    .add_filter("Supported formats (.png, .jpg)", &["png", "jpg"])
+                    .pick_file()
+                {
+                    match create_profile_pictures(&picture_path) {
+                        Ok((normal_profile_picture, small_profile_picture)) => {
+                            profile_pictures = ProfilePictures {
+                                small: small_profile_picture,
+                                normal: normal_profile_picture,
+                            };
+
+                            profile.set_profile_pictures(&profile_pictures);
+                        },
+                        Err(err) => {
+                            display_error_message(err, self.toasts.clone());
+                        },
+                    }
+                }
+            }
+        });
+
+        let is_modified = profile != self.opened_user_information.profile;
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(is_modified, Button::new("Save")).clicked() {
+                self.opened_user_information.profile = profile.clone();
+                self.opened_user_information.profile_pictures = profile_pictures.clone();
+
+                self.save_user_information();
+
+                self.send_profile_update();
+
+                display_info_message("The profile has been saved!", self.toasts.clone());
+            }
+
+            if ui
+                .add_enabled(is_modified, Button::new("Discard"))
+                .clicked()
+            {
+                profile = self.opened_user_information.profile.clone();
+            }
+        });
+
+        //Only the unsaved changes are kept, the hashes in the profile change with the pictures
+        if profile != self.opened_user_information.profile {
+            self.account_management.edited_profile = Some((profile, profile_pictures));
+        }
+    }
+
+    /// Sends the saved profile to every server the client is connected to, the servers which arent connected receive it when connecting
+    fn send_profile_update(&self)
+    {
+        let connections = std::iter::once(&self.server_session.client_connection).chain(
+            self.server_sessions
+                .iter()
+                .map(|server_session| &server_session.client_connection),
+        );
+
+        for connection in connections {
+            if !matches!(connection.state, ConnectionState::Connected(_)) {
+                continue;
+            }
+
+            let connection = connection.clone();
+
+            let message = ClientMessage::construct_profile_update_msg(
+                self.opened_user_information.profile.clone(),
+                self.opened_user_information.profile_pictures.clone(),
+                &self.opened_user_information.uuid,
+            );
+
+            tokio::spawn(async move {
+                if let Err(err) = connection.send_message(message).await {
+                    tracing::error!("Failed to send the profile update: {err}");
+                }
+            });
+        }
+    }
+
+    fn change_password_ui(&mut self, ui: &mut egui::Ui)
+    {
+        let account_management = &mut self.account_management;
+
+        ui.add(
+            TextEdit::singleline(&mut account_management.current_password)
+                .password(true)
+                .hint_text("Current password"),
+        );
+        ui.add(
+            TextEdit::singleline(&mut account_management.new_password)
+                .password(true)
+                .hint_text("New password"),
+        );
+        ui.add(
+            TextEdit::singleline(&mut account_management.new_password_again)
+                .password(true)
+                .hint_text("New password again"),
+        );
+
+        let passwords_match =
+            account_management.new_password == account_management.new_password_again;
+
+        if !passwords_match && !account_management.new_password_again.is_empty() {
+            ui.label(RichText::from("The new passwords dont match!").color(Color32::RED));
+        }
+
+        if ui
+            .add_enabled(
+                passwords_match
+                    && !account_management.current_password.is_empty()
+                    && !account_management.new_password.is_empty(),
+                Button::new("Change password"),
+            )
+            .clicked()
+        {
+            match change_password(
+                &mut self.opened_user_information,
+                &account_management.current_password,
+                &account_management.new_password,
+            ) {
+                Ok(_) => {
+                    //The outbox is encrypted with a key derived from the password too
+                    self.outbox
+                        .lock()
+                        .unwrap()
+                        .change_password(&self.opened_user_information.password);
+
+                    account_management.current_password.clear();
+                    account_management.new_password.clear();
+                    account_management.new_password_again.clear();
+
+                    display_info_message("The password has been changed!", self.toasts.clone());
+                },
+                Err(err) => {
+                    display_error_message(err, self.toasts.clone());
+                },
+            }
+        }
+    }
+
+    fn export_account_ui(&mut self, ui: &mut egui::Ui)
+    {
+        ui.label(
+            RichText::from("The exported bundle can be imported on another computer from the login page, it is protected by its own password.")
+                .weak(),
+        );
+
+        ui.add(
+            TextEdit::singleline(&mut self.account_management.bundle_password)
+                .password(true)
+                .hint_text("Password of the bundle"),
+        );
+
+        if ui
+            .add_enabled(
+                !self.account_management.bundle_password.is_empty(),
+                Button::new("Export"),
+            )
+            .clicked()
+        {
+            if let Some(bundle_path) = rfd::FileDialog::new()
+                .add_filter("Matthias account bundle", &[ACCOUNT_BUNDLE_EXTENSION])
+                .set_file_name(format!(
+                    "{}.{ACCOUNT_BUNDLE_EXTENSION}",
+                    self.opened_user_information.username
+                ))
+                .save_file()
+            {
+                match export_account(
+                    &self.opened_user_information,
+                    &self.account_management.bundle_password,
+                    &bundle_path,
+                ) {
+                    Ok(_) => {
+                        self.account_management.bundle_password.clear();
+
+                        display_info_message("The account has been exported!", self.toasts.clone());
+                    },
+                    Err(err) => {
+                        display_error_message(err, self.toasts.clone());
+                    },
+                }
+            }
+        }
+    }
+
+    fn delete_account_ui(&mut self, ui: &mut egui::Ui)
+    {
+        ui.label(
+            RichText::from("The account's file, its backups and its cached messages are deleted from this computer. This can not be undone!")
+                .color(Color32::RED),
+        );
+
+        ui.add(
+            TextEdit::singleline(&mut self.account_management.delete_password)
+                .password(true)
+                .hint_text("Password"),
+        );
+
+        if !ui
+            .add_enabled(
+                !self.account_management.delete_password.is_empty(),
+                Button::new(RichText::from("Delete account").color(Color32::RED)),
+            )
+            .clicked()
+        {
+            return;
+        }
+
+        let password = std::mem::take(&mut self.account_management.delete_password);
+
+        if self.server_has_started {
+            display_error_message("Server is running!", self.toasts.clone());
+
+            return;
+        }
+
+        if !self
+            .opened_user_information
+            .verify_password(password.clone())
+        {
+            display_error_message("Incorrect password!", self.toasts.clone());
+
+            return;
+        }
+
+        if matches!(
+            self.server_session.client_connection.state,
+            ConnectionState::Connected(_)
+        ) {
+            self.with_opened_session(|app, session| app.disconnect_from_server(session));
+        }
+
+        self.log_out();
+
+        //The caches are deleted with the account, so they must not be saved again
+        self.server_session.incoming_messages = ServerMaster::default();
+
+        match delete_account(&self.opened_user_information, &password) {
+            Ok(_) => {
+                self.opened_user_information = UserInformation::default();
+
+                *self.outbox.lock().unwrap() = Outbox::default();
+
+                display_info_message("The account has been deleted!", self.toasts.clone());
+            },
+            Err(err) => {
+                display_error_message(err, self.toasts.clone());
+            },
+        }
+    }
+
+    /// Imports an exported account on the login page
+    pub fn import_account_ui(&mut self, ui: &mut egui::Ui)
+    {
+        let Some(bundle_path) = self.account_management.imported_bundle_path.clone()
+        else {
+            if ui.button("Import account").clicked() {
+                self.account_management.imported_bundle_path = rfd::FileDialog::new()
+                    .add_filter("Matthias account bundle", &[ACCOUNT_BUNDLE_EXTENSION])
+                    .pick_file();
+            }
+
+            return;
+        };
+
+        ui.label(format!("Importing {}", bundle_path.display()));
+
+        ui.add(
+            TextEdit::singleline(&mut self.account_management.bundle_password)
+                .password(true)
+                .hint_text("Password of the bundle"),
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Import").clicked() {
+                match import_account(&bundle_path, &self.account_management.bundle_password) {
+                    Ok(username) => {
+                        self.login_username = username;
+
+                        self.account_management = Default::default();
+
+                        display_info_message(
+                            "The account has been imported, log in with the password of the account!",
+                            self.toasts.clone(),
+                        );
+                    },
+                    Err(err) => {
+                        display_error_message(err, self.toasts.clone());
+                    },
+                }
+            }
+
+            if ui.button("Cancel").clicked() {
+                self.account_management = Default::default();
+            }
+        });
+    }
+}
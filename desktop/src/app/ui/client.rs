@@ -1,12 +1,22 @@
 use egui::{
-    load::LoadError, vec2, Align, Align2, Area, Color32, FontFamily, FontId, Id, Image,
-    ImageButton, Layout, Pos2, RichText, Sense, Stroke,
+    vec2, Align, Align2, Area, Color32, FontFamily, FontId, Id, Image, ImageButton, Layout, Pos2,
+    RichText, Sense, Slider, Stroke,
 };
 use rodio::Decoder;
-use std::{net::SocketAddr, sync::atomic::Ordering::Relaxed};
+use std::{collections::BTreeMap, net::SocketAddr, sync::atomic::Ordering::Relaxed};
 use tokio_util::sync::CancellationToken;
 
-use crate::app::backend::{display_error_message, ClientMessage, ConnectionState, Voip};
+use crate::app::{
+    backend::{
+        display_error_message, is_private_voice_room, ClientMessage, ClientMessageType,
+        ConnectionState, InvitationAnswer, OutgoingCallInvitation, ServerVoipRoomState, Voip,
+        VoipMixingMode, DEFAULT_VOICE_ROOM, INVITATION_TIMEOUT, PRIVATE_VOICE_ROOM_PREFIX,
+    },
+    ui::client_ui::client_actions::{
+        call_recording::CallRecorder, connection_quality::ConnectionQuality, outbox::DeliveryState,
+        voice_activity::SPEAKING_INDICATOR_HOLD,
+    },
+};
 
 use crate::app::backend::{Application, SearchType, ServerMessageType};
 
@@ -14,6 +24,12 @@ impl Application
 {
     pub fn state_client(&mut self, _frame: &mut eframe::Frame, ctx: &egui::Context)
     {
+        //Display the ringing windows of the incoming call invitations
+        self.call_invitations_ui(ctx);
+
+        //Display the servers we are connected to
+        self.server_switcher_ui(ctx);
+
         egui::TopBottomPanel::new(egui::panel::TopBottomSide::Top, "menu_area").show(ctx, |ui| {
             ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
                 ui.allocate_ui(vec2(300., 40.), |ui| {
@@ -29,10 +45,7 @@ impl Application
                             display_error_message("Server is running!", self.toasts.clone());
                         }
                         else {
-                            self.autosync_shutdown_token.cancel();
-                            self.server_sender_thread = None;
-
-                            self.main.client_mode = false;
+                            self.log_out();
                         }
                     };
                 })
@@ -72,9 +85,33 @@ impl Application
                     ui.separator();
                 });
 
-                if matches!(self.client_connection.state, ConnectionState::Connected(_)) {
+                //While we are offline the messages are still displayed, but nothing can be sent
+                if matches!(self.server_session.client_connection.state, ConnectionState::Reconnecting) {
+                    ui.label(
+                        RichText::from(format!(
+                            "⚠ {}",
+                            crate::app::reconnection_status(&self.server_session.client_connection)
+                        ))
+                        .color(Color32::YELLOW),
+                    );
+
+                    //Update the countdown
+                    ctx.request_repaint_after(std::time::Duration::from_secs(1));
+                }
+
+                if matches!(self.server_session.client_connection.state, ConnectionState::Connected(_)) {
+                    //Display the quality of our connection to the server
+                    let connection_quality = self.server_session.connection_quality.lock().unwrap().clone();
+
+                    ui.label(connection_quality_text(&connection_quality))
+                        .on_hover_text("The round trip time and the packet loss of the connection to the server");
+
+                    ui.allocate_ui(vec2(10., 40.), |ui| {
+                        ui.separator();
+                    });
+
                     let port = self
-                        .client_ui
+                        .server_session
                         .send_on_ip
                         .split(":")
                         .last()
@@ -94,27 +131,22 @@ impl Application
                     }
 
                     ui.allocate_ui(vec2(40., 40.), |ui| {
-                        if let Some(voip) = self.client_ui.voip.clone() {
+                        if let Some(voip) = self.server_session.voip.clone() {
                             let disconnect_button = ui.add(ImageButton::new(Image::new(
                                 egui::include_image!("../../../../assets/icons/call_red.png"),
                             )));
 
                             if disconnect_button.clicked() {
+                                //Finish the recording before leaving the call
+                                self.stop_call_recording(&voip);
+
                                 //Disable camera if it exists before everything else
                                 if voip.camera_handle_is_open.load(Relaxed) {
                                     self.disable_camera(voip);
                                 }
 
-                                if let Some(voip_connected_clients) = &self
-                                    .client_ui
-                                    .incoming_messages
-                                    .ongoing_voip_call
-                                    .connected_clients
-                                {
-                                    voip_connected_clients.iter().for_each(|uuid| {
-                                        ctx.forget_image(&format!("bytes://video_stream:{uuid}"));
-                                    });
-                                }
+                                //Free the textures of the participants' video streams
+                                voip.video_frames.clear();
 
                                 //Shut down listener server, and disconnect from server
                                 self.send_msg(ClientMessage::construct_voip_disconnect(
@@ -122,13 +154,21 @@ impl Application
                                 ));
 
                                 //Shutdown listener and recorder thread
-                                self.voip_shutdown_token.cancel();
+                                self.server_session.voip_shutdown_token.cancel();
 
                                 //Signal the voice recorder function to stop
                                 let _ = self.record_audio_interrupter.send(());
                                 //Reset state
-                                self.client_ui.voip = None;
-                                self.voip_thread = None;
+                                self.server_session.voip = None;
+                                self.server_session.voip_thread = None;
+
+                                //Private rooms cant be rejoined once they are closed, so we shouldnt keep the name of the room
+                                if is_private_voice_room(&self.server_session.voip_room) {
+                                    self.server_session.voip_room = DEFAULT_VOICE_ROOM.to_string();
+                                }
+
+                                //If the invited client has not answered yet, the server cancels the invitation
+                                self.client_ui.outgoing_call_invitation = None;
                             }
                         }
                         else {
@@ -138,58 +178,46 @@ impl Application
                                 )));
 
                                 if call_button.clicked() {
-                                    //Move sender into thread
-                                    let sender = self.voip_connection_sender.clone();
-
-                                    //Reset shutdown token State, if we had cancelled this token we must create a new one in order to reset its state
-                                    //else its going to be cancelled and new threads will shut dwon immediately
-                                    self.voip_shutdown_token = CancellationToken::new();
-
-                                    let toasts = self.toasts.clone();
-
-                                    match self.client_ui.send_on_ip.parse::<SocketAddr>() {
-                                        Ok(socket_addr) => {
-                                            //Spawn thread which will create the ```Voip``` instance
-                                            tokio::spawn(async move {
-                                                match socket_addr.is_ipv6() {
-                                                    true => {
-                                                        match Voip::new("[::]:0".to_string()).await {
-                                                            Ok(voip) => {
-                                                                // It is okay to unwrap since it doesnt matter if we panic
-                                                                sender.send(voip).unwrap();
-                                                            },
-                                                            Err(err_ipv6) => {
-                                                                display_error_message(format!("Could not bind to IPv6: {err_ipv6}"), toasts);
-                                                            },
-                                                        }
-                                                    },
-                                                    false => {
-                                                        match Voip::new("0.0.0.0:0".to_string()).await {
-                                                            Ok(voip) => {
-                                                                sender.send(voip).unwrap();
-                                                            },
-                                                            Err(err_ipv4) => {
-                                                                display_error_message(format!("Could not bind to IPv4: {err_ipv4}"), toasts);
-                                                            },
-                                                        }
-                                                    },
-                                                }
-                                            });
-                                        },
-                                        Err(_err) => {
-                                            display_error_message("Invalid address.", self.toasts.clone());
-                                        },
-                                    };
-
-                                    //Lua callback
-                                    self.client_ui.extension.event_call_extensions(
-                                        crate::app::lua::EventCall::OnCallSend,
-                                        &self.lua,
-                                        None,
-                                    );
+                                    self.join_voice_room();
                                 }
 
-                                call_button.on_hover_text("Start a group call");
+                                call_button.context_menu(|ui| {
+                                    ui.label("Voice room");
+
+                                    ui.text_edit_singleline(&mut self.server_session.voip_room);
+
+                                    //The creator of the call can choose how the audio is distributed
+                                    if !self
+                                        .server_session
+                                        .incoming_messages
+                                        .ongoing_voip_call
+                                        .rooms
+                                        .contains_key(&self.server_session.voip_room)
+                                    {
+                                        ui.separator();
+
+                                        ui.label("Call audio");
+
+                                        ui.radio_value(
+                                            &mut self.client_ui.voip_mixing_mode,
+                                            VoipMixingMode::Relay,
+                                            VoipMixingMode::Relay.to_string(),
+                                        )
+                                        .on_hover_text("Every participant's audio is sent to everyone separately");
+
+                                        ui.radio_value(
+                                            &mut self.client_ui.voip_mixing_mode,
+                                            VoipMixingMode::Mixed,
+                                            VoipMixingMode::Mixed.to_string(),
+                                        )
+                                        .on_hover_text("The server mixes the participants' audio, this uses less bandwidth in bigger calls");
+                                    }
+                                });
+
+                                call_button.on_hover_text(format!(
+                                    "Join the voice room \"{}\" (Right click to change)",
+                                    self.server_session.voip_room
+                                ));
 
                                 //Callback
                                 self.client_ui.extension.event_call_extensions(
@@ -206,21 +234,31 @@ impl Application
             ui.allocate_space(vec2(ui.available_width(), 5.));
         });
 
-        //IF there is an existing Voice call we can assume there are people connected to it
-        if let Some(connected_clients) = self
-            .client_ui
+        //IF there is an existing Voice room we can assume there are people connected to it
+        //Private calls are only displayed to their participants
+        let rooms: BTreeMap<String, ServerVoipRoomState> = self
+            .server_session
             .incoming_messages
             .ongoing_voip_call
-            .clone()
-            .connected_clients
-        {
+            .rooms
+            .iter()
+            .filter(|(room_name, room)| {
+                !is_private_voice_room(room_name)
+                    || room
+                        .connected_clients
+                        .contains(&self.opened_user_information.uuid)
+            })
+            .map(|(room_name, room)| (room_name.clone(), room.clone()))
+            .collect();
+
+        if !rooms.is_empty() {
             egui::TopBottomPanel::new(egui::panel::TopBottomSide::Top, "voip_connected_users")
                 .resizable(true)
                 .show(ctx, |ui| {
                     let uuid = self.opened_user_information.uuid.clone();
 
                     //We should only display the settings menu if we are connected to a Voip call
-                    if let Some(voip) = self.client_ui.voip.clone() {
+                    if let Some(voip) = self.server_session.voip.clone() {
                         //Settings for the client connected to an ongoing call
                         ui.allocate_ui(vec2(ui.available_width(), 30.), |ui| {
                             ui.horizontal_centered(|ui| {
@@ -247,25 +285,25 @@ impl Application
                                         .clicked()
                                     {
                                         //Reset cancellation token
-                                        self.voip_video_shutdown_token = CancellationToken::new();
+                                        self.server_session.voip_video_shutdown_token = CancellationToken::new();
 
                                         //Reset thread
                                         self.voip_video_thread = None;
 
-                                        //Add camera handle to the voip
-                                        match voip.add_camera_handle() {
-                                            Ok(_) => (),
+                                        //Add the video source selected in the settings to the voip
+                                        match voip.add_camera_handle(&self.client_ui.video_source) {
+                                            Ok(_) => {
+                                                voip.camera_handle_is_open.store(true, Relaxed);
+
+                                                //Send image connection message
+                                                self.send_msg(ClientMessage::construct_voip_event(uuid.clone(), crate::app::backend::ClientVoipRequest::ImageConnected));
+                                            },
                                             Err(err) => {
                                                 tracing::error!("{err}");
 
                                                 display_error_message(err, self.toasts.clone());
                                             },
                                         };
-
-                                        voip.camera_handle_is_open.store(true, Relaxed);
-
-                                        //Send image connection message
-                                        self.send_msg(ClientMessage::construct_voip_event(uuid.clone(), crate::app::backend::ClientVoipRequest::ImageConnected));
                                     }
                                 }
                                 else {
@@ -276,9 +314,57 @@ impl Application
                                         )))
                                         .clicked()
                                     {
-                                        self.disable_camera(voip);
+                                        self.disable_camera(voip.clone());
+                                    }
+                                }
+
+                                ui.separator();
+
+                                //Display the quality of our connection to the server's voip service
+                                let connection_quality = voip.connection_quality.lock().unwrap().clone();
+
+                                ui.label(connection_quality_text(&connection_quality))
+                                    .on_hover_text("The round trip time and the packet loss of the call");
+
+                                ui.separator();
+
+                                let is_recording = voip.call_recorder.lock().unwrap().is_some();
+
+                                if is_recording {
+                                    if ui
+                                        .button(RichText::from("⏹ Stop recording").color(Color32::RED))
+                                        .clicked()
+                                    {
+                                        self.stop_call_recording(&voip);
                                     }
                                 }
+                                else {
+                                    if ui
+                                        .button("⏺ Record call")
+                                        .on_hover_text("Record the call's audio into an Ogg/Opus file, every participant will be notified")
+                                        .clicked()
+                                    {
+                                        self.start_call_recording(&voip);
+                                    }
+
+                                    ui.checkbox(&mut self.client_ui.record_call_video, "Record video")
+                                        .on_hover_text("Record the participants' video streams into WebM files next to the audio file");
+                                }
+
+                                //Display who we are calling until they answer
+                                if let Some(outgoing_call_invitation) = &self.client_ui.outgoing_call_invitation {
+                                    ui.separator();
+
+                                    let username = self
+                                        .server_session
+                                        .incoming_messages
+                                        .connected_clients_profile
+                                        .get(&outgoing_call_invitation.invitee)
+                                        .map(|profile| profile.username.clone())
+                                        .unwrap_or_else(|| outgoing_call_invitation.invitee.clone());
+
+                                    ui.label(RichText::from(format!("📞 Ringing @{username}...")).weak());
+                                }
                             });
                         });
 
@@ -287,72 +373,215 @@ impl Application
 
                     //Display the name of this part of the ui
                     ui.label(
-                        RichText::from("Users connected to the voice chat:")
+                        RichText::from("Voice rooms:")
                             .weak()
                             .size(self.font_size / 2.),
                     );
 
-                    //Put all of the connected users nxt to eachother
-                    ui.horizontal(|ui| {
-                        for connected_client_uuid in connected_clients.iter() {
-                            ui.horizontal(|ui| {
-                                self.display_icon_from_server(
-                                    ctx,
-                                    connected_client_uuid.clone(),
-                                    ui,
-                                );
-                                ui.vertical(|ui| {
-                                    //Display username
-                                    match self
-                                    .client_ui
-                                    .incoming_messages
-                                    .connected_clients_profile
-                                    .get(connected_client_uuid)
-                                    {
-                                        Some(profile) => {
-                                            ui.label(RichText::from(&profile.username).weak());
-                                        },
-                                        None => {
-                                            self.request_client(connected_client_uuid.to_string());
-
-                                            ui.label(RichText::from(format!(
-                                                "Profile not found for: {connected_client_uuid}"
-                                            )));
-                                        },
+                    for (room_name, room) in rooms.iter() {
+                        let is_private_room = is_private_voice_room(room_name);
+
+                        ui.horizontal(|ui| {
+                            if is_private_room {
+                                ui.label(RichText::from("📞 Private call").strong());
+                            }
+                            else {
+                                ui.label(RichText::from(format!("🔊 {room_name}")).strong());
+                            }
+
+                            //We can only join a room if we arent in a call
+                            if !is_private_room
+                                && self.server_session.voip.is_none()
+                                && ui
+                                    .add_enabled(self.atx.is_none(), egui::Button::new("Join"))
+                                    .clicked()
+                            {
+                                self.server_session.voip_room.clone_from(room_name);
+
+                                self.join_voice_room();
+                            }
+                        });
+
+                        //Display the recording indicator to every participant of the room
+                        if !room.recording_clients.is_empty() {
+                            let recording_usernames = room
+                                .recording_clients
+                                .iter()
+                                .map(|recording_client_uuid| {
+                                    self.server_session
+                                        .incoming_messages
+                                        .connected_clients_profile
+                                        .get(recording_client_uuid)
+                                        .map(|profile| format!("@{}", profile.username))
+                                        .unwrap_or_else(|| recording_client_uuid.clone())
+                                })
+                                .collect::<Vec<String>>()
+                                .join(", ");
+
+                            ui.label(
+                                RichText::from(format!(
+                                    "⏺ This call is being recorded by {recording_usernames}"
+                                ))
+                                .color(Color32::RED)
+                                .strong(),
+                            );
+                        }
+
+                        //We can only hear the participants of the room we are connected to
+                        let is_own_room = self.server_session.voip.is_some() && room.connected_clients.contains(&uuid);
+
+                        //Put all of the connected users nxt to eachother
+                        ui.horizontal(|ui| {
+                            for connected_client_uuid in room.connected_clients.iter() {
+                                //Check if the user is speaking, we can only know this if we are connected to the call
+                                let is_speaking = self.server_session.voip.as_ref().is_some_and(|voip| {
+                                    if *connected_client_uuid == uuid {
+                                        voip.is_speaking.load(Relaxed)
+                                    }
+                                    else {
+                                        voip.remote_speakers
+                                            .get(connected_client_uuid)
+                                            .is_some_and(|remote_speaker| remote_speaker.is_speaking())
                                     }
+                                });
 
-                                    //Display image
-                                    match ctx.try_load_bytes(&format!("bytes://video_stream:{connected_client_uuid}")) {
-                                        Ok(bytes_poll) => {
-                                            match bytes_poll {
-                                                egui::load::BytesPoll::Pending { .. } => {
-                                                    ui.spinner();
-                                                },
-                                                egui::load::BytesPoll::Ready { bytes, .. } => {
-                                                    dbg!(bytes.len());
-                                                    ui.allocate_ui(vec2(360., 360.), |ui| {
-                                                        ui.add(
-                                                            Image::from_uri(format!("bytes://video_stream:{connected_client_uuid}"))
-                                                        );
-                                                    });
-                                                },
-                                            }
-                                        },
-                                        Err(err) => {
-                                            if let LoadError::Loading(inner) = err {
-                                                if inner != "Bytes not found. Did you forget to call Context::include_bytes?" {
-                                                    tracing::error!("{}", inner);
+                                let connected_client_group = ui.horizontal(|ui| {
+                                    self.display_icon_from_server(
+                                        ctx,
+                                        connected_client_uuid.clone(),
+                                        ui,
+                                    );
+                                    ui.vertical(|ui| {
+                                        //Display username
+                                        match self
+                                        .server_session
+                                        .incoming_messages
+                                        .connected_clients_profile
+                                        .get(connected_client_uuid)
+                                        {
+                                            Some(profile) => {
+                                                if is_speaking {
+                                                    ui.label(RichText::from(&profile.username).color(Color32::GREEN));
                                                 }
+                                                else {
+                                                    ui.label(RichText::from(&profile.username).weak());
+                                                }
+                                            },
+                                            None => {
+                                                self.request_client(connected_client_uuid.to_string());
+
+                                                ui.label(RichText::from(format!(
+                                                    "Profile not found for: {connected_client_uuid}"
+                                                )));
+                                            },
+                                        }
+
+                                        //Display the local volume and mute of the participant, we dont need these for ourselves
+                                        if *connected_client_uuid != uuid && is_own_room {
+                                            let mut participant_audio = self
+                                                .client_ui
+                                                .participant_audio
+                                                .get(connected_client_uuid)
+                                                .map(|settings| *settings)
+                                                .unwrap_or_default();
+
+                                            let original_participant_audio = participant_audio;
+
+                                            ui.horizontal(|ui| {
+                                                ui.checkbox(&mut participant_audio.muted, "Mute")
+                                                    .on_hover_text("Mute this user only for yourself");
+
+                                                ui.add_enabled(
+                                                    !participant_audio.muted,
+                                                    Slider::new(&mut participant_audio.volume, 0.0..=2.0)
+                                                        .show_value(false),
+                                                )
+                                                .on_hover_text(format!("Volume: {}%", (participant_audio.volume * 100.) as i32));
+                                            });
+
+                                            if participant_audio != original_participant_audio {
+                                                self.client_ui
+                                                    .participant_audio
+                                                    .insert(connected_client_uuid.clone(), participant_audio);
+
+                                                //The mixer of the server applies the volume too, as the mixed audio cant be split up by the client
+                                                self.send_msg(ClientMessage::construct_voip_event(
+                                                    self.opened_user_information.uuid.clone(),
+                                                    crate::app::backend::ClientVoipRequest::ParticipantVolume(
+                                                        connected_client_uuid.clone(),
+                                                        participant_audio.effective_volume(),
+                                                    ),
+                                                ));
+                                            }
+                                        }
+
+                                        //Display the last decoded frame of the participant's video stream
+                                        if let Some(voip) = &self.server_session.voip {
+                                            if let Some(texture) = voip.video_frames.get(connected_client_uuid) {
+                                                ui.allocate_ui(vec2(360., 360.), |ui| {
+                                                    ui.add(
+                                                        Image::from_texture(&*texture).shrink_to_fit()
+                                                    );
+                                                });
                                             }
-                                            else {
-                                                tracing::error!("{}", err);
+
+                                            //Display the quality of the participant's video stream
+                                            if let Some(statistics) = voip.video_receiver_statistics.get(connected_client_uuid) {
+                                                let resolution = statistics
+                                                    .resolution
+                                                    .map(|[width, height]| format!("{width}x{height}, "))
+                                                    .unwrap_or_default();
+
+                                                ui.label(
+                                                    RichText::from(format!(
+                                                        "{resolution}{:.0} fps, {:.0}% loss, {:.0} ms jitter",
+                                                        statistics.frame_rate,
+                                                        statistics.loss_fraction * 100.,
+                                                        statistics.jitter_ms,
+                                                    ))
+                                                    .weak()
+                                                    .size(self.font_size / 2.),
+                                                );
+                                            }
+
+                                            //Display the quality we are sending our video stream with
+                                            if *connected_client_uuid == uuid && voip.camera_handle_is_open.load(Relaxed) {
+                                                let video_quality_controller = voip.video_quality_controller.lock().unwrap();
+
+                                                let round_trip_time = video_quality_controller
+                                                    .round_trip_time_ms
+                                                    .map(|round_trip_time_ms| format!(", {round_trip_time_ms:.0} ms RTT"))
+                                                    .unwrap_or_default();
+
+                                                ui.label(
+                                                    RichText::from(format!(
+                                                        "Sending: {}{round_trip_time}",
+                                                        video_quality_controller.current()
+                                                    ))
+                                                    .weak()
+                                                    .size(self.font_size / 2.),
+                                                );
                                             }
                                         }
-                                    }
+                                    });
                                 });
-                            });
-                        }
-                    });
+
+                                //Display speaking indicator
+                                if is_speaking {
+                                    ui.painter().rect_stroke(
+                                        connected_client_group.response.rect,
+                                        5.,
+                                        Stroke::new(2., Color32::GREEN),
+                                    );
+
+                                    //Make sure the indicator disappears once they have stopped talking
+                                    ctx.request_repaint_after(SPEAKING_INDICATOR_HOLD);
+                                }
+                            }
+                        });
+
+                        ui.separator();
+                    }
                 });
         }
 
@@ -360,8 +589,14 @@ impl Application
         let usr_panel = egui::TopBottomPanel::bottom("usr_input")
             .max_height(ctx.used_size().y / 2.)
             .show_animated(ctx, self.client_ui.usr_msg_expanded, |ui| {
+                self.outbox_ui(ui);
+
+                //The messages sent while reconnecting are kept in the outbox, until they can be delivered
                 ui.add_enabled_ui(
-                    matches!(self.client_connection.state, ConnectionState::Connected(_)),
+                    matches!(
+                        self.server_session.client_connection.state,
+                        ConnectionState::Connected(_) | ConnectionState::Reconnecting
+                    ),
                     |ui| {
                         let msg_tray = self.message_tray(ui, ctx);
 
@@ -460,7 +695,10 @@ impl Application
 
             //Messages go here, check if there is a connection
             ui.add_enabled_ui(
-                matches!(self.client_connection.state, ConnectionState::Connected(_)),
+                matches!(
+                    self.server_session.client_connection.state,
+                    ConnectionState::Connected(_)
+                ),
                 |ui| {
                     self.client_ui_message_main(ui, ctx);
                 },
@@ -499,7 +737,7 @@ impl Application
                 egui::ScrollArea::new([true, true]).auto_shrink([false, true]).show(ui, |ui|{
                     ui.allocate_ui(ui.available_size(), |ui|{
                         let mut has_search = false;
-                        for (index, message) in self.client_ui.incoming_messages.message_list.iter().enumerate() {
+                        for (index, message) in self.server_session.incoming_messages.message_list.iter().enumerate() {
                             match self.client_ui.search_parameter {
                                 SearchType::Name => {
                                     if let ServerMessageType::Normal(inner_message) = &message.message_type {
@@ -681,7 +919,7 @@ impl Application
             });
 
         //Server receiver
-        self.client_recv(ctx);
+        self.with_opened_session(|app, session| app.client_recv(session, ctx));
 
         //Client voip thread managemant
         self.client_voip_thread(ctx);
@@ -748,16 +986,372 @@ impl Application
         }
     }
 
+    /// Creates the ```Voip``` instance, which connects to the voice room selected in ```voip_room``` once its created
+    fn join_voice_room(&mut self)
+    {
+        //Move sender into thread
+        let sender = self.voip_connection_sender.clone();
+
+        //Reset shutdown token State, if we had cancelled this token we must create a new one in order to reset its state
+        //else its going to be cancelled and new threads will shut dwon immediately
+        self.server_session.voip_shutdown_token = CancellationToken::new();
+
+        let toasts = self.toasts.clone();
+
+        match self.server_session.send_on_ip.parse::<SocketAddr>() {
+            Ok(socket_addr) => {
+                //Spawn thread which will create the ```Voip``` instance
+                tokio::spawn(async move {
+                    match socket_addr.is_ipv6() {
+                        true => {
+                            match Voip::new("[::]:0".to_string()).await {
+                                Ok(voip) => {
+                                    // It is okay to unwrap since it doesnt matter if we panic
+                                    sender.send(voip).unwrap();
+                                },
+                                Err(err_ipv6) => {
+                                    display_error_message(
+                                        format!("Could not bind to IPv6: {err_ipv6}"),
+                                        toasts,
+                                    );
+                                },
+                            }
+                        },
+                        false => {
+                            match Voip::new("0.0.0.0:0".to_string()).await {
+                                Ok(voip) => {
+                                    sender.send(voip).unwrap();
+                                },
+                                Err(err_ipv4) => {
+                                    display_error_message(
+                                        format!("Could not bind to IPv4: {err_ipv4}"),
+                                        toasts,
+                                    );
+                                },
+                            }
+                        },
+                    }
+                });
+            },
+            Err(_err) => {
+                display_error_message("Invalid address.", self.toasts.clone());
+            },
+        };
+
+        //Lua callback
+        self.client_ui.extension.event_call_extensions(
+            crate::app::lua::EventCall::OnCallSend,
+            &self.lua,
+            None,
+        );
+    }
+
+    /// Creates a private voice room and joins it, then invites the client to it
+    /// The invited client's ui will ring until they answer, or the invitation times out
+    pub fn invite_to_private_call(&mut self, invitee: String)
+    {
+        //The name of the private room is generated, so it cannot be guessed by the other clients
+        let room = format!("{PRIVATE_VOICE_ROOM_PREFIX}{}", uuid::Uuid::new_v4());
+
+        self.server_session.voip_room.clone_from(&room);
+
+        //The server doesnt depend on the order of the invitation and the connection, as the invitation also allows the caller to join the room
+        self.send_msg(ClientMessage::construct_voip_invite(
+            &self.opened_user_information.uuid,
+            invitee.clone(),
+            room.clone(),
+        ));
+
+        self.client_ui.outgoing_call_invitation = Some(OutgoingCallInvitation { invitee, room });
+
+        self.join_voice_room();
+    }
+
+    /// Displays the servers we are connected to as tabs, the opened server is always the first one
+    /// The servers which arent opened display the amount of messages received since they were opened
+    fn server_switcher_ui(&mut self, ctx: &egui::Context)
+    {
+        egui::TopBottomPanel::top("server_switcher").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                //The call would be left without ui, so the servers cant be switched during a call
+                let can_switch = self.server_session.voip.is_none();
+
+                ui.add(egui::SelectableLabel::new(
+                    true,
+                    server_tab_text(&self.server_session.send_on_ip, 0, false),
+                ));
+
+                let mut switch_to = None;
+
+                for (index, session) in self.server_sessions.iter().enumerate() {
+                    if ui
+                        .add_enabled(
+                            can_switch,
+                            egui::SelectableLabel::new(
+                                false,
+                                server_tab_text(
+                                    &session.send_on_ip,
+                                    session.unread_messages,
+                                    !session.incoming_call_invitations.is_empty(),
+                                ),
+                            ),
+                        )
+                        .on_disabled_hover_text("Leave the call before switching servers")
+                        .clicked()
+                    {
+                        switch_to = Some(index);
+                    }
+                }
+
+                if let Some(index) = switch_to {
+                    self.switch_server(index);
+                }
+
+                if ui
+                    .add_enabled(can_switch, egui::Button::new("➕"))
+                    .on_hover_text("Connect to another server")
+                    .clicked()
+                {
+                    self.add_server();
+                }
+
+                if !self.server_sessions.is_empty()
+                    && ui
+                        .add_enabled(can_switch, egui::Button::new("✖"))
+                        .on_hover_text("Disconnect from the opened server and close it")
+                        .clicked()
+                {
+                    self.close_server();
+                }
+            });
+        });
+    }
+
+    /// Displays the messages of the outbox sent to this server, with their delivery state
+    /// The failed messages can be sent again or discarded
+    fn outbox_ui(&mut self, ui: &mut egui::Ui)
+    {
+        //Only the previews are collected, so the files' bytes dont have to be cloned every frame
+        let entries = {
+            let mut outbox = self.outbox.lock().unwrap();
+
+            outbox.remove_delivered();
+
+            outbox
+                .entries()
+                .iter()
+                .filter(|entry| entry.server_address == self.server_session.send_on_ip)
+                .map(|entry| {
+                    let preview = match &entry.message.message_type {
+                        ClientMessageType::NormalMessage(message) => message.message.clone(),
+                        ClientMessageType::FileUpload(upload) => {
+                            format!(
+                                "📁 {}.{}",
+                                upload.name.clone().unwrap_or_default(),
+                                upload.extension.clone().unwrap_or_default()
+                            )
+                        },
+                        _ => String::new(),
+                    };
+
+                    (entry.message_id().to_string(), entry.state, preview)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (message_id, state, preview) in entries {
+            ui.horizontal(|ui| {
+                match state {
+                    DeliveryState::Pending => {
+                        ui.label(RichText::from("🕓 Sending").color(Color32::GRAY));
+                    },
+                    DeliveryState::Sent => {
+                        ui.label(RichText::from("✔ Sent").color(Color32::GREEN));
+                    },
+                    DeliveryState::Failed => {
+                        ui.label(RichText::from("⚠ Failed to send").color(Color32::RED));
+                    },
+                }
+
+                ui.add(egui::Label::new(RichText::from(preview).weak()).truncate());
+
+                if state == DeliveryState::Failed
+                    && ui
+                        .add_enabled(
+                            matches!(
+                                self.server_session.client_connection.state,
+                                ConnectionState::Connected(_)
+                            ),
+                            egui::Button::new("Retry"),
+                        )
+                        .clicked()
+                {
+                    self.retry_outbox_message(&message_id);
+                }
+
+                if state != DeliveryState::Sent && ui.button("Discard").clicked() {
+                    self.outbox.lock().unwrap().discard(&message_id);
+                }
+            });
+        }
+    }
+
+    /// Displays a ringing window for every incoming call invitation, the server closes the windows of the invitations which have timed out
+    fn call_invitations_ui(&mut self, ctx: &egui::Context)
+    {
+        let incoming_call_invitations = self.server_session.incoming_call_invitations.clone();
+
+        for invitation in incoming_call_invitations {
+            let elapsed = invitation.received_at.elapsed();
+
+            let answer = {
+                let username = self
+                    .server_session
+                    .incoming_messages
+                    .connected_clients_profile
+                    .get(&invitation.caller)
+                    .map(|profile| profile.username.clone())
+                    .unwrap_or_else(|| invitation.caller.clone());
+
+                let mut answer = None;
+
+                egui::Window::new("Incoming call")
+                    .id(Id::new(&invitation.room))
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(Align2::RIGHT_TOP, vec2(-20., 60.))
+                    .show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            self.display_icon_from_server(ctx, invitation.caller.clone(), ui);
+
+                            ui.label(
+                                RichText::from(format!("📞 @{username} is calling you")).strong(),
+                            );
+                        });
+
+                        ui.label(
+                            RichText::from(format!(
+                                "Ringing for {} more seconds",
+                                INVITATION_TIMEOUT.saturating_sub(elapsed).as_secs()
+                            ))
+                            .weak(),
+                        );
+
+                        ui.horizontal(|ui| {
+                            //We can only accept the call if we arent in another one
+                            if ui
+                                .add_enabled(
+                                    self.server_session.voip.is_none() && self.atx.is_none(),
+                                    egui::Button::new(
+                                        RichText::from("Accept").color(Color32::GREEN),
+                                    ),
+                                )
+                                .on_disabled_hover_text(
+                                    "Leave your current call to accept this one",
+                                )
+                                .clicked()
+                            {
+                                answer = Some(InvitationAnswer::Accepted);
+                            }
+
+                            if ui
+                                .button(RichText::from("Decline").color(Color32::RED))
+                                .clicked()
+                            {
+                                answer = Some(InvitationAnswer::Declined);
+                            }
+                        });
+                    });
+
+                //Update the countdown
+                ctx.request_repaint_after(std::time::Duration::from_secs(1));
+
+                answer
+            };
+
+            if let Some(answer) = answer {
+                self.server_session
+                    .incoming_call_invitations
+                    .retain(|incoming_invitation| incoming_invitation.room != invitation.room);
+
+                self.send_msg(ClientMessage::construct_voip_invitation_answer(
+                    &self.opened_user_information.uuid,
+                    invitation.room.clone(),
+                    answer,
+                ));
+
+                if answer == InvitationAnswer::Accepted {
+                    self.server_session.voip_room.clone_from(&invitation.room);
+
+                    self.join_voice_room();
+                }
+            }
+        }
+    }
+
+    /// Asks for the path of the recording, then starts recording the call and notifies the participants
+    fn start_call_recording(&mut self, voip: &Voip)
+    {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Save recording to")
+            .set_file_name(format!(
+                "call_{}.ogg",
+                chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+            ))
+            .add_filter("Ogg/Opus", &["ogg", "opus"])
+            .save_file()
+        else {
+            return;
+        };
+
+        match CallRecorder::start(path, self.client_ui.record_call_video) {
+            Ok(call_recorder) => {
+                *voip.call_recorder.lock().unwrap() = Some(call_recorder);
+
+                self.send_msg(ClientMessage::construct_voip_event(
+                    self.opened_user_information.uuid.clone(),
+                    crate::app::backend::ClientVoipRequest::RecordingStarted,
+                ));
+            },
+            Err(err) => {
+                tracing::error!("{err}");
+
+                display_error_message(
+                    format!("Failed to start recording: {err}"),
+                    self.toasts.clone(),
+                );
+            },
+        }
+    }
+
+    /// Stops the recording of the call if there is one, and notifies the participants
+    fn stop_call_recording(&mut self, voip: &Voip)
+    {
+        let call_recorder = voip.call_recorder.lock().unwrap().take();
+
+        if let Some(call_recorder) = call_recorder {
+            call_recorder.stop();
+
+            self.send_msg(ClientMessage::construct_voip_event(
+                self.opened_user_information.uuid.clone(),
+                crate::app::backend::ClientVoipRequest::RecordingStopped,
+            ));
+        }
+    }
+
     fn disable_camera(&mut self, voip: Voip)
     {
         let uuid = self.opened_user_information.uuid.clone();
         //Drop camera handle
-        voip.remove_camera_handle(&self.client_connection.client_secret, uuid.clone());
+        voip.remove_camera_handle(
+            &self.server_session.client_connection.client_secret,
+            uuid.clone(),
+        );
 
         voip.camera_handle_is_open.store(false, Relaxed);
 
         //Cancel webcam recording
-        self.voip_video_shutdown_token.cancel();
+        self.server_session.voip_video_shutdown_token.cancel();
 
         //Send image disconnection message
         self.send_msg(ClientMessage::construct_voip_event(
@@ -766,3 +1360,51 @@ impl Application
         ));
     }
 }
+
+/// Creates the text displaying the round trip time and the packet loss of a connection, the color of the text shows how good the connection is
+/// Returns the text of a server's tab, the servers with unread messages are highlighted
+fn server_tab_text(address: &str, unread_messages: usize, is_ringing: bool) -> RichText
+{
+    let mut text = if address.is_empty() {
+        "New server".to_string()
+    }
+    else {
+        address.to_string()
+    };
+
+    if is_ringing {
+        text.insert_str(0, "📞 ");
+    }
+
+    if unread_messages > 0 {
+        return RichText::from(format!("{text} ({unread_messages})")).strong();
+    }
+
+    RichText::from(text)
+}
+
+fn connection_quality_text(connection_quality: &ConnectionQuality) -> RichText
+{
+    let loss_fraction = connection_quality.loss_fraction();
+
+    let Some(round_trip_time_ms) = connection_quality.round_trip_time_ms
+    else {
+        return RichText::from("📶 Measuring...").weak();
+    };
+
+    let color = if loss_fraction > 0.1 || round_trip_time_ms > 300. {
+        Color32::RED
+    }
+    else if loss_fraction > 0.02 || round_trip_time_ms > 150. {
+        Color32::YELLOW
+    }
+    else {
+        Color32::GREEN
+    };
+
+    RichText::from(format!(
+        "📶 {round_trip_time_ms:.0} ms, {:.0}% loss",
+        loss_fraction * 100.
+    ))
+    .color(color)
+}
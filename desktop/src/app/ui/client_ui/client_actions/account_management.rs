@@ -0,0 +1,256 @@
+use std::{
+    env, fs,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Error, Result};
+use argon2::Config;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use rand::RngCore;
+
+use crate::app::{
+    backend::{account_file_backups, decrypt_aes256, encrypt, encrypt_aes256, UserInformation},
+    ui::register::create_dynamic_image_from_bytes,
+};
+
+use super::message_cache::MessageCache;
+
+/// The extension of the exported account bundles
+pub const ACCOUNT_BUNDLE_EXTENSION: &str = "mbundle";
+
+/// The version of the bundle's format, the bundles saved by newer versions can not be imported
+const ACCOUNT_BUNDLE_VERSION: u32 = 1;
+
+/// An exported account, this can be imported on another computer
+/// The account is encrypted with a key derived from the password of the bundle, so the bundle can be shared over untrusted channels
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AccountBundle
+{
+    version: u32,
+
+    /// The salt of the key derivation, this is generated for every export
+    salt: String,
+
+    /// The encrypted ```AccountBundleContents```
+    data: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AccountBundleContents
+{
+    username: String,
+
+    /// The account file as it is stored on the disk, this is still encrypted with the password of the account
+    account_file: String,
+}
+
+/// Encrypts the account file with the new password
+/// The message caches are encrypted again too, and the backups which could still be opened with the old password are deleted
+pub fn change_password(
+    user_information: &mut UserInformation,
+    current_password: &str,
+    new_password: &str,
+) -> Result<()>
+{
+    ensure!(
+        user_information.verify_password(current_password.to_string()),
+        "The current password is incorrect!"
+    );
+    ensure!(
+        !new_password.trim().is_empty(),
+        "The new password cant be empty!"
+    );
+
+    let old_password = user_information.password.clone();
+
+    let mut updated_user_information = user_information.clone();
+
+    updated_user_information.password = encrypt(new_password.to_string());
+
+    updated_user_information.write_file(updated_user_information.path.clone())?;
+
+    *user_information = updated_user_information;
+
+    //The account is already encrypted with the new password, the caches can be downloaded again if this fails
+    if let Err(err) = MessageCache::change_password(
+        &user_information.uuid,
+        &old_password,
+        &user_information.password,
+    ) {
+        tracing::error!("Failed to encrypt the message caches with the new password: {err}");
+    }
+
+    for backup in account_file_backups(&user_information.path) {
+        secure_delete_file(&backup)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the account into a bundle protected by its own password
+pub fn export_account(
+    user_information: &UserInformation,
+    bundle_password: &str,
+    bundle_path: &Path,
+) -> Result<()>
+{
+    ensure!(
+        !bundle_password.is_empty(),
+        "The password of the bundle cant be empty!"
+    );
+
+    let contents = AccountBundleContents {
+        username: user_information.username.clone(),
+        account_file: user_information.serialize()?,
+    };
+
+    let salt = rand::random::<[u8; 16]>();
+
+    let bundle = AccountBundle {
+        version: ACCOUNT_BUNDLE_VERSION,
+        salt: hex::encode(salt),
+        data: encrypt_aes256(
+            serde_json::to_string(&contents)?,
+            &derive_bundle_key(bundle_password, &salt)?,
+        )?,
+    };
+
+    fs::write(bundle_path, serde_json::to_string(&bundle)?)?;
+
+    Ok(())
+}
+
+/// Saves the account of the bundle next to the other accounts, returns the name of the imported account
+/// The imported account can be logged into with its own password, not with the password of the bundle
+pub fn import_account(bundle_path: &Path, bundle_password: &str) -> Result<String>
+{
+    let bundle = serde_json::from_str::<AccountBundle>(&fs::read_to_string(bundle_path)?)
+        .map_err(|_| Error::msg("The file is not a Matthias account bundle!"))?;
+
+    ensure!(
+        bundle.version <= ACCOUNT_BUNDLE_VERSION,
+        "The bundle has been exported by a newer version of Matthias, please update the application."
+    );
+
+    let key = derive_bundle_key(bundle_password, &hex::decode(bundle.salt)?)?;
+
+    let contents = serde_json::from_str::<AccountBundleContents>(
+        &decrypt_aes256(&bundle.data, &key)
+            .map_err(|_| Error::msg("Invalid password for the bundle!"))?,
+    )?;
+
+    //The name is used as the name of the account file
+    ensure!(
+        !contents.username.is_empty()
+            && !contents
+                .username
+                .contains(|character: char| " @/\\.:".contains(character)),
+        "The bundle contains an invalid username!"
+    );
+
+    let app_data = env::var("APPDATA")?;
+
+    let account_path = PathBuf::from(format!("{app_data}\\Matthias\\{}.szch", contents.username));
+
+    ensure!(
+        fs::metadata(&account_path).is_err(),
+        "An account named {} already exists on this computer!",
+        contents.username
+    );
+
+    fs::write(&account_path, contents.account_file)?;
+
+    Ok(contents.username)
+}
+
+/// Deletes every file of the account, the files are overwritten before they are removed
+pub fn delete_account(user_information: &UserInformation, password: &str) -> Result<()>
+{
+    ensure!(
+        user_information.verify_password(password.to_string()),
+        "Incorrect password!"
+    );
+
+    MessageCache::delete_caches_of_account(&user_information.uuid, &user_information.password)?;
+
+    let outbox_path = user_information.path.with_extension("outbox");
+
+    if outbox_path.exists() {
+        secure_delete_file(&outbox_path)?;
+    }
+
+    for backup in account_file_backups(&user_information.path) {
+        secure_delete_file(&backup)?;
+    }
+
+    secure_delete_file(&user_information.path)?;
+
+    Ok(())
+}
+
+/// Overwrites the file with random bytes before removing it, so its contents cant be easily recovered from the disk
+pub fn secure_delete_file(path: &Path) -> Result<()>
+{
+    let length = fs::metadata(path)?.len() as usize;
+
+    let mut random_bytes = vec![0; length];
+
+    rand::thread_rng().fill_bytes(&mut random_bytes);
+
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+
+    file.write_all(&random_bytes)?;
+
+    //Make sure the random bytes are written to the disk before the file is removed
+    file.sync_all()?;
+
+    drop(file);
+
+    fs::remove_file(path)?;
+
+    Ok(())
+}
+
+/// Reads the picture and creates the profile pictures from it, the picture is cropped to a square around its center
+/// Returns the 256x256 and the 64x64 profile pictures encoded as pngs
+pub fn create_profile_pictures(picture_path: &Path) -> Result<(Vec<u8>, Vec<u8>)>
+{
+    let picture = create_dynamic_image_from_bytes(&fs::read(picture_path)?)?;
+
+    let size_of_side = picture.width().min(picture.height());
+
+    let picture = picture.crop_imm(
+        (picture.width() - size_of_side) / 2,
+        (picture.height() - size_of_side) / 2,
+        size_of_side,
+        size_of_side,
+    );
+
+    Ok((
+        encode_profile_picture(&picture, 256)?,
+        encode_profile_picture(&picture, 64)?,
+    ))
+}
+
+fn encode_profile_picture(picture: &DynamicImage, size_of_side: u32) -> Result<Vec<u8>>
+{
+    let mut bytes = Cursor::new(Vec::new());
+
+    picture
+        .resize(size_of_side, size_of_side, FilterType::CatmullRom)
+        .write_to(&mut bytes, ImageFormat::Png)?;
+
+    Ok(bytes.into_inner())
+}
+
+/// Derives the key of the bundle, a slow hash is used so the password of the bundle cant be easily brute forced
+fn derive_bundle_key(bundle_password: &str, salt: &[u8]) -> Result<Vec<u8>>
+{
+    let config = Config {
+        hash_length: 32,
+        ..Config::owasp1()
+    };
+
+    Ok(argon2::hash_raw(bundle_password.as_bytes(), salt, &config)?)
+}
@@ -0,0 +1,335 @@
+use cpal::SupportedStreamConfig;
+
+use super::voice_activity::calculate_loudness_db;
+
+/// The cutoff frequency of the high-pass filter, this removes rumble and the DC offset of the microphone
+const HIGH_PASS_CUTOFF_HZ: f32 = 80.;
+
+/// The length of the blocks the noise suppression and the gain control work on
+const PROCESSING_BLOCK_LENGTH_MS: usize = 10;
+
+/// The amount (in dB) a block has to be louder than the noise floor to be left untouched by the noise suppression
+const NOISE_GATE_MARGIN_DB: f32 = 6.;
+
+/// The attenuation (in dB) of the blocks considered noise
+const NOISE_ATTENUATION_DB: f32 = -30.;
+
+/// The speed (in dB per block) the estimated noise floor rises, it falls immediately
+const NOISE_FLOOR_RISE_DB: f32 = 0.05;
+
+/// If the speech lasts longer than this, the background noise has probably gotten louder, so the noise floor starts rising during it too
+const MAX_SPEECH_LENGTH_MS: usize = 5000;
+
+/// The loudness the automatic gain control is trying to reach
+const AGC_TARGET_DB: f32 = -20.;
+
+/// The highest gain the automatic gain control can apply
+const AGC_MAX_GAIN_DB: f32 = 20.;
+
+/// The lowest gain the automatic gain control can apply
+const AGC_MIN_GAIN_DB: f32 = -10.;
+
+/// The speed (in dB per block) the automatic gain control is allowed to change its gain
+const AGC_GAIN_STEP_DB: f32 = 0.5;
+
+/// The settings of the microphone's audio processing, each stage can be turned on or off in the settings
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AudioProcessingSettings
+{
+    /// Removes low frequency rumble (ie. desk bumps, wind) from the audio
+    pub high_pass_filter: bool,
+
+    /// Attenuates the audio while its loudness is close to the estimated noise floor (ie. fans, keyboard noise between words)
+    pub noise_suppression: bool,
+
+    /// Keeps the loudness of the speech at a steady level
+    pub automatic_gain_control: bool,
+}
+
+impl Default for AudioProcessingSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            high_pass_filter: true,
+            noise_suppression: true,
+            automatic_gain_control: false,
+        }
+    }
+}
+
+impl AudioProcessingSettings
+{
+    /// Returns whether any of the stages are enabled
+    pub fn is_enabled(&self) -> bool
+    {
+        self.high_pass_filter || self.noise_suppression || self.automatic_gain_control
+    }
+}
+
+/// The DSP stage between capturing and encoding the microphone's audio.
+/// This struct keeps the state of the filters, so the same instance should be used for consecutive buffers of the same recording.
+#[derive(Debug, Clone)]
+pub struct AudioProcessor
+{
+    /// The enabled stages of the processor
+    pub settings: AudioProcessingSettings,
+
+    /// The amount of interleaved channels of the samples
+    channels: usize,
+
+    /// The sample rate of the samples
+    sample_rate: u32,
+
+    /// The last input and output sample of the high-pass filter for every channel
+    high_pass_state: Vec<(f32, f32)>,
+
+    /// The estimated loudness of the background noise in dBFS
+    noise_floor_db: f32,
+
+    /// The amount of consecutive blocks considered speech
+    speech_blocks: usize,
+
+    /// The current gain of the noise suppression in dB
+    noise_gain_db: f32,
+
+    /// The current gain of the automatic gain control in dB
+    agc_gain_db: f32,
+}
+
+impl AudioProcessor
+{
+    pub fn new(settings: AudioProcessingSettings, channels: u16, sample_rate: u32) -> Self
+    {
+        Self {
+            settings,
+            channels: channels.max(1) as usize,
+            sample_rate,
+            high_pass_state: vec![(0., 0.); channels.max(1) as usize],
+            //The noise floor falls to the level of the first block immediately
+            noise_floor_db: 0.,
+            speech_blocks: 0,
+            noise_gain_db: 0.,
+            agc_gain_db: 0.,
+        }
+    }
+
+    /// Processes the interleaved samples in place, based on the enabled stages
+    pub fn process(&mut self, samples: &mut [f32])
+    {
+        if self.settings.high_pass_filter {
+            self.high_pass_filter(samples);
+        }
+
+        if !(self.settings.noise_suppression || self.settings.automatic_gain_control) {
+            return;
+        }
+
+        let block_length =
+            (self.sample_rate as usize * PROCESSING_BLOCK_LENGTH_MS / 1000 * self.channels).max(1);
+
+        for block in samples.chunks_mut(block_length) {
+            let loudness_db = calculate_loudness_db(block);
+
+            let is_speech = loudness_db > self.noise_floor_db + NOISE_GATE_MARGIN_DB;
+
+            self.speech_blocks = if is_speech { self.speech_blocks + 1 } else { 0 };
+
+            //The noise floor is only adapted to the blocks between the words, so that speech doesnt raise it
+            if !is_speech || self.speech_blocks > MAX_SPEECH_LENGTH_MS / PROCESSING_BLOCK_LENGTH_MS
+            {
+                //The noise floor falls immediately, but only rises slowly
+                self.noise_floor_db = if loudness_db < self.noise_floor_db {
+                    loudness_db
+                }
+                else {
+                    self.noise_floor_db + NOISE_FLOOR_RISE_DB
+                };
+            }
+
+            let mut gain_db = 0.;
+
+            if self.settings.noise_suppression {
+                let target_gain_db = if is_speech { 0. } else { NOISE_ATTENUATION_DB };
+
+                //Open the gate quickly, but close it slowly, so the ends of the words arent cut off
+                self.noise_gain_db = if target_gain_db > self.noise_gain_db {
+                    target_gain_db
+                }
+                else {
+                    (self.noise_gain_db - 3.).max(target_gain_db)
+                };
+
+                gain_db += self.noise_gain_db;
+            }
+
+            if self.settings.automatic_gain_control {
+                //Only adjust the gain on speech, so the background noise doesnt get amplified
+                if is_speech {
+                    let desired_gain_db =
+                        (AGC_TARGET_DB - loudness_db).clamp(AGC_MIN_GAIN_DB, AGC_MAX_GAIN_DB);
+
+                    self.agc_gain_db += (desired_gain_db - self.agc_gain_db)
+                        .clamp(-AGC_GAIN_STEP_DB, AGC_GAIN_STEP_DB);
+                }
+
+                gain_db += self.agc_gain_db;
+            }
+
+            let multiplier = 10_f32.powf(gain_db / 20.);
+
+            for sample in block.iter_mut() {
+                *sample = (*sample * multiplier).clamp(-1., 1.);
+            }
+        }
+    }
+
+    /// A first order high-pass filter applied to every channel
+    fn high_pass_filter(&mut self, samples: &mut [f32])
+    {
+        let rc = 1. / (2. * std::f32::consts::PI * HIGH_PASS_CUTOFF_HZ);
+        let dt = 1. / self.sample_rate as f32;
+        let alpha = rc / (rc + dt);
+
+        for frame in samples.chunks_mut(self.channels) {
+            for (sample, (last_input, last_output)) in
+                frame.iter_mut().zip(self.high_pass_state.iter_mut())
+            {
+                let output = alpha * (*last_output + *sample - *last_input);
+
+                *last_input = *sample;
+                *last_output = output;
+                *sample = output;
+            }
+        }
+    }
+}
+
+/// Processes a whole recording (ie. a voice message) recorded with the ```config```, based on the settings
+pub fn process_recording(
+    samples: &mut [f32],
+    settings: AudioProcessingSettings,
+    config: &SupportedStreamConfig,
+)
+{
+    if !settings.is_enabled() {
+        return;
+    }
+
+    AudioProcessor::new(settings, config.channels(), config.sample_rate().0).process(samples);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    /// The length of a processing block in samples at ```SAMPLE_RATE```
+    const BLOCK_LENGTH: usize = SAMPLE_RATE as usize * PROCESSING_BLOCK_LENGTH_MS / 1000;
+
+    fn noise_suppressor() -> AudioProcessor
+    {
+        AudioProcessor::new(
+            AudioProcessingSettings {
+                high_pass_filter: false,
+                noise_suppression: true,
+                automatic_gain_control: false,
+            },
+            1,
+            SAMPLE_RATE,
+        )
+    }
+
+    /// A square wave, so every block has the same loudness
+    fn signal(amplitude: f32, blocks: usize) -> Vec<f32>
+    {
+        (0..BLOCK_LENGTH * blocks)
+            .map(|index| {
+                if index % 2 == 0 {
+                    amplitude
+                }
+                else {
+                    -amplitude
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn speech_doesnt_raise_noise_floor()
+    {
+        let mut processor = noise_suppressor();
+
+        processor.process(&mut signal(0.001, 10));
+
+        let noise_floor_db = processor.noise_floor_db;
+
+        let mut speech = signal(0.5, 100);
+
+        processor.process(&mut speech);
+
+        assert_eq!(processor.noise_floor_db, noise_floor_db);
+
+        //The speech is left untouched
+        assert!(speech.iter().all(|sample| sample.abs() == 0.5));
+    }
+
+    #[test]
+    fn attenuates_noise()
+    {
+        let mut processor = noise_suppressor();
+
+        processor.process(&mut signal(0.5, 10));
+
+        let mut noise = signal(0.001, 50);
+
+        processor.process(&mut noise);
+
+        let attenuated_db = calculate_loudness_db(&noise[noise.len() - BLOCK_LENGTH..]);
+
+        assert!(
+            attenuated_db < calculate_loudness_db(&signal(0.001, 1)) + NOISE_ATTENUATION_DB / 2.
+        );
+    }
+
+    #[test]
+    fn follows_louder_noise()
+    {
+        let mut processor = noise_suppressor();
+
+        processor.process(&mut signal(0.001, 10));
+
+        let noise_floor_db = processor.noise_floor_db;
+
+        //The background noise got permanently louder
+        processor.process(&mut signal(
+            0.1,
+            MAX_SPEECH_LENGTH_MS / PROCESSING_BLOCK_LENGTH_MS * 2,
+        ));
+
+        assert!(processor.noise_floor_db > noise_floor_db);
+    }
+
+    #[test]
+    fn removes_dc_offset()
+    {
+        let mut processor = AudioProcessor::new(
+            AudioProcessingSettings {
+                high_pass_filter: true,
+                noise_suppression: false,
+                automatic_gain_control: false,
+            },
+            1,
+            48000,
+        );
+
+        let mut samples = vec![0.5; 48000];
+
+        processor.process(&mut samples);
+
+        assert!(samples.last().unwrap().abs() < 0.001);
+    }
+}
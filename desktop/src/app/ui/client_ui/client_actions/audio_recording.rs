@@ -1,7 +1,7 @@
 use anyhow::Error;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, Sample, SupportedStreamConfig,
+    Device, Sample, SampleRate, StreamError, SupportedStreamConfig,
 };
 use hound::WavWriter;
 use opus::Encoder;
@@ -11,7 +11,7 @@ use std::{
     io::{BufWriter, Cursor},
     sync::{
         atomic::{AtomicBool, AtomicI64, Ordering::Relaxed},
-        mpsc::{self, Receiver},
+        mpsc::{self, Receiver, RecvTimeoutError},
         Arc, Mutex,
     },
     thread::JoinHandle,
@@ -24,30 +24,107 @@ pub const SAMPLE_RATE: usize = 48000;
 pub const STEREO_PACKET_BUFFER_LENGTH: usize =
     SAMPLE_RATE * 2 * VOIP_PACKET_BUFFER_LENGTH_MS / 1000;
 
-struct Opt
+/// The sample rates offered in the settings, if the input device supports them
+pub const COMMON_SAMPLE_RATES: [u32; 5] = [16000, 22050, 44100, 48000, 96000];
+
+/// The amount of time we wait before trying to reopen a lost input device
+const INPUT_DEVICE_REOPEN_DELAY: Duration = Duration::from_secs(1);
+
+/// The interval the recording thread checks for interrupts, device errors and settings changes on
+const INPUT_DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The input device settings, these are modified in the settings
+/// If a field is ```None``` the default of the device (or the default device itself) is used
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct InputDeviceSettings
 {
-    /// The audio device to use
-    device: String,
-
-    /// Use the JACK host
-    #[cfg(all(any(
-        target_os = "linux",
-        target_os = "dragonfly",
-        target_os = "freebsd",
-        target_os = "netbsd"
-    ),))]
-    #[arg(short, long)]
-    #[allow(dead_code)]
-    jack: bool,
+    /// The name of the input device
+    pub device: Option<String>,
+
+    /// The sample rate the device is opened with
+    pub sample_rate: Option<u32>,
+
+    /// The amount of channels the device is opened with
+    pub channels: Option<u16>,
 }
-impl Default for Opt
+
+/// The information about an input device displayed in the settings
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo
 {
-    fn default() -> Self
-    {
-        Self {
-            device: "default".into(),
+    /// The name of the device
+    pub name: String,
+
+    /// The channel counts supported by the device
+    pub channels: Vec<u16>,
+
+    /// The sample rates from ```COMMON_SAMPLE_RATES``` which are supported by the device
+    pub sample_rates: Vec<u32>,
+}
+
+/// Returns all the input devices of the default host, with their supported configs
+pub fn get_input_devices() -> anyhow::Result<Vec<InputDeviceInfo>>
+{
+    let mut input_devices = Vec::new();
+
+    for device in cpal::default_host().input_devices()? {
+        let Ok(name) = device.name()
+        else {
+            continue;
+        };
+
+        let mut channels = Vec::new();
+        let mut sample_rates = Vec::new();
+
+        if let Ok(supported_configs) = device.supported_input_configs() {
+            for supported_config in supported_configs {
+                if !channels.contains(&supported_config.channels()) {
+                    channels.push(supported_config.channels());
+                }
+
+                for sample_rate in COMMON_SAMPLE_RATES {
+                    if (supported_config.min_sample_rate().0..=supported_config.max_sample_rate().0)
+                        .contains(&sample_rate)
+                        && !sample_rates.contains(&sample_rate)
+                    {
+                        sample_rates.push(sample_rate);
+                    }
+                }
+            }
         }
+
+        channels.sort();
+        sample_rates.sort();
+
+        input_devices.push(InputDeviceInfo {
+            name,
+            channels,
+            sample_rates,
+        });
     }
+
+    Ok(input_devices)
+}
+
+/// Returns the information of the input device selected in the ```input_device``` settings (Or the default input device if none is selected)
+pub fn get_input_device_info(input_device: &InputDeviceSettings)
+    -> anyhow::Result<InputDeviceInfo>
+{
+    let device_name = match &input_device.device {
+        Some(device_name) => device_name.clone(),
+        None => {
+            cpal::default_host()
+                .default_input_device()
+                .ok_or_else(|| Error::msg("Failed to find input device"))?
+                .name()?
+        },
+    };
+
+    get_input_devices()?
+        .into_iter()
+        .find(|device| device.name == device_name)
+        .ok_or_else(|| Error::msg(format!("Input device ({device_name}) is not available")))
 }
 
 /// This function records audio for the passed in duration, then it returns the recorded bytes wrapped in a result
@@ -55,25 +132,10 @@ impl Default for Opt
 pub fn record_audio_for_set_duration(
     dur: Duration,
     amplification_percentage: f32,
+    input_device: &InputDeviceSettings,
 ) -> anyhow::Result<Vec<f32>>
 {
-    let opt = Opt::default();
-
-    let host = cpal::default_host();
-
-    // Set up the input device and stream with the default input config.
-    let device = if opt.device == "default" {
-        host.default_input_device()
-    }
-    else {
-        host.input_devices()?
-            .find(|x| x.name().map(|y| y == opt.device).unwrap_or(false))
-    }
-    .expect("failed to find input device");
-
-    let config = device
-        .default_input_config()
-        .expect("Failed to get default input config");
+    let (device, config) = get_recording_device(input_device)?;
 
     let wav_buffer: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::new()));
 
@@ -101,73 +163,197 @@ pub fn record_audio_for_set_duration(
     Ok(recording)
 }
 
+/// The config the input device has been opened with by ```record_audio_with_interrupt```, this is ```None``` while there is no input device available
+pub type OpenedInputConfig = Arc<Mutex<Option<SupportedStreamConfig>>>;
+
 /// This function returns a handle to a `queue` of bytes (```Arc<Mutex<VecDeque<u8>>>```), while spawning a thread which constantly writes the incoming audio into the buffer
 /// This  `queue` or `buffer` gets updated from left to right, the new element always pushes back all the elements behind it, if the value's index reaches ```idx > queue_length```, it gets dropped.
+/// If the input device is lost (ie. unplugged) or the ```input_device``` settings are modified, the device is reopened without stopping the recording.
+/// The config the device has been opened with is returned too, as it may differ from the settings (ie. the default device is used if the selected one isnt available)
 pub fn record_audio_with_interrupt(
     interrupt: Receiver<()>,
     amplification_percentage: Arc<AtomicI64>,
     buffer_handle: Arc<Mutex<VecDeque<f32>>>,
     should_record: Arc<AtomicBool>,
-) -> anyhow::Result<Arc<Mutex<VecDeque<f32>>>>
+    input_device: Arc<Mutex<InputDeviceSettings>>,
+) -> anyhow::Result<(Arc<Mutex<VecDeque<f32>>>, OpenedInputConfig)>
 {
     let wav_buffer_clone = buffer_handle.clone();
 
+    let opened_config: OpenedInputConfig = Arc::new(Mutex::new(None));
+    let opened_config_clone = opened_config.clone();
+
     let _: JoinHandle<anyhow::Result<()>> = std::thread::spawn(move || {
-        //Create scope, so it will show the compiler that the ```stream``` will NOT be used after the await
-        let (device, config) = get_recording_device()?;
+        loop {
+            //Save the settings we have opened the device with, so we know if they have been modified
+            let opened_with = input_device.lock().unwrap().clone();
 
-        let err_fn = move |err| {
-            eprintln!("An error occurred on stream: {}", err);
-        };
+            let (device, config) = match get_recording_device(&opened_with) {
+                Ok(recording_device) => recording_device,
+                Err(err) => {
+                    tracing::error!("Failed to open input device: {err}");
 
-        let stream = device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| {
-                {
-                    //If the function returned false
-                    if !write_input_data_to_buffer_with_set_len::<f32>(
-                        data,
-                        buffer_handle.clone(),
-                        amplification_percentage.load(Relaxed) as f32 / 100.,
-                        should_record.clone(),
-                    ) {
-                        //Quit the thread, stop recording
+                    if wait_for_input_device(&interrupt, &opened_config) {
+                        continue;
                     }
+
+                    return Ok(());
+                },
+            };
+
+            //The samples recorded with the previous config cant be interpreted with the new one, the buffer is locked so the config and the samples are always read together
+            {
+                let mut buffer = buffer_handle.lock().unwrap();
+
+                buffer.clear();
+
+                *opened_config.lock().unwrap() = Some(config.clone());
+            }
+
+            let (error_sender, error_receiver) = mpsc::channel::<StreamError>();
+
+            let err_fn = move |err| {
+                let _ = error_sender.send(err);
+            };
+
+            let buffer_handle = buffer_handle.clone();
+            let amplification_percentage = amplification_percentage.clone();
+            let should_record = should_record.clone();
+
+            let stream = device
+                .build_input_stream(
+                    &config.into(),
+                    move |data, _: &_| {
+                        {
+                            //If the function returned false
+                            if !write_input_data_to_buffer_with_set_len::<f32>(
+                                data,
+                                buffer_handle.clone(),
+                                amplification_percentage.load(Relaxed) as f32 / 100.,
+                                should_record.clone(),
+                            ) {
+                                //Quit the thread, stop recording
+                            }
+                        }
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(anyhow::Error::from)
+                .and_then(|stream| {
+                    stream.play()?;
+
+                    Ok(stream)
+                });
+
+            //The device may disappear again while we are reopening it
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::error!("Failed to start recording on input device: {err}");
+
+                    if wait_for_input_device(&interrupt, &opened_config) {
+                        continue;
+                    }
+
+                    return Ok(());
+                },
+            };
+
+            loop {
+                //Wait for interrupt
+                match interrupt.recv_timeout(INPUT_DEVICE_POLL_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    //End thread
+                    _ => return Ok(()),
                 }
-            },
-            err_fn,
-            None,
-        )?;
 
-        stream.play()?;
+                //Check if the device has been lost, WASAPI reports an unplugged device as a backend specific error, so both errors reopen the device
+                if let Ok(err) = error_receiver.try_recv() {
+                    tracing::warn!("Input device lost ({err}), reopening it");
 
-        //Wait for interrupt
-        interrupt.recv()?;
+                    if wait_for_input_device(&interrupt, &opened_config) {
+                        break;
+                    }
 
-        //End thread
-        Ok(())
+                    return Ok(());
+                }
+
+                //Reopen the device if the settings have been modified
+                if *input_device.lock().unwrap() != opened_with {
+                    break;
+                }
+            }
+
+            //Drop the old stream before opening the new one
+            drop(stream);
+        }
     });
 
-    Ok(wav_buffer_clone)
+    Ok((wav_buffer_clone, opened_config_clone))
+}
+
+/// Marks the input device as unavailable, and waits ```INPUT_DEVICE_REOPEN_DELAY``` before it is opened again
+/// Returns false if the recording has been interrupted while waiting
+fn wait_for_input_device(interrupt: &Receiver<()>, opened_config: &OpenedInputConfig) -> bool
+{
+    *opened_config.lock().unwrap() = None;
+
+    //Wait for the device to come back, or for the interrupt
+    matches!(
+        interrupt.recv_timeout(INPUT_DEVICE_REOPEN_DELAY),
+        Err(RecvTimeoutError::Timeout)
+    )
 }
 
 /// This function fetches the audio recording device, returning a result of the Device and Config handle
-fn get_recording_device() -> Result<(Device, SupportedStreamConfig), Error>
+/// If the device specified in the ```input_device``` settings isnt available, the default input device is used
+/// If the config specified in the ```input_device``` settings isnt supported, the default config of the device is used
+pub fn get_recording_device(
+    input_device: &InputDeviceSettings,
+) -> Result<(Device, SupportedStreamConfig), Error>
 {
-    let opt = Opt::default();
     let host = cpal::default_host();
-    let device = if opt.device == "default" {
-        host.default_input_device()
+
+    let selected_device = match &input_device.device {
+        Some(device_name) => {
+            host.input_devices()?
+                .find(|device| device.name().is_ok_and(|name| name == *device_name))
+        },
+        None => None,
+    };
+
+    let device = match selected_device {
+        Some(device) => device,
+        None => {
+            host.default_input_device()
+                .ok_or_else(|| Error::msg("Failed to find input device"))?
+        },
+    };
+
+    let default_config = device.default_input_config()?;
+
+    //Return the default config if the user hasnt selected anything
+    if input_device.sample_rate.is_none() && input_device.channels.is_none() {
+        return Ok((device, default_config));
     }
-    else {
-        host.input_devices()?
-            .find(|x| x.name().map(|y| y == opt.device).unwrap_or(false))
+
+    let channels = input_device.channels.unwrap_or(default_config.channels());
+    let sample_rate = input_device
+        .sample_rate
+        .unwrap_or(default_config.sample_rate().0);
+
+    let selected_config = device.supported_input_configs()?.find(|supported_config| {
+        supported_config.channels() == channels
+            && supported_config.sample_format() == default_config.sample_format()
+            && (supported_config.min_sample_rate().0..=supported_config.max_sample_rate().0)
+                .contains(&sample_rate)
+    });
+
+    match selected_config {
+        Some(config) => Ok((device, config.with_sample_rate(SampleRate(sample_rate)))),
+        None => Ok((device, default_config)),
     }
-    .expect("failed to find input device");
-    let config = device
-        .default_input_config()
-        .expect("Failed to get default input config");
-    Ok((device, config))
 }
 
 /// This function records audio on a different thread, until the receiver receives something, then the recorded buffer is returned
@@ -175,9 +361,10 @@ fn get_recording_device() -> Result<(Device, SupportedStreamConfig), Error>
 pub fn audio_recording_with_recv(
     receiver: mpsc::Receiver<bool>,
     amplification_percentage: f32,
+    input_device: &InputDeviceSettings,
 ) -> anyhow::Result<Vec<f32>>
 {
-    let (device, config) = get_recording_device()?;
+    let (device, config) = get_recording_device(input_device)?;
 
     let wav_buffer: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::new()));
 
@@ -231,13 +418,12 @@ fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> hound::WavSpec
 }
 
 /// This function creates a wav foramtted audio file, containing the samples provided to this function
-pub fn create_wav_file(samples: Vec<f32>) -> Vec<u8>
+/// The ```config``` should be the config of the device the samples were recorded with
+pub fn create_wav_file(samples: Vec<f32>, config: &SupportedStreamConfig) -> Vec<u8>
 {
     let writer = Arc::new(Mutex::new(Vec::new()));
 
-    let (_, config) = get_recording_device().unwrap();
-
-    let spec = wav_spec_from_config(&config);
+    let spec = wav_spec_from_config(config);
 
     let writer_lock = &mut *writer.lock().unwrap();
 
@@ -0,0 +1,374 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::bail;
+use dashmap::DashMap;
+use egui::ColorImage;
+use image::{imageops::FilterType, RgbImage, RgbaImage};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use opencv::{
+    core::{Mat, Size, Vec3b},
+    prelude::*,
+    videoio::VideoWriter,
+};
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+
+use super::jitter_buffer::AudioFrame;
+
+/// The sample rate of the recorded audio, Opus always operates on 48kHz
+const RECORDING_SAMPLE_RATE: u32 = 48000;
+
+/// The amount of channels of the recorded audio
+const RECORDING_CHANNELS: usize = 2;
+
+/// The length of an encoded Opus packet
+const RECORDING_PACKET_LENGTH: Duration = Duration::from_millis(20);
+
+/// The amount of samples (per channel) in an encoded Opus packet
+const RECORDING_PACKET_SAMPLES: usize = 960;
+
+/// The bitrate of the recorded audio
+const RECORDING_BITRATE_BPS: i32 = 96_000;
+
+/// The largest size of an encoded Opus packet
+const MAX_OPUS_PACKET_LENGTH: usize = 4000;
+
+/// The amount of samples the decoder has to discard from the start of the stream, this is the lookahead of the encoder at 48kHz
+const OPUS_PRE_SKIP: u16 = 312;
+
+/// The serial of the Ogg stream, a recording only contains one logical stream
+const OGG_STREAM_SERIAL: u32 = 1;
+
+/// The amount of samples a participant's audio queue can hold, if a participant's audio falls more behind we drop the backlog so that the recording stays in sync (500ms)
+const MAX_QUEUED_SAMPLES: usize = RECORDING_SAMPLE_RATE as usize / 2 * RECORDING_CHANNELS;
+
+/// The frame rate of the recorded video files
+const RECORDING_FRAME_RATE: f64 = 15.;
+
+/// Records a call into a local file.
+/// The incoming and the outgoing audio is mixed and encoded into an Ogg/Opus file, the received video streams are written into a WebM file per participant next to it.
+/// The encoding happens on background threads, which stop once ```CallRecorder::stop``` is called.
+#[derive(Clone, Debug)]
+pub struct CallRecorder
+{
+    /// The audio waiting to be mixed paired with the uuid of its speaker, the samples are stored in the recording's format
+    audio_queues: Arc<DashMap<String, VecDeque<f32>>>,
+
+    /// The latest frame of the participants' video streams paired with their uuid, this is ```None``` if the video isnt recorded
+    video_frames: Option<Arc<DashMap<String, ColorImage>>>,
+
+    /// This token stops the encoding threads
+    cancellation_token: CancellationToken,
+
+    /// The path of the audio file
+    pub path: PathBuf,
+}
+
+impl CallRecorder
+{
+    /// Creates the audio file at ```path```, and starts recording
+    /// If ```record_video``` is true the received video streams are also recorded
+    pub fn start(path: PathBuf, record_video: bool) -> anyhow::Result<Self>
+    {
+        let mut encoder = opus::Encoder::new(
+            RECORDING_SAMPLE_RATE,
+            opus::Channels::Stereo,
+            opus::Application::Audio,
+        )?;
+
+        encoder.set_bitrate(opus::Bitrate::Bits(RECORDING_BITRATE_BPS))?;
+
+        let mut packet_writer = PacketWriter::new(BufWriter::new(File::create(&path)?));
+
+        write_opus_headers(&mut packet_writer)?;
+
+        let recorder = Self {
+            audio_queues: Arc::new(DashMap::new()),
+            video_frames: record_video.then(|| Arc::new(DashMap::new())),
+            cancellation_token: CancellationToken::new(),
+            path,
+        };
+
+        //Audio encoder thread
+        let audio_queues = recorder.audio_queues.clone();
+        let cancellation_token = recorder.cancellation_token.clone();
+
+        tokio::spawn(async move {
+            let mut packet_interval = tokio::time::interval(RECORDING_PACKET_LENGTH);
+
+            //The amount of samples (per channel) written, this is the granule position of the pages
+            let mut granule_position: u64 = 0;
+
+            loop {
+                let is_last_packet = select! {
+                    _ = packet_interval.tick() => false,
+                    _ = cancellation_token.cancelled() => true,
+                };
+
+                let mixed_samples = mix_audio_queues(&audio_queues);
+
+                granule_position += RECORDING_PACKET_SAMPLES as u64;
+
+                let result = encoder
+                    .encode_vec_float(&mixed_samples, MAX_OPUS_PACKET_LENGTH)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|packet| {
+                        packet_writer.write_packet(
+                            packet,
+                            OGG_STREAM_SERIAL,
+                            if is_last_packet {
+                                PacketWriteEndInfo::EndStream
+                            }
+                            else {
+                                PacketWriteEndInfo::NormalPacket
+                            },
+                            granule_position,
+                        )?;
+
+                        Ok(())
+                    });
+
+                if let Err(err) = result {
+                    tracing::error!("Failed to write call recording: {err}");
+
+                    break;
+                }
+
+                if is_last_packet {
+                    break;
+                }
+            }
+
+            if let Err(err) = packet_writer.into_inner().flush() {
+                tracing::error!("Failed to write call recording: {err}");
+            }
+        });
+
+        //Video writer thread
+        if let Some(video_frames) = recorder.video_frames.clone() {
+            let cancellation_token = recorder.cancellation_token.clone();
+            let path = recorder.path.clone();
+
+            tokio::spawn(async move {
+                let mut frame_interval =
+                    tokio::time::interval(Duration::from_secs_f64(1. / RECORDING_FRAME_RATE));
+
+                //The video writers of the participants paired with their uuid, these are created when their first frame is written
+                let mut video_writers: HashMap<String, (VideoWriter, [usize; 2])> = HashMap::new();
+
+                loop {
+                    select! {
+                        _ = frame_interval.tick() => {
+                            //The last frame is repeated until a new one arrives, so that the video stays in sync with the audio
+                            for entry in video_frames.iter() {
+                                if let Err(err) = write_video_frame(&mut video_writers, &path, entry.key(), entry.value()) {
+                                    tracing::error!("Failed to write video recording: {err}");
+                                }
+                            }
+                        },
+                        _ = cancellation_token.cancelled() => {
+                            break;
+                        },
+                    }
+                }
+
+                for (_, (mut video_writer, _)) in video_writers {
+                    if let Err(err) = video_writer.release() {
+                        tracing::error!("Failed to finish video recording: {err}");
+                    }
+                }
+            });
+        }
+
+        Ok(recorder)
+    }
+
+    /// Adds the audio of a participant to the recording
+    pub fn push_audio(&self, uuid: &str, frame: &AudioFrame)
+    {
+        let samples = frame
+            .convert(RECORDING_CHANNELS as u16, RECORDING_SAMPLE_RATE)
+            .samples;
+
+        let mut audio_queue = self.audio_queues.entry(uuid.to_string()).or_default();
+
+        audio_queue.extend(samples);
+
+        //Drop the backlog if the participant's audio has fallen behind
+        if audio_queue.len() > MAX_QUEUED_SAMPLES {
+            let overflow = audio_queue.len() - MAX_QUEUED_SAMPLES;
+
+            audio_queue.drain(..overflow);
+        }
+    }
+
+    /// Sets the latest frame of a participant's video stream, this is ignored if the video isnt recorded
+    pub fn push_video_frame(&self, uuid: &str, image: &ColorImage)
+    {
+        if let Some(video_frames) = &self.video_frames {
+            video_frames.insert(uuid.to_string(), image.clone());
+        }
+    }
+
+    /// Stops writing the video stream of a participant, this is called when they turn off their camera
+    pub fn remove_video_stream(&self, uuid: &str)
+    {
+        if let Some(video_frames) = &self.video_frames {
+            video_frames.remove(uuid);
+        }
+    }
+
+    /// Returns whether the received video streams are recorded
+    pub fn is_recording_video(&self) -> bool
+    {
+        self.video_frames.is_some()
+    }
+
+    /// Stops the recording, the files are finished by the encoding threads
+    pub fn stop(&self)
+    {
+        self.cancellation_token.cancel();
+    }
+}
+
+/// Writes the identification and the comment header of the Ogg/Opus file (RFC 7845)
+fn write_opus_headers(packet_writer: &mut PacketWriter<BufWriter<File>>) -> anyhow::Result<()>
+{
+    let mut identification_header = b"OpusHead".to_vec();
+
+    //Version
+    identification_header.push(1);
+    identification_header.push(RECORDING_CHANNELS as u8);
+    identification_header.extend(OPUS_PRE_SKIP.to_le_bytes());
+    identification_header.extend(RECORDING_SAMPLE_RATE.to_le_bytes());
+    //Output gain
+    identification_header.extend(0_i16.to_le_bytes());
+    //Channel mapping family
+    identification_header.push(0);
+
+    let vendor = format!("Matthias {}", env!("CARGO_PKG_VERSION"));
+
+    let mut comment_header = b"OpusTags".to_vec();
+
+    comment_header.extend((vendor.len() as u32).to_le_bytes());
+    comment_header.extend(vendor.as_bytes());
+    //User comment list length
+    comment_header.extend(0_u32.to_le_bytes());
+
+    //Both headers must be on their own page
+    packet_writer.write_packet(
+        identification_header,
+        OGG_STREAM_SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    packet_writer.write_packet(
+        comment_header,
+        OGG_STREAM_SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+
+    Ok(())
+}
+
+/// Mixes one packet worth of audio from the participants' queues, the missing samples are filled with silence
+fn mix_audio_queues(audio_queues: &DashMap<String, VecDeque<f32>>) -> Vec<f32>
+{
+    let mut mixed_samples = vec![0.; RECORDING_PACKET_SAMPLES * RECORDING_CHANNELS];
+
+    for mut audio_queue in audio_queues.iter_mut() {
+        let sample_count = audio_queue.len().min(mixed_samples.len());
+
+        for (mixed_sample, sample) in mixed_samples
+            .iter_mut()
+            .zip(audio_queue.drain(..sample_count))
+        {
+            *mixed_sample += sample;
+        }
+    }
+
+    for sample in mixed_samples.iter_mut() {
+        *sample = sample.clamp(-1., 1.);
+    }
+
+    mixed_samples
+}
+
+/// Writes a frame to the video file of the participant, the file is created on the participant's first frame.
+/// The frames are scaled to the resolution of the participant's first frame.
+fn write_video_frame(
+    video_writers: &mut HashMap<String, (VideoWriter, [usize; 2])>,
+    audio_path: &Path,
+    uuid: &str,
+    image: &ColorImage,
+) -> anyhow::Result<()>
+{
+    let (video_writer, [width, height]) = match video_writers.entry(uuid.to_string()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            let [width, height] = image.size;
+
+            let file_stem = audio_path
+                .file_stem()
+                .map(|file_stem| file_stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            let video_path = audio_path.with_file_name(format!("{file_stem}_{uuid}.webm"));
+
+            let video_writer = VideoWriter::new(
+                &video_path.to_string_lossy(),
+                VideoWriter::fourcc('V', 'P', '8', '0')?,
+                RECORDING_FRAME_RATE,
+                Size::new(width as i32, height as i32),
+                true,
+            )?;
+
+            if !video_writer.is_opened()? {
+                bail!("Failed to create video file: {}", video_path.display());
+            }
+
+            entry.insert((video_writer, [width, height]))
+        },
+    };
+
+    let Some(rgba_image) = RgbaImage::from_raw(
+        image.size[0] as u32,
+        image.size[1] as u32,
+        image.as_raw().to_vec(),
+    )
+    else {
+        bail!("Invalid video frame");
+    };
+
+    let mut rgb_image: RgbImage = image::DynamicImage::ImageRgba8(rgba_image).into_rgb8();
+
+    if image.size != [*width, *height] {
+        rgb_image = image::imageops::resize(
+            &rgb_image,
+            *width as u32,
+            *height as u32,
+            FilterType::Triangle,
+        );
+    }
+
+    //OpenCV expects the frames in Bgr8 format
+    let pixels: Vec<Vec3b> = rgb_image
+        .pixels()
+        .map(|pixel| Vec3b::from([pixel[2], pixel[1], pixel[0]]))
+        .collect();
+
+    let frame =
+        Mat::new_rows_cols_with_data(*height as i32, *width as i32, &pixels)?.try_clone()?;
+
+    video_writer.write(&frame)?;
+
+    Ok(())
+}
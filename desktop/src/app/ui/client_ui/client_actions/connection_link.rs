@@ -0,0 +1,186 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use url::Url;
+
+/// The scheme of the links opening this application
+/// The registry keys for opening these links should already be added by the installer
+pub const CONNECTION_LINK_SCHEME: &str = "matthias";
+
+/// The name of the query parameter containing the invite token
+const INVITE_QUERY_PARAMETER: &str = "invite";
+
+/// A link which connects to a server, these are shared by the hosts of the servers
+/// The links only contain the address of the server and an invite token, so the password of the server is never shared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionLink
+{
+    /// The address of the server, the ipv6 addresses are enclosed in brackets
+    pub address: String,
+
+    /// The token of the invite, if this is ```None``` the password of the server has to be entered
+    pub invite_token: Option<String>,
+}
+
+impl ConnectionLink
+{
+    pub fn new(address: String, invite_token: Option<String>) -> Self
+    {
+        Self {
+            address,
+            invite_token,
+        }
+    }
+
+    /// Returns whether the argument is meant to be opened as a connection link
+    pub fn is_connection_link(argument: &str) -> bool
+    {
+        argument
+            .trim()
+            .trim_matches('"')
+            .to_lowercase()
+            .starts_with(&format!("{CONNECTION_LINK_SCHEME}:"))
+    }
+
+    /// Parses and validates a link, the returned error is displayed to the user
+    pub fn parse(link: &str) -> Result<Self>
+    {
+        //The shell might pass the link with its quotes
+        let url = Url::parse(link.trim().trim_matches('"'))
+            .map_err(|err| anyhow!("The link is not a valid URL: {err}"))?;
+
+        ensure!(
+            url.scheme() == CONNECTION_LINK_SCHEME,
+            "The link doesnt belong to Matthias."
+        );
+
+        let host = url
+            .host()
+            .context("The link doesnt contain the address of the server.")?;
+
+        let port = url
+            .port()
+            .context("The link doesnt contain the port of the server.")?;
+
+        ensure!(port != 0, "The port of the server is invalid.");
+
+        //Some shells append a slash to the links
+        ensure!(
+            url.path().is_empty() || url.path() == "/",
+            "The link contains an unknown path."
+        );
+
+        let mut invite_token = None;
+
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                INVITE_QUERY_PARAMETER => {
+                    ensure!(
+                        invite_token.is_none(),
+                        "The link contains multiple invites."
+                    );
+
+                    //The tokens are base64 encoded parts separated by a dot
+                    ensure!(
+                        !value.is_empty()
+                            && value.chars().all(|character| {
+                                character.is_ascii_alphanumeric() || "-_.".contains(character)
+                            }),
+                        "The invite of the link is malformed."
+                    );
+
+                    invite_token = Some(value.to_string());
+                },
+                _ => bail!("The link contains an unknown parameter: {key}"),
+            }
+        }
+
+        //The ipv6 addresses are displayed in brackets
+        Ok(Self::new(format!("{host}:{port}"), invite_token))
+    }
+}
+
+impl std::fmt::Display for ConnectionLink
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{CONNECTION_LINK_SCHEME}://{}", self.address)?;
+
+        if let Some(invite_token) = &self.invite_token {
+            write!(f, "?{INVITE_QUERY_PARAMETER}={invite_token}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parses_links()
+    {
+        assert_eq!(
+            ConnectionLink::parse("matthias://127.0.0.1:3004?invite=abc-_.def").unwrap(),
+            ConnectionLink::new("127.0.0.1:3004".to_string(), Some("abc-_.def".to_string()))
+        );
+
+        assert_eq!(
+            ConnectionLink::parse("matthias://[::1]:3004").unwrap(),
+            ConnectionLink::new("[::1]:3004".to_string(), None)
+        );
+
+        //The links passed by the shells
+        assert_eq!(
+            ConnectionLink::parse(" \"matthias://127.0.0.1:3004/?invite=abc\" ").unwrap(),
+            ConnectionLink::new("127.0.0.1:3004".to_string(), Some("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_links()
+    {
+        for link in [
+            "",
+            "not a link",
+            "http://127.0.0.1:3004",
+            "matthias:127.0.0.1:3004",
+            "matthias://127.0.0.1",
+            "matthias://127.0.0.1:0",
+            "matthias://127.0.0.1:99999",
+            "matthias://127.0.0.1:3004/path",
+            "matthias://127.0.0.1:3004?password=abc",
+            "matthias://127.0.0.1:3004?invite=abc&invite=def",
+            "matthias://127.0.0.1:3004?invite=",
+            "matthias://127.0.0.1:3004?invite=abc%20def",
+        ] {
+            assert!(ConnectionLink::parse(link).is_err(), "{link}");
+        }
+    }
+
+    #[test]
+    fn displays_parsable_links()
+    {
+        for connection_link in [
+            ConnectionLink::new("127.0.0.1:3004".to_string(), Some("abc.def".to_string())),
+            ConnectionLink::new("[::1]:3004".to_string(), None),
+        ] {
+            let link = connection_link.to_string();
+
+            assert!(ConnectionLink::is_connection_link(&link));
+            assert_eq!(ConnectionLink::parse(&link).unwrap(), connection_link);
+        }
+    }
+
+    #[test]
+    fn recognizes_connection_links()
+    {
+        assert!(ConnectionLink::is_connection_link(
+            "\"MATTHIAS://127.0.0.1:3004\""
+        ));
+        assert!(!ConnectionLink::is_connection_link(
+            "C:\\Matthias\\matthias.exe"
+        ));
+        assert!(!ConnectionLink::is_connection_link("--server"));
+    }
+}
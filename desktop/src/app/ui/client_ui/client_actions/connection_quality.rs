@@ -0,0 +1,256 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The interval the heartbeats are sent on, both on the TCP connection and in voip calls
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// If no heartbeat has been answered for this long, the connection is considered dead
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The amount of the last heartbeats the loss fraction is calculated from
+const HEARTBEAT_HISTORY_LENGTH: usize = 30;
+
+/// The weight of the latest measurement in the smoothed round trip time
+const ROUND_TRIP_TIME_SMOOTHING: f32 = 0.125;
+
+/// This struct measures the quality of a connection, by sending heartbeats and waiting for the other side to answer them
+#[derive(Debug, Clone)]
+pub struct ConnectionQuality
+{
+    /// The sequence number of the next heartbeat
+    next_sequence_number: u64,
+
+    /// The heartbeats which havent been answered yet, with the time they were sent at
+    pending_heartbeats: VecDeque<(u64, Instant)>,
+
+    /// Whether the last heartbeats were answered, the oldest ones are in the front
+    answered_heartbeats: VecDeque<bool>,
+
+    /// The last time a heartbeat was answered, or the time the measurement was started at
+    last_answer: Instant,
+
+    /// The smoothed round trip time in milliseconds
+    pub round_trip_time_ms: Option<f32>,
+}
+
+impl Default for ConnectionQuality
+{
+    fn default() -> Self
+    {
+        Self {
+            next_sequence_number: 0,
+            pending_heartbeats: VecDeque::new(),
+            answered_heartbeats: VecDeque::new(),
+            last_answer: Instant::now(),
+            round_trip_time_ms: None,
+        }
+    }
+}
+
+impl ConnectionQuality
+{
+    /// Saves the time the heartbeat has been sent at, and returns its sequence number
+    pub fn on_heartbeat_sent(&mut self) -> u64
+    {
+        self.expire_heartbeats();
+
+        let sequence_number = self.next_sequence_number;
+
+        self.next_sequence_number += 1;
+
+        self.pending_heartbeats
+            .push_back((sequence_number, Instant::now()));
+
+        sequence_number
+    }
+
+    /// Calculates the round trip time from the answered heartbeat
+    pub fn on_heartbeat_answered(&mut self, sequence_number: u64)
+    {
+        let Some(index) = self
+            .pending_heartbeats
+            .iter()
+            .position(|(pending_sequence_number, _)| *pending_sequence_number == sequence_number)
+        else {
+            //The heartbeat has already expired
+            return;
+        };
+
+        if let Some((_, sent_at)) = self.pending_heartbeats.remove(index) {
+            let round_trip_time_ms = sent_at.elapsed().as_secs_f32() * 1000.;
+
+            self.round_trip_time_ms = Some(match self.round_trip_time_ms {
+                Some(smoothed_round_trip_time_ms) => {
+                    smoothed_round_trip_time_ms
+                        + ROUND_TRIP_TIME_SMOOTHING
+                            * (round_trip_time_ms - smoothed_round_trip_time_ms)
+                },
+                None => round_trip_time_ms,
+            });
+        }
+
+        self.last_answer = Instant::now();
+
+        self.push_outcome(true);
+    }
+
+    /// Returns the fraction of the last heartbeats which were not answered in time
+    pub fn loss_fraction(&self) -> f32
+    {
+        if self.answered_heartbeats.is_empty() {
+            return 0.;
+        }
+
+        self.answered_heartbeats
+            .iter()
+            .filter(|answered| !**answered)
+            .count() as f32
+            / self.answered_heartbeats.len() as f32
+    }
+
+    /// Returns whether no heartbeat has been answered for ```HEARTBEAT_TIMEOUT```
+    pub fn is_timed_out(&self) -> bool
+    {
+        self.last_answer.elapsed() > HEARTBEAT_TIMEOUT
+    }
+
+    /// The heartbeats which havent been answered for ```HEARTBEAT_TIMEOUT``` are counted as lost
+    fn expire_heartbeats(&mut self)
+    {
+        while self
+            .pending_heartbeats
+            .front()
+            .is_some_and(|(_, sent_at)| sent_at.elapsed() > HEARTBEAT_TIMEOUT)
+        {
+            self.pending_heartbeats.pop_front();
+
+            self.push_outcome(false);
+        }
+    }
+
+    fn push_outcome(&mut self, answered: bool)
+    {
+        self.answered_heartbeats.push_back(answered);
+
+        while self.answered_heartbeats.len() > HEARTBEAT_HISTORY_LENGTH {
+            self.answered_heartbeats.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn measures_round_trip_time()
+    {
+        let mut connection_quality = ConnectionQuality::default();
+
+        let first = connection_quality.on_heartbeat_sent();
+        let second = connection_quality.on_heartbeat_sent();
+
+        assert_eq!((first, second), (0, 1));
+
+        connection_quality.on_heartbeat_answered(second);
+        connection_quality.on_heartbeat_answered(first);
+
+        assert!(connection_quality.round_trip_time_ms.is_some());
+        assert!(connection_quality.pending_heartbeats.is_empty());
+        assert_eq!(connection_quality.loss_fraction(), 0.);
+        assert!(!connection_quality.is_timed_out());
+    }
+
+    #[test]
+    fn smooths_round_trip_time()
+    {
+        let mut connection_quality = ConnectionQuality {
+            round_trip_time_ms: Some(1000.),
+            ..Default::default()
+        };
+
+        let sequence_number = connection_quality.on_heartbeat_sent();
+
+        connection_quality.on_heartbeat_answered(sequence_number);
+
+        //A single fast answer only moves the estimate by the smoothing factor
+        let round_trip_time_ms = connection_quality.round_trip_time_ms.unwrap();
+
+        assert!(round_trip_time_ms < 1000.);
+        assert!(round_trip_time_ms > 1000. * (1. - ROUND_TRIP_TIME_SMOOTHING) - 1.);
+    }
+
+    #[test]
+    fn ignores_unknown_answers()
+    {
+        let mut connection_quality = ConnectionQuality::default();
+
+        connection_quality.on_heartbeat_answered(5);
+
+        assert!(connection_quality.round_trip_time_ms.is_none());
+        assert!(connection_quality.answered_heartbeats.is_empty());
+    }
+
+    #[test]
+    fn counts_expired_heartbeats_as_lost()
+    {
+        let mut connection_quality = ConnectionQuality::default();
+
+        let expired_at = Instant::now() - HEARTBEAT_TIMEOUT - Duration::from_secs(1);
+
+        connection_quality
+            .pending_heartbeats
+            .push_back((0, expired_at));
+        connection_quality.next_sequence_number = 1;
+
+        let sequence_number = connection_quality.on_heartbeat_sent();
+
+        connection_quality.on_heartbeat_answered(sequence_number);
+
+        //The expired heartbeat cant be answered anymore
+        connection_quality.on_heartbeat_answered(0);
+
+        assert_eq!(connection_quality.loss_fraction(), 0.5);
+    }
+
+    #[test]
+    fn times_out_without_answers()
+    {
+        let mut connection_quality = ConnectionQuality::default();
+
+        connection_quality.last_answer =
+            Instant::now() - HEARTBEAT_TIMEOUT - Duration::from_secs(1);
+
+        assert!(connection_quality.is_timed_out());
+
+        let sequence_number = connection_quality.on_heartbeat_sent();
+
+        connection_quality.on_heartbeat_answered(sequence_number);
+
+        assert!(!connection_quality.is_timed_out());
+    }
+
+    #[test]
+    fn limits_history_length()
+    {
+        let mut connection_quality = ConnectionQuality::default();
+
+        for _ in 0..HEARTBEAT_HISTORY_LENGTH * 2 {
+            connection_quality.push_outcome(false);
+        }
+
+        connection_quality.push_outcome(true);
+
+        assert_eq!(
+            connection_quality.answered_heartbeats.len(),
+            HEARTBEAT_HISTORY_LENGTH
+        );
+        assert_eq!(
+            connection_quality.loss_fraction(),
+            (HEARTBEAT_HISTORY_LENGTH - 1) as f32 / HEARTBEAT_HISTORY_LENGTH as f32
+        );
+    }
+}
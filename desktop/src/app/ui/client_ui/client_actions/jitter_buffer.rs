@@ -0,0 +1,828 @@
+use std::{
+    collections::BTreeMap,
+    io::Cursor,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use dashmap::DashMap;
+use rodio::{buffer::SamplesBuffer, OutputStreamHandle, Sink};
+
+use crate::app::backend::ParticipantAudioSettings;
+
+use super::{
+    audio_recording::VOIP_PACKET_BUFFER_LENGTH_MS, call_recording::CallRecorder,
+    voice_activity::SPEAKING_INDICATOR_HOLD,
+};
+
+/// The least amount of frames we buffer before starting the playback of a speaker
+pub const MIN_TARGET_LATENCY_FRAMES: usize = 2;
+
+/// The most amount of frames we buffer before starting the playback of a speaker
+pub const MAX_TARGET_LATENCY_FRAMES: usize = 10;
+
+/// The amount of frames allowed above the target latency before we start dropping the backlog
+const BACKLOG_TOLERANCE_FRAMES: usize = 4;
+
+/// The amount of lost frames we conceal in a row, after this we only play silence until a real frame arrives
+const MAX_CONCEALED_FRAMES: usize = 5;
+
+/// The volume multiplier of each concealed frame compared to the previous one
+const CONCEALMENT_FADE_MULTIPLIER: f32 = 0.5;
+
+/// The sample rate the frames are converted to before mixing them
+pub const MIXER_SAMPLE_RATE: u32 = 48000;
+
+/// The amount of channels the frames are converted to before mixing them
+pub const MIXER_CHANNELS: u16 = 2;
+
+/// The amount of audio sources a speaker's ```Sink``` can have queued up, before we stop feeding it
+const SINK_QUEUE_LENGTH: usize = 2;
+
+/// The bitrate of the mixed audio the server sends to the clients
+const MIXED_AUDIO_BITRATE_BPS: i32 = 32_000;
+
+/// The lengths of the Opus frames in samples (at ```MIXER_SAMPLE_RATE```), the mixed frames are split into the longest ones fitting
+/// Opus only supports these lengths, while a voip packet contains ```VOIP_PACKET_BUFFER_LENGTH_MS``` of audio
+const OPUS_FRAME_LENGTHS: [usize; 4] = [960, 480, 240, 120];
+
+/// The largest size of an encoded Opus frame
+const MAX_OPUS_FRAME_BYTES: usize = 1275;
+
+/// The byte length of the length prepended to every encoded Opus frame (u16)
+const OPUS_FRAME_LENGTH_BYTE_LENGTH: usize = 2;
+
+/// This is the list of the remote speakers in a call, with their uuid as the key
+pub type RemoteSpeakers = Arc<DashMap<String, RemoteSpeaker>>;
+
+/// A remote speaker in a call, every speaker has their own ```Sink``` and ```JitterBuffer```
+pub struct RemoteSpeaker
+{
+    /// The ```Sink``` the speaker's audio is played on
+    pub sink: Arc<Sink>,
+
+    /// The jitter buffer of the speaker, the received frames are inserted into this
+    pub jitter_buffer: Arc<Mutex<JitterBuffer>>,
+
+    /// The last time we have received speech from this speaker, this is used to display the speaking indicator
+    pub last_voice_activity: Option<Instant>,
+}
+
+impl RemoteSpeaker
+{
+    pub fn new(stream_handle: &OutputStreamHandle) -> anyhow::Result<Self>
+    {
+        Ok(Self {
+            sink: Arc::new(Sink::try_new(stream_handle)?),
+            jitter_buffer: Arc::new(Mutex::new(JitterBuffer::default())),
+            last_voice_activity: None,
+        })
+    }
+
+    /// Returns whether the speaker has been speaking in the last ```SPEAKING_INDICATOR_HOLD```
+    pub fn is_speaking(&self) -> bool
+    {
+        self.last_voice_activity.is_some_and(|last_voice_activity| {
+            last_voice_activity.elapsed() <= SPEAKING_INDICATOR_HOLD
+        })
+    }
+}
+
+/// A decoded audio frame
+#[derive(Clone, Debug)]
+pub struct AudioFrame
+{
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl AudioFrame
+{
+    /// Decodes a wav file (Created with ```create_wav_file```) into an ```AudioFrame```
+    pub fn from_wav(bytes: &[u8]) -> anyhow::Result<Self>
+    {
+        let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+
+        let spec = reader.spec();
+
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => {
+                reader
+                    .samples::<f32>()
+                    .collect::<Result<Vec<f32>, hound::Error>>()?
+            },
+            hound::SampleFormat::Int => {
+                let max_value = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / max_value))
+                    .collect::<Result<Vec<f32>, hound::Error>>()?
+            },
+        };
+
+        Ok(Self {
+            channels: spec.channels,
+            sample_rate: spec.sample_rate,
+            samples,
+        })
+    }
+
+    /// Encodes the frame into a wav file, which can be decoded with ```AudioFrame::from_wav```
+    pub fn to_wav(&self) -> anyhow::Result<Vec<u8>>
+    {
+        let mut buffer = Cursor::new(Vec::new());
+
+        let mut writer = hound::WavWriter::new(
+            &mut buffer,
+            hound::WavSpec {
+                channels: self.channels,
+                sample_rate: self.sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+        )?;
+
+        for sample in &self.samples {
+            writer.write_sample(*sample)?;
+        }
+
+        writer.finalize()?;
+
+        Ok(buffer.into_inner())
+    }
+
+    /// Encodes the frame with Opus in mono, this is how the server sends the mixed audio.
+    /// The frame is split into the longest Opus frames fitting, every encoded frame is prepended with its length.
+    /// The returned bytes can be decoded with ```AudioFrame::from_opus```, with a decoder created by ```create_mixed_audio_decoder```
+    pub fn to_opus(&self, encoder: &mut opus::Encoder) -> anyhow::Result<Vec<u8>>
+    {
+        let mut samples = self.convert(1, MIXER_SAMPLE_RATE).samples;
+
+        //Pad the samples to the length of the shortest Opus frame
+        let shortest_frame_length = OPUS_FRAME_LENGTHS[OPUS_FRAME_LENGTHS.len() - 1];
+
+        samples.resize(
+            samples.len().div_ceil(shortest_frame_length) * shortest_frame_length,
+            0.,
+        );
+
+        let mut bytes = Vec::new();
+
+        let mut remaining_samples = samples.as_slice();
+
+        while !remaining_samples.is_empty() {
+            let frame_length = OPUS_FRAME_LENGTHS
+                .into_iter()
+                .find(|frame_length| *frame_length <= remaining_samples.len())
+                .unwrap_or(shortest_frame_length);
+
+            let (frame_samples, rest) = remaining_samples.split_at(frame_length);
+
+            let encoded_frame = encoder.encode_vec_float(frame_samples, MAX_OPUS_FRAME_BYTES)?;
+
+            bytes.extend_from_slice(&(encoded_frame.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(&encoded_frame);
+
+            remaining_samples = rest;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decodes the bytes created with ```AudioFrame::to_opus```
+    pub fn from_opus(bytes: &[u8], decoder: &mut opus::Decoder) -> anyhow::Result<Self>
+    {
+        let mut samples = Vec::new();
+
+        let mut decoded_frame = vec![0.; OPUS_FRAME_LENGTHS[0]];
+
+        let mut remaining_bytes = bytes;
+
+        while !remaining_bytes.is_empty() {
+            anyhow::ensure!(
+                remaining_bytes.len() >= OPUS_FRAME_LENGTH_BYTE_LENGTH,
+                "The length of an Opus frame is missing"
+            );
+
+            let (frame_length, rest) = remaining_bytes.split_at(OPUS_FRAME_LENGTH_BYTE_LENGTH);
+
+            let frame_length = u16::from_be_bytes(frame_length.try_into()?) as usize;
+
+            anyhow::ensure!(
+                rest.len() >= frame_length,
+                "The Opus frame is shorter than its length"
+            );
+
+            let (encoded_frame, rest) = rest.split_at(frame_length);
+
+            let decoded_length = decoder.decode_float(encoded_frame, &mut decoded_frame, false)?;
+
+            samples.extend_from_slice(&decoded_frame[..decoded_length]);
+
+            remaining_bytes = rest;
+        }
+
+        Ok(Self {
+            channels: 1,
+            sample_rate: MIXER_SAMPLE_RATE,
+            samples,
+        })
+    }
+
+    /// Converts the frame to the given format.
+    /// The extra channels are dropped, the missing channels are filled with the last channel (ie. mono audio is duplicated).
+    /// The samples are resampled with linear interpolation.
+    pub fn convert(&self, channels: u16, sample_rate: u32) -> AudioFrame
+    {
+        let source_channels = self.channels.max(1) as usize;
+        let channels = channels.max(1);
+
+        let converted_frames: Vec<Vec<f32>> = self
+            .samples
+            .chunks_exact(source_channels)
+            .map(|samples| {
+                (0..channels as usize)
+                    .map(|channel| samples[channel.min(source_channels - 1)])
+                    .collect()
+            })
+            .collect();
+
+        if self.sample_rate == sample_rate || converted_frames.is_empty() {
+            return AudioFrame {
+                channels,
+                sample_rate,
+                samples: converted_frames.concat(),
+            };
+        }
+
+        //Resample with linear interpolation
+        let output_length = (converted_frames.len() as u64 * sample_rate as u64
+            / self.sample_rate.max(1) as u64) as usize;
+
+        let step = self.sample_rate as f64 / sample_rate as f64;
+
+        let mut samples = Vec::with_capacity(output_length * channels as usize);
+
+        for index in 0..output_length {
+            let position = index as f64 * step;
+
+            let previous = &converted_frames[(position as usize).min(converted_frames.len() - 1)];
+            let next = &converted_frames[(position as usize + 1).min(converted_frames.len() - 1)];
+
+            let fraction = position.fract() as f32;
+
+            for (previous, next) in previous.iter().zip(next) {
+                samples.push(previous + (next - previous) * fraction);
+            }
+        }
+
+        AudioFrame {
+            channels,
+            sample_rate,
+            samples,
+        }
+    }
+
+    /// Mixes the frames into one frame by summing their samples multiplied by their volume, the result is clamped to avoid clipping.
+    /// The frames are converted to ```MIXER_SAMPLE_RATE``` and ```MIXER_CHANNELS``` first, because the clients may record in different formats.
+    /// Returns ```None``` if there were no frames to mix.
+    pub fn mix<'a>(frames: impl IntoIterator<Item = (&'a AudioFrame, f32)>) -> Option<AudioFrame>
+    {
+        let mut frames = frames.into_iter().map(|(frame, volume)| {
+            let mut frame = frame.convert(MIXER_CHANNELS, MIXER_SAMPLE_RATE);
+
+            for sample in frame.samples.iter_mut() {
+                *sample *= volume;
+            }
+
+            frame
+        });
+
+        let mut mixed_frame = frames.next()?;
+
+        for frame in frames {
+            if mixed_frame.samples.len() < frame.samples.len() {
+                mixed_frame.samples.resize(frame.samples.len(), 0.);
+            }
+
+            for (mixed_sample, sample) in mixed_frame.samples.iter_mut().zip(&frame.samples) {
+                *mixed_sample += sample;
+            }
+        }
+
+        for sample in mixed_frame.samples.iter_mut() {
+            *sample = sample.clamp(-1., 1.);
+        }
+
+        Some(mixed_frame)
+    }
+
+    /// Creates a playable source from the frame
+    pub fn into_source(self) -> SamplesBuffer<f32>
+    {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples)
+    }
+}
+
+/// The state of the ```JitterBuffer```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PlayoutState
+{
+    /// We are waiting for enough frames to arrive to reach the target latency
+    Buffering,
+    /// We are playing back the frames
+    Playing,
+}
+
+/// An adaptive jitter buffer for a single remote speaker.
+/// The frames are reordered by their sequence number, lost frames are concealed by fading out the last played frame.
+/// The target latency is adjusted to the measured jitter of the incoming packets, and any backlog exceeding it is dropped.
+#[derive(Debug)]
+pub struct JitterBuffer
+{
+    /// The frames waiting to be played, ordered by their sequence number
+    frames: BTreeMap<u64, AudioFrame>,
+
+    /// The sequence number of the frame we are going to play next
+    next_sequence_number: Option<u64>,
+
+    /// The last frame we have played, this is used to conceal lost frames
+    last_frame: Option<AudioFrame>,
+
+    /// The amount of frames concealed in a row
+    concealed_frames: usize,
+
+    /// The state of the playout
+    state: PlayoutState,
+
+    /// The arrival time and the sequence number of the last received packet
+    last_arrival: Option<(Instant, u64)>,
+
+    /// The estimated interarrival jitter in milliseconds (RFC 3550)
+    jitter_ms: f32,
+
+    /// The amount of frames we are trying to keep in the buffer
+    target_latency_frames: usize,
+}
+
+impl Default for JitterBuffer
+{
+    fn default() -> Self
+    {
+        Self {
+            frames: BTreeMap::new(),
+            next_sequence_number: None,
+            last_frame: None,
+            concealed_frames: 0,
+            state: PlayoutState::Buffering,
+            last_arrival: None,
+            jitter_ms: 0.,
+            target_latency_frames: MIN_TARGET_LATENCY_FRAMES,
+        }
+    }
+}
+
+impl JitterBuffer
+{
+    /// Inserts a received frame into the buffer.
+    /// Frames which have arrived too late (Their place has already been played or concealed) are dropped.
+    /// The buffer of a speaker is replaced when they join or leave the call, as their sequence numbers start over.
+    pub fn insert(&mut self, sequence_number: u64, frame: AudioFrame)
+    {
+        self.update_jitter(sequence_number);
+
+        //Drop the frame if its already too late
+        if let Some(next_sequence_number) = self.next_sequence_number {
+            if sequence_number < next_sequence_number {
+                return;
+            }
+        }
+
+        self.frames.insert(sequence_number, frame);
+
+        self.drop_backlog();
+    }
+
+    /// Returns the next frame which should be played.
+    /// If the frame we are supposed to play has been lost, a concealment frame is returned.
+    /// If the buffer is still buffering, or has run out of frames ```None``` is returned.
+    pub fn pop_frame(&mut self) -> Option<AudioFrame>
+    {
+        if self.state == PlayoutState::Buffering {
+            if self.frames.len() < self.target_latency_frames {
+                return None;
+            }
+
+            //Continue from the first buffered frame, so that a long silence (ie. muted microphone) isnt concealed frame by frame
+            self.next_sequence_number = self
+                .frames
+                .first_key_value()
+                .map(|(sequence_number, _)| *sequence_number);
+
+            self.state = PlayoutState::Playing;
+        }
+
+        //If we have run out of frames we start buffering again
+        if self.frames.is_empty() {
+            self.state = PlayoutState::Buffering;
+
+            return None;
+        }
+
+        let next_sequence_number = self.next_sequence_number?;
+
+        self.next_sequence_number = Some(next_sequence_number + 1);
+
+        match self.frames.remove(&next_sequence_number) {
+            Some(frame) => {
+                self.concealed_frames = 0;
+                self.last_frame = Some(frame.clone());
+
+                Some(frame)
+            },
+            //The frame has been lost, so we conceal it
+            None => Some(self.conceal_frame()),
+        }
+    }
+
+    /// Creates a frame which replaces a lost frame, by fading out the last played frame.
+    /// After ```MAX_CONCEALED_FRAMES``` frames the returned frame will be silent.
+    fn conceal_frame(&mut self) -> AudioFrame
+    {
+        self.concealed_frames += 1;
+
+        let mut frame = self.last_frame.clone().unwrap_or(AudioFrame {
+            channels: 2,
+            sample_rate: 48000,
+            samples: Vec::new(),
+        });
+
+        let multiplier = if self.concealed_frames > MAX_CONCEALED_FRAMES {
+            0.
+        }
+        else {
+            CONCEALMENT_FADE_MULTIPLIER.powi(self.concealed_frames as i32)
+        };
+
+        for sample in frame.samples.iter_mut() {
+            *sample *= multiplier;
+        }
+
+        frame
+    }
+
+    /// Drops the oldest frames if the buffer has grown too far above the target latency
+    fn drop_backlog(&mut self)
+    {
+        if self.frames.len() <= self.target_latency_frames + BACKLOG_TOLERANCE_FRAMES {
+            return;
+        }
+
+        while self.frames.len() > self.target_latency_frames {
+            if let Some((sequence_number, _)) = self.frames.pop_first() {
+                self.next_sequence_number = Some(sequence_number + 1);
+            }
+        }
+    }
+
+    /// Updates the jitter estimate and the target latency based on the arrival time of the packet
+    fn update_jitter(&mut self, sequence_number: u64)
+    {
+        let now = Instant::now();
+
+        if let Some((last_arrival, last_sequence_number)) = self.last_arrival {
+            let arrival_difference = now.duration_since(last_arrival).as_secs_f32() * 1000.;
+
+            let expected_difference = (sequence_number as f32 - last_sequence_number as f32)
+                * VOIP_PACKET_BUFFER_LENGTH_MS as f32;
+
+            let deviation = (arrival_difference - expected_difference).abs();
+
+            self.jitter_ms += (deviation - self.jitter_ms) / 16.;
+
+            self.target_latency_frames =
+                ((self.jitter_ms * 2. / VOIP_PACKET_BUFFER_LENGTH_MS as f32).ceil() as usize + 1)
+                    .clamp(MIN_TARGET_LATENCY_FRAMES, MAX_TARGET_LATENCY_FRAMES);
+        }
+
+        //Only save the arrival time if the packet wasnt reordered
+        if self.last_arrival.map_or(true, |(_, last_sequence_number)| {
+            sequence_number > last_sequence_number
+        }) {
+            self.last_arrival = Some((now, sequence_number));
+        }
+    }
+}
+
+/// Creates the encoder of a client's mixed audio on the server, every client has their own as the encoder keeps state between the frames
+pub fn create_mixed_audio_encoder() -> anyhow::Result<opus::Encoder>
+{
+    let mut encoder = opus::Encoder::new(
+        MIXER_SAMPLE_RATE,
+        opus::Channels::Mono,
+        opus::Application::Voip,
+    )?;
+
+    encoder.set_bitrate(opus::Bitrate::Bits(MIXED_AUDIO_BITRATE_BPS))?;
+
+    Ok(encoder)
+}
+
+/// Creates the decoder of the mixed audio sent by the server
+pub fn create_mixed_audio_decoder() -> anyhow::Result<opus::Decoder>
+{
+    Ok(opus::Decoder::new(MIXER_SAMPLE_RATE, opus::Channels::Mono)?)
+}
+
+/// Feeds the ```Sink``` of every remote speaker from their ```JitterBuffer```, this should be called every ```VOIP_PACKET_BUFFER_LENGTH_MS```
+/// The volume of the sinks are set based on the participant's local audio settings
+/// The played frames are also added to the ```call_recorder```, if we are recording the call
+pub fn playout_remote_speakers(
+    speakers: &RemoteSpeakers,
+    participant_audio: &DashMap<String, ParticipantAudioSettings>,
+    call_recorder: Option<&CallRecorder>,
+)
+{
+    for speaker in speakers.iter() {
+        speaker.sink.set_volume(
+            participant_audio
+                .get(speaker.key())
+                .map(|settings| settings.effective_volume())
+                .unwrap_or(1.),
+        );
+
+        //Dont queue up more audio if the sink already has enough
+        if speaker.sink.len() >= SINK_QUEUE_LENGTH {
+            continue;
+        }
+
+        let frame = speaker.jitter_buffer.lock().unwrap().pop_frame();
+
+        if let Some(frame) = frame {
+            //The recording contains the audio regardless of the local volume of the speaker
+            if let Some(call_recorder) = call_recorder {
+                call_recorder.push_audio(speaker.key(), &frame);
+            }
+
+            speaker.sink.append(frame.into_source());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Creates a frame whose samples are all ```value```, so the frames can be told apart
+    fn frame(value: f32) -> AudioFrame
+    {
+        AudioFrame {
+            channels: 1,
+            sample_rate: 48000,
+            samples: vec![value; 4],
+        }
+    }
+
+    /// Returns the value of the frame created with ```frame```
+    fn value(frame: &AudioFrame) -> f32
+    {
+        frame.samples[0]
+    }
+
+    #[test]
+    fn reorders_frames()
+    {
+        let mut jitter_buffer = JitterBuffer::default();
+
+        for sequence_number in [2, 0, 3, 1] {
+            jitter_buffer.insert(sequence_number, frame(sequence_number as f32 / 10.));
+        }
+
+        for sequence_number in 0..4 {
+            assert_eq!(
+                jitter_buffer.pop_frame().map(|frame| value(&frame)),
+                Some(sequence_number as f32 / 10.)
+            );
+        }
+
+        //The buffer has run out of frames
+        assert!(jitter_buffer.pop_frame().is_none());
+    }
+
+    #[test]
+    fn drops_late_frames()
+    {
+        let mut jitter_buffer = JitterBuffer::default();
+
+        for sequence_number in 1..4 {
+            jitter_buffer.insert(sequence_number, frame(sequence_number as f32 / 10.));
+        }
+
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.1)
+        );
+
+        //The playout has already started after this frame
+        jitter_buffer.insert(0, frame(0.));
+
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.2)
+        );
+    }
+
+    #[test]
+    fn conceals_lost_frames()
+    {
+        let mut jitter_buffer = JitterBuffer::default();
+
+        for sequence_number in [0, 1, 4] {
+            jitter_buffer.insert(sequence_number, frame(0.8));
+        }
+
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8)
+        );
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8)
+        );
+
+        //The lost frames fade out the last played frame
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8 * CONCEALMENT_FADE_MULTIPLIER)
+        );
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8 * CONCEALMENT_FADE_MULTIPLIER * CONCEALMENT_FADE_MULTIPLIER)
+        );
+
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8)
+        );
+    }
+
+    #[test]
+    fn silences_long_losses()
+    {
+        let mut jitter_buffer = JitterBuffer::default();
+
+        let last_sequence_number = MAX_CONCEALED_FRAMES as u64 + 3;
+
+        for sequence_number in [0, 1, last_sequence_number] {
+            jitter_buffer.insert(sequence_number, frame(0.8));
+        }
+
+        for _ in 0..2 + MAX_CONCEALED_FRAMES {
+            jitter_buffer.pop_frame();
+        }
+
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.)
+        );
+        assert_eq!(
+            jitter_buffer.pop_frame().map(|frame| value(&frame)),
+            Some(0.8)
+        );
+    }
+
+    #[test]
+    fn drops_backlog()
+    {
+        let mut jitter_buffer = JitterBuffer::default();
+
+        let frame_count = 20;
+
+        for sequence_number in 0..frame_count {
+            jitter_buffer.insert(sequence_number, frame(sequence_number as f32));
+        }
+
+        assert!(
+            jitter_buffer.frames.len()
+                <= jitter_buffer.target_latency_frames + BACKLOG_TOLERANCE_FRAMES
+        );
+
+        //The oldest frames have been dropped, so the playout continues from a recent frame
+        let first_played = jitter_buffer
+            .pop_frame()
+            .map(|frame| value(&frame))
+            .unwrap();
+
+        assert!(
+            first_played
+                >= (frame_count - (MAX_TARGET_LATENCY_FRAMES + BACKLOG_TOLERANCE_FRAMES) as u64)
+                    as f32
+        );
+    }
+
+    #[test]
+    fn converts_mono_to_stereo()
+    {
+        let frame = AudioFrame {
+            channels: 1,
+            sample_rate: 48000,
+            samples: vec![0.1, 0.2],
+        };
+
+        assert_eq!(frame.convert(2, 48000).samples, vec![0.1, 0.1, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn resamples_frames()
+    {
+        let frame = AudioFrame {
+            channels: 1,
+            sample_rate: 24000,
+            samples: vec![0., 0.5, 1., 1.],
+        };
+
+        let converted_frame = frame.convert(1, 48000);
+
+        assert_eq!(converted_frame.sample_rate, 48000);
+        assert_eq!(
+            converted_frame.samples,
+            vec![0., 0.25, 0.5, 0.75, 1., 1., 1., 1.]
+        );
+    }
+
+    #[test]
+    fn mixes_different_formats()
+    {
+        let mono_frame = AudioFrame {
+            channels: 1,
+            sample_rate: MIXER_SAMPLE_RATE / 2,
+            samples: vec![0.25; 2],
+        };
+
+        let stereo_frame = AudioFrame {
+            channels: 2,
+            sample_rate: MIXER_SAMPLE_RATE,
+            samples: vec![0.5; 8],
+        };
+
+        let mixed_frame = AudioFrame::mix([(&mono_frame, 1.), (&stereo_frame, 1.)]).unwrap();
+
+        assert_eq!(mixed_frame.channels, MIXER_CHANNELS);
+        assert_eq!(mixed_frame.sample_rate, MIXER_SAMPLE_RATE);
+        assert_eq!(mixed_frame.samples, vec![0.75; 8]);
+    }
+
+    #[test]
+    fn mixes_with_volume()
+    {
+        let mixed_frame = AudioFrame::mix([(&frame(0.5), 0.5), (&frame(0.5), 0.)]).unwrap();
+
+        assert_eq!(value(&mixed_frame), 0.25);
+    }
+
+    #[test]
+    fn opus_round_trip_keeps_length()
+    {
+        let mut encoder = create_mixed_audio_encoder().unwrap();
+        let mut decoder = create_mixed_audio_decoder().unwrap();
+
+        //A voip packet's length isnt an Opus frame length, so it is split into multiple frames
+        let sample_count = MIXER_SAMPLE_RATE as usize / 1000 * VOIP_PACKET_BUFFER_LENGTH_MS;
+
+        let mixed_frame = AudioFrame {
+            channels: MIXER_CHANNELS,
+            sample_rate: MIXER_SAMPLE_RATE,
+            samples: (0..sample_count * MIXER_CHANNELS as usize)
+                .map(|index| ((index / 2) as f32 / 20.).sin() * 0.5)
+                .collect(),
+        };
+
+        for _ in 0..3 {
+            let bytes = mixed_frame.to_opus(&mut encoder).unwrap();
+
+            //The mono Opus stream is much smaller than the float samples
+            assert!(bytes.len() < sample_count);
+
+            let decoded_frame = AudioFrame::from_opus(&bytes, &mut decoder).unwrap();
+
+            assert_eq!(decoded_frame.channels, 1);
+            assert_eq!(decoded_frame.sample_rate, MIXER_SAMPLE_RATE);
+            assert_eq!(decoded_frame.samples.len(), sample_count);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_opus_frames()
+    {
+        let mut encoder = create_mixed_audio_encoder().unwrap();
+        let mut decoder = create_mixed_audio_decoder().unwrap();
+
+        let bytes = frame(0.5).to_opus(&mut encoder).unwrap();
+
+        assert!(AudioFrame::from_opus(&bytes[..bytes.len() - 1], &mut decoder).is_err());
+        assert!(AudioFrame::from_opus(&bytes[..1], &mut decoder).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{net::UdpSocket, select};
+use tokio_util::sync::CancellationToken;
+
+use crate::app::backend::{
+    ServerAnnouncement, LAN_ANNOUNCEMENT_INTERVAL, LAN_DISCOVERY_MULTICAST_ADDRESS,
+    LAN_DISCOVERY_PORT,
+};
+
+/// The servers which havent announced themselves for this long are considered to be shut down
+pub const DISCOVERED_SERVER_TIMEOUT: Duration =
+    Duration::from_secs(3 * LAN_ANNOUNCEMENT_INTERVAL.as_secs());
+
+/// A server which has announced itself on the local network
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer
+{
+    pub announcement: ServerAnnouncement,
+
+    /// The last time the server has announced itself
+    pub last_seen: Instant,
+}
+
+impl DiscoveredServer
+{
+    pub fn is_timed_out(&self) -> bool
+    {
+        self.last_seen.elapsed() > DISCOVERED_SERVER_TIMEOUT
+    }
+}
+
+/// Listens for the announcements of the servers on the local network, until the token is cancelled
+/// The discovered servers are inserted with the address they can be connected to
+pub async fn discover_servers(
+    discovered_servers: Arc<DashMap<String, DiscoveredServer>>,
+    cancellation_token: CancellationToken,
+    ctx: egui::Context,
+) -> Result<()>
+{
+    let socket = bind_discovery_socket()?;
+
+    let mut buffer = vec![0; 1024];
+
+    loop {
+        select! {
+            _ = cancellation_token.cancelled() => {
+                break;
+            }
+
+            received = socket.recv_from(&mut buffer) => {
+                //The errors of a single datagram (ie. WSAECONNRESET) dont stop the discovery
+                let (length, sender_address) = match received {
+                    Ok(received) => received,
+                    Err(err) => {
+                        tracing::error!("Failed to receive a server announcement: {err}");
+
+                        continue;
+                    },
+                };
+
+                let Ok(announcement) = serde_json::from_slice::<ServerAnnouncement>(&buffer[..length])
+                else {
+                    continue;
+                };
+
+                //The server is listening on the announced port, not the one the announcement was sent from
+                let address = SocketAddr::new(sender_address.ip(), announcement.port).to_string();
+
+                discovered_servers.insert(
+                    address,
+                    DiscoveredServer {
+                        announcement,
+                        last_seen: Instant::now(),
+                    },
+                );
+
+                discovered_servers.retain(|_, discovered_server| !discovered_server.is_timed_out());
+
+                ctx.request_repaint();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The address has to be reused, so that multiple clients on the same machine can listen for the announcements
+fn bind_discovery_socket() -> Result<UdpSocket>
+{
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, LAN_DISCOVERY_PORT)).into())?;
+
+    let socket = UdpSocket::from_std(socket.into())?;
+
+    socket.join_multicast_v4(LAN_DISCOVERY_MULTICAST_ADDRESS, Ipv4Addr::UNSPECIFIED)?;
+
+    Ok(socket)
+}
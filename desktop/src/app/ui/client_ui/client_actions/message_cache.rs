@@ -0,0 +1,183 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine};
+
+use crate::app::backend::{
+    decrypt_aes256, decrypt_aes256_bytes, encrypt_aes256, encrypt_aes256_bytes, ServerMaster,
+    UserInformation,
+};
+
+use super::account_management::secure_delete_file;
+
+/// This is appended to the password before deriving the key of the cache, so it will not be the same as the key of the user's file
+const MESSAGE_CACHE_KEY_SALT: &str = "message_cache";
+
+/// The messages, the reactions and the downloaded attachments of a server, stored encrypted on the disk
+/// This allows the client to display the messages before the server has synced them, and while being offline
+pub struct MessageCache
+{
+    /// The folder of the server's cache
+    directory: PathBuf,
+
+    /// The key the cache is encrypted with, this is derived from the account's password
+    key: Vec<u8>,
+}
+
+impl MessageCache
+{
+    /// Every account has its own cache folder, which contains a folder for every server
+    pub fn new(user_information: &UserInformation, server_address: &str) -> Result<Self>
+    {
+        Self::from_directory(
+            account_cache_root(&user_information.uuid)
+                .join(general_purpose::URL_SAFE_NO_PAD.encode(server_address)),
+            &user_information.password,
+        )
+    }
+
+    fn from_directory(directory: PathBuf, password: &str) -> Result<Self>
+    {
+        //The key is derived like the key of the user's file, but with a salt
+        let key = hex::decode(sha256::digest(format!(
+            "{password}{MESSAGE_CACHE_KEY_SALT}"
+        )))?;
+
+        Ok(Self { directory, key })
+    }
+
+    /// Returns the caches of the account, these are the folders in the account's cache folder
+    fn caches_of_account(uuid: &str, password: &str) -> Result<Vec<Self>>
+    {
+        let mut caches = Vec::new();
+
+        if let Ok(entries) = fs::read_dir(account_cache_root(uuid)) {
+            for entry in entries.flatten() {
+                caches.push(Self::from_directory(entry.path(), password)?);
+            }
+        }
+
+        Ok(caches)
+    }
+
+    /// Encrypts the caches of the account with the new password, this is called when the password of the account is changed
+    pub fn change_password(uuid: &str, old_password: &str, new_password: &str) -> Result<()>
+    {
+        for cache in Self::caches_of_account(uuid, old_password)? {
+            let new_cache = Self::from_directory(cache.directory.clone(), new_password)?;
+
+            //The messages are synced again if they cant be decrypted
+            match cache.load_messages() {
+                Ok(messages) => new_cache.save_messages(&messages)?,
+                Err(_) => {
+                    let _ = fs::remove_file(cache.directory.join("messages"));
+                },
+            }
+
+            if let Ok(attachments) = fs::read_dir(cache.directory.join("Attachments")) {
+                for attachment in attachments.flatten() {
+                    let signature = attachment.file_name().to_string_lossy().to_string();
+
+                    //The attachments which cant be decrypted are downloaded again
+                    match cache.load_attachment(&signature) {
+                        Ok(bytes) => new_cache.save_attachment(&signature, &bytes)?,
+                        Err(_) => fs::remove_file(attachment.path())?,
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the caches of the account, the files are overwritten before they are removed
+    pub fn delete_caches_of_account(uuid: &str, password: &str) -> Result<()>
+    {
+        for cache in Self::caches_of_account(uuid, password)? {
+            let attachments_directory = cache.directory.join("Attachments");
+
+            if let Ok(attachments) = fs::read_dir(&attachments_directory) {
+                for attachment in attachments.flatten() {
+                    secure_delete_file(&attachment.path())?;
+                }
+            }
+
+            let messages_path = cache.directory.join("messages");
+
+            if messages_path.exists() {
+                secure_delete_file(&messages_path)?;
+            }
+
+            fs::remove_dir_all(&cache.directory)?;
+        }
+
+        let _ = fs::remove_dir(account_cache_root(uuid));
+
+        Ok(())
+    }
+
+    /// Reads the cached messages of the server
+    pub fn load_messages(&self) -> Result<ServerMaster>
+    {
+        let mut messages = serde_json::from_str::<ServerMaster>(&decrypt_aes256(
+            &fs::read_to_string(self.directory.join("messages"))?,
+            &self.key,
+        )?)?;
+
+        //The calls might have ended since, the ongoing calls are synced by the server after connecting
+        messages.ongoing_voip_call = Default::default();
+        messages.first_message_index = 0;
+
+        Ok(messages)
+    }
+
+    /// Overwrites the cached messages of the server
+    pub fn save_messages(&self, messages: &ServerMaster) -> Result<()>
+    {
+        fs::create_dir_all(&self.directory)?;
+
+        fs::write(
+            self.directory.join("messages"),
+            encrypt_aes256(messages.struct_into_string(), &self.key)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Reads a downloaded attachment, the attachments are stored by their sha256 signature
+    pub fn load_attachment(&self, signature: &str) -> Result<Vec<u8>>
+    {
+        decrypt_aes256_bytes(
+            &fs::read(self.directory.join("Attachments").join(signature))?,
+            &self.key,
+        )
+    }
+
+    /// Stores a downloaded attachment, so it will not have to be requested from the server again
+    pub fn save_attachment(&self, signature: &str, bytes: &[u8]) -> Result<()>
+    {
+        let attachments_directory = self.directory.join("Attachments");
+
+        fs::create_dir_all(&attachments_directory)?;
+
+        fs::write(
+            attachments_directory.join(signature),
+            encrypt_aes256_bytes(bytes, &self.key)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The folder containing the caches of every account
+fn cache_root() -> PathBuf
+{
+    PathBuf::from(format!("{}\\Matthias\\Cache", env!("APPDATA")))
+}
+
+/// The folder containing the cache of every server the account has connected to
+/// The caches are separated by the account's uuid, so the accounts using the same password dont share their caches
+fn account_cache_root(uuid: &str) -> PathBuf
+{
+    cache_root().join(uuid)
+}
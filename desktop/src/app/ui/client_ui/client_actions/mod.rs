@@ -1 +1,14 @@
+pub mod account_management;
+pub mod audio_processing;
 pub mod audio_recording;
+pub mod call_recording;
+pub mod connection_link;
+pub mod connection_quality;
+pub mod jitter_buffer;
+pub mod lan_discovery;
+pub mod message_cache;
+pub mod outbox;
+pub mod video_codec;
+pub mod video_quality;
+pub mod video_source;
+pub mod voice_activity;
@@ -0,0 +1,472 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::app::backend::{decrypt_aes256, encrypt_aes256, ClientMessage, ClientMessageType};
+
+/// The amount of times a message is sent to the server before it is marked as failed
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+
+/// How long the sent messages are displayed after the server has acknowledged them
+pub const SENT_MESSAGE_DISPLAY_DURATION: Duration = Duration::from_secs(3);
+
+/// This is appended to the password before deriving the key of the outbox, so it will not be the same as the key of the user's file
+const OUTBOX_KEY_SALT: &str = "outbox";
+
+/// The state of a message in the outbox
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryState
+{
+    /// The message hasnt been acknowledged by the server yet, it will be sent again after reconnecting
+    Pending,
+
+    /// The server has stored the message
+    Sent,
+
+    /// The message couldnt be delivered in ```MAX_DELIVERY_ATTEMPTS``` attempts, it is only sent again if the user retries it
+    Failed,
+}
+
+/// A message waiting for the server's acknowledgement
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OutboxEntry
+{
+    /// The message itself, its ```message_id``` is always ```Some```
+    pub message: ClientMessage,
+
+    /// The address of the server the message was sent to, so it will not be sent to a different server
+    pub server_address: String,
+
+    pub state: DeliveryState,
+
+    /// How many times the message has been sent
+    pub attempts: u32,
+
+    /// When the server has acknowledged the message
+    #[serde(skip)]
+    acknowledged_at: Option<Instant>,
+}
+
+impl OutboxEntry
+{
+    pub fn message_id(&self) -> &str
+    {
+        self.message.message_id.as_deref().unwrap_or_default()
+    }
+}
+
+/// The outbox contains the messages which havent been acknowledged by the server yet
+/// It is saved encrypted next to the user's file, so the messages survive a restart of the application
+#[derive(Debug, Clone, Default)]
+pub struct Outbox
+{
+    entries: Vec<OutboxEntry>,
+
+    /// The path the outbox is saved to, if this is ```None``` the outbox is not saved
+    path: Option<PathBuf>,
+
+    /// The key the outbox is encrypted with, this is derived from the account's password
+    key: Vec<u8>,
+}
+
+impl Outbox
+{
+    /// Reads the outbox from the path, if it doesnt exist yet an empty outbox is created
+    /// The password should be the one stored in ```UserInformation```
+    pub fn load(path: PathBuf, password: &str) -> Self
+    {
+        let key = derive_outbox_key(password);
+
+        let mut entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| decrypt_aes256(&contents, &key).ok())
+            .and_then(|contents| serde_json::from_str::<Vec<OutboxEntry>>(&contents).ok())
+            .unwrap_or_default();
+
+        //The acknowledged messages have already been stored by the server
+        entries.retain(|entry| entry.state != DeliveryState::Sent);
+
+        Self {
+            entries,
+            path: Some(path),
+            key,
+        }
+    }
+
+    /// Encrypts the outbox with the new password, this is called when the password of the account is changed
+    pub fn change_password(&mut self, password: &str)
+    {
+        self.key = derive_outbox_key(password);
+
+        self.save();
+    }
+
+    /// Adds the message to the outbox, this counts as the first delivery attempt
+    pub fn push(&mut self, message: ClientMessage, server_address: String)
+    {
+        self.entries.push(OutboxEntry {
+            message,
+            server_address,
+            state: DeliveryState::Pending,
+            attempts: 1,
+            acknowledged_at: None,
+        });
+
+        self.save();
+    }
+
+    /// Marks the message as sent, returns whether the message was in the outbox
+    pub fn acknowledge(&mut self, message_id: &str) -> bool
+    {
+        let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.message_id() == message_id)
+        else {
+            return false;
+        };
+
+        entry.state = DeliveryState::Sent;
+        entry.acknowledged_at = Some(Instant::now());
+
+        self.save();
+
+        true
+    }
+
+    /// Marks the message as failed, the server has refused to store it so it is not sent again automatically
+    pub fn reject(&mut self, message_id: &str)
+    {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.message_id() == message_id)
+        {
+            entry.state = DeliveryState::Failed;
+
+            self.save();
+        }
+    }
+
+    /// Called when the message couldnt be written to the connection
+    pub fn on_send_failed(&mut self, message_id: &str)
+    {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.message_id() == message_id && entry.state == DeliveryState::Pending)
+        {
+            if entry.attempts >= MAX_DELIVERY_ATTEMPTS {
+                entry.state = DeliveryState::Failed;
+
+                self.save();
+            }
+        }
+    }
+
+    /// Returns the pending messages of the server, which should be sent again after (re)connecting
+    /// The messages which have run out of attempts are marked as failed instead
+    pub fn messages_to_resend(&mut self, server_address: &str) -> Vec<ClientMessage>
+    {
+        let mut messages = Vec::new();
+
+        for entry in self.entries.iter_mut().filter(|entry| {
+            entry.server_address == server_address && entry.state == DeliveryState::Pending
+        }) {
+            if entry.attempts >= MAX_DELIVERY_ATTEMPTS {
+                entry.state = DeliveryState::Failed;
+
+                continue;
+            }
+
+            entry.attempts += 1;
+
+            messages.push(entry.message.clone());
+        }
+
+        self.save();
+
+        messages
+    }
+
+    /// Resets the attempts of a failed message, and returns its entry so it can be sent again
+    pub fn retry(&mut self, message_id: &str) -> Option<OutboxEntry>
+    {
+        let entry = self.entries.iter_mut().find(|entry| {
+            entry.message_id() == message_id && entry.state == DeliveryState::Failed
+        })?;
+
+        entry.state = DeliveryState::Pending;
+        entry.attempts = 1;
+
+        let entry = entry.clone();
+
+        self.save();
+
+        Some(entry)
+    }
+
+    /// Removes the message from the outbox without sending it
+    pub fn discard(&mut self, message_id: &str)
+    {
+        self.entries
+            .retain(|entry| entry.message_id() != message_id);
+
+        self.save();
+    }
+
+    /// Removes the sent messages which have been displayed for ```SENT_MESSAGE_DISPLAY_DURATION```
+    pub fn remove_delivered(&mut self)
+    {
+        self.entries.retain(|entry| {
+            entry.acknowledged_at.map_or(true, |acknowledged_at| {
+                acknowledged_at.elapsed() < SENT_MESSAGE_DISPLAY_DURATION
+            })
+        });
+    }
+
+    pub fn entries(&self) -> &[OutboxEntry]
+    {
+        &self.entries
+    }
+
+    fn save(&self)
+    {
+        if let Err(err) = self.write_file() {
+            tracing::error!("Failed to save the outbox: {err}");
+        }
+    }
+
+    fn write_file(&self) -> Result<()>
+    {
+        if let Some(path) = &self.path {
+            //The uploads are only kept in memory, so the uploaded files are not written to the disk again
+            let entries = self
+                .entries
+                .iter()
+                .filter(|entry| {
+                    !matches!(entry.message.message_type, ClientMessageType::FileUpload(_))
+                })
+                .collect::<Vec<&OutboxEntry>>();
+
+            fs::write(
+                path,
+                encrypt_aes256(serde_json::to_string(&entries)?, &self.key)?,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The key is derived like the key of the user's file, but with a salt
+fn derive_outbox_key(password: &str) -> Vec<u8>
+{
+    //The digest is always a valid hex string
+    hex::decode(sha256::digest(format!("{password}{OUTBOX_KEY_SALT}"))).unwrap()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SERVER_ADDRESS: &str = "127.0.0.1:3004";
+
+    fn message() -> ClientMessage
+    {
+        ClientMessage::construct_normal_msg("Hello", &uuid::Uuid::new_v4().to_string(), None)
+    }
+
+    /// Pushes a new message to the outbox, and returns its id
+    fn push(outbox: &mut Outbox, server_address: &str) -> String
+    {
+        let message = message();
+        let message_id = message.message_id.clone().unwrap();
+
+        outbox.push(message, server_address.to_string());
+
+        message_id
+    }
+
+    fn state(outbox: &Outbox, message_id: &str) -> Option<DeliveryState>
+    {
+        outbox
+            .entries()
+            .iter()
+            .find(|entry| entry.message_id() == message_id)
+            .map(|entry| entry.state)
+    }
+
+    #[test]
+    fn generates_unique_message_ids()
+    {
+        let (message, other_message) = (message(), message());
+
+        assert!(message.message_id.is_some());
+        assert_ne!(message.message_id, other_message.message_id);
+    }
+
+    #[test]
+    fn acknowledges_and_rejects_messages()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+        let rejected_message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Pending));
+
+        assert!(outbox.acknowledge(&message_id));
+        assert!(!outbox.acknowledge("unknown"));
+
+        outbox.reject(&rejected_message_id);
+
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Sent));
+        assert_eq!(
+            state(&outbox, &rejected_message_id),
+            Some(DeliveryState::Failed)
+        );
+
+        //The acknowledged and the rejected messages are not sent again
+        assert!(outbox.messages_to_resend(SERVER_ADDRESS).is_empty());
+    }
+
+    #[test]
+    fn resends_pending_messages_of_the_server()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+        let acknowledged_message_id = push(&mut outbox, SERVER_ADDRESS);
+        push(&mut outbox, "127.0.0.1:3005");
+
+        outbox.acknowledge(&acknowledged_message_id);
+
+        let messages = outbox.messages_to_resend(SERVER_ADDRESS);
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id.as_deref(), Some(message_id.as_str()));
+        assert_eq!(outbox.entries()[0].attempts, 2);
+    }
+
+    #[test]
+    fn fails_after_max_delivery_attempts()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        for _ in 1..MAX_DELIVERY_ATTEMPTS {
+            assert_eq!(outbox.messages_to_resend(SERVER_ADDRESS).len(), 1);
+        }
+
+        assert!(outbox.messages_to_resend(SERVER_ADDRESS).is_empty());
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Failed));
+
+        //A failed write only fails the message after the last attempt
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        outbox.on_send_failed(&message_id);
+
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Pending));
+
+        outbox.entries[1].attempts = MAX_DELIVERY_ATTEMPTS;
+
+        outbox.on_send_failed(&message_id);
+
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Failed));
+    }
+
+    #[test]
+    fn retries_failed_messages()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        //Only the failed messages can be retried
+        assert!(outbox.retry(&message_id).is_none());
+
+        outbox.reject(&message_id);
+
+        let entry = outbox.retry(&message_id).unwrap();
+
+        assert_eq!(entry.server_address, SERVER_ADDRESS);
+        assert_eq!(entry.message_id(), message_id);
+        assert_eq!(entry.state, DeliveryState::Pending);
+        assert_eq!(entry.attempts, 1);
+        assert_eq!(state(&outbox, &message_id), Some(DeliveryState::Pending));
+    }
+
+    #[test]
+    fn discards_messages()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        outbox.discard(&message_id);
+
+        assert!(outbox.entries().is_empty());
+    }
+
+    #[test]
+    fn removes_delivered_messages()
+    {
+        let mut outbox = Outbox::default();
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+        let displayed_message_id = push(&mut outbox, SERVER_ADDRESS);
+        let pending_message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        outbox.acknowledge(&message_id);
+        outbox.acknowledge(&displayed_message_id);
+
+        outbox.entries[0].acknowledged_at =
+            Instant::now().checked_sub(SENT_MESSAGE_DISPLAY_DURATION);
+
+        outbox.remove_delivered();
+
+        assert_eq!(state(&outbox, &message_id), None);
+        assert_eq!(
+            state(&outbox, &displayed_message_id),
+            Some(DeliveryState::Sent)
+        );
+        assert_eq!(
+            state(&outbox, &pending_message_id),
+            Some(DeliveryState::Pending)
+        );
+    }
+
+    #[test]
+    fn loads_saved_messages()
+    {
+        let path = std::env::temp_dir().join(format!("matthias_outbox_{}", uuid::Uuid::new_v4()));
+
+        let mut outbox = Outbox::load(path.clone(), "password");
+
+        let message_id = push(&mut outbox, SERVER_ADDRESS);
+        let acknowledged_message_id = push(&mut outbox, SERVER_ADDRESS);
+
+        outbox.acknowledge(&acknowledged_message_id);
+
+        //The message keeps its id, so the server can ignore it if it has already been stored
+        let loaded_outbox = Outbox::load(path.clone(), "password");
+
+        assert_eq!(loaded_outbox.entries().len(), 1);
+        assert_eq!(loaded_outbox.entries()[0].message_id(), message_id);
+        assert_eq!(loaded_outbox.entries()[0].server_address, SERVER_ADDRESS);
+
+        //The outbox cant be decrypted with a different password
+        assert!(Outbox::load(path.clone(), "other password")
+            .entries()
+            .is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,519 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use egui::ColorImage;
+use image::{imageops::FilterType, RgbImage};
+use openh264::{
+    decoder::Decoder,
+    encoder::{Encoder, EncoderConfig, FrameType},
+    formats::{RgbSliceU8, YUVBuffer, YUVSource},
+    OpenH264API,
+};
+
+use super::video_quality::VideoQuality;
+
+/// The amount of frames after which a keyframe is sent even if no one has requested it
+const KEYFRAME_INTERVAL_FRAMES: usize = 150;
+
+/// The least amount of time between two keyframe requests sent to the same sender, so that we dont flood them while the keyframe is on its way
+const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The byte length of the frame number appended to an encoded frame (u64)
+pub const FRAME_NUMBER_BYTE_LENGTH: usize = 8;
+
+/// The byte length of the frame type appended to an encoded frame (u8)
+pub const FRAME_TYPE_BYTE_LENGTH: usize = 1;
+
+/// The frame type byte of a keyframe, which can be decoded on its own
+const KEYFRAME_FLAG: u8 = 1;
+
+/// The frame type byte of a delta frame, which can only be decoded if all the frames since the last keyframe have been decoded
+const DELTA_FRAME_FLAG: u8 = 0;
+
+/// The H.264 encoder of the outgoing camera stream.
+/// The first frame, every ```KEYFRAME_INTERVAL_FRAMES```th frame, and the frames after a keyframe request are keyframes, the rest are delta frames.
+/// __Encoded frame contents:__
+/// - ```[..len - 9]``` = Contains the H.264 bitstream of the frame
+/// - ```[len - 9]``` = Contains whether the frame is a keyframe
+/// - ```[len - 8..]``` = Contains the frame number, the receivers use it to detect lost frames
+pub struct VideoEncoder
+{
+    /// The inner encoder, this is recreated if the resolution of the frames change
+    encoder: Option<Encoder>,
+
+    /// The resolution the encoder has been created with
+    dimensions: (usize, usize),
+
+    /// The quality the encoder has been created with
+    quality: Option<VideoQuality>,
+
+    /// The number of the next encoded frame
+    frame_number: u64,
+
+    /// The amount of frames encoded since the last keyframe
+    frames_since_keyframe: usize,
+
+    /// This is set when a receiver has requested a keyframe (ie. they have lost a part of a frame)
+    keyframe_requested: Arc<AtomicBool>,
+}
+
+impl VideoEncoder
+{
+    pub fn new(keyframe_requested: Arc<AtomicBool>) -> Self
+    {
+        Self {
+            encoder: None,
+            dimensions: (0, 0),
+            quality: None,
+            frame_number: 0,
+            frames_since_keyframe: 0,
+            keyframe_requested,
+        }
+    }
+
+    /// Encodes an Rgb8 frame with the provided quality, the returned bytes can be sent with ```Voip::send_image```
+    /// If the quality has changed since the last frame the encoder is recreated, thus the frame will be a keyframe
+    pub fn encode(
+        &mut self,
+        rgb: &[u8],
+        width: usize,
+        height: usize,
+        quality: VideoQuality,
+    ) -> anyhow::Result<Vec<u8>>
+    {
+        let (rgb, width, height) = scale_frame(rgb, width, height, quality.resolution_scale)?;
+
+        //The YUV420 format requires the dimensions to be even
+        let (rgb, width, height) = crop_to_even_dimensions(&rgb, width, height)?;
+
+        if self.encoder.is_none()
+            || self.dimensions != (width, height)
+            || self.quality != Some(quality)
+        {
+            let config = EncoderConfig::new()
+                .set_bitrate_bps(quality.bitrate_bps)
+                .max_frame_rate(quality.frame_rate as f32);
+
+            self.encoder = Some(Encoder::with_api_config(
+                OpenH264API::from_source(),
+                config,
+            )?);
+            self.dimensions = (width, height);
+            self.quality = Some(quality);
+        }
+
+        let encoder = self.encoder.as_mut().unwrap();
+
+        //Force a keyframe if it was requested or it is time for one, the new encoder's first frame is always a keyframe
+        if self.keyframe_requested.swap(false, Relaxed)
+            || self.frames_since_keyframe >= KEYFRAME_INTERVAL_FRAMES
+        {
+            encoder.force_intra_frame();
+        }
+
+        let yuv = YUVBuffer::from_rgb_source(RgbSliceU8::new(&rgb, (width, height)));
+
+        let bitstream = encoder.encode(&yuv)?;
+
+        let is_keyframe = matches!(bitstream.frame_type(), FrameType::IDR | FrameType::I);
+
+        if is_keyframe {
+            self.frames_since_keyframe = 0;
+        }
+        else {
+            self.frames_since_keyframe += 1;
+        }
+
+        let mut bytes = bitstream.to_vec();
+
+        //Append the frame type
+        bytes.push(
+            if is_keyframe {
+                KEYFRAME_FLAG
+            }
+            else {
+                DELTA_FRAME_FLAG
+            },
+        );
+
+        //Append the frame number
+        bytes.append(&mut self.frame_number.to_be_bytes().to_vec());
+
+        self.frame_number += 1;
+
+        Ok(bytes)
+    }
+}
+
+/// The result of decoding a received frame
+pub enum DecodedVideoFrame
+{
+    /// The frame has been decoded and can be displayed
+    Frame(ColorImage),
+
+    /// The frame couldnt be decoded because a previous frame has been lost, a keyframe should be requested from the sender
+    KeyframeNeeded,
+
+    /// The frame has been decoded but there is nothing to display yet (Or a keyframe has already been requested recently)
+    Pending,
+}
+
+/// The H.264 decoder of a remote participant's camera stream.
+/// After a lost frame the delta frames are dropped until the next keyframe arrives, because they would decode into a corrupted image.
+pub struct VideoDecoder
+{
+    /// The inner decoder
+    decoder: Decoder,
+
+    /// The number of the frame we are expecting next
+    next_frame_number: Option<u64>,
+
+    /// Whether we have lost a frame and can only continue with a keyframe
+    waiting_for_keyframe: bool,
+
+    /// The last time we have requested a keyframe from the sender
+    last_keyframe_request: Option<Instant>,
+}
+
+impl VideoDecoder
+{
+    pub fn new() -> anyhow::Result<Self>
+    {
+        Ok(Self {
+            decoder: Decoder::new()?,
+            next_frame_number: None,
+            //We can only start decoding with a keyframe
+            waiting_for_keyframe: true,
+            last_keyframe_request: None,
+        })
+    }
+
+    /// Decodes a frame encoded by ```VideoEncoder::encode```
+    pub fn decode(&mut self, bytes: &[u8]) -> anyhow::Result<DecodedVideoFrame>
+    {
+        if bytes.len() < FRAME_TYPE_BYTE_LENGTH + FRAME_NUMBER_BYTE_LENGTH {
+            anyhow::bail!("Video frame is too short, with length of {}", bytes.len());
+        }
+
+        let (bitstream, trailer) =
+            bytes.split_at(bytes.len() - FRAME_TYPE_BYTE_LENGTH - FRAME_NUMBER_BYTE_LENGTH);
+
+        let is_keyframe = trailer[0] == KEYFRAME_FLAG;
+        let frame_number = u64::from_be_bytes(trailer[FRAME_TYPE_BYTE_LENGTH..].try_into()?);
+
+        //Drop the frames which have arrived too late, a keyframe is always accepted as the sender might have restarted its stream
+        if let Some(next_frame_number) = self.next_frame_number {
+            if frame_number < next_frame_number && !is_keyframe {
+                return Ok(DecodedVideoFrame::Pending);
+            }
+
+            //A frame has been lost, the delta frames cant be decoded until the next keyframe
+            if frame_number > next_frame_number {
+                self.waiting_for_keyframe = true;
+            }
+        }
+
+        self.next_frame_number = Some(frame_number + 1);
+
+        if is_keyframe {
+            self.waiting_for_keyframe = false;
+        }
+
+        if self.waiting_for_keyframe {
+            return Ok(self.request_keyframe());
+        }
+
+        let decoded_frame = match self.decoder.decode(bitstream) {
+            Ok(decoded_frame) => decoded_frame,
+            Err(err) => {
+                tracing::error!("Failed to decode video frame: {err}");
+
+                self.waiting_for_keyframe = true;
+
+                return Ok(self.request_keyframe());
+            },
+        };
+
+        match decoded_frame {
+            Some(yuv) => {
+                let (width, height) = yuv.dimensions();
+
+                let mut rgb = vec![0; width * height * 3];
+
+                yuv.write_rgb8(&mut rgb);
+
+                Ok(DecodedVideoFrame::Frame(ColorImage::from_rgb(
+                    [width, height],
+                    &rgb,
+                )))
+            },
+            None => Ok(DecodedVideoFrame::Pending),
+        }
+    }
+
+    /// Returns whether a keyframe should be requested, the requests are limited to one per ```KEYFRAME_REQUEST_INTERVAL```
+    fn request_keyframe(&mut self) -> DecodedVideoFrame
+    {
+        if self
+            .last_keyframe_request
+            .is_some_and(|last_request| last_request.elapsed() < KEYFRAME_REQUEST_INTERVAL)
+        {
+            return DecodedVideoFrame::Pending;
+        }
+
+        self.last_keyframe_request = Some(Instant::now());
+
+        DecodedVideoFrame::KeyframeNeeded
+    }
+}
+
+/// Returns the frame number of a frame encoded by ```VideoEncoder::encode```
+pub fn frame_number(bytes: &[u8]) -> Option<u64>
+{
+    if bytes.len() < FRAME_TYPE_BYTE_LENGTH + FRAME_NUMBER_BYTE_LENGTH {
+        return None;
+    }
+
+    Some(u64::from_be_bytes(
+        bytes[bytes.len() - FRAME_NUMBER_BYTE_LENGTH..]
+            .try_into()
+            .ok()?,
+    ))
+}
+
+/// Scales the Rgb8 frame by the multiplier, the frame is returned unchanged if the multiplier is 1
+fn scale_frame(
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+    resolution_scale: f32,
+) -> anyhow::Result<(Vec<u8>, usize, usize)>
+{
+    if resolution_scale >= 1. {
+        return Ok((rgb.to_vec(), width, height));
+    }
+
+    let image = RgbImage::from_raw(width as u32, height as u32, rgb.to_vec()).ok_or_else(|| {
+        anyhow::Error::msg(format!(
+            "Camera frame is too short for its resolution ({width}x{height})"
+        ))
+    })?;
+
+    let (scaled_width, scaled_height) = (
+        ((width as f32 * resolution_scale) as u32).max(2),
+        ((height as f32 * resolution_scale) as u32).max(2),
+    );
+
+    let scaled_image =
+        image::imageops::resize(&image, scaled_width, scaled_height, FilterType::Triangle);
+
+    Ok((
+        scaled_image.into_raw(),
+        scaled_width as usize,
+        scaled_height as usize,
+    ))
+}
+
+/// Crops the last row and column of the Rgb8 frame if the dimensions are odd
+fn crop_to_even_dimensions(
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+) -> anyhow::Result<(Vec<u8>, usize, usize)>
+{
+    anyhow::ensure!(
+        rgb.len() >= width * height * 3,
+        "Camera frame is too short for its resolution ({width}x{height})"
+    );
+
+    let (even_width, even_height) = (width & !1, height & !1);
+
+    anyhow::ensure!(
+        even_width > 0 && even_height > 0,
+        "Camera frame is too small ({width}x{height})"
+    );
+
+    if (even_width, even_height) == (width, height) {
+        return Ok((rgb[..width * height * 3].to_vec(), width, height));
+    }
+
+    let cropped = rgb
+        .chunks_exact(width * 3)
+        .take(even_height)
+        .flat_map(|row| &row[..even_width * 3])
+        .copied()
+        .collect();
+
+    Ok((cropped, even_width, even_height))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{super::video_quality::VIDEO_QUALITY_LEVELS, *};
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 48;
+
+    /// Creates a gradient frame, the gradient is shifted by ```offset``` so that the frames differ
+    fn rgb_frame(offset: usize) -> Vec<u8>
+    {
+        (0..WIDTH * HEIGHT)
+            .flat_map(|index| {
+                let (x, y) = (index % WIDTH, index / WIDTH);
+
+                [
+                    (x * 4 + offset) as u8,
+                    (y * 5) as u8,
+                    (x + y + offset) as u8,
+                ]
+            })
+            .collect()
+    }
+
+    /// Encodes ```count``` frames, the first frame is always a keyframe
+    fn encode_frames(encoder: &mut VideoEncoder, count: usize) -> Vec<Vec<u8>>
+    {
+        (0..count)
+            .map(|offset| {
+                encoder
+                    .encode(&rgb_frame(offset), WIDTH, HEIGHT, VIDEO_QUALITY_LEVELS[0])
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn is_keyframe(bytes: &[u8]) -> bool
+    {
+        bytes[bytes.len() - FRAME_NUMBER_BYTE_LENGTH - FRAME_TYPE_BYTE_LENGTH] == KEYFRAME_FLAG
+    }
+
+    #[test]
+    fn encodes_keyframe_first()
+    {
+        let mut encoder = VideoEncoder::new(Arc::new(AtomicBool::new(false)));
+
+        let frames = encode_frames(&mut encoder, 2);
+
+        assert!(is_keyframe(&frames[0]));
+        assert!(!is_keyframe(&frames[1]));
+
+        assert_eq!(frame_number(&frames[0]), Some(0));
+        assert_eq!(frame_number(&frames[1]), Some(1));
+    }
+
+    #[test]
+    fn forces_requested_keyframe()
+    {
+        let keyframe_requested = Arc::new(AtomicBool::new(false));
+        let mut encoder = VideoEncoder::new(keyframe_requested.clone());
+
+        encode_frames(&mut encoder, 2);
+
+        keyframe_requested.store(true, Relaxed);
+
+        let bytes = encoder
+            .encode(&rgb_frame(2), WIDTH, HEIGHT, VIDEO_QUALITY_LEVELS[0])
+            .unwrap();
+
+        assert!(is_keyframe(&bytes));
+
+        //The request has been answered
+        assert!(!keyframe_requested.load(Relaxed));
+    }
+
+    #[test]
+    fn requests_keyframe_after_lost_frame()
+    {
+        let keyframe_requested = Arc::new(AtomicBool::new(false));
+        let mut encoder = VideoEncoder::new(keyframe_requested.clone());
+        let mut decoder = VideoDecoder::new().unwrap();
+
+        let frames = encode_frames(&mut encoder, 5);
+
+        for bytes in &frames[..2] {
+            assert!(!matches!(
+                decoder.decode(bytes).unwrap(),
+                DecodedVideoFrame::KeyframeNeeded
+            ));
+        }
+
+        //The third frame has been lost
+        assert!(matches!(
+            decoder.decode(&frames[3]).unwrap(),
+            DecodedVideoFrame::KeyframeNeeded
+        ));
+        assert!(decoder.waiting_for_keyframe);
+
+        //The keyframe has already been requested, the delta frames are dropped until it arrives
+        assert!(matches!(
+            decoder.decode(&frames[4]).unwrap(),
+            DecodedVideoFrame::Pending
+        ));
+
+        keyframe_requested.store(true, Relaxed);
+
+        let keyframe = encoder
+            .encode(&rgb_frame(5), WIDTH, HEIGHT, VIDEO_QUALITY_LEVELS[0])
+            .unwrap();
+
+        assert!(is_keyframe(&keyframe));
+
+        //The decoding continues after the gap
+        assert!(!matches!(
+            decoder.decode(&keyframe).unwrap(),
+            DecodedVideoFrame::KeyframeNeeded
+        ));
+        assert!(!decoder.waiting_for_keyframe);
+    }
+
+    #[test]
+    fn requests_keyframe_before_first_keyframe()
+    {
+        let mut encoder = VideoEncoder::new(Arc::new(AtomicBool::new(false)));
+        let mut decoder = VideoDecoder::new().unwrap();
+
+        let frames = encode_frames(&mut encoder, 2);
+
+        //We have joined after the sender's first keyframe
+        assert!(matches!(
+            decoder.decode(&frames[1]).unwrap(),
+            DecodedVideoFrame::KeyframeNeeded
+        ));
+    }
+
+    #[test]
+    fn drops_late_frames()
+    {
+        let mut encoder = VideoEncoder::new(Arc::new(AtomicBool::new(false)));
+        let mut decoder = VideoDecoder::new().unwrap();
+
+        let frames = encode_frames(&mut encoder, 3);
+
+        for bytes in &frames {
+            decoder.decode(bytes).unwrap();
+        }
+
+        //The frame arrived again after the newer ones, so it is dropped without requesting a keyframe
+        assert!(matches!(
+            decoder.decode(&frames[1]).unwrap(),
+            DecodedVideoFrame::Pending
+        ));
+        assert!(!decoder.waiting_for_keyframe);
+        assert_eq!(decoder.next_frame_number, Some(3));
+    }
+
+    #[test]
+    fn rejects_short_frames()
+    {
+        let mut decoder = VideoDecoder::new().unwrap();
+
+        assert!(decoder.decode(&[KEYFRAME_FLAG; 4]).is_err());
+        assert_eq!(frame_number(&[0; 4]), None);
+    }
+}
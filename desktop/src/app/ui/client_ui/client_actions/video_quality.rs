@@ -0,0 +1,478 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+/// The interval the receiver reports are sent on
+pub const RECEIVER_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// If no frames have arrived from a sender for this long, we stop sending reports about their stream
+const STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// If a frame arrives this many frames behind the highest one, the sender has restarted its stream (ie. after turning their camera back on)
+const STREAM_RESTART_THRESHOLD_FRAMES: u64 = 50;
+
+/// The loss fraction above which the sender lowers its quality
+const DOWNGRADE_LOSS_FRACTION: f32 = 0.05;
+
+/// The round trip time (in milliseconds) above which the sender lowers its quality
+const DOWNGRADE_ROUND_TRIP_TIME_MS: f32 = 400.;
+
+/// The jitter (in milliseconds) above which the sender lowers its quality
+const DOWNGRADE_JITTER_MS: f32 = 60.;
+
+/// The loss fraction below which a report is considered good
+const UPGRADE_LOSS_FRACTION: f32 = 0.01;
+
+/// The amount of good reports in a row needed for the sender to raise its quality
+const UPGRADE_GOOD_REPORTS: usize = 5;
+
+/// The least amount of time between two quality changes, so that the effect of the last change can show up in the reports
+const QUALITY_CHANGE_HOLD: Duration = Duration::from_secs(2);
+
+/// The amount of sent frames we remember, this is used to calculate the round trip time from the reports
+const SENT_FRAME_HISTORY_LENGTH: usize = 256;
+
+/// The parameters of the outgoing video stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoQuality
+{
+    /// The amount of frames sent every second
+    pub frame_rate: u32,
+
+    /// The multiplier of the camera's resolution
+    pub resolution_scale: f32,
+
+    /// The bitrate the encoder is aiming for
+    pub bitrate_bps: u32,
+}
+
+impl Display for VideoQuality
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str(&format!(
+            "{}% resolution, {} fps, {} kbps",
+            (self.resolution_scale * 100.) as u32,
+            self.frame_rate,
+            self.bitrate_bps / 1000
+        ))
+    }
+}
+
+/// The quality levels of the outgoing video stream, from the best to the worst
+pub const VIDEO_QUALITY_LEVELS: [VideoQuality; 4] = [
+    VideoQuality {
+        frame_rate: 30,
+        resolution_scale: 1.,
+        bitrate_bps: 1_500_000,
+    },
+    VideoQuality {
+        frame_rate: 24,
+        resolution_scale: 1.,
+        bitrate_bps: 1_000_000,
+    },
+    VideoQuality {
+        frame_rate: 15,
+        resolution_scale: 0.75,
+        bitrate_bps: 500_000,
+    },
+    VideoQuality {
+        frame_rate: 10,
+        resolution_scale: 0.5,
+        bitrate_bps: 250_000,
+    },
+];
+
+/// The report a receiver sends to the sender of a video stream about the stream's reception
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ReceiverReport
+{
+    /// The uuid of the receiver who has sent the report
+    pub reporter_uuid: String,
+
+    /// The fraction of the frames lost since the last report
+    pub loss_fraction: f32,
+
+    /// The estimated interarrival jitter of the frames in milliseconds
+    pub jitter_ms: f32,
+
+    /// The highest frame number received, this is used by the sender to calculate the round trip time
+    pub highest_frame_number: Option<u64>,
+
+    /// The amount of time passed between receiving the highest frame and sending this report
+    pub delay_since_highest_frame_ms: u64,
+}
+
+/// The statistics of a received video stream, these are used to create the ```ReceiverReport```s and are displayed in the ui
+#[derive(Debug, Clone, Default)]
+pub struct ReceiverStatistics
+{
+    /// The highest frame number received, and its arrival time
+    highest_frame: Option<(u64, Instant)>,
+
+    /// The arrival time of the last frame
+    last_arrival: Option<Instant>,
+
+    /// The time passed between the arrival of the last two frames in milliseconds
+    last_interarrival_ms: Option<f32>,
+
+    /// The first frame number of the current report interval
+    interval_first_frame_number: Option<u64>,
+
+    /// The amount of frames received in the current report interval
+    interval_received_frames: u64,
+
+    /// The time the last report has been created
+    last_report: Option<Instant>,
+
+    /// The estimated interarrival jitter of the frames in milliseconds
+    pub jitter_ms: f32,
+
+    /// The fraction of the frames lost in the last report interval
+    pub loss_fraction: f32,
+
+    /// The amount of frames received every second in the last report interval
+    pub frame_rate: f32,
+
+    /// The resolution of the last decoded frame
+    pub resolution: Option<[usize; 2]>,
+}
+
+impl ReceiverStatistics
+{
+    /// Updates the statistics with a received frame
+    pub fn on_frame(&mut self, frame_number: u64)
+    {
+        //The sender has restarted its stream, the frame numbers start from zero again
+        if self.highest_frame.is_some_and(|(highest_frame_number, _)| {
+            frame_number + STREAM_RESTART_THRESHOLD_FRAMES < highest_frame_number
+        }) {
+            *self = Self {
+                resolution: self.resolution,
+                ..Default::default()
+            };
+        }
+
+        let now = Instant::now();
+
+        //Update the jitter estimate based on the variation of the interarrival times
+        if let Some(last_arrival) = self.last_arrival {
+            let interarrival_ms = now.duration_since(last_arrival).as_secs_f32() * 1000.;
+
+            if let Some(last_interarrival_ms) = self.last_interarrival_ms {
+                self.jitter_ms +=
+                    ((interarrival_ms - last_interarrival_ms).abs() - self.jitter_ms) / 16.;
+            }
+
+            self.last_interarrival_ms = Some(interarrival_ms);
+        }
+
+        self.last_arrival = Some(now);
+
+        //The frames which have arrived after the end of their interval are already counted as lost
+        if self
+            .interval_first_frame_number
+            .is_some_and(|first_frame_number| frame_number < first_frame_number)
+        {
+            return;
+        }
+
+        self.interval_first_frame_number.get_or_insert(frame_number);
+        self.interval_received_frames += 1;
+
+        if self
+            .highest_frame
+            .map_or(true, |(highest_frame_number, _)| {
+                frame_number > highest_frame_number
+            })
+        {
+            self.highest_frame = Some((frame_number, now));
+        }
+    }
+
+    /// Creates a report of the current interval, and starts a new one
+    /// Returns ```None``` if the stream is inactive
+    pub fn create_report(&mut self, reporter_uuid: String) -> Option<ReceiverReport>
+    {
+        let last_arrival = self.last_arrival?;
+
+        if last_arrival.elapsed() > STREAM_TIMEOUT {
+            return None;
+        }
+
+        let now = Instant::now();
+
+        let interval_length = self
+            .last_report
+            .map(|last_report| now.duration_since(last_report))
+            .unwrap_or(RECEIVER_REPORT_INTERVAL);
+
+        self.loss_fraction = match (self.interval_first_frame_number, self.highest_frame) {
+            (Some(first_frame_number), Some((highest_frame_number, _))) => {
+                let expected_frames = highest_frame_number.saturating_sub(first_frame_number) + 1;
+
+                (1. - self.interval_received_frames as f32 / expected_frames as f32).clamp(0., 1.)
+            },
+            //No frames have arrived in this interval, although the stream is still active
+            _ => 1.,
+        };
+
+        self.frame_rate = self.interval_received_frames as f32 / interval_length.as_secs_f32();
+
+        //Start a new interval
+        self.interval_first_frame_number = self
+            .highest_frame
+            .map(|(highest_frame_number, _)| highest_frame_number + 1);
+        self.interval_received_frames = 0;
+        self.last_report = Some(now);
+
+        Some(ReceiverReport {
+            reporter_uuid,
+            loss_fraction: self.loss_fraction,
+            jitter_ms: self.jitter_ms,
+            highest_frame_number: self.highest_frame.map(|(frame_number, _)| frame_number),
+            delay_since_highest_frame_ms: self
+                .highest_frame
+                .map(|(_, arrival)| now.duration_since(arrival).as_millis() as u64)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// This struct decides the quality of the outgoing video stream based on the ```ReceiverReport```s.
+/// The quality is lowered if any of the receivers reports loss, high jitter or a high round trip time, and is raised once every receiver has sent ```UPGRADE_GOOD_REPORTS``` good reports in a row.
+#[derive(Debug, Clone)]
+pub struct VideoQualityController
+{
+    /// The index of the current quality in ```VIDEO_QUALITY_LEVELS```
+    level: usize,
+
+    /// The amount of good reports received in a row from every receiver, with the time of their last report
+    good_reports: HashMap<String, (usize, Instant)>,
+
+    /// The last time the quality has been changed
+    last_change: Option<Instant>,
+
+    /// The frame numbers of the last sent frames, with the time they were sent at
+    sent_frames: VecDeque<(u64, Instant)>,
+
+    /// The last calculated round trip time in milliseconds
+    pub round_trip_time_ms: Option<f32>,
+}
+
+impl Default for VideoQualityController
+{
+    fn default() -> Self
+    {
+        Self {
+            //Start from the middle, so that we dont flood the receivers right away
+            level: 1,
+            good_reports: HashMap::new(),
+            last_change: None,
+            sent_frames: VecDeque::new(),
+            round_trip_time_ms: None,
+        }
+    }
+}
+
+impl VideoQualityController
+{
+    /// Returns the quality the stream should be sent with
+    pub fn current(&self) -> VideoQuality
+    {
+        VIDEO_QUALITY_LEVELS[self.level]
+    }
+
+    /// Saves the time the frame has been sent at
+    pub fn on_frame_sent(&mut self, frame_number: u64)
+    {
+        //The stream has been restarted, the reports about the old stream are meaningless
+        if self
+            .sent_frames
+            .back()
+            .is_some_and(|(last_frame_number, _)| frame_number <= *last_frame_number)
+        {
+            *self = Self::default();
+        }
+
+        self.sent_frames.push_back((frame_number, Instant::now()));
+
+        while self.sent_frames.len() > SENT_FRAME_HISTORY_LENGTH {
+            self.sent_frames.pop_front();
+        }
+    }
+
+    /// Adjusts the quality based on a received report
+    pub fn on_report(&mut self, report: &ReceiverReport)
+    {
+        //Calculate the round trip time from the time we have sent the frame, and the time the frame has spent at the receiver
+        if let Some(highest_frame_number) = report.highest_frame_number {
+            if let Some((_, sent_at)) = self
+                .sent_frames
+                .iter()
+                .find(|(frame_number, _)| *frame_number == highest_frame_number)
+            {
+                self.round_trip_time_ms = Some(
+                    (sent_at.elapsed().as_secs_f32() * 1000.
+                        - report.delay_since_highest_frame_ms as f32)
+                        .max(0.),
+                );
+            }
+        }
+
+        let is_congested = report.loss_fraction > DOWNGRADE_LOSS_FRACTION
+            || report.jitter_ms > DOWNGRADE_JITTER_MS
+            || self.round_trip_time_ms.is_some_and(|round_trip_time_ms| {
+                round_trip_time_ms > DOWNGRADE_ROUND_TRIP_TIME_MS
+            });
+
+        //Forget the receivers which have stopped reporting (ie. they have left the call)
+        self.good_reports
+            .retain(|_, (_, last_report)| last_report.elapsed() <= STREAM_TIMEOUT);
+
+        let can_change = self.can_change();
+
+        let (good_reports, last_report) = self
+            .good_reports
+            .entry(report.reporter_uuid.clone())
+            .or_insert((0, Instant::now()));
+
+        *last_report = Instant::now();
+
+        if is_congested {
+            *good_reports = 0;
+
+            if can_change && self.level < VIDEO_QUALITY_LEVELS.len() - 1 {
+                self.change_level(self.level + 1);
+            }
+        }
+        else if report.loss_fraction < UPGRADE_LOSS_FRACTION {
+            *good_reports += 1;
+
+            //Only raise the quality if none of the receivers have had problems lately
+            if can_change
+                && self.level > 0
+                && self
+                    .good_reports
+                    .values()
+                    .all(|(good_reports, _)| *good_reports >= UPGRADE_GOOD_REPORTS)
+            {
+                self.change_level(self.level - 1);
+            }
+        }
+    }
+
+    /// Changes the quality level, the good reports are counted again at the new level
+    fn change_level(&mut self, level: usize)
+    {
+        self.level = level;
+        self.last_change = Some(Instant::now());
+
+        for (good_reports, _) in self.good_reports.values_mut() {
+            *good_reports = 0;
+        }
+    }
+
+    /// Returns whether enough time has passed since the last quality change
+    fn can_change(&self) -> bool
+    {
+        self.last_change.map_or(true, |last_change| {
+            last_change.elapsed() >= QUALITY_CHANGE_HOLD
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn report(reporter_uuid: &str, loss_fraction: f32) -> ReceiverReport
+    {
+        ReceiverReport {
+            reporter_uuid: reporter_uuid.to_string(),
+            loss_fraction,
+            jitter_ms: 0.,
+            highest_frame_number: None,
+            delay_since_highest_frame_ms: 0,
+        }
+    }
+
+    #[test]
+    fn upgrades_after_every_receiver_is_good()
+    {
+        let mut controller = VideoQualityController::default();
+
+        controller.on_report(&report("lossy", 0.02));
+
+        //One receiver being good isnt enough
+        for _ in 0..UPGRADE_GOOD_REPORTS {
+            controller.on_report(&report("good", 0.));
+        }
+
+        assert_eq!(controller.current(), VIDEO_QUALITY_LEVELS[1]);
+
+        for _ in 0..UPGRADE_GOOD_REPORTS {
+            controller.on_report(&report("lossy", 0.));
+        }
+
+        assert_eq!(controller.current(), VIDEO_QUALITY_LEVELS[0]);
+    }
+
+    #[test]
+    fn downgrades_on_congestion()
+    {
+        let mut controller = VideoQualityController::default();
+
+        controller.on_report(&report("good", 0.));
+        controller.on_report(&report("congested", 0.5));
+
+        assert_eq!(controller.current(), VIDEO_QUALITY_LEVELS[2]);
+
+        //The quality is held for a while after a change
+        controller.on_report(&report("congested", 0.5));
+
+        assert_eq!(controller.current(), VIDEO_QUALITY_LEVELS[2]);
+    }
+
+    #[test]
+    fn controller_resets_on_stream_restart()
+    {
+        let mut controller = VideoQualityController::default();
+
+        controller.on_report(&report("congested", 0.5));
+
+        for frame_number in 0..10 {
+            controller.on_frame_sent(frame_number);
+        }
+
+        controller.on_frame_sent(0);
+
+        assert_eq!(controller.current(), VIDEO_QUALITY_LEVELS[1]);
+        assert_eq!(controller.sent_frames.len(), 1);
+    }
+
+    #[test]
+    fn statistics_reset_on_stream_restart()
+    {
+        let mut statistics = ReceiverStatistics::default();
+
+        for frame_number in 1000..1010 {
+            statistics.on_frame(frame_number);
+        }
+
+        statistics.create_report(String::new());
+
+        //The frames of the restarted stream arent counted as late frames
+        for frame_number in 0..10 {
+            statistics.on_frame(frame_number);
+        }
+
+        let report = statistics.create_report(String::new()).unwrap();
+
+        assert_eq!(report.highest_frame_number, Some(9));
+        assert_eq!(report.loss_fraction, 0.);
+    }
+}
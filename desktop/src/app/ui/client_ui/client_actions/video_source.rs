@@ -0,0 +1,405 @@
+use std::{fmt::Display, path::PathBuf, time::Instant};
+
+use anyhow::{bail, Error};
+use image::imageops;
+use opencv::{
+    core::Mat,
+    imgproc,
+    prelude::*,
+    videoio::{VideoCapture, CAP_ANY, CAP_PROP_POS_FRAMES},
+};
+#[cfg(target_os = "windows")]
+use wincam::Webcam;
+
+/// The resolution of the frames created by the ```TestPatternSource```
+const TEST_PATTERN_RESOLUTION: (usize, usize) = (640, 480);
+
+/// The colors of the bars drawn by the ```TestPatternSource```
+const TEST_PATTERN_COLORS: [[u8; 3]; 7] = [
+    [192, 192, 192],
+    [192, 192, 0],
+    [0, 192, 192],
+    [0, 192, 0],
+    [192, 0, 192],
+    [192, 0, 0],
+    [0, 0, 192],
+];
+
+/// A raw video frame in Rgb8 format
+#[derive(Debug, Clone, Default)]
+pub struct VideoFrame
+{
+    pub rgb: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A source of video frames, which can be sent in a call.
+/// The frames are pulled by the video sender thread at the frame rate decided by the ```VideoQualityController```.
+pub trait VideoSource: Send
+{
+    /// Returns the next frame of the source
+    fn get_frame(&mut self) -> anyhow::Result<VideoFrame>;
+}
+
+/// The video source selected in the settings, this is opened when the video is turned on in a call
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum VideoSourceSettings
+{
+    /// The default camera of the host
+    #[default]
+    Camera,
+    /// A monitor, or a region of it
+    Screen
+    {
+        /// The name of the monitor, if this is ```None``` the primary monitor is used
+        monitor: Option<String>,
+
+        /// The captured region of the monitor, if this is ```None``` the whole monitor is captured
+        region: Option<ScreenRegion>,
+    },
+    /// A video file, which is played in a loop
+    File(PathBuf),
+    /// A synthetic test pattern, this doesnt require any device
+    TestPattern,
+}
+
+impl Display for VideoSourceSettings
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str(match self {
+            VideoSourceSettings::Camera => "Camera",
+            VideoSourceSettings::Screen { .. } => "Screen",
+            VideoSourceSettings::File(_) => "Video file",
+            VideoSourceSettings::TestPattern => "Test pattern",
+        })
+    }
+}
+
+impl VideoSourceSettings
+{
+    /// Opens the video source
+    pub fn open(&self) -> anyhow::Result<Box<dyn VideoSource>>
+    {
+        Ok(match self {
+            #[cfg(target_os = "windows")]
+            VideoSourceSettings::Camera => Box::new(CameraSource::new()?),
+            #[cfg(not(target_os = "windows"))]
+            VideoSourceSettings::Camera => bail!("Cameras are only supported on Windows"),
+            VideoSourceSettings::Screen { monitor, region } => {
+                Box::new(ScreenSource::new(monitor.clone(), *region)?)
+            },
+            VideoSourceSettings::File(path) => Box::new(FileSource::new(path)?),
+            VideoSourceSettings::TestPattern => Box::new(TestPatternSource::new()),
+        })
+    }
+}
+
+/// Returns the names of the monitors which can be captured by the ```ScreenSource```
+pub fn get_monitor_names() -> anyhow::Result<Vec<String>>
+{
+    Ok(xcap::Monitor::all()?
+        .iter()
+        .map(|monitor| monitor.name().to_string())
+        .collect())
+}
+
+/// A region of a monitor, in pixels
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ScreenRegion
+{
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ScreenRegion
+{
+    fn default() -> Self
+    {
+        Self {
+            x: 0,
+            y: 0,
+            width: 1280,
+            height: 720,
+        }
+    }
+}
+
+/// The frames of the host's default camera, the camera is captured with ```wincam``` which is only available on Windows
+#[cfg(target_os = "windows")]
+pub struct CameraSource
+{
+    webcam: Webcam,
+}
+
+#[cfg(target_os = "windows")]
+impl CameraSource
+{
+    pub fn new() -> anyhow::Result<Self>
+    {
+        Ok(Self {
+            webcam: Webcam::new_def_auto_detect()?,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl VideoSource for CameraSource
+{
+    fn get_frame(&mut self) -> anyhow::Result<VideoFrame>
+    {
+        let (rgb, size) = self
+            .webcam
+            .get_frame()
+            .map_err(|err| Error::msg(format!("Failed to get camera frame: {err:?}")))?;
+
+        Ok(VideoFrame {
+            rgb,
+            width: size.width as usize,
+            height: size.height as usize,
+        })
+    }
+}
+
+/// The frames of a monitor, or a region of it
+pub struct ScreenSource
+{
+    /// The name of the captured monitor, if this is ```None``` the primary monitor is captured
+    monitor: Option<String>,
+
+    /// The captured region of the monitor
+    region: Option<ScreenRegion>,
+
+    /// The monitor found by ```find_monitor```, this is looked up again if capturing it fails (ie. it has been reconnected)
+    cached_monitor: Option<xcap::Monitor>,
+}
+
+impl ScreenSource
+{
+    pub fn new(monitor: Option<String>, region: Option<ScreenRegion>) -> anyhow::Result<Self>
+    {
+        let mut source = Self {
+            monitor,
+            region,
+            cached_monitor: None,
+        };
+
+        //Make sure the monitor exists
+        source.cached_monitor = Some(source.find_monitor()?);
+
+        Ok(source)
+    }
+
+    /// Returns the monitor we are capturing, enumerating the monitors is slow so this is only called if the cached monitor is lost
+    fn find_monitor(&self) -> anyhow::Result<xcap::Monitor>
+    {
+        let monitors = xcap::Monitor::all()?;
+
+        let monitor = match &self.monitor {
+            Some(name) => monitors.into_iter().find(|monitor| monitor.name() == name),
+            None => monitors.into_iter().find(|monitor| monitor.is_primary()),
+        };
+
+        monitor.ok_or_else(|| Error::msg("Failed to find monitor"))
+    }
+}
+
+impl VideoSource for ScreenSource
+{
+    fn get_frame(&mut self) -> anyhow::Result<VideoFrame>
+    {
+        let monitor = match self.cached_monitor.take() {
+            Some(monitor) => monitor,
+            None => self.find_monitor()?,
+        };
+
+        //The monitor is looked up again on the next frame if it cant be captured
+        let mut image = monitor.capture_image()?;
+
+        self.cached_monitor = Some(monitor);
+
+        if let Some(region) = self.region {
+            //Clamp the region to the monitor
+            let x = region.x.min(image.width().saturating_sub(1));
+            let y = region.y.min(image.height().saturating_sub(1));
+            let width = region.width.min(image.width() - x);
+            let height = region.height.min(image.height() - y);
+
+            image = imageops::crop_imm(&image, x, y, width, height).to_image();
+        }
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        Ok(VideoFrame {
+            rgb: image::DynamicImage::ImageRgba8(image)
+                .into_rgb8()
+                .into_raw(),
+            width,
+            height,
+        })
+    }
+}
+
+/// The frames of a video file, the file is played in a loop
+pub struct FileSource
+{
+    capture: VideoCapture,
+}
+
+impl FileSource
+{
+    pub fn new(path: &PathBuf) -> anyhow::Result<Self>
+    {
+        let capture = VideoCapture::from_file(&path.to_string_lossy(), CAP_ANY)?;
+
+        if !capture.is_opened()? {
+            bail!("Failed to open video file: {}", path.display());
+        }
+
+        Ok(Self { capture })
+    }
+}
+
+impl VideoSource for FileSource
+{
+    fn get_frame(&mut self) -> anyhow::Result<VideoFrame>
+    {
+        let mut frame = Mat::default();
+
+        //Rewind the file if we have reached its end
+        if !self.capture.read(&mut frame)? || frame.empty() {
+            self.capture.set(CAP_PROP_POS_FRAMES, 0.)?;
+
+            if !self.capture.read(&mut frame)? || frame.empty() {
+                bail!("Failed to read frame from video file");
+            }
+        }
+
+        //OpenCV decodes the frames in Bgr8 format
+        let mut rgb_frame = Mat::default();
+
+        imgproc::cvt_color(&frame, &mut rgb_frame, imgproc::COLOR_BGR2RGB, 0)?;
+
+        let size = rgb_frame.size()?;
+
+        Ok(VideoFrame {
+            rgb: rgb_frame.data_bytes()?.to_vec(),
+            width: size.width as usize,
+            height: size.height as usize,
+        })
+    }
+}
+
+/// A synthetic test pattern of color bars with a moving square, this source doesnt depend on any device
+pub struct TestPatternSource
+{
+    /// The time the source has been created at, the square is moved based on this
+    started_at: Instant,
+}
+
+impl TestPatternSource
+{
+    pub fn new() -> Self
+    {
+        Self {
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl Default for TestPatternSource
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl VideoSource for TestPatternSource
+{
+    fn get_frame(&mut self) -> anyhow::Result<VideoFrame>
+    {
+        let (width, height) = TEST_PATTERN_RESOLUTION;
+
+        let square_size = height / 4;
+
+        //The square moves across the frame once every 4 seconds
+        let progress = (self.started_at.elapsed().as_secs_f32() / 4.).fract();
+        let square_x = ((width - square_size) as f32 * progress) as usize;
+        let square_y = (height - square_size) / 2;
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = if (square_x..square_x + square_size).contains(&x)
+                    && (square_y..square_y + square_size).contains(&y)
+                {
+                    [255, 255, 255]
+                }
+                else {
+                    TEST_PATTERN_COLORS[x * TEST_PATTERN_COLORS.len() / width]
+                };
+
+                rgb.extend_from_slice(&color);
+            }
+        }
+
+        Ok(VideoFrame { rgb, width, height })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use super::{
+        super::{
+            video_codec::{DecodedVideoFrame, VideoDecoder, VideoEncoder},
+            video_quality::VIDEO_QUALITY_LEVELS,
+        },
+        *,
+    };
+
+    #[test]
+    fn test_pattern_round_trip()
+    {
+        let mut source = TestPatternSource::new();
+        let mut encoder = VideoEncoder::new(Arc::new(AtomicBool::new(false)));
+        let mut decoder = VideoDecoder::new().unwrap();
+
+        let mut decoded_frames = Vec::new();
+
+        for _ in 0..5 {
+            let frame = source.get_frame().unwrap();
+
+            assert_eq!(frame.rgb.len(), frame.width * frame.height * 3);
+
+            let bytes = encoder
+                .encode(
+                    &frame.rgb,
+                    frame.width,
+                    frame.height,
+                    VIDEO_QUALITY_LEVELS[0],
+                )
+                .unwrap();
+
+            if let DecodedVideoFrame::Frame(image) = decoder.decode(&bytes).unwrap() {
+                decoded_frames.push(image);
+            }
+        }
+
+        assert!(!decoded_frames.is_empty());
+
+        for image in decoded_frames {
+            assert_eq!(
+                image.size,
+                [TEST_PATTERN_RESOLUTION.0, TEST_PATTERN_RESOLUTION.1]
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use egui::Key;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, VIRTUAL_KEY, VK_BACK, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_F1, VK_F24,
+    VK_HOME, VK_INSERT, VK_LEFT, VK_NEXT, VK_OEM_1, VK_OEM_2, VK_OEM_3, VK_OEM_4, VK_OEM_5,
+    VK_OEM_6, VK_OEM_7, VK_OEM_COMMA, VK_OEM_MINUS, VK_OEM_PERIOD, VK_OEM_PLUS, VK_PRIOR,
+    VK_RETURN, VK_RIGHT, VK_SPACE, VK_TAB, VK_UP,
+};
+
+/// The loudness returned for silent (or empty) buffers, in dBFS
+pub const SILENCE_DB: f32 = -100.;
+
+/// The loudness (in dBFS) a remote speaker's audio has to reach to display them as speaking
+pub const SPEAKING_THRESHOLD_DB: f32 = -50.;
+
+/// The amount of time the speaking indicator is displayed after the last detected speech
+pub const SPEAKING_INDICATOR_HOLD: Duration = Duration::from_millis(300);
+
+/// This struct is used to decide whether there is someone talking in the recorded audio.
+/// The loudness of the samples are compared to the threshold, after the loudness falls below the threshold the voice is kept active for the ```hangover``` time, so that the ends of the words arent cut off.
+#[derive(Debug, Clone)]
+pub struct VoiceActivityDetector
+{
+    /// The loudness (in dBFS) the audio has to reach to be considered speech
+    pub threshold_db: f32,
+
+    /// The amount of time the voice is kept active after the loudness falls below the threshold
+    pub hangover: Duration,
+
+    /// The last time the audio was louder than the threshold
+    last_activity: Option<Instant>,
+}
+
+impl VoiceActivityDetector
+{
+    pub fn new(threshold_db: f32, hangover: Duration) -> Self
+    {
+        Self {
+            threshold_db,
+            hangover,
+            last_activity: None,
+        }
+    }
+
+    /// Returns whether the samples contain speech (Or the hangover time hasnt passed yet since the last time they did)
+    pub fn detect(&mut self, samples: &[f32]) -> bool
+    {
+        let now = Instant::now();
+
+        if calculate_loudness_db(samples) >= self.threshold_db {
+            self.last_activity = Some(now);
+        }
+
+        self.last_activity
+            .is_some_and(|last_activity| now.duration_since(last_activity) <= self.hangover)
+    }
+}
+
+/// Calculates the loudness of the samples (Root mean square) in dBFS
+pub fn calculate_loudness_db(samples: &[f32]) -> f32
+{
+    if samples.is_empty() {
+        return SILENCE_DB;
+    }
+
+    let root_mean_square =
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+
+    if root_mean_square == 0. {
+        return SILENCE_DB;
+    }
+
+    (20. * root_mean_square.log10()).max(SILENCE_DB)
+}
+
+/// Returns whether the key is held down, this is used to read the push-to-talk key.
+/// The state is read from the operating system, so the key is registered while the application isnt focused too.
+pub fn is_key_held(key: Key) -> bool
+{
+    virtual_key_code(key).is_some_and(|virtual_key_code| {
+        //The most significant bit is set if the key is down
+        unsafe { GetAsyncKeyState(virtual_key_code as i32) < 0 }
+    })
+}
+
+/// Returns the virtual-key code of the key, the punctuation keys are mapped based on the US keyboard layout
+/// Returns ```None``` if the key doesnt have a virtual-key code (ie. F25)
+fn virtual_key_code(key: Key) -> Option<VIRTUAL_KEY>
+{
+    let virtual_key_code = match key {
+        Key::ArrowDown => VK_DOWN,
+        Key::ArrowLeft => VK_LEFT,
+        Key::ArrowRight => VK_RIGHT,
+        Key::ArrowUp => VK_UP,
+        Key::Escape => VK_ESCAPE,
+        Key::Tab => VK_TAB,
+        Key::Backspace => VK_BACK,
+        Key::Enter => VK_RETURN,
+        Key::Space => VK_SPACE,
+        Key::Insert => VK_INSERT,
+        Key::Delete => VK_DELETE,
+        Key::Home => VK_HOME,
+        Key::End => VK_END,
+        Key::PageUp => VK_PRIOR,
+        Key::PageDown => VK_NEXT,
+        Key::Colon | Key::Semicolon => VK_OEM_1,
+        Key::Slash | Key::Questionmark => VK_OEM_2,
+        Key::Backtick => VK_OEM_3,
+        Key::OpenBracket => VK_OEM_4,
+        Key::Backslash | Key::Pipe => VK_OEM_5,
+        Key::CloseBracket => VK_OEM_6,
+        Key::Quote => VK_OEM_7,
+        Key::Comma => VK_OEM_COMMA,
+        Key::Minus => VK_OEM_MINUS,
+        Key::Period => VK_OEM_PERIOD,
+        Key::Plus | Key::Equals => VK_OEM_PLUS,
+        //These are commands, not physical keys
+        Key::Copy | Key::Cut | Key::Paste => return None,
+        _ => {
+            let name = key.name();
+
+            //The letters and the digits have the code of their character
+            if let [character] = name.as_bytes() {
+                return Some(*character as VIRTUAL_KEY);
+            }
+
+            //The function keys are numbered from ```VK_F1```
+            let number = name.strip_prefix('F')?.parse::<VIRTUAL_KEY>().ok()?;
+
+            if !(1..=VK_F24 - VK_F1 + 1).contains(&number) {
+                return None;
+            }
+
+            VK_F1 + number - 1
+        },
+    };
+
+    Some(virtual_key_code)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn maps_keys_to_virtual_key_codes()
+    {
+        assert_eq!(virtual_key_code(Key::A), Some(b'A' as VIRTUAL_KEY));
+        assert_eq!(virtual_key_code(Key::Z), Some(b'Z' as VIRTUAL_KEY));
+        assert_eq!(virtual_key_code(Key::Num0), Some(b'0' as VIRTUAL_KEY));
+        assert_eq!(virtual_key_code(Key::F1), Some(VK_F1));
+        assert_eq!(virtual_key_code(Key::F8), Some(VK_F1 + 7));
+        assert_eq!(virtual_key_code(Key::F24), Some(VK_F24));
+        assert_eq!(virtual_key_code(Key::F25), None);
+        assert_eq!(virtual_key_code(Key::Space), Some(VK_SPACE));
+        assert_eq!(virtual_key_code(Key::Copy), None);
+
+        //Every key with a code is mapped to a different one, except the ones sharing a physical key
+        let mut virtual_key_codes: Vec<VIRTUAL_KEY> = Key::ALL
+            .iter()
+            .filter(|key| ![Key::Colon, Key::Questionmark, Key::Pipe, Key::Plus].contains(key))
+            .filter_map(|key| virtual_key_code(*key))
+            .collect();
+
+        let key_count = virtual_key_codes.len();
+
+        virtual_key_codes.sort();
+        virtual_key_codes.dedup();
+
+        assert_eq!(virtual_key_codes.len(), key_count);
+    }
+}
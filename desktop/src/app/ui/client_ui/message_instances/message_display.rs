@@ -6,7 +6,7 @@ use egui::{
 
 use crate::app::backend::{
     parse_incoming_message, Application, ClientMessage, ClientProfile, MessageDisplay,
-    ServerFileReply, ServerMessageType,
+    ServerAudioReply, ServerFileReply, ServerMessageType,
 };
 use rodio::Decoder;
 
@@ -36,7 +36,7 @@ impl Application
                         &self.opened_user_information.uuid,
                     );
 
-                    let connection = self.client_connection.clone();
+                    let connection = self.server_session.client_connection.clone();
 
                     tokio::spawn(async move {
                         connection.send_message(message).await.unwrap();
@@ -167,13 +167,18 @@ impl Application
                                     if !ui.is_rect_visible(ui.min_rect()) {
                                         return;
                                     }
+                                    //The image has already been downloaded once
+                                    if let Some(bytes) = self.load_cached_attachment(&picture.signature) {
+                                        ctx.include_bytes(format!("bytes://{}", picture.signature), bytes);
+                                        return;
+                                    }
                                     //Load an empty byte to the said URI
                                     ctx.include_bytes(format!("bytes://{}", picture.signature), vec![0]);
                                     //We dont have file on our local system so we have to ask the server to provide it
                                     let uuid = &self.opened_user_information.uuid;
                                     let message =
                                         ClientMessage::construct_image_request_msg(picture.signature.clone(), uuid);
-                                    let connection = self.client_connection.clone();
+                                    let connection = self.server_session.client_connection.clone();
                                     tokio::spawn(async move {
                                         //We only have to send the message it will get received in a diff place
                                         connection.clone().send_message(message).await.unwrap();
@@ -194,7 +199,7 @@ impl Application
                     "{}{}{}{}",
                     env!("APPDATA"),
                     "\\Matthias\\Client\\",
-                    self.client_ui.send_on_ip_base64_encoded,
+                    self.server_session.send_on_ip_base64_encoded,
                     "\\Audios"
                 )));
 
@@ -202,7 +207,7 @@ impl Application
                 let path_to_audio = PathBuf::from(format!(
                     "{}\\Matthias\\Client\\{}\\Audios\\{}",
                     env!("APPDATA"),
-                    self.client_ui.send_on_ip_base64_encoded,
+                    self.server_session.send_on_ip_base64_encoded,
                     audio.signature
                 ));
 
@@ -282,6 +287,28 @@ impl Application
                                     if ui.button("Play").clicked() {
                                         //If the user has clicked the play button only then we download the desirted audio file! Great optimisation
                                         if !path_to_audio.exists() {
+                                            //The audio has already been downloaded once, so we dont have to request it
+                                            if let Some(bytes) =
+                                                self.load_cached_attachment(&audio.signature)
+                                            {
+                                                self.load_audio(
+                                                    &self.server_session,
+                                                    ServerAudioReply {
+                                                        bytes,
+                                                        signature: audio.signature.clone(),
+                                                        file_name: audio.file_name.clone(),
+                                                        audio_idx: current_index_in_message_list
+                                                            as u64,
+                                                    },
+                                                );
+
+                                                self.client_ui.audio_playback.settings_list
+                                                    [current_index_in_message_list]
+                                                    .is_loading = true;
+
+                                                return;
+                                            }
+
                                             let message =
                                                 ClientMessage::construct_audio_request_msg(
                                                     audio.signature.clone(),
@@ -289,7 +316,8 @@ impl Application
                                                     current_index_in_message_list as u64,
                                                 );
 
-                                            let connection = self.client_connection.clone();
+                                            let connection =
+                                                self.server_session.client_connection.clone();
 
                                             tokio::spawn(async move {
                                                 connection.send_message(message).await.unwrap();
@@ -393,10 +421,22 @@ impl Application
                 }))
             },
             crate::app::backend::ServerMessageType::VoipEvent(server_voip_event) => {
-                match server_voip_event.event {
+                //The events sent before the voice rooms were introduced dont have a room
+                let room = if server_voip_event.room.is_empty() {
+                    String::from("the group call")
+                }
+                //The names of the private rooms are generated, they shouldnt be displayed
+                else if crate::app::backend::is_private_voice_room(&server_voip_event.room) {
+                    String::from("a private call")
+                }
+                else {
+                    format!("the voice room \"{}\"", server_voip_event.room)
+                };
+
+                match &server_voip_event.event {
                     crate::app::backend::VoipEvent::Connected => {
                         let profile = match self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .connected_clients_profile
                             .get(server_voip_event.uuid.as_str())
@@ -413,7 +453,7 @@ impl Application
                         ui.horizontal(|ui| {
                             ui.label(
                                 RichText::from(format!(
-                                    "@{} has connected to the group call.",
+                                    "@{} has connected to {room}.",
                                     profile.username
                                 ))
                                 .size(self.font_size),
@@ -423,7 +463,7 @@ impl Application
                     },
                     crate::app::backend::VoipEvent::Disconnected => {
                         let profile = match self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .connected_clients_profile
                             .get(server_voip_event.uuid.as_str())
@@ -440,7 +480,7 @@ impl Application
                         ui.horizontal(|ui| {
                             ui.label(
                                 RichText::from(format!(
-                                    "@{} has disconnected from the group call.",
+                                    "@{} has disconnected from {room}.",
                                     profile.username
                                 ))
                                 .size(self.font_size),
@@ -449,12 +489,89 @@ impl Application
                         .response
                     },
 
+                    crate::app::backend::VoipEvent::RecordingStarted
+                    | crate::app::backend::VoipEvent::RecordingStopped => {
+                        let profile = match self
+                            .server_session
+                            .incoming_messages
+                            .connected_clients_profile
+                            .get(server_voip_event.uuid.as_str())
+                        {
+                            Some(profile) => profile,
+                            //If we dont have the profile we ask for it then return to avoid panicking
+                            None => {
+                                self.request_client(server_voip_event.uuid.to_string());
+
+                                &ClientProfile::default()
+                            },
+                        };
+
+                        ui.horizontal(|ui| {
+                            if server_voip_event.event
+                                == crate::app::backend::VoipEvent::RecordingStarted
+                            {
+                                ui.label(
+                                    RichText::from(format!(
+                                        "⏺ @{} has started recording {room}.",
+                                        profile.username
+                                    ))
+                                    .color(Color32::RED)
+                                    .size(self.font_size),
+                                );
+                            }
+                            else {
+                                ui.label(
+                                    RichText::from(format!(
+                                        "@{} has stopped recording {room}.",
+                                        profile.username
+                                    ))
+                                    .size(self.font_size),
+                                );
+                            }
+                        })
+                        .response
+                    },
+
+                    crate::app::backend::VoipEvent::MissedCall(caller) => {
+                        //The uuid of the event is the client who has missed the call
+                        let mut username_of = |uuid: &str| {
+                            match self
+                                .server_session
+                                .incoming_messages
+                                .connected_clients_profile
+                                .get(uuid)
+                            {
+                                Some(profile) => profile.username.clone(),
+                                //If we dont have the profile we ask for it
+                                None => {
+                                    self.request_client(uuid.to_string());
+
+                                    String::new()
+                                },
+                            }
+                        };
+
+                        let caller_username = username_of(caller);
+                        let invitee_username = username_of(&server_voip_event.uuid);
+
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                RichText::from(format!(
+                                    "📞 @{invitee_username} has missed a call from @{caller_username}."
+                                ))
+                                .color(Color32::LIGHT_RED)
+                                .size(self.font_size),
+                            );
+                        })
+                        .response
+                    },
                     crate::app::backend::VoipEvent::ImageConnected => unreachable!(),
                     crate::app::backend::VoipEvent::ImageDisconnected => unreachable!(),
                 }
             },
             crate::app::backend::ServerMessageType::Edit(_)
             | ServerMessageType::VoipState(_)
+            | ServerMessageType::ProfileUpdate(_)
             | crate::app::backend::ServerMessageType::Reaction(_)
             | crate::app::backend::ServerMessageType::Sync(_) => {
                 unimplemented!("Message type should not be displayed")
@@ -1,7 +1,9 @@
+use std::time::Instant;
+
 use crate::app::{
     backend::{
         Application, AudioSettings, ClientMessage, MessagingMode, ScrollToMessage,
-        ServerMessageType,
+        ServerMessageType, PROFILE_PICTURE_REQUEST_TIMEOUT,
     },
     server::SERVER_UUID,
     ui::client_ui::widgets::emoji_tray::emoji::display_emoji,
@@ -27,21 +29,21 @@ impl Application
                     .show(ui, |ui| {
                         ui.allocate_ui(ui.available_size(), |ui| {
                             //Display welcome message if self.send_on_ip is empty
-                            if self.client_ui.send_on_ip.is_empty() {
+                            if self.server_session.send_on_ip.is_empty() {
                                 ui.with_layout(Layout::centered_and_justified(egui::Direction::TopDown), |ui|{
                                     ui.label(RichText::from("To start chatting go to settings and set the IP to the server you want to connect to!").size(self.font_size).color(Color32::LIGHT_BLUE));
                                 });
                             }
 
                             //Check if sink_list is bigger than messages, to avoid crashing
-                            if self.client_ui.audio_playback.sink_list.len() > self.client_ui.incoming_messages.message_list.len() {
-                                for _ in 0..(self.client_ui.audio_playback.sink_list.len() as i32 - self.client_ui.incoming_messages.message_list.len() as i32).abs() {
+                            if self.client_ui.audio_playback.sink_list.len() > self.server_session.incoming_messages.message_list.len() {
+                                for _ in 0..(self.client_ui.audio_playback.sink_list.len() as i32 - self.server_session.incoming_messages.message_list.len() as i32).abs() {
                                     self.client_ui.audio_playback.sink_list.remove(self.client_ui.audio_playback.sink_list.len() - 1);
                                 }
                             }
 
                             //Allocate places manually for the audio playback (sink_list), but only allocate what we need
-                            for _ in 0..(self.client_ui.incoming_messages.message_list.len() - self.client_ui.audio_playback.sink_list.len()) {
+                            for _ in 0..(self.server_session.incoming_messages.message_list.len() - self.client_ui.audio_playback.sink_list.len()) {
                                 self.client_ui.audio_playback.sink_list.push(None);
 
                                 //Define defaults, for speed and volume based on the same logic as above ^
@@ -50,7 +52,7 @@ impl Application
 
                             let mut message_instances: Vec<Response> = Vec::new();
 
-                            for (iter_index, item) in self.client_ui.incoming_messages.clone().message_list.iter().enumerate() {
+                            for (iter_index, item) in self.server_session.incoming_messages.clone().message_list.iter().enumerate() {
                                 self.draw_message(ui, item, ctx, iter_index, &mut message_instances);
                             };
 
@@ -106,7 +108,7 @@ impl Application
                         ui.horizontal(|ui| {
                             self.display_icon_from_server(
                                 ctx,
-                                self.client_ui.incoming_messages.message_list[replied_to]
+                                self.server_session.incoming_messages.message_list[replied_to]
                                     .uuid
                                     .clone(),
                                 ui,
@@ -116,9 +118,10 @@ impl Application
                                 egui::widgets::Button::new(
                                     RichText::from(format!(
                                         "{}: {}",
-                                        self.client_ui.incoming_messages.message_list[replied_to]
+                                        self.server_session.incoming_messages.message_list
+                                            [replied_to]
                                             .author,
-                                        match &self.client_ui.incoming_messages.message_list
+                                        match &self.server_session.incoming_messages.message_list
                                             [replied_to]
                                             .message_type
                                         {
@@ -162,6 +165,7 @@ impl Application
                                             ServerMessageType::Sync(_) => unreachable!(),
                                             ServerMessageType::VoipEvent(_) => unreachable!(),
                                             ServerMessageType::VoipState(_) => unreachable!(),
+                                            ServerMessageType::ProfileUpdate(_) => unreachable!(),
                                         }
                                     ))
                                     .size(self.font_size / 1.5),
@@ -229,7 +233,7 @@ impl Application
                     ui.horizontal(|ui| {
                         //Check if there is a reaction list vector already allocated non the index of the specific message
                         match self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .reaction_list
                             .clone()
@@ -272,15 +276,15 @@ impl Application
                 });
 
             if ui.is_rect_visible(ui.min_rect())
-                && *self.client_ui.last_seen_msg_index.lock().unwrap() < iter_index
+                && *self.server_session.last_seen_msg_index.lock().unwrap() < iter_index
             {
-                *self.client_ui.last_seen_msg_index.lock().unwrap() = iter_index;
+                *self.server_session.last_seen_msg_index.lock().unwrap() = iter_index;
             }
         });
 
         //Display where the users seen their last message
         ui.horizontal(|ui| {
-            for client in self.client_ui.incoming_messages.user_seen_list.clone() {
+            for client in self.server_session.incoming_messages.user_seen_list.clone() {
                 if iter_index == client.index {
                     //Make it more visible
                     ui.group(|ui| {
@@ -291,7 +295,7 @@ impl Application
 
                         //Client name
                         if let Some(profile) = self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .connected_clients_profile
                             .get(&client.uuid)
@@ -345,19 +349,19 @@ impl Application
                     else {
                         //We can safely unwrap here
                         let user_profile = self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .connected_clients_profile
                             .get(&item.uuid)
-                            .unwrap();
-                        //Include full profile picture so it can be displayed
-                        ctx.include_bytes(
-                            "bytes://profile_picture",
-                            user_profile.normal_profile_picture.clone(),
-                        );
+                            .unwrap()
+                            .clone();
 
                         //Display 256px profile picture
-                        ui.image("bytes://profile_picture");
+                        self.display_profile_picture(
+                            ctx,
+                            &user_profile.normal_profile_picture_hash,
+                            ui,
+                        );
 
                         ui.label(
                             RichText::from(user_profile.username.clone())
@@ -394,8 +398,34 @@ impl Application
                 });
 
                 //If profile_menu_button.inner.is_none() it is closed, so we can deallocate / forget the before loaded image
+                //The picture is read from the cache again when the menu is opened
                 if profile_menu_button.inner.is_none() {
-                    ctx.forget_image("bytes://profile_picture");
+                    if let Some(user_profile) = self
+                        .server_session
+                        .incoming_messages
+                        .connected_clients_profile
+                        .get(&item.uuid)
+                    {
+                        ctx.forget_image(&format!(
+                            "bytes://{}",
+                            user_profile.normal_profile_picture_hash
+                        ));
+                    }
+                }
+
+                //Invite the author of the message to a private call, we can only do this if we arent in a call
+                if item.uuid != SERVER_UUID && item.uuid != self.opened_user_information.uuid {
+                    if ui
+                        .add_enabled(
+                            self.server_session.voip.is_none() && self.atx.is_none(),
+                            Button::new("📞 Call"),
+                        )
+                        .on_hover_text("Invite this user to a private call")
+                        .clicked()
+                    {
+                        self.invite_to_private_call(item.uuid.clone());
+                        ui.close_menu();
+                    }
                 }
 
                 ui.separator();
@@ -478,7 +508,7 @@ impl Application
     fn change_send_emoji(&mut self, iter_index: usize, selected_emoji_name: String)
     {
         //Check if there is an emoji already added
-        if let Some(reaction) = self.client_ui.incoming_messages.reaction_list[iter_index]
+        if let Some(reaction) = self.server_session.incoming_messages.reaction_list[iter_index]
             .message_reactions
             .iter()
             .find(|reaction| reaction.emoji_name == selected_emoji_name)
@@ -524,7 +554,7 @@ impl Application
     }
 
     /// This function displays the 64x64 icon of a client based on their uuid
-    /// This function also requests the server for the image if the image isnt available on the given URI
+    /// This function also requests the server for the client's profile if it isnt available yet
     pub fn display_icon_from_server(&mut self, ctx: &egui::Context, uuid: String, ui: &mut egui::Ui)
     {
         //If uuid is the server's we just include the image of the server
@@ -535,7 +565,42 @@ impl Application
 
             return;
         }
-        match ctx.try_load_bytes(&format!("bytes://{}", &uuid)) {
+
+        //The profile only contains the hash of the picture
+        if let Some(profile) = self
+            .server_session
+            .incoming_messages
+            .connected_clients_profile
+            .get(&uuid)
+        {
+            let small_profile_picture_hash = profile.small_profile_picture_hash.clone();
+
+            self.display_profile_picture(ctx, &small_profile_picture_hash, ui);
+
+            return;
+        }
+
+        ui.spinner();
+
+        //check if we are visible, so there are no unnecessary requests
+        if !ui.is_rect_visible(ui.min_rect()) {
+            return;
+        }
+
+        //If the server takees a lot of time to respond, we will prevent asking multiple times by creating a placeholder
+        //We will forget this URI when the profile arrives
+        if ctx.try_load_bytes(&format!("bytes://{}", &uuid)).is_err() {
+            self.request_client(uuid.clone());
+
+            ctx.include_bytes(format!("bytes://{}", &uuid), vec![0]);
+        }
+    }
+
+    /// This function displays the profile picture with the given sha256 hash
+    /// The picture is read from the cache, or requested from the server if it isnt available on the given URI
+    pub fn display_profile_picture(&mut self, ctx: &egui::Context, hash: &str, ui: &mut egui::Ui)
+    {
+        match ctx.try_load_bytes(&format!("bytes://{}", hash)) {
             //If the image was found on the URI
             Ok(bytes) => {
                 //We want to wait until all the bytes are ready to display the image
@@ -548,9 +613,22 @@ impl Application
                     //If there is only a 0 in the bytes that indicates its a placeholder, thus we can display the spinner
                     if bytes.to_vec() == vec![0] {
                         ui.spinner();
+
+                        //The reply might have been lost, so the placeholder is removed after a while to request the picture again
+                        let is_request_pending = self
+                            .server_session
+                            .profile_picture_requests
+                            .get(hash)
+                            .is_some_and(|requested_at| {
+                                requested_at.elapsed() < PROFILE_PICTURE_REQUEST_TIMEOUT
+                            });
+
+                        if !is_request_pending {
+                            ctx.forget_image(&format!("bytes://{}", hash));
+                        }
                     }
                     else {
-                        ui.add(egui::Image::from_uri(format!("bytes://{}", &uuid)));
+                        ui.add(egui::Image::from_uri(format!("bytes://{}", hash)));
                     }
                 }
             },
@@ -559,20 +637,34 @@ impl Application
                 ui.spinner();
                 if let LoadError::Loading(inner) = err {
                     if inner == "Bytes not found. Did you forget to call Context::include_bytes?" {
+                        //The pictures are stored by their hash, so a cached picture can never be outdated
+                        if let Some(bytes) = self.load_cached_attachment(hash) {
+                            ctx.include_bytes(format!("bytes://{}", hash), bytes);
+
+                            return;
+                        }
+
                         //check if we are visible, so there are no unnecessary requests
                         if !ui.is_rect_visible(ui.min_rect()) {
                             return;
                         }
 
-                        //Ask the server for the specified client's profile picture
-                        self.send_msg(ClientMessage::construct_client_request_msg(
-                            uuid.clone(),
+                        //Ask the server for the profile picture, the reply is cached like the images
+                        self.send_msg(ClientMessage::construct_profile_picture_request_msg(
+                            hash.to_string(),
                             &self.opened_user_information.uuid,
                         ));
 
                         //If the server takees a lot of time to respond, we will prevent asking multiple times by creating a placeholder just as in the image displaying code
                         //We will forget this URI when loading in the real image
-                        ctx.include_bytes(format!("bytes://{}", &uuid), vec![0]);
+                        ctx.include_bytes(format!("bytes://{}", hash), vec![0]);
+
+                        self.server_session
+                            .profile_picture_requests
+                            .insert(hash.to_string(), Instant::now());
+
+                        //Check the placeholder again once the request has timed out
+                        ctx.request_repaint_after(PROFILE_PICTURE_REQUEST_TIMEOUT);
                     }
                     else {
                         tracing::error!("{}", inner);
@@ -2,7 +2,10 @@ use crate::app::{
     backend::{
         Application, ClientMessage, ConnectionState, MessagingMode, ServerMessageType, EMOJI_TUPLES,
     },
-    ui::client_ui::client_actions::audio_recording::{audio_recording_with_recv, create_wav_file},
+    ui::client_ui::client_actions::{
+        audio_processing::process_recording,
+        audio_recording::{audio_recording_with_recv, create_wav_file, get_recording_device},
+    },
 };
 use chrono::Utc;
 use egui::{
@@ -53,7 +56,7 @@ impl Application
                 if reader.key_pressed(Key::ArrowUp) {
                     //Iter over all the messages so we will get the latest message sent by us
                     for (idx, message) in self
-                        .client_ui
+                        .server_session
                         .incoming_messages
                         .message_list
                         .iter()
@@ -82,7 +85,7 @@ impl Application
                 size: self.font_size,
                 family: FontFamily::default(),
             })
-            .hint_text(format!("Message to: {}", self.client_ui.send_on_ip))
+            .hint_text(format!("Message to: {}", self.server_session.send_on_ip))
             .desired_width(ui.available_width() - self.client_ui.text_widget_offset * 1.3)
             .desired_rows(0)
             .return_key(KeyboardShortcut::new(Modifiers::SHIFT, Key::Enter))
@@ -134,10 +137,14 @@ impl Application
             )
             .show(ctx, |ui| {
                 //We should also pass in whether it should be enabled
+                //The messages sent while reconnecting are delivered from the outbox after the connection has been restored
                 self.buttons(
                     ui,
                     ctx,
-                    matches!(self.client_connection.state, ConnectionState::Connected(_)),
+                    matches!(
+                        self.server_session.client_connection.state,
+                        ConnectionState::Connected(_) | ConnectionState::Reconnecting
+                    ),
                 );
             })
     }
@@ -169,9 +176,9 @@ impl Application
 
                         if let Some(buffer) = split.last_mut() {
                             //If we have already typed in the full username OR there are no username matches in what we typed in we can return, so we wont consume the enter key therefor were going to send the message
-                            for seen in &self.client_ui.incoming_messages.user_seen_list {
+                            for seen in &self.server_session.incoming_messages.user_seen_list {
                                 let profile = self
-                                    .client_ui
+                                    .server_session
                                     .incoming_messages
                                     .connected_clients_profile
                                     .get(&seen.uuid);
@@ -186,15 +193,19 @@ impl Application
 
                             //If the ENTER key is pressed append the name to the self.client_ui.text_edit_buffer
                             if reader.consume_key(Modifiers::NONE, Key::Enter)
-                                && !self.client_ui.incoming_messages.user_seen_list.is_empty()
+                                && !self
+                                    .server_session
+                                    .incoming_messages
+                                    .user_seen_list
+                                    .is_empty()
                             {
                                 //format the string so the @ stays
                                 if let Some(profile) = self
-                                    .client_ui
+                                    .server_session
                                     .incoming_messages
                                     .connected_clients_profile
                                     .get(
-                                        &self.client_ui.incoming_messages.user_seen_list
+                                        &self.server_session.incoming_messages.user_seen_list
                                             [self.client_ui.user_selector_index as usize]
                                             .uuid,
                                     )
@@ -464,7 +475,7 @@ impl Application
                         });
                     }
                     else {
-                        ui.add_enabled_ui(self.client_ui.voip.is_none(), |ui| {
+                        ui.add_enabled_ui(self.server_session.voip.is_none(), |ui| {
                             if ui
                                 .add(egui::ImageButton::new(egui::include_image!(
                                     "../../../../../../../assets/icons/record.png"
@@ -482,18 +493,45 @@ impl Application
                                 let audio_bytes_sender = self.audio_bytes_tx.clone();
                                 let microphone_percentage =
                                     self.client_ui.microphone_volume.clone();
+                                let audio_processing = self.client_ui.audio_processing.clone();
+                                let input_device =
+                                    self.client_ui.input_device.lock().unwrap().clone();
 
                                 tokio::spawn(async move {
-                                    let bytes = audio_recording_with_recv(
-                                        rx,
-                                        microphone_percentage
-                                            .load(std::sync::atomic::Ordering::Relaxed)
-                                            as f32,
-                                    )
-                                    .unwrap();
+                                    let recording = get_recording_device(&input_device).and_then(
+                                        |(_, config)| {
+                                            audio_recording_with_recv(
+                                                rx,
+                                                microphone_percentage
+                                                    .load(std::sync::atomic::Ordering::Relaxed)
+                                                    as f32,
+                                                &input_device,
+                                            )
+                                            .map(|bytes| (bytes, config))
+                                        },
+                                    );
+
+                                    let (mut bytes, config) = match recording {
+                                        Ok(recording) => recording,
+                                        Err(err) => {
+                                            tracing::error!("Failed to record audio: {err}");
+
+                                            return;
+                                        },
+                                    };
+
+                                    //Process the voice message the same way as the audio in calls
+                                    let audio_processing_settings =
+                                        audio_processing.lock().unwrap().clone();
+
+                                    process_recording(
+                                        &mut bytes,
+                                        audio_processing_settings,
+                                        &config,
+                                    );
 
                                     //These bytes can be played back with rodio (Wav format)
-                                    let playback_bytes = create_wav_file(bytes);
+                                    let playback_bytes = create_wav_file(bytes, &config);
 
                                     audio_bytes_sender.send(playback_bytes).unwrap();
                                 });
@@ -531,13 +569,18 @@ impl Application
                 let message_group = ui.group(|ui| {
                     ui.label(RichText::from("Users:").strong());
                     if let Some(last_str) = split_user_msg.last() {
-                        if self.client_ui.incoming_messages.user_seen_list.is_empty() {
+                        if self
+                            .server_session
+                            .incoming_messages
+                            .user_seen_list
+                            .is_empty()
+                        {
                             //Display greeting message
                             ui.label(RichText::from("Syncing. . .").color(Color32::RED));
                         }
 
                         for (index, client) in self
-                            .client_ui
+                            .server_session
                             .incoming_messages
                             .user_seen_list
                             .iter()
@@ -545,7 +588,7 @@ impl Application
                         {
                             //If the search buffer is contained in the clients' username
                             if let Some(profile) = self
-                                .client_ui
+                                .server_session
                                 .incoming_messages
                                 .connected_clients_profile
                                 .get(&client.uuid)
@@ -577,7 +620,12 @@ impl Application
                 self.client_ui.connected_users_display_rect = Some(message_group.response.rect);
 
                 //If the seen list is empty we should display a message indicating its loading but we should return before clamping because it would go -1 therefor we would be panicking
-                if self.client_ui.incoming_messages.user_seen_list.is_empty() {
+                if self
+                    .server_session
+                    .incoming_messages
+                    .user_seen_list
+                    .is_empty()
+                {
                     return;
                 }
 
@@ -599,7 +647,7 @@ impl Application
                     //*Make sure we return if ```self.client_ui.incoming_msg.user_seen_list``` is empty because then it'd overflow
                     .clamp(
                         0,
-                        self.client_ui.incoming_messages.user_seen_list.len() as i32 - 1,
+                        self.server_session.incoming_messages.user_seen_list.len() as i32 - 1,
                     );
             });
 
@@ -1,4 +1,7 @@
-use crate::app::backend::{display_error_message, login};
+use crate::app::{
+    backend::{display_error_message, login},
+    ui::client_ui::client_actions::outbox::Outbox,
+};
 
 use crate::app::backend::Application;
 use egui::{Align, Layout, RichText};
@@ -28,10 +31,18 @@ impl Application
                 {
                     self.main.client_mode =
                         match login(self.login_username.clone(), self.login_password.clone()) {
-                            Ok((account, _path_to_account)) => {
+                            Ok((account, path_to_account)) => {
                                 //Load the parsed text into the variable
                                 self.opened_user_information = account;
 
+                                //Load the messages which havent been delivered before the application was closed
+                                *self.outbox.lock().unwrap() = Outbox::load(
+                                    path_to_account.with_extension("outbox"),
+                                    &self.opened_user_information.password,
+                                );
+
+                                self.auto_connect_bookmarks(ctx);
+
                                 true
                             },
                             Err(err) => {
@@ -49,6 +60,10 @@ impl Application
                 if ui.button("Register").clicked() {
                     self.main.register_mode = true;
                 };
+
+                ui.separator();
+                ui.label(RichText::from("Moving from another computer?").weak());
+                self.import_account_ui(ui);
             });
         });
     }
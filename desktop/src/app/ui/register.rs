@@ -1,7 +1,10 @@
 use std::{env, fs, io::Cursor, path::PathBuf};
 
-use crate::app::backend::{
-    display_error_message, display_info_message, register, Application, ProfileImage, Register,
+use crate::app::{
+    backend::{
+        display_error_message, display_info_message, register, Application, ProfileImage, Register,
+    },
+    ui::client_ui::client_actions::outbox::Outbox,
 };
 use anyhow::bail;
 use egui::{
@@ -105,6 +108,11 @@ impl Application
                                             self.main.client_mode = true;
                                             self.main.register_mode = false;
 
+                                            *self.outbox.lock().unwrap() = Outbox::load(
+                                                user_information.path.with_extension("outbox"),
+                                                &user_information.password,
+                                            );
+
                                             self.opened_user_information = user_information;
                                         },
                                         Err(err) => {
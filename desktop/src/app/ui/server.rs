@@ -1,9 +1,13 @@
 use crate::app::{
-    backend::{display_error_message, ipv4_get, ipv6_get, Application, ClientProfile},
+    backend::{
+        display_error_message, display_info_message, ipv4_get, ipv6_get, Application,
+        ClientProfile, ClientRole,
+    },
     server,
+    ui::client_ui::client_actions::connection_link::ConnectionLink,
 };
 use dashmap::DashMap;
-use egui::{vec2, Align, Color32, Context, Image, Layout, RichText};
+use egui::{vec2, Align, Color32, Context, DragValue, Image, Layout, RichText};
 use egui_extras::{Column, TableBuilder};
 use tokio_util::sync::CancellationToken;
 
@@ -32,6 +36,16 @@ impl Application
                             false => "".to_string(),
                         };
 
+                        //The name defaults to the host's name
+                        let lan_announcement_name = self.server_lan_discovery.then(|| {
+                            if self.server_name.trim().is_empty() {
+                                format!("{}'s server", self.login_username)
+                            }
+                            else {
+                                self.server_name.trim().to_string()
+                            }
+                        });
+
                         //Overwrite the channel we have in the TemplateApp struct
                         self.server_shutdown_token = CancellationToken::new();
 
@@ -41,6 +55,8 @@ impl Application
                         let connected_clients: std::sync::Arc<DashMap<String, ClientProfile>> =
                             self.server_connected_clients_profile.clone();
 
+                        let profile_pictures = self.server_profile_pictures.clone();
+
                         let shared_fields_clone = self.client_ui.shared_fields.clone();
 
                         //Move context so we can request_repaint
@@ -52,8 +68,10 @@ impl Application
                                     match server::server_main(
                                         port.to_string(),
                                         server_pw,
+                                        lan_announcement_name,
                                         token,
                                         connected_clients,
+                                        profile_pictures,
                                         ctx,
                                     )
                                     .await
@@ -87,6 +105,16 @@ impl Application
                     if self.server_req_password {
                         ui.text_edit_singleline(&mut self.server_password);
                     }
+
+                    ui.checkbox(&mut self.server_lan_discovery, "Announce on the local network")
+                        .on_hover_text("The clients on the local network will be able to find the server without its address");
+
+                    if self.server_lan_discovery {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.server_name)
+                                .hint_text(format!("{}'s server", self.login_username)),
+                        );
+                    }
                 }
                 else {
                     ui.label("Server settings");
@@ -119,7 +147,7 @@ impl Application
                                 //Set the ip we are connecting to so we dont need to paste it
                                 let pub_ip: Vec<&str> = self.public_ip.rsplit(';').collect();
 
-                                self.client_ui.send_on_ip =
+                                self.server_session.send_on_ip =
                                     format!("[{}]:{}", pub_ip[0], self.open_on_port);
                             },
                             Err(err) => {
@@ -148,16 +176,23 @@ impl Application
 
                     ui.horizontal(|ui| {
                         ui.label(RichText::from("Share").weak().size(self.font_size / 2.));
-                        if ui.button("Copy connection link").clicked() {
-                            ctx.copy_text(format!(
-                                "matthias://{formatted_ip}&{}",
-                                self.server_password
-                            ));
+                        if ui
+                            .button("Copy connection link")
+                            .on_hover_text("The clients will have to enter the password of the server")
+                            .clicked()
+                        {
+                            ctx.copy_text(
+                                ConnectionLink::new(formatted_ip.clone(), None).to_string(),
+                            );
                         }
                     });
 
                     ui.separator();
 
+                    self.invites_ui(ui, ctx, &formatted_ip);
+
+                    ui.separator();
+
                     ui.label("Clients' profiles");
                     //Display connected users, with a Table
                     ui.allocate_ui(vec2(ui.available_width(), 200.), |ui| {
@@ -204,13 +239,20 @@ impl Application
                                         //Profile picture
                                         row.col(|ui| {
                                             ui.centered_and_justified(|ui| {
-                                                ui.add(Image::from_bytes(
-                                                    format!(
-                                                        "bytes://server_connect_preview_{}",
-                                                        key.clone()
-                                                    ),
-                                                    value.small_profile_picture.clone(),
-                                                ));
+                                                //The uri changes with the picture, so the updated profile pictures are displayed
+                                                if let Some(profile_picture) = self
+                                                    .server_profile_pictures
+                                                    .get(&value.small_profile_picture_hash)
+                                                {
+                                                    ui.add(Image::from_bytes(
+                                                        format!(
+                                                            "bytes://server_connect_preview_{}_{}",
+                                                            key.clone(),
+                                                            value.small_profile_picture_hash
+                                                        ),
+                                                        profile_picture.clone(),
+                                                    ));
+                                                }
                                             });
                                         });
                                         //Ban button
@@ -271,4 +313,118 @@ impl Application
             });
         });
     }
+
+    /// The clients joining with an invite link dont need the password of the server
+    fn invites_ui(&mut self, ui: &mut egui::Ui, ctx: &Context, server_address: &str)
+    {
+        ui.label("Invites");
+
+        ui.horizontal(|ui| {
+            ui.label("Valid for");
+            ui.add(DragValue::new(&mut self.invite_expiry_hours).suffix(" hours"))
+                .on_hover_text("The invite doesnt expire if this is 0");
+
+            ui.label("Max uses");
+            ui.add(DragValue::new(&mut self.invite_max_uses))
+                .on_hover_text("The invite can be used any amount of times if this is 0");
+
+            egui::ComboBox::from_label("Role")
+                .selected_text(self.invite_role.to_string())
+                .show_ui(ui, |ui| {
+                    for role in [ClientRole::Member, ClientRole::ReadOnly] {
+                        ui.selectable_value(&mut self.invite_role, role, role.to_string());
+                    }
+                });
+        });
+
+        //Clone the handles, so the lock isnt held while drawing
+        let shared_fields = self.client_ui.shared_fields.lock().unwrap().clone();
+
+        if ui.button("Create invite link").clicked() {
+            match shared_fields.create_invite(
+                (self.invite_expiry_hours != 0)
+                    .then(|| chrono::Duration::hours(i64::from(self.invite_expiry_hours))),
+                (self.invite_max_uses != 0).then_some(self.invite_max_uses),
+                self.invite_role,
+            ) {
+                Ok(token) => {
+                    ctx.copy_text(
+                        ConnectionLink::new(server_address.to_string(), Some(token)).to_string(),
+                    );
+
+                    display_info_message(
+                        "The invite link has been copied to the clipboard.",
+                        self.toasts.clone(),
+                    );
+                },
+                Err(err) => {
+                    display_error_message(err, self.toasts.clone());
+                },
+            }
+        }
+
+        match shared_fields.invites.try_lock() {
+            Ok(mut invites) => {
+                for invite in invites.iter_mut() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} ({} / {} uses)",
+                            invite.role,
+                            invite.uses,
+                            invite
+                                .max_uses
+                                .map_or_else(|| "∞".to_string(), |max_uses| max_uses.to_string())
+                        ));
+
+                        let status = if invite.revoked {
+                            RichText::from("Revoked").color(Color32::RED)
+                        }
+                        else if invite.is_expired() {
+                            RichText::from("Expired").color(Color32::GRAY)
+                        }
+                        else if !invite.is_usable() {
+                            RichText::from("Used up").color(Color32::GRAY)
+                        }
+                        else {
+                            RichText::from(invite.expires_at.map_or_else(
+                                || "Doesnt expire".to_string(),
+                                |expires_at| {
+                                    format!(
+                                        "Expires at {}",
+                                        expires_at
+                                            .with_timezone(&chrono::Local)
+                                            .format("%Y.%m.%d. %H:%M")
+                                    )
+                                },
+                            ))
+                        };
+
+                        ui.label(status);
+
+                        if ui.button("Copy link").clicked() {
+                            ctx.copy_text(
+                                ConnectionLink::new(
+                                    server_address.to_string(),
+                                    Some(invite.token.clone()),
+                                )
+                                .to_string(),
+                            );
+                        }
+
+                        //The clients who have joined with the invite are disconnected too
+                        if !invite.revoked
+                            && ui
+                                .button(RichText::from("Revoke").color(Color32::RED))
+                                .clicked()
+                        {
+                            invite.revoked = true;
+                        }
+                    });
+                }
+            },
+            Err(err) => {
+                tracing::error!("{}", err);
+            },
+        };
+    }
 }